@@ -0,0 +1,131 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::{CoingeckoClient, CoingeckoCoinsList};
+
+/// Where `CoinsListCache` keeps the coins list between restarts
+#[async_trait]
+pub trait CoinsListStore: Send + Sync {
+    async fn load(&self) -> anyhow::Result<Option<CoingeckoCoinsList>>;
+
+    async fn save(&self, list: &CoingeckoCoinsList) -> anyhow::Result<()>;
+}
+
+/// Stores the coins list as a JSON file
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl CoinsListStore for FileStore {
+    async fn load(&self) -> anyhow::Result<Option<CoingeckoCoinsList>> {
+        let content = match tokio::fs::read(&self.path).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error).with_context(|| format!("Unable to read {}", self.path.display())),
+        };
+
+        Ok(Some(serde_json::from_slice(&content).with_context(|| {
+            format!("Unable to parse coins list from {}", self.path.display())
+        })?))
+    }
+
+    async fn save(&self, list: &CoingeckoCoinsList) -> anyhow::Result<()> {
+        // Write to a temporary file first so a crash never leaves a broken list
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(list)?)
+            .await
+            .with_context(|| format!("Unable to write {}", tmp.display()))?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .with_context(|| format!("Unable to write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Coins list which survives restarts: the stored copy is revalidated with its ETag,
+/// so the full list is downloaded only when it has changed
+pub struct CoinsListCache<S = FileStore> {
+    client: CoingeckoClient,
+    store: S,
+    list: Mutex<Option<Arc<CoingeckoCoinsList>>>,
+}
+
+impl<S: CoinsListStore> CoinsListCache<S> {
+    pub fn new(client: CoingeckoClient, store: S) -> Self {
+        Self {
+            client,
+            store,
+            list: Mutex::new(None),
+        }
+    }
+
+    pub async fn get_or_refresh(&self) -> anyhow::Result<Arc<CoingeckoCoinsList>> {
+        let mut guard = self.list.lock().await;
+
+        if guard.is_none() {
+            match self.store.load().await {
+                Ok(list) => *guard = list.map(Arc::new),
+                Err(error) => tracing::warn!(%error, "unable to load stored coins list"),
+            }
+        }
+
+        let etag = guard.as_ref().and_then(|list| list.etag.as_ref());
+
+        match (self.client.get_all_metadata(etag).await?, guard.as_ref()) {
+            (Some(list), _) => {
+                if let Err(error) = self.store.save(&list).await {
+                    tracing::warn!(%error, "unable to store coins list");
+                }
+
+                let list = Arc::new(list);
+                *guard = Some(list.clone());
+                Ok(list)
+            },
+            (None, Some(list)) => Ok(list.clone()),
+            (None, None) => unreachable!("coins list is not modified only when it's cached"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::types::{CoingeckoInfo, CoingeckoInfoWithAddress};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn file_store() {
+        let path = std::env::temp_dir().join(format!("coins-list-{}.json", std::process::id()));
+        let store = FileStore::new(&path);
+
+        assert!(store.load().await.unwrap().is_none());
+
+        let coin = CoingeckoInfoWithAddress {
+            metadata: CoingeckoInfo::new("solana".into(), "Solana".into(), "sol".into()),
+            addresses: HashMap::new(),
+        };
+        let list = CoingeckoCoinsList {
+            coins_list: [("solana".to_owned(), coin)].into_iter().collect(),
+            etag: Some("W/\"etag\"".into()),
+        };
+        store.save(&list).await.unwrap();
+
+        let loaded = store.load().await.unwrap().unwrap();
+        assert_eq!(loaded.etag, list.etag);
+        assert_eq!(loaded.coins_list, list.coins_list);
+
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+}
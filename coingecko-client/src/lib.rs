@@ -1,64 +1,69 @@
 use anyhow::Context;
 use chrono::{NaiveDate, NaiveTime};
+use futures::{Stream, StreamExt};
 use http::{
     header::{ETAG, IF_NONE_MATCH},
     HeaderMap, HeaderName, StatusCode,
 };
 use http_client::settings::HttpClientSettings;
 use normdecimal::NormDecimal;
-use serde::{de::DeserializeOwned, Deserialize};
-use std::{collections::HashMap, ops::Range};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::HashMap, ops::Range, time::Duration};
+use stream::JsonArrayItems;
 use token_address::StoredTokenAddress;
-use types::{CoingeckoInfo, CoingeckoInfoWithAddress};
+use types::{Candle, ChartInterval, CoinMarket, CoinTickers, CoingeckoInfo, CoingeckoInfoWithAddress, MarketChart};
 
+pub mod cache;
+mod stream;
 pub mod types;
 
 pub const PUBLIC_BASE_URL: &str = "https://api.coingecko.com/api/v3";
 pub const PRO_BASE_URL: &str = "https://pro-api.coingecko.com/api/v3";
+/// Free tier of the public API allows about 10 calls per minute
+pub const PUBLIC_RATE_LIMIT_PER_SEC: f64 = 10.0 / 60.0;
+pub const PRO_RATE_LIMIT_PER_SEC: f64 = 500.0 / 60.0;
+/// Full coins list is several megabytes, so it gets a longer timeout than the client default
+pub const COINS_LIST_TIMEOUT: Duration = Duration::from_secs(120);
 
 #[derive(Clone)]
 pub struct CoingeckoClient {
-    client: reqwest::Client,
+    client: ClientWithMiddleware,
     base_url: String,
 }
 
 impl Default for CoingeckoClient {
     /// Default client with public base url
     fn default() -> Self {
-        Self {
-            client: (&HttpClientSettings::default()).into(),
-            base_url: PUBLIC_BASE_URL.to_owned(),
-        }
+        Self::new(HttpClientSettings::default()).expect("Default coingecko client must be built")
     }
 }
 
 impl CoingeckoClient {
-    pub fn new(settings: HttpClientSettings) -> anyhow::Result<Self> {
-        let HttpClientSettings {
-            tcp_keepalive,
-            pool_idle_timeout,
-            api_key,
-            ..
-        } = settings;
-
-        let base_url = if api_key.is_some() {
-            PRO_BASE_URL
+    /// Requests are rate limited by `HttpClientSettings::rate_limit_per_sec` or the limit of the API tier,
+    /// `429 Too Many Requests` is retried in addition to `retry_on`
+    pub fn new(mut settings: HttpClientSettings) -> anyhow::Result<Self> {
+        let (base_url, default_rate_limit) = if settings.api_key.is_some() {
+            (PRO_BASE_URL, PRO_RATE_LIMIT_PER_SEC)
         } else {
-            PUBLIC_BASE_URL
+            (PUBLIC_BASE_URL, PUBLIC_RATE_LIMIT_PER_SEC)
         };
+        settings.rate_limit_per_sec = settings.rate_limit_per_sec.or(Some(default_rate_limit));
+        if !settings.retry_on.contains(&StatusCode::TOO_MANY_REQUESTS.as_u16()) {
+            settings.retry_on.push(StatusCode::TOO_MANY_REQUESTS.as_u16());
+        }
 
-        let mut builder = reqwest::ClientBuilder::new()
-            .tcp_keepalive(Some(tcp_keepalive))
-            .pool_idle_timeout(Some(pool_idle_timeout));
+        let mut builder = settings.client_builder()?;
 
-        if let Some(api_key) = api_key {
+        if let Some(api_key) = &settings.api_key {
             builder = builder.default_headers(HeaderMap::from_iter([(
                 HeaderName::from_static("x-cg-pro-api-key"),
-                api_key.try_into()?,
+                api_key.expose_secret().as_str().try_into()?,
             )]));
         };
 
-        let client = builder.build().context("Unable to build coingecko client")?;
+        let client =
+            http_client::with_middleware(builder.build().context("Unable to build coingecko client")?, &settings)?;
 
         Ok(Self {
             client,
@@ -72,11 +77,7 @@ impl CoingeckoClient {
     ) -> anyhow::Result<Option<CoingeckoInfoWithAddress>> {
         let response = self
             .client
-            .get(format!(
-                "{base_url}/coins/{platform}/contract/{address}",
-                base_url = self.base_url,
-                platform = address.platform(),
-            ))
+            .get(format!("{}/{}", self.base_url, contract_path(address)))
             .send()
             .await?;
 
@@ -106,10 +107,13 @@ impl CoingeckoClient {
     }
 
     pub async fn get_all_metadata(&self, etag: Option<&String>) -> anyhow::Result<Option<CoingeckoCoinsList>> {
-        let mut builder = self.client.get(format!(
-            "{base_url}/coins/list?include_platform=true",
-            base_url = self.base_url
-        ));
+        let mut builder = self
+            .client
+            .get(format!(
+                "{base_url}/coins/list?include_platform=true",
+                base_url = self.base_url
+            ))
+            .timeout(COINS_LIST_TIMEOUT);
 
         if let Some(etag) = etag {
             builder = builder.header(IF_NONE_MATCH, etag);
@@ -136,17 +140,75 @@ impl CoingeckoClient {
         Ok(CoingeckoCoinsList { coins_list, etag }.into())
     }
 
+    /// Same list as `get_all_metadata`, but coins are parsed while the response is downloaded
+    /// instead of holding the whole response in memory
+    pub async fn stream_all_metadata(
+        &self,
+    ) -> anyhow::Result<impl Stream<Item = anyhow::Result<CoingeckoInfoWithAddress>>> {
+        let response = self
+            .client
+            .get(format!(
+                "{base_url}/coins/list?include_platform=true",
+                base_url = self.base_url
+            ))
+            .timeout(COINS_LIST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let state = (Box::pin(response.bytes_stream()), JsonArrayItems::default());
+
+        Ok(futures::stream::try_unfold(
+            state,
+            |(mut bytes, mut items)| async move {
+                loop {
+                    if let Some(item) = items.pop() {
+                        let coin: CoingeckoCoinsResponse =
+                            serde_json::from_slice(&item).context("Unable to parse coingecko coin")?;
+                        return Ok(Some((coin.into(), (bytes, items))));
+                    }
+
+                    match bytes.next().await {
+                        Some(chunk) => items.push(&chunk?),
+                        None => return Ok(None),
+                    }
+                }
+            },
+        ))
+    }
+
     pub async fn get_historical_prices(
         &self,
         coin_id: &str,
         date_range: &Range<NaiveDate>,
         currency: &impl std::fmt::Display,
+    ) -> anyhow::Result<Vec<(i64, NormDecimal)>> {
+        self.get_market_chart_range(&format!("coins/{coin_id}"), date_range, currency)
+            .await
+    }
+
+    /// Same as `get_historical_prices` for a token identified by its contract address
+    pub async fn get_historical_prices_by_address(
+        &self,
+        address: &StoredTokenAddress,
+        date_range: &Range<NaiveDate>,
+        currency: &impl std::fmt::Display,
+    ) -> anyhow::Result<Vec<(i64, NormDecimal)>> {
+        self.get_market_chart_range(&contract_path(address), date_range, currency)
+            .await
+    }
+
+    async fn get_market_chart_range(
+        &self,
+        coin_path: &str,
+        date_range: &Range<NaiveDate>,
+        currency: &impl std::fmt::Display,
     ) -> anyhow::Result<Vec<(i64, NormDecimal)>> {
         let url = format!(
-            "{base_url}/coins/{coin_id}/market_chart/range?vs_currency={currency}&from={from}&to={to}",
+            "{base_url}/{coin_path}/market_chart/range?vs_currency={currency}&from={from}&to={to}",
             base_url = self.base_url,
-            from = date_range.start.and_time(NaiveTime::default()).timestamp(),
-            to = date_range.end.and_time(NaiveTime::default()).timestamp(),
+            from = date_range.start.and_time(NaiveTime::default()).and_utc().timestamp(),
+            to = date_range.end.and_time(NaiveTime::default()).and_utc().timestamp(),
         );
 
         #[derive(Deserialize)]
@@ -159,13 +221,96 @@ impl CoingeckoClient {
         Ok(prices)
     }
 
+    /// Market data of the coins, `page` starts from 1 with 100 coins per page
+    pub async fn get_coin_markets(
+        &self,
+        vs_currency: &str,
+        ids: &[&str],
+        page: u32,
+    ) -> anyhow::Result<Vec<CoinMarket>> {
+        let mut url = format!(
+            "{base_url}/coins/markets?vs_currency={vs_currency}&page={page}",
+            base_url = self.base_url,
+        );
+
+        if !ids.is_empty() {
+            url.push_str(&format!("&ids={}", ids.join(",")));
+        }
+
+        self.request(&url).await
+    }
+
+    pub async fn get_coin_tickers(&self, coin_id: &str) -> anyhow::Result<CoinTickers> {
+        self.request(&format!("{base_url}/coins/{coin_id}/tickers", base_url = self.base_url))
+            .await
+    }
+
+    /// OHLC candles for the last `days`, Coingecko accepts 1, 7, 14, 30, 90, 180, 365
+    pub async fn get_ohlc(&self, coin_id: &str, vs_currency: &str, days: u32) -> anyhow::Result<Vec<Candle>> {
+        self.request(&format!(
+            "{base_url}/coins/{coin_id}/ohlc?vs_currency={vs_currency}&days={days}",
+            base_url = self.base_url,
+        ))
+        .await
+    }
+
+    pub async fn get_market_chart(
+        &self,
+        coin_id: &str,
+        vs_currency: &str,
+        days: u32,
+        interval: Option<ChartInterval>,
+    ) -> anyhow::Result<MarketChart> {
+        let mut url = format!(
+            "{base_url}/coins/{coin_id}/market_chart?vs_currency={vs_currency}&days={days}",
+            base_url = self.base_url,
+        );
+
+        if let Some(interval) = interval {
+            url.push_str(&format!("&interval={interval}"));
+        }
+
+        self.request(&url).await
+    }
+
+    /// Spot prices of the coins: coin id -> currency -> price
+    pub async fn get_simple_prices(
+        &self,
+        ids: &[&str],
+        vs_currencies: &[&str],
+    ) -> anyhow::Result<HashMap<String, HashMap<String, NormDecimal>>> {
+        self.request(&format!(
+            "{base_url}/simple/price?ids={ids}&vs_currencies={vs_currencies}",
+            base_url = self.base_url,
+            ids = ids.join(","),
+            vs_currencies = vs_currencies.join(","),
+        ))
+        .await
+    }
+
+    /// Spot prices of the tokens by contract addresses: address -> currency -> price
+    pub async fn get_simple_token_prices(
+        &self,
+        platform: &str,
+        contract_addresses: &[&str],
+        vs_currencies: &[&str],
+    ) -> anyhow::Result<HashMap<String, HashMap<String, NormDecimal>>> {
+        self.request(&format!(
+            "{base_url}/simple/token_price/{platform}?contract_addresses={addresses}&vs_currencies={vs_currencies}",
+            base_url = self.base_url,
+            addresses = contract_addresses.join(","),
+            vs_currencies = vs_currencies.join(","),
+        ))
+        .await
+    }
+
     pub async fn request<T: DeserializeOwned>(&self, url: &str) -> anyhow::Result<T> {
         let response = self.client.get(url).send().await?.error_for_status()?;
         Ok(response.json().await?)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CoingeckoCoinsList {
     pub coins_list: HashMap<String, CoingeckoInfoWithAddress>,
     pub etag: Option<String>,
@@ -179,6 +324,15 @@ struct CoingeckoCoinsResponse {
     platforms: HashMap<String, Option<String>>,
 }
 
+/// Path of the token by its contract address on its platform
+fn contract_path(address: &StoredTokenAddress) -> String {
+    format!(
+        "coins/{platform}/contract/{address}",
+        platform = address.platform().coingecko_platform(),
+        address = address.to_unprefixed_string()
+    )
+}
+
 impl From<CoingeckoCoinsResponse> for CoingeckoInfoWithAddress {
     fn from(value: CoingeckoCoinsResponse) -> Self {
         Self {
@@ -194,8 +348,45 @@ impl From<CoingeckoCoinsResponse> for CoingeckoInfoWithAddress {
 
 #[cfg(test)]
 mod tests {
-    use super::{CoingeckoClient, CoingeckoCoinsList};
+    use super::{contract_path, CoingeckoClient, CoingeckoCoinsList};
     use claims::{assert_none, assert_some};
+    use http_client::{settings::HttpClientSettings, testing::serve};
+    use normdecimal::NormDecimal;
+    use std::str::FromStr;
+    use token_address::StoredTokenAddress;
+
+    #[test]
+    fn should_build_contract_path_without_chain_prefix() {
+        let address: StoredTokenAddress = "polygon:0x2791bca1f2de4661ed88a30c99a7a9449aa84174".parse().unwrap();
+        assert_eq!(
+            contract_path(&address),
+            "coins/polygon-pos/contract/0x2791bca1f2de4661ed88a30c99a7a9449aa84174"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_get_simple_prices() -> anyhow::Result<()> {
+        let (base_url, request_line) = serve(|_| {
+            let body = r#"{"bitcoin":{"usd":67187.33,"eur":61990.2},"solana":{"usd":145.12,"eur":133.9}}"#;
+            (200, body.to_owned())
+        });
+        let client = CoingeckoClient {
+            base_url,
+            ..CoingeckoClient::new(HttpClientSettings::default())?
+        };
+
+        let prices = client
+            .get_simple_prices(&["bitcoin", "solana"], &["usd", "eur"])
+            .await?;
+        assert_eq!(
+            request_line.recv()?,
+            "GET /simple/price?ids=bitcoin,solana&vs_currencies=usd,eur HTTP/1.1"
+        );
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices["bitcoin"]["usd"], NormDecimal::from_str("67187.33")?);
+        assert_eq!(prices["solana"]["eur"], NormDecimal::from_str("133.9")?);
+        Ok(())
+    }
 
     #[tokio::test]
     async fn should_cache_coins_list() -> anyhow::Result<()> {
@@ -0,0 +1,74 @@
+use std::{collections::VecDeque, mem};
+
+/// Splits a JSON array received in chunks into raw items, so they can be deserialized one by one
+#[derive(Default)]
+pub(crate) struct JsonArrayItems {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    item: Vec<u8>,
+    ready: VecDeque<Vec<u8>>,
+}
+
+impl JsonArrayItems {
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if self.depth > 1 {
+                self.item.push(byte);
+            }
+
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {},
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    if self.depth == 2 {
+                        self.item.push(byte);
+                    }
+                },
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 1 {
+                        self.ready.push_back(mem::take(&mut self.item));
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Vec<u8>> {
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_array_in_chunks() {
+        let json = br#"[{"id": "a", "platforms": {"solana": "x"}}, {"id": "b\"}", "platforms": {}}]"#;
+        let mut items = JsonArrayItems::default();
+
+        for chunk in json.chunks(7) {
+            items.push(chunk);
+        }
+
+        assert_eq!(
+            items.pop().unwrap(),
+            br#"{"id": "a", "platforms": {"solana": "x"}}"#.to_vec()
+        );
+        assert_eq!(items.pop().unwrap(), br#"{"id": "b\"}", "platforms": {}}"#.to_vec());
+        assert_eq!(items.pop(), None);
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
+use normdecimal::NormDecimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
@@ -16,8 +18,153 @@ impl CoingeckoInfo {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct CoingeckoInfoWithAddress {
     pub metadata: CoingeckoInfo,
     pub addresses: HashMap<String, String>, // Platform, address
 }
+
+/// Item of `/coins/markets`
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct CoinMarket {
+    pub id: String,
+    pub symbol: String,
+    pub name: String,
+    pub image: Option<String>,
+    pub current_price: Option<NormDecimal>,
+    pub market_cap: Option<NormDecimal>,
+    pub market_cap_rank: Option<u32>,
+    pub fully_diluted_valuation: Option<NormDecimal>,
+    pub total_volume: Option<NormDecimal>,
+    pub high_24h: Option<NormDecimal>,
+    pub low_24h: Option<NormDecimal>,
+    pub price_change_24h: Option<NormDecimal>,
+    pub price_change_percentage_24h: Option<NormDecimal>,
+    pub circulating_supply: Option<NormDecimal>,
+    pub total_supply: Option<NormDecimal>,
+    pub max_supply: Option<NormDecimal>,
+    pub last_updated: Option<DateTime<Utc>>,
+}
+
+/// Response of `/coins/{id}/tickers`
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct CoinTickers {
+    pub name: String,
+    pub tickers: Vec<Ticker>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct Ticker {
+    pub base: String,
+    pub target: String,
+    pub market: TickerMarket,
+    pub last: Option<NormDecimal>,
+    pub volume: Option<NormDecimal>,
+    #[serde(default)]
+    pub converted_last: HashMap<String, NormDecimal>,
+    #[serde(default)]
+    pub converted_volume: HashMap<String, NormDecimal>,
+    pub trust_score: Option<String>,
+    pub bid_ask_spread_percentage: Option<NormDecimal>,
+    #[serde(default)]
+    pub is_anomaly: bool,
+    #[serde(default)]
+    pub is_stale: bool,
+    pub trade_url: Option<String>,
+    pub coin_id: Option<String>,
+    pub target_coin_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct TickerMarket {
+    pub name: String,
+    pub identifier: String,
+    #[serde(default)]
+    pub has_trading_incentive: bool,
+}
+
+/// Item of `/coins/{id}/ohlc`
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(from = "(i64, NormDecimal, NormDecimal, NormDecimal, NormDecimal)")]
+pub struct Candle {
+    /// Close time in milliseconds
+    pub timestamp: i64,
+    pub open: NormDecimal,
+    pub high: NormDecimal,
+    pub low: NormDecimal,
+    pub close: NormDecimal,
+}
+
+impl From<(i64, NormDecimal, NormDecimal, NormDecimal, NormDecimal)> for Candle {
+    fn from((timestamp, open, high, low, close): (i64, NormDecimal, NormDecimal, NormDecimal, NormDecimal)) -> Self {
+        Self {
+            timestamp,
+            open,
+            high,
+            low,
+            close,
+        }
+    }
+}
+
+/// Response of `/coins/{id}/market_chart`, items are (timestamp in milliseconds, value)
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct MarketChart {
+    pub prices: Vec<(i64, NormDecimal)>,
+    pub market_caps: Vec<(i64, NormDecimal)>,
+    pub total_volumes: Vec<(i64, NormDecimal)>,
+}
+
+/// Granularity of the market chart, chosen by Coingecko from `days` if not set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartInterval {
+    Daily,
+    Hourly,
+}
+
+impl std::fmt::Display for ChartInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ChartInterval::Daily => "daily",
+            ChartInterval::Hourly => "hourly",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_coin_market() {
+        let market: CoinMarket = serde_json::from_str(
+            r#"{
+                "id": "solana", "symbol": "sol", "name": "Solana", "image": null,
+                "current_price": 21.37, "market_cap": 8123456789, "market_cap_rank": 11,
+                "fully_diluted_valuation": null, "total_volume": 512345678.5, "high_24h": 22.1,
+                "low_24h": 20.9, "price_change_24h": -0.45, "price_change_percentage_24h": -2.06,
+                "circulating_supply": 380000000.0, "total_supply": null, "max_supply": null,
+                "last_updated": "2023-03-01T12:00:00.000Z"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(market.current_price, Some("21.37".parse().unwrap()));
+        assert_eq!(market.market_cap_rank, Some(11));
+        assert_eq!(market.total_supply, None);
+        assert!(market.last_updated.is_some());
+    }
+
+    #[test]
+    fn deserialize_candle() {
+        let candles: Vec<Candle> = serde_json::from_str("[[1677628800000, 21.5, 22.1, 20.9, 21.37]]").unwrap();
+
+        assert_eq!(candles, [Candle {
+            timestamp: 1677628800000,
+            open: "21.5".parse().unwrap(),
+            high: "22.1".parse().unwrap(),
+            low: "20.9".parse().unwrap(),
+            close: "21.37".parse().unwrap(),
+        }]);
+    }
+}
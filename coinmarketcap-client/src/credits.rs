@@ -0,0 +1,181 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Datelike, Utc};
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram},
+};
+
+/// Monthly limit of the API plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreditBudget {
+    pub monthly_credits: u64,
+    /// Share of the budget after which every request is logged with a warning
+    pub warn_ratio: f64,
+    /// Refuse requests when the budget is exhausted instead of only logging
+    pub refuse_when_exhausted: bool,
+}
+
+impl CreditBudget {
+    pub fn new(monthly_credits: u64) -> Self {
+        Self {
+            monthly_credits,
+            warn_ratio: 0.9,
+            refuse_when_exhausted: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Coinmarketcap credit budget is exhausted: {used} of {budget} credits are used")]
+pub struct CreditsExhausted {
+    pub used: u64,
+    pub budget: u64,
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+    /// Months since year 0, the usage is reset when it changes
+    month: i32,
+    credits: u64,
+}
+
+/// Counts credits reported in `status.credit_count` of the responses
+pub struct CreditTracker {
+    budget: Option<CreditBudget>,
+    usage: Mutex<Usage>,
+    credits: Counter<u64>,
+    elapsed: Histogram<u64>,
+}
+
+impl CreditTracker {
+    pub fn new(budget: Option<CreditBudget>) -> Self {
+        let meter = global::meter("coinmarketcap-client");
+
+        Self {
+            budget,
+            usage: Mutex::new(Usage::default()),
+            credits: meter
+                .u64_counter("coinmarketcap.credits")
+                .with_description("Credits spent on Coinmarketcap requests")
+                .init(),
+            elapsed: meter
+                .u64_histogram("coinmarketcap.elapsed_ms")
+                .with_description("Processing time of Coinmarketcap requests reported by the API")
+                .init(),
+        }
+    }
+
+    /// Credits used in the current month
+    pub fn used(&self) -> u64 {
+        self.used_at(Utc::now())
+    }
+
+    pub(crate) fn check(&self) -> Result<(), CreditsExhausted> {
+        self.check_at(Utc::now())
+    }
+
+    pub(crate) fn record(&self, credit_count: u64, elapsed_ms: u64) {
+        let cx = opentelemetry::Context::current();
+        self.credits.add(&cx, credit_count, &[]);
+        self.elapsed.record(&cx, elapsed_ms, &[]);
+
+        self.record_at(Utc::now(), credit_count);
+    }
+
+    fn used_at(&self, now: DateTime<Utc>) -> u64 {
+        let usage = self.usage.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if usage.month == month(now) {
+            usage.credits
+        } else {
+            0
+        }
+    }
+
+    fn check_at(&self, now: DateTime<Utc>) -> Result<(), CreditsExhausted> {
+        let Some(budget) = &self.budget else {
+            return Ok(());
+        };
+
+        let used = self.used_at(now);
+        if used < budget.monthly_credits {
+            return Ok(());
+        }
+
+        let error = CreditsExhausted {
+            used,
+            budget: budget.monthly_credits,
+        };
+
+        if budget.refuse_when_exhausted {
+            return Err(error);
+        }
+
+        tracing::warn!(%error, "request is sent over the budget");
+        Ok(())
+    }
+
+    fn record_at(&self, now: DateTime<Utc>, credit_count: u64) {
+        let used = {
+            let mut usage = self.usage.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let month = month(now);
+
+            if usage.month != month {
+                *usage = Usage { month, credits: 0 };
+            }
+
+            usage.credits += credit_count;
+            usage.credits
+        };
+
+        if let Some(budget) = &self.budget {
+            if used as f64 >= budget.monthly_credits as f64 * budget.warn_ratio {
+                tracing::warn!(
+                    used,
+                    budget = budget.monthly_credits,
+                    "coinmarketcap credit budget is nearly exhausted"
+                );
+            }
+        }
+    }
+}
+
+impl Default for CreditTracker {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+fn month(date: DateTime<Utc>) -> i32 {
+    date.year() * 12 + date.month0() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn budget() {
+        let tracker = CreditTracker::new(Some(CreditBudget {
+            refuse_when_exhausted: true,
+            ..CreditBudget::new(10)
+        }));
+        let march = Utc.with_ymd_and_hms(2023, 3, 1, 0, 0, 0).unwrap();
+        let april = Utc.with_ymd_and_hms(2023, 4, 1, 0, 0, 0).unwrap();
+
+        tracker.record_at(march, 6);
+        assert!(tracker.check_at(march).is_ok());
+
+        tracker.record_at(march, 4);
+        assert_eq!(tracker.used_at(march), 10);
+        assert_eq!(tracker.check_at(march), Err(CreditsExhausted { used: 10, budget: 10 }));
+
+        // usage is reset every month
+        assert_eq!(tracker.used_at(april), 0);
+        assert!(tracker.check_at(april).is_ok());
+        tracker.record_at(april, 1);
+        assert_eq!(tracker.used_at(april), 1);
+    }
+}
@@ -1,13 +1,22 @@
-use std::ops::Range;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Arc,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::NaiveDate;
+use credits::{CreditBudget, CreditTracker};
+use futures::{StreamExt, TryStreamExt};
 use http_client::settings::HttpClientSettings;
-use reqwest::Client;
-use serde::de::DeserializeOwned;
-use serde_json::Value;
-use types::PricesResponse;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{de::DeserializeOwned, Deserialize};
+use types::{
+    CoinId, CryptocurrencyInfo, CryptocurrencyInfoResponse, CryptocurrencyMapItem, DataResponse, LatestQuote, Listing,
+    PricesResponse, Status,
+};
 
+pub mod credits;
 pub mod types;
 
 pub static URL: &str = "https://pro-api.coinmarketcap.com";
@@ -16,17 +25,27 @@ static SANDBOX_URL: &str = "https://sandbox-api.coinmarketcap.com";
 static SANDBOX_API_KEY: &str = "b54bcf4d-1bca-4e8e-9a24-22ff2c3d462c";
 
 static CRYPTOCURRENCY_INFO: &str = "v2/cryptocurrency/info";
+static QUOTES_LATEST: &str = "v2/cryptocurrency/quotes/latest";
+static CRYPTOCURRENCY_MAP: &str = "v1/cryptocurrency/map";
+static LISTINGS_LATEST: &str = "v1/cryptocurrency/listings/latest";
+
+/// Maximum `limit` of the paginated endpoints
+pub const MAX_PAGE_SIZE: usize = 5000;
 
 #[derive(Clone)]
 pub struct CoinmarketcapClient {
-    client: Client,
+    client: ClientWithMiddleware,
     api_key: String,
     pub base_url: String,
+    history_chunk_size: usize,
+    history_parallelism: usize,
+    credits: Arc<CreditTracker>,
 }
 
 impl Default for CoinmarketcapClient {
+    /// Sandbox client, the production API requires an API key
     fn default() -> Self {
-        Self::new(Default::default())
+        Self::new(HttpClientSettings::sandbox()).expect("Default coinmarketcap client must be built")
     }
 }
 
@@ -36,16 +55,48 @@ impl CoinmarketcapClient {
         format!("{url}/{CRYPTOCURRENCY_INFO}?address={address}", url = self.base_url)
     }
 
+    /// Request pages of `url` until `limit` items are received or the last page is reached
+    async fn request_pages<T: DeserializeOwned>(&self, url: &str, limit: Option<usize>) -> Result<Vec<T>> {
+        let mut items = vec![];
+
+        loop {
+            let page_size = limit.map_or(MAX_PAGE_SIZE, |limit| {
+                limit.saturating_sub(items.len()).min(MAX_PAGE_SIZE)
+            });
+            if page_size == 0 {
+                break;
+            }
+
+            let response: DataResponse<Vec<T>> = self
+                .request(&format!(
+                    "{url}start={start}&limit={page_size}",
+                    start = items.len() + 1
+                ))
+                .await?;
+            let page = response.into_data()?;
+            let is_last = page.len() < page_size;
+
+            items.extend(page);
+            if is_last {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// `key` is the query parameter of the coins, `symbol` or `id`
     fn build_historical_prices_url(
         &self,
-        coin_ids: &[&str],
+        key: &str,
+        coins: &[String],
         date_range: Range<NaiveDate>,
         currency: &impl std::fmt::Display,
     ) -> String {
         format!(
-            "{}/v2/cryptocurrency/quotes/historical?interval=daily&aux=price&symbol={}&time_start={}&time_end={}&convert={}",
+            "{}/v2/cryptocurrency/quotes/historical?interval=daily&aux=price&{key}={}&time_start={}&time_end={}&convert={}",
             self.base_url,
-            coin_ids.join(","),
+            coins.join(","),
             date_range.start.format("%Y-%m-%d"),
             date_range.end.format("%Y-%m-%d"),
             currency,
@@ -55,49 +106,241 @@ impl CoinmarketcapClient {
 
 // Pub api
 impl CoinmarketcapClient {
-    pub fn new(settings: HttpClientSettings) -> Self {
-        let client = (&settings).into();
+    /// Fails if the HTTP client can't be built or the API key is missing outside of the sandbox
+    pub fn new(settings: HttpClientSettings) -> Result<Self> {
+        let client = ClientWithMiddleware::try_from(&settings).context("Unable to build Coinmarketcap client")?;
+        let api_key = settings.api_key.as_ref().map(|api_key| api_key.expose_secret().clone());
         let (base_url, api_key) = if settings.is_sandbox {
-            (
-                SANDBOX_URL.into(),
-                settings.api_key.unwrap_or_else(|| SANDBOX_API_KEY.into()),
-            )
+            (SANDBOX_URL.into(), api_key.unwrap_or_else(|| SANDBOX_API_KEY.into()))
         } else {
-            (URL.into(), settings.api_key.expect("Missing CMC API key"))
+            (URL.into(), api_key.context("Missing CMC API key")?)
         };
 
-        Self {
+        Ok(Self {
             base_url,
             client,
             api_key,
-        }
+            history_chunk_size: settings.history_chunk_size.max(1),
+            history_parallelism: settings.history_parallelism.max(1),
+            credits: Default::default(),
+        })
+    }
+
+    /// Warn about or refuse requests when the monthly credit budget is nearly exhausted
+    pub fn with_credit_budget(mut self, budget: CreditBudget) -> Self {
+        self.credits = Arc::new(CreditTracker::new(Some(budget)));
+        self
+    }
+
+    pub fn credits(&self) -> &CreditTracker {
+        &self.credits
     }
 
+    /// Credits of every response are counted by `CreditTracker`
     pub async fn request<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        #[derive(Deserialize)]
+        struct StatusOnly {
+            status: Option<Status>,
+        }
+
+        self.credits.check()?;
+
         let response = self
             .client
             .get(url)
             .header("X-CMC_PRO_API_KEY", &self.api_key)
             .send()
+            .await?
+            .bytes()
             .await?;
 
-        Ok(response.json().await?)
+        if let Ok(StatusOnly { status: Some(status) }) = serde_json::from_slice(&response) {
+            self.credits.record(status.credit_count, status.elapsed);
+        }
+
+        Ok(serde_json::from_slice(&response)?)
     }
 
-    pub async fn cryptocurrency_info(&self, address: String) -> Result<Value> {
-        self.request(self.build_cryptocurrency_info_url(address).as_str()).await
+    /// Errors reported by Coinmarketcap can be downcasted to `CoinmarketcapError`
+    pub async fn cryptocurrency_info(&self, address: String) -> Result<HashMap<CoinId, CryptocurrencyInfo>> {
+        let response: CryptocurrencyInfoResponse = self
+            .request(self.build_cryptocurrency_info_url(address).as_str())
+            .await?;
+
+        Ok(response.into_data()?)
     }
 
+    /// Latest quotes of the coins by CMC ids in `convert` currencies (comma separated)
+    pub async fn quotes_latest(&self, ids: &[u64], convert: &str) -> Result<HashMap<CoinId, LatestQuote>> {
+        let ids = ids.iter().map(ToString::to_string).collect::<Vec<_>>().join(",");
+        let response: DataResponse<HashMap<CoinId, LatestQuote>> = self
+            .request(&format!(
+                "{url}/{QUOTES_LATEST}?id={ids}&convert={convert}",
+                url = self.base_url
+            ))
+            .await?;
+
+        Ok(response.into_data()?)
+    }
+
+    /// All cryptocurrencies known to Coinmarketcap with their platforms and contract addresses
+    pub async fn cryptocurrency_map(&self) -> Result<Vec<CryptocurrencyMapItem>> {
+        self.request_pages(&format!("{url}/{CRYPTOCURRENCY_MAP}?", url = self.base_url), None)
+            .await
+    }
+
+    /// Top `limit` cryptocurrencies by market cap with quotes in `convert` currencies (comma separated)
+    pub async fn listings_latest(&self, limit: usize, convert: &str) -> Result<Vec<Listing>> {
+        self.request_pages(
+            &format!("{url}/{LISTINGS_LATEST}?convert={convert}&", url = self.base_url),
+            Some(limit),
+        )
+        .await
+    }
+
+    /// Coins are requested in chunks of `HttpClientSettings::history_chunk_size`,
+    /// `HttpClientSettings::history_parallelism` chunks at a time, and merged into one response. Symbols are
+    /// case-insensitive, repeated ones are requested once. Prefer `historical_prices_by_ids` as symbols are
+    /// ambiguous
     pub async fn historical_prices(
         &self,
         coin_ids: &[&str],
         date_range: Range<NaiveDate>,
-        currency: &impl std::fmt::Display,
+        currency: &(impl std::fmt::Display + Sync),
     ) -> Result<PricesResponse> {
-        self.request(
-            self.build_historical_prices_url(coin_ids, date_range, currency)
-                .as_str(),
-        )
-        .await
+        let mut requested = HashSet::new();
+        let symbols: Vec<String> = coin_ids
+            .iter()
+            .filter(|coin_id| requested.insert(coin_id.to_uppercase()))
+            .map(ToString::to_string)
+            .collect();
+
+        self.historical_prices_by("symbol", symbols, date_range, currency).await
+    }
+
+    /// Same as `historical_prices`, but coins are requested by CMC ids and the response is keyed by them
+    pub async fn historical_prices_by_ids(
+        &self,
+        ids: &[u64],
+        date_range: Range<NaiveDate>,
+        currency: &(impl std::fmt::Display + Sync),
+    ) -> Result<PricesResponse> {
+        let mut requested = HashSet::new();
+        let ids: Vec<String> = ids
+            .iter()
+            .filter(|id| requested.insert(**id))
+            .map(ToString::to_string)
+            .collect();
+
+        self.historical_prices_by("id", ids, date_range, currency).await
+    }
+
+    async fn historical_prices_by(
+        &self,
+        key: &str,
+        coins: Vec<String>,
+        date_range: Range<NaiveDate>,
+        currency: &(impl std::fmt::Display + Sync),
+    ) -> Result<PricesResponse> {
+        let urls: Vec<_> = coins
+            .chunks(self.history_chunk_size)
+            .map(|chunk| self.build_historical_prices_url(key, chunk, date_range.clone(), currency))
+            .collect();
+
+        futures::stream::iter(urls)
+            .map(|url| async move { self.request::<PricesResponse>(&url).await })
+            .buffer_unordered(self.history_parallelism)
+            .try_fold(PricesResponse::default(), |mut prices, chunk| async move {
+                prices.merge(chunk);
+                Ok(prices)
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    /// Respond to requests of historical quotes with a quote of every requested symbol or id
+    fn serve_quotes() -> (String, mpsc::Receiver<String>) {
+        http_client::testing::serve(|request_line| {
+            let query = request_line.split(['?', ' ']).nth(2).unwrap();
+            let coins = query
+                .split('&')
+                .find_map(|param| param.strip_prefix("symbol=").or_else(|| param.strip_prefix("id=")))
+                .unwrap();
+            let data: Vec<_> = coins
+                .split(',')
+                .map(|coin| format!(r#""{coin}":[{{"timestamp":"2024-01-01T00:00:00Z"}}]"#))
+                .collect();
+            let body = format!(
+                r#"{{"status":{{"error_code":0,"credit_count":1}},"data":{{{}}}}}"#,
+                data.join(",")
+            );
+            (200, body)
+        })
+    }
+
+    #[test]
+    fn require_api_key() {
+        let error = CoinmarketcapClient::new(HttpClientSettings::default()).err().unwrap();
+        assert_eq!(error.to_string(), "Missing CMC API key");
+        assert!(CoinmarketcapClient::new(HttpClientSettings::sandbox()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn historical_prices_in_chunks() -> anyhow::Result<()> {
+        let (base_url, request_lines) = serve_quotes();
+        let client = CoinmarketcapClient {
+            base_url,
+            ..CoinmarketcapClient::new(HttpClientSettings {
+                is_sandbox: true,
+                history_chunk_size: 2,
+                history_parallelism: 1,
+                ..Default::default()
+            })?
+        };
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let prices = client
+            .historical_prices(&["BTC", "ETH", "btc", "SOL"], date..date, &"USD")
+            .await?
+            .into_data()?;
+
+        let mut requested: Vec<_> = request_lines.try_iter().collect();
+        requested.sort();
+        assert_eq!(requested.len(), 2);
+        assert!(requested[0].contains("symbol=BTC,ETH&"));
+        assert!(requested[1].contains("symbol=SOL&"));
+
+        let mut symbols: Vec<_> = prices.keys().cloned().collect();
+        symbols.sort();
+        assert_eq!(symbols, ["BTC", "ETH", "SOL"]);
+        assert_eq!(prices["SOL"].len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn historical_prices_by_ids() -> anyhow::Result<()> {
+        let (base_url, request_lines) = serve_quotes();
+        let client = CoinmarketcapClient {
+            base_url,
+            ..CoinmarketcapClient::new(HttpClientSettings::sandbox())?
+        };
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let prices = client
+            .historical_prices_by_ids(&[1, 1027, 1], date..date, &"USD")
+            .await?
+            .into_data()?;
+
+        assert!(request_lines.recv()?.contains("&id=1,1027&"));
+        let mut ids: Vec<_> = prices.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, ["1", "1027"]);
+        Ok(())
     }
 }
@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use normdecimal::NormDecimal;
 use serde::Deserialize;
+use serde_with::{serde_as, BoolFromInt};
 
 #[derive(Deserialize)]
 pub struct Quotes {
@@ -20,19 +21,40 @@ pub struct Price {
     pub price: NormDecimal,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct Status {
     error_code: u32,
-    error_message: String,
+    #[serde(default)]
+    error_message: Option<String>,
+    /// Processing time in milliseconds
+    #[serde(default)]
+    pub elapsed: u64,
+    #[serde(default)]
+    pub credit_count: u64,
     // These fields can be used later
     //timestamp: DateTime<Utc>,
-    //elapsed: u32,
-    //credit_count: u32,
+}
+
+impl Status {
+    pub fn error(&self) -> Option<CoinmarketcapError> {
+        (self.error_code != 0).then(|| CoinmarketcapError {
+            code: self.error_code,
+            message: self.error_message.clone().unwrap_or_default(),
+        })
+    }
+}
+
+/// Error reported by Coinmarketcap in the `status` of the response
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Coinmarketcap error {code}: {message}")]
+pub struct CoinmarketcapError {
+    pub code: u32,
+    pub message: String,
 }
 
 pub type CoinId = String;
 
-#[derive(Deserialize)]
+#[derive(Default, Deserialize)]
 pub struct PricesResponse {
     #[serde(default)]
     data: Option<HashMap<CoinId, Vec<Quotes>>>,
@@ -50,13 +72,216 @@ impl PricesResponse {
     }
 
     fn error(&self) -> Result<()> {
+        match self.status.error() {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Response of both requests, it's the first error if either failed
+    pub(crate) fn merge(&mut self, other: PricesResponse) {
         if self.is_error() {
-            bail!(
-                "Coinmarketcap error {}: {}",
-                self.status.error_code,
-                self.status.error_message
-            );
+            return;
+        }
+        if other.is_error() {
+            *self = other;
+            return;
+        }
+
+        self.status.elapsed += other.status.elapsed;
+        self.status.credit_count += other.status.credit_count;
+        if let Some(data) = other.data {
+            self.data.get_or_insert_with(HashMap::new).extend(data);
+        }
+    }
+}
+
+/// Item of `v2/cryptocurrency/info`
+#[derive(Debug, Clone, Deserialize)]
+pub struct CryptocurrencyInfo {
+    pub id: u64,
+    pub name: String,
+    pub symbol: String,
+    pub slug: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Platform of the token, `None` for coins with their own chain
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    #[serde(default)]
+    pub contract_address: Vec<ContractAddress>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Platform {
+    pub id: u64,
+    pub name: String,
+    pub slug: String,
+    pub symbol: String,
+    pub token_address: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContractAddress {
+    pub contract_address: String,
+    pub platform: ContractPlatform,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContractPlatform {
+    pub name: String,
+    pub coin: PlatformCoin,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlatformCoin {
+    pub id: String,
+    pub name: String,
+    pub symbol: String,
+    pub slug: String,
+}
+
+/// Common envelope of Coinmarketcap responses
+#[derive(Deserialize)]
+pub struct DataResponse<T> {
+    #[serde(default = "Option::default")]
+    data: Option<T>,
+    status: Status,
+}
+
+impl<T: Default> DataResponse<T> {
+    /// Empty if nothing is found
+    pub fn into_data(self) -> Result<T, CoinmarketcapError> {
+        match self.status.error() {
+            Some(error) => Err(error),
+            None => Ok(self.data.unwrap_or_default()),
         }
-        Ok(())
+    }
+}
+
+pub type CryptocurrencyInfoResponse = DataResponse<HashMap<CoinId, CryptocurrencyInfo>>;
+
+/// Item of `v2/cryptocurrency/quotes/latest`
+#[derive(Deserialize)]
+pub struct LatestQuote {
+    pub id: u64,
+    pub symbol: String,
+    /// Currency -> price
+    pub quote: HashMap<String, Price>,
+}
+
+/// Item of `v1/cryptocurrency/map`
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub struct CryptocurrencyMapItem {
+    pub id: u64,
+    #[serde(default)]
+    pub rank: Option<u32>,
+    pub name: String,
+    pub symbol: String,
+    pub slug: String,
+    #[serde_as(as = "BoolFromInt")]
+    pub is_active: bool,
+    #[serde(default)]
+    pub first_historical_data: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_historical_data: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub platform: Option<Platform>,
+}
+
+/// Item of `v1/cryptocurrency/listings/latest`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Listing {
+    pub id: u64,
+    pub name: String,
+    pub symbol: String,
+    pub slug: String,
+    #[serde(default)]
+    pub cmc_rank: Option<u32>,
+    #[serde(default)]
+    pub num_market_pairs: Option<u32>,
+    #[serde(default)]
+    pub circulating_supply: Option<NormDecimal>,
+    #[serde(default)]
+    pub total_supply: Option<NormDecimal>,
+    #[serde(default)]
+    pub max_supply: Option<NormDecimal>,
+    #[serde(default)]
+    pub platform: Option<Platform>,
+    /// Currency -> quote
+    pub quote: HashMap<String, ListingQuote>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListingQuote {
+    #[serde(default)]
+    pub price: Option<NormDecimal>,
+    #[serde(default)]
+    pub volume_24h: Option<NormDecimal>,
+    #[serde(default)]
+    pub market_cap: Option<NormDecimal>,
+    #[serde(default)]
+    pub percent_change_24h: Option<NormDecimal>,
+    pub last_updated: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cryptocurrency_info_response() {
+        let response: CryptocurrencyInfoResponse = serde_json::from_str(
+            r#"{
+                "status": {"error_code": 0, "error_message": null},
+                "data": {"6538": {
+                    "id": 6538, "name": "Curve DAO Token", "symbol": "CRV", "slug": "curve-dao-token",
+                    "category": "token", "tags": ["defi"],
+                    "platform": {"id": 1027, "name": "Ethereum", "slug": "ethereum", "symbol": "ETH",
+                        "token_address": "0xd533a949740bb3306d119cc777fa900ba034cd52"},
+                    "contract_address": [{"contract_address": "7gjNiPun3AzEazTZoFEjZgcBMeuaXdpjHq2raZTmTrfs",
+                        "platform": {"name": "Solana", "coin": {"id": "5426", "name": "Solana", "symbol": "SOL", "slug": "solana"}}}]
+                }}
+            }"#,
+        )
+        .unwrap();
+
+        let data = response.into_data().unwrap();
+        let info = &data["6538"];
+        assert_eq!(info.symbol, "CRV");
+        assert_eq!(info.platform.as_ref().unwrap().name, "Ethereum");
+        assert_eq!(info.contract_address[0].platform.coin.symbol, "SOL");
+
+        let response: CryptocurrencyInfoResponse = serde_json::from_str(
+            r#"{"status": {"error_code": 400, "error_message": "Invalid value for \"address\""}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.into_data().unwrap_err().code, 400);
+    }
+
+    #[test]
+    fn cryptocurrency_map_response() {
+        let response: DataResponse<Vec<CryptocurrencyMapItem>> = serde_json::from_str(
+            r#"{
+                "status": {"error_code": 0, "error_message": null},
+                "data": [{
+                    "id": 6538, "rank": 90, "name": "Curve DAO Token", "symbol": "CRV", "slug": "curve-dao-token",
+                    "is_active": 1, "first_historical_data": "2020-08-14T00:00:00.000Z",
+                    "last_historical_data": "2023-03-01T00:00:00.000Z",
+                    "platform": {"id": 1027, "name": "Ethereum", "slug": "ethereum", "symbol": "ETH",
+                        "token_address": "0xd533a949740bb3306d119cc777fa900ba034cd52"}
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let data = response.into_data().unwrap();
+        assert!(data[0].is_active);
+        assert_eq!(
+            data[0].platform.as_ref().unwrap().token_address,
+            "0xd533a949740bb3306d119cc777fa900ba034cd52"
+        );
     }
 }
@@ -1 +1,43 @@
+use std::sync::Arc;
+
+use middleware::{RetryMiddleware, TracingMiddleware};
+use rate_limit::RateLimiter;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use settings::HttpClientSettings;
+
+pub mod middleware;
+pub mod rate_limit;
 pub mod settings;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/// Wrap `client` with the shared middleware stack, every retry attempt is rate limited and traced separately.
+/// Fails if `rate_limit_per_sec` isn't positive
+pub fn with_middleware(client: reqwest::Client, settings: &HttpClientSettings) -> anyhow::Result<ClientWithMiddleware> {
+    let rate_limiter = settings.rate_limit_per_sec.map(RateLimiter::new).transpose()?;
+    Ok(build(client, settings, rate_limiter.map(Arc::new)))
+}
+
+/// Same as `with_middleware`, but requests are limited by `rate_limiter` shared with other clients instead of
+/// `rate_limit_per_sec`
+pub fn with_shared_rate_limiter(
+    client: reqwest::Client,
+    settings: &HttpClientSettings,
+    rate_limiter: Arc<RateLimiter>,
+) -> ClientWithMiddleware {
+    build(client, settings, Some(rate_limiter))
+}
+
+fn build(
+    client: reqwest::Client,
+    settings: &HttpClientSettings,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> ClientWithMiddleware {
+    let mut builder = ClientBuilder::new(client).with(RetryMiddleware::from(settings));
+
+    if let Some(rate_limiter) = rate_limiter {
+        builder = builder.with_arc(rate_limiter);
+    }
+
+    builder.with(TracingMiddleware).build()
+}
@@ -0,0 +1,268 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use backoff::{backoff::Backoff, ExponentialBackoff};
+
+use opentelemetry::{
+    global,
+    metrics::{Histogram, Unit},
+    propagation::Injector,
+    Context, KeyValue,
+};
+use reqwest::{
+    header::{HeaderName, HeaderValue, RETRY_AFTER},
+    Method, Request, Response, StatusCode,
+};
+use reqwest_middleware::{Middleware, Next};
+use task_local_extensions::Extensions;
+use tracing::{field::Empty, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::settings::HttpClientSettings;
+
+static DURATION: OnceLock<Histogram<f64>> = OnceLock::new();
+
+/// Longest `Retry-After` honored, so a misbehaving server can't stall the request for hours
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Records a client span per request, propagates W3C trace context and reports
+/// `http_client.duration` histogram by method, host and status.
+///
+/// Query is stripped from the url recorded in the span as it may contain API keys.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingMiddleware;
+
+#[async_trait::async_trait]
+impl Middleware for TracingMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let method = req.method().to_string();
+        let host = req.url().host_str().unwrap_or_default().to_owned();
+        let span = tracing::info_span!(
+            "http_request",
+            otel.name = %format!("{method} {host}"),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            http.method = %method,
+            http.url = %format!("{}{}", req.url().origin().ascii_serialization(), req.url().path()),
+            http.status_code = Empty,
+        );
+
+        let cx = span.context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&cx, &mut HeaderInjector(req.headers_mut()))
+        });
+
+        let started = Instant::now();
+        let result = next.run(req, extensions).instrument(span.clone()).await;
+        let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+
+        let status = match &result {
+            Ok(response) => {
+                span.record("http.status_code", response.status().as_u16());
+                if response.status().is_server_error() {
+                    span.record("otel.status_code", "ERROR");
+                }
+                response.status().as_str().to_owned()
+            },
+            Err(_) => {
+                span.record("otel.status_code", "ERROR");
+                "error".to_owned()
+            },
+        };
+
+        duration().record(&Context::current(), elapsed, &[
+            KeyValue::new("method", method),
+            KeyValue::new("host", host),
+            KeyValue::new("status", status),
+        ]);
+
+        result
+    }
+}
+
+/// Retries `GET` and `HEAD` requests on connection errors and configured statuses
+/// with jittered exponential backoff, or after the delay of the `Retry-After` header
+#[derive(Debug, Clone)]
+pub struct RetryMiddleware {
+    max_retries: u32,
+    base_delay: Duration,
+    retry_on: Vec<StatusCode>,
+}
+
+impl From<&HttpClientSettings> for RetryMiddleware {
+    fn from(settings: &HttpClientSettings) -> Self {
+        Self {
+            max_retries: settings.max_retries,
+            base_delay: settings.retry_base_delay,
+            retry_on: settings
+                .retry_on
+                .iter()
+                .filter_map(|status| StatusCode::from_u16(*status).ok())
+                .collect(),
+        }
+    }
+}
+
+impl RetryMiddleware {
+    fn should_retry(&self, result: &reqwest_middleware::Result<Response>) -> bool {
+        match result {
+            Ok(response) => self.retry_on.contains(&response.status()),
+            Err(reqwest_middleware::Error::Reqwest(error)) => error.is_connect() || error.is_timeout(),
+            Err(reqwest_middleware::Error::Middleware(_)) => false,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if self.max_retries == 0 || !matches!(*req.method(), Method::GET | Method::HEAD) {
+            return next.run(req, extensions).await;
+        }
+
+        let mut backoff = ExponentialBackoff {
+            current_interval: self.base_delay,
+            initial_interval: self.base_delay,
+            max_elapsed_time: None,
+            ..Default::default()
+        };
+
+        for attempt in 1..=self.max_retries {
+            // requests with streaming body can't be cloned and retried
+            let Some(request) = req.try_clone() else {
+                break;
+            };
+
+            let result = next.clone().run(request, extensions).await;
+            if !self.should_retry(&result) {
+                return result;
+            }
+
+            let delay = match &result {
+                Ok(response) => retry_after(response),
+                Err(_) => None,
+            }
+            .unwrap_or_else(|| backoff.next_backoff().unwrap_or(self.base_delay));
+            tracing::warn!(url = %req.url().path(), attempt, ?delay, "request failed, retrying");
+            tokio::time::sleep(delay).await;
+        }
+
+        next.run(req, extensions).await
+    }
+}
+
+/// Only the delay in seconds is supported, HTTP dates are ignored. Capped by `MAX_RETRY_AFTER`
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_AFTER))
+}
+
+fn duration() -> &'static Histogram<f64> {
+    DURATION.get_or_init(|| {
+        global::meter("http-client")
+            .f64_histogram("http_client.duration")
+            .with_unit(Unit::new("ms"))
+            .with_description("Duration of outgoing HTTP requests")
+            .init()
+    })
+}
+
+struct HeaderInjector<'a>(&'a mut reqwest::header::HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(key.as_bytes()), HeaderValue::from_str(&value)) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    /// Respond with `statuses` in order, the last one is repeated
+    fn serve(statuses: &'static [u16]) -> (String, mpsc::Receiver<String>) {
+        let mut requests = 0;
+        let (url, request_lines) = crate::testing::serve(move |_| {
+            let status = statuses[requests.min(statuses.len() - 1)];
+            requests += 1;
+            (status, String::new())
+        });
+
+        (format!("{url}/"), request_lines)
+    }
+
+    fn client(max_retries: u32) -> reqwest_middleware::ClientWithMiddleware {
+        crate::with_middleware(reqwest::Client::new(), &HttpClientSettings {
+            max_retries,
+            retry_base_delay: Duration::from_millis(1),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn retry_transient_statuses() {
+        let (url, requests) = serve(&[502, 503, 200]);
+
+        let response = client(3).get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.try_iter().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_retries() {
+        let (url, requests) = serve(&[502]);
+
+        let response = client(2).get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(requests.try_iter().count(), 3);
+    }
+
+    #[test]
+    fn parse_retry_after() {
+        let response = |value: &str| -> Response {
+            http::Response::builder()
+                .status(429)
+                .header(RETRY_AFTER, value)
+                .body("")
+                .unwrap()
+                .into()
+        };
+
+        assert_eq!(retry_after(&response("30")), Some(Duration::from_secs(30)));
+        assert_eq!(retry_after(&response("86400")), Some(MAX_RETRY_AFTER));
+        assert_eq!(retry_after(&response("Wed, 21 Oct 2015 07:28:00 GMT")), None);
+    }
+
+    #[tokio::test]
+    async fn do_not_retry_post() {
+        let (url, requests) = serve(&[502, 200]);
+
+        let response = client(3).post(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(requests.try_iter().count(), 1);
+    }
+}
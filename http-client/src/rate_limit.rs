@@ -0,0 +1,120 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use task_local_extensions::Extensions;
+use tokio::{sync::Mutex, time::Instant};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket per host, allows bursts of up to one second worth of requests.
+///
+/// Cloned limiters share the buckets, so a single limiter can be used by several clients.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    rate_per_sec: f64,
+    capacity: f64,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate_per_sec: f64) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            rate_per_sec.is_finite() && rate_per_sec > 0.0,
+            "Rate limit must be positive, got {rate_per_sec}"
+        );
+
+        Ok(Self {
+            rate_per_sec,
+            capacity: rate_per_sec.max(1.0),
+            buckets: Default::default(),
+        })
+    }
+
+    /// Wait until a request to `host` is allowed
+    pub async fn acquire(&self, host: &str) {
+        let wait = {
+            let mut buckets = self.buckets.lock().await;
+            let now = Instant::now();
+            let bucket = buckets.entry(host.to_owned()).or_insert(Bucket {
+                tokens: self.capacity,
+                updated: now,
+            });
+
+            bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * self.rate_per_sec)
+                .min(self.capacity);
+            bucket.updated = now;
+            // the token is reserved right away, so waiting requests are served in order
+            bucket.tokens -= 1.0;
+
+            (bucket.tokens < 0.0).then(|| Duration::from_secs_f64(-bucket.tokens / self.rate_per_sec))
+        };
+
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RateLimiter {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        self.acquire(req.url().host_str().unwrap_or_default()).await;
+        next.run(req, extensions).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire() {
+        let limiter = RateLimiter::new(10.0).unwrap();
+        let started = Instant::now();
+
+        // burst
+        for _ in 0..10 {
+            limiter.acquire("a.com").await;
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        for _ in 0..2 {
+            limiter.acquire("a.com").await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        // other hosts have their own buckets
+        let started = Instant::now();
+        limiter.acquire("b.com").await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slower_than_one_per_second() {
+        let limiter = RateLimiter::new(10.0 / 60.0).unwrap();
+        let started = Instant::now();
+
+        for _ in 0..3 {
+            limiter.acquire("a.com").await;
+        }
+
+        assert!(started.elapsed() >= Duration::from_secs(12));
+    }
+
+    #[test]
+    fn positive_rate() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(RateLimiter::new(rate).is_err());
+        }
+    }
+}
@@ -1,40 +1,142 @@
+use anyhow::Context;
+use rust_utils::{
+    secret::Secret,
+    wrappers::serde::{DurationMs, DurationSec},
+};
 use serde::Deserialize;
-use serde_with::{serde_as, DurationSeconds};
-use std::time::Duration;
+use serde_with::serde_as;
+use std::{path::PathBuf, time::Duration};
 
 #[serde_as]
-#[derive(Deserialize, Eq, PartialEq, Debug)]
+#[derive(Deserialize, PartialEq, Debug)]
 pub struct HttpClientSettings {
     #[serde(rename = "tcp_keepalive_sec", default = "HttpClientSettings::default_tcp_keepalive")]
-    #[serde_as(as = "DurationSeconds")]
+    #[serde_as(as = "DurationSec")]
     pub tcp_keepalive: Duration,
     #[serde(
         rename = "pool_idle_timeout_sec",
         default = "HttpClientSettings::default_pool_idle_timeout"
     )]
-    #[serde_as(as = "DurationSeconds")]
+    #[serde_as(as = "DurationSec")]
     pub pool_idle_timeout: Duration,
+    /// Timeout of the whole request, can be overridden per request with `RequestBuilder::timeout`
+    #[serde(
+        rename = "request_timeout_ms",
+        default = "HttpClientSettings::default_request_timeout"
+    )]
+    #[serde_as(as = "DurationMs")]
+    pub request_timeout: Duration,
+    #[serde(
+        rename = "connect_timeout_ms",
+        default = "HttpClientSettings::default_connect_timeout"
+    )]
+    #[serde_as(as = "DurationMs")]
+    pub connect_timeout: Duration,
     #[serde(default)]
-    pub api_key: Option<String>,
+    pub api_key: Option<Secret<String>>,
     #[serde(default)]
     pub is_sandbox: bool,
     #[serde(default = "HttpClientSettings::default_enabled")]
     pub enabled: bool,
     #[serde(default = "HttpClientSettings::default_history_chunk_size")]
     pub history_chunk_size: usize,
+    /// How many history chunks are requested concurrently
+    #[serde(default = "HttpClientSettings::default_history_parallelism")]
+    pub history_parallelism: usize,
+    /// Client side rate limit per host, the client may pick a default for its API tier if not set
+    #[serde(default)]
+    pub rate_limit_per_sec: Option<f64>,
+    /// How many times idempotent requests are retried on connection errors and `retry_on` statuses
+    #[serde(default = "HttpClientSettings::default_max_retries")]
+    pub max_retries: u32,
+    /// First retry delay, doubled with jitter for every next retry
+    #[serde(
+        rename = "retry_base_delay_ms",
+        default = "HttpClientSettings::default_retry_base_delay"
+    )]
+    #[serde_as(as = "DurationMs")]
+    pub retry_base_delay: Duration,
+    #[serde(default = "HttpClientSettings::default_retry_on")]
+    pub retry_on: Vec<u16>,
+    /// Proxy for all requests, e.g. `http://egress-proxy:3128`
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// PEM bundle of root certificates trusted in addition to the system ones
+    #[serde(default)]
+    pub root_ca_path: Option<PathBuf>,
+    #[serde(default)]
+    pub tls_min_version: Option<TlsVersion>,
+}
+
+#[derive(Deserialize, Eq, PartialEq, Debug, Clone, Copy)]
+pub enum TlsVersion {
+    #[serde(rename = "1.0")]
+    Tls1_0,
+    #[serde(rename = "1.1")]
+    Tls1_1,
+    #[serde(rename = "1.2")]
+    Tls1_2,
+    #[serde(rename = "1.3")]
+    Tls1_3,
+}
+
+impl From<TlsVersion> for reqwest::tls::Version {
+    fn from(version: TlsVersion) -> Self {
+        match version {
+            TlsVersion::Tls1_0 => reqwest::tls::Version::TLS_1_0,
+            TlsVersion::Tls1_1 => reqwest::tls::Version::TLS_1_1,
+            TlsVersion::Tls1_2 => reqwest::tls::Version::TLS_1_2,
+            TlsVersion::Tls1_3 => reqwest::tls::Version::TLS_1_3,
+        }
+    }
 }
 
-impl From<&HttpClientSettings> for reqwest::Client {
-    fn from(settings: &HttpClientSettings) -> Self {
-        reqwest::ClientBuilder::new()
-            .tcp_keepalive(Some(settings.tcp_keepalive))
-            .pool_idle_timeout(Some(settings.pool_idle_timeout))
+/// Fails on an invalid proxy url, root CA or rate limit
+impl TryFrom<&HttpClientSettings> for reqwest::Client {
+    type Error = anyhow::Error;
+
+    fn try_from(settings: &HttpClientSettings) -> anyhow::Result<Self> {
+        settings
+            .client_builder()?
             .build()
-            .expect("Client must be built")
+            .context("Unable to build HTTP client")
+    }
+}
+
+impl TryFrom<&HttpClientSettings> for reqwest_middleware::ClientWithMiddleware {
+    type Error = anyhow::Error;
+
+    fn try_from(settings: &HttpClientSettings) -> anyhow::Result<Self> {
+        crate::with_middleware(settings.try_into()?, settings)
     }
 }
 
 impl HttpClientSettings {
+    /// Builder with connection, proxy and TLS settings applied, for clients which need to customize it further
+    pub fn client_builder(&self) -> anyhow::Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::ClientBuilder::new()
+            .tcp_keepalive(Some(self.tcp_keepalive))
+            .pool_idle_timeout(Some(self.pool_idle_timeout))
+            .timeout(self.request_timeout)
+            .connect_timeout(self.connect_timeout);
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy).context("Invalid proxy url")?);
+        }
+
+        if let Some(path) = &self.root_ca_path {
+            let pem = std::fs::read(path).with_context(|| format!("Unable to read root CA {}", path.display()))?;
+            let certificate = reqwest::Certificate::from_pem(&pem).context("Invalid root CA")?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        if let Some(version) = self.tls_min_version {
+            builder = builder.min_tls_version(version.into());
+        }
+
+        Ok(builder)
+    }
+
     fn default_tcp_keepalive() -> Duration {
         Duration::from_secs(20)
     }
@@ -43,6 +145,14 @@ impl HttpClientSettings {
         Duration::from_secs(20)
     }
 
+    fn default_request_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn default_connect_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
     fn default_enabled() -> bool {
         false
     }
@@ -51,6 +161,22 @@ impl HttpClientSettings {
         10
     }
 
+    fn default_history_parallelism() -> usize {
+        1
+    }
+
+    fn default_max_retries() -> u32 {
+        3
+    }
+
+    fn default_retry_base_delay() -> Duration {
+        Duration::from_millis(200)
+    }
+
+    fn default_retry_on() -> Vec<u16> {
+        vec![502, 503, 504]
+    }
+
     pub fn enabled() -> Self {
         Self {
             enabled: true,
@@ -64,10 +190,20 @@ impl Default for HttpClientSettings {
         Self {
             tcp_keepalive: Duration::from_secs(20),
             pool_idle_timeout: Duration::from_secs(20),
+            request_timeout: Self::default_request_timeout(),
+            connect_timeout: Self::default_connect_timeout(),
             api_key: None,
             is_sandbox: false,
             enabled: Self::default_enabled(),
             history_chunk_size: Self::default_history_chunk_size(),
+            history_parallelism: Self::default_history_parallelism(),
+            rate_limit_per_sec: None,
+            max_retries: Self::default_max_retries(),
+            retry_base_delay: Self::default_retry_base_delay(),
+            retry_on: Self::default_retry_on(),
+            proxy: None,
+            root_ca_path: None,
+            tls_min_version: None,
         }
     }
 }
@@ -80,3 +216,48 @@ impl HttpClientSettings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT_CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBejCCASGgAwIBAgIUfCJbe4Fg18a6tmwEpkg/pHXDJlkwCgYIKoZIzj0EAwIw
+EjEQMA4GA1UEAwwHdGVzdC1jYTAgFw0yNjEwMTcxOTUyMTZaGA8yMTI2MDkyMzE5
+NTIxNlowEjEQMA4GA1UEAwwHdGVzdC1jYTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABJhHdUDa7+67gm7PzCu3sabbnt2EolLXRjfFGaH8l4o3y/OVe/N0TzGOLLSq
+3RgahyVhoKvAc9RA/DOLDoBIhaqjUzBRMB0GA1UdDgQWBBS2THHRTh/8mQtA5PCv
+XrkMt6YYMTAfBgNVHSMEGDAWgBS2THHRTh/8mQtA5PCvXrkMt6YYMTAPBgNVHRMB
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCIE5KeODKNnhf1MZSqG8mn9McbyKk
+OdYv0u7hHSmJYa7mAiAl71y3eKsuzwtoIMZbophMlGIkfrQ5/1FRzDiBDx3C8Q==
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn build_client() {
+        let root_ca_path = std::env::temp_dir().join(format!("http-client-ca-{}.pem", std::process::id()));
+        std::fs::write(&root_ca_path, ROOT_CA).unwrap();
+
+        let mut settings = HttpClientSettings {
+            proxy: Some("http://egress-proxy:3128".to_owned()),
+            root_ca_path: Some(root_ca_path.clone()),
+            tls_min_version: Some(TlsVersion::Tls1_2),
+            ..Default::default()
+        };
+        let client = reqwest_middleware::ClientWithMiddleware::try_from(&settings);
+        std::fs::remove_file(&root_ca_path).unwrap();
+        assert!(client.is_ok());
+
+        settings.root_ca_path = Some(root_ca_path);
+        assert!(reqwest::Client::try_from(&settings).is_err());
+
+        settings.root_ca_path = None;
+        settings.rate_limit_per_sec = Some(0.0);
+        assert!(reqwest::Client::try_from(&settings).is_ok());
+        assert!(reqwest_middleware::ClientWithMiddleware::try_from(&settings).is_err());
+
+        settings.rate_limit_per_sec = None;
+        settings.proxy = Some("not a url".to_owned());
+        assert!(reqwest::Client::try_from(&settings).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+//! Local HTTP server for tests of the API clients
+//!
+//! # Usage
+//! ```ignore
+//! let (base_url, request_lines) = serve(|_| (200, r#"{"bitcoin":{"usd":67187.33}}"#.to_owned()));
+//! let client = CoingeckoClient { base_url, ..CoingeckoClient::new(HttpClientSettings::default())? };
+//! client.get_simple_prices(&["bitcoin"], &["usd"]).await?;
+//! assert_eq!(request_lines.recv()?, "GET /simple/price?ids=bitcoin&vs_currencies=usd HTTP/1.1");
+//! ```
+
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::mpsc,
+};
+
+/// Serves requests on a local port until the test process exits. `respond` gets the request line and returns the
+/// status and the JSON body of the response. Request lines are sent to the receiver in the order of requests,
+/// before the response is written
+pub fn serve(mut respond: impl FnMut(&str) -> (u16, String) + Send + 'static) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (requested, request_lines) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut buf = [0; 1024];
+            let read = stream.read(&mut buf).unwrap();
+            let request_line = String::from_utf8_lossy(&buf[..read])
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_owned();
+
+            let (status, body) = respond(&request_line);
+            let _ = requested.send(request_line);
+            write!(
+                stream,
+                "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+    });
+
+    (url, request_lines)
+}
@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use normdecimal::NormDecimal;
+use token_address::TokenAddress;
+
+use crate::PriceProvider;
+
+/// Asks the providers in order until one of them knows the price
+#[derive(Default)]
+pub struct PriceProviderChain {
+    providers: Vec<Box<dyn PriceProvider>>,
+}
+
+impl PriceProviderChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, provider: impl PriceProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+}
+
+#[async_trait]
+impl PriceProvider for PriceProviderChain {
+    fn name(&self) -> &str {
+        "Chain"
+    }
+
+    /// The error of the last provider is returned if no provider knows the price
+    async fn spot_price(&self, token: &TokenAddress, currency: &str) -> anyhow::Result<Option<NormDecimal>> {
+        let mut result = Ok(None);
+
+        for provider in &self.providers {
+            result = provider.spot_price(token, currency).await;
+            match &result {
+                Ok(Some(_)) => break,
+                Ok(None) => tracing::debug!(provider = provider.name(), %token, "spot price is not found"),
+                Err(error) => tracing::warn!(provider = provider.name(), %token, %error, "unable to get spot price"),
+            }
+        }
+
+        result
+    }
+
+    async fn historical_prices(
+        &self,
+        token: &TokenAddress,
+        date_range: Range<NaiveDate>,
+        currency: &str,
+    ) -> anyhow::Result<Vec<(DateTime<Utc>, NormDecimal)>> {
+        let mut result = Ok(vec![]);
+
+        for provider in &self.providers {
+            result = provider.historical_prices(token, date_range.clone(), currency).await;
+            match &result {
+                Ok(prices) if !prices.is_empty() => break,
+                Ok(_) => tracing::debug!(provider = provider.name(), %token, "historical prices are not found"),
+                Err(error) => {
+                    tracing::warn!(provider = provider.name(), %token, %error, "unable to get historical prices")
+                },
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+    use token_address::ChainId;
+
+    use super::*;
+
+    struct Fixed(Option<NormDecimal>);
+
+    struct Failing;
+
+    #[async_trait]
+    impl PriceProvider for Fixed {
+        fn name(&self) -> &str {
+            "Fixed"
+        }
+
+        async fn spot_price(&self, _: &TokenAddress, _: &str) -> anyhow::Result<Option<NormDecimal>> {
+            Ok(self.0)
+        }
+
+        async fn historical_prices(
+            &self,
+            _: &TokenAddress,
+            _: Range<NaiveDate>,
+            _: &str,
+        ) -> anyhow::Result<Vec<(DateTime<Utc>, NormDecimal)>> {
+            Ok(self.0.map(|price| (Utc::now(), price)).into_iter().collect())
+        }
+    }
+
+    #[async_trait]
+    impl PriceProvider for Failing {
+        fn name(&self) -> &str {
+            "Failing"
+        }
+
+        async fn spot_price(&self, _: &TokenAddress, _: &str) -> anyhow::Result<Option<NormDecimal>> {
+            bail!("unavailable")
+        }
+
+        async fn historical_prices(
+            &self,
+            _: &TokenAddress,
+            _: Range<NaiveDate>,
+            _: &str,
+        ) -> anyhow::Result<Vec<(DateTime<Utc>, NormDecimal)>> {
+            bail!("unavailable")
+        }
+    }
+
+    #[tokio::test]
+    async fn fallback() {
+        let token = TokenAddress::Native(ChainId::Solana);
+        let today = Utc::now().date_naive();
+
+        let chain = PriceProviderChain::new()
+            .with(Failing)
+            .with(Fixed(None))
+            .with(Fixed(Some(NormDecimal::ONE)));
+
+        assert_eq!(chain.spot_price(&token, "usd").await.unwrap(), Some(NormDecimal::ONE));
+        assert_eq!(
+            chain
+                .historical_prices(&token, today..today, "usd")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let chain = PriceProviderChain::new().with(Fixed(None)).with(Failing);
+        assert!(chain.spot_price(&token, "usd").await.is_err());
+
+        let chain = PriceProviderChain::new().with(Failing).with(Fixed(None));
+        assert_eq!(chain.spot_price(&token, "usd").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,73 @@
+use std::ops::Range;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use coingecko_client::CoingeckoClient;
+use normdecimal::NormDecimal;
+use token_address::{ChainId, TokenAddress};
+
+use crate::PriceProvider;
+
+fn native_coin_id(chain: ChainId) -> &'static str {
+    match chain {
+        ChainId::Solana => "solana",
+        ChainId::Ethereum | ChainId::Arbitrum | ChainId::Base => "ethereum",
+        ChainId::Polygon => "matic-network",
+        ChainId::Bsc => "binancecoin",
+        ChainId::Bitcoin => "bitcoin",
+        ChainId::Ton => "the-open-network",
+    }
+}
+
+#[async_trait]
+impl PriceProvider for CoingeckoClient {
+    fn name(&self) -> &str {
+        "Coingecko"
+    }
+
+    async fn spot_price(&self, token: &TokenAddress, currency: &str) -> anyhow::Result<Option<NormDecimal>> {
+        let prices = match token.as_stored_token_address() {
+            Some(address) => {
+                self.get_simple_token_prices(
+                    address.platform().coingecko_platform(),
+                    &[&address.to_unprefixed_string()],
+                    &[currency],
+                )
+                .await?
+            },
+            None => {
+                self.get_simple_prices(&[native_coin_id(token.platform())], &[currency])
+                    .await?
+            },
+        };
+
+        // Only one token is requested, the key case differs between platforms
+        Ok(prices
+            .into_values()
+            .next()
+            .and_then(|mut prices| prices.remove(currency)))
+    }
+
+    async fn historical_prices(
+        &self,
+        token: &TokenAddress,
+        date_range: Range<NaiveDate>,
+        currency: &str,
+    ) -> anyhow::Result<Vec<(DateTime<Utc>, NormDecimal)>> {
+        let prices = match token.as_stored_token_address() {
+            Some(address) => {
+                self.get_historical_prices_by_address(&address, &date_range, &currency)
+                    .await?
+            },
+            None => {
+                self.get_historical_prices(native_coin_id(token.platform()), &date_range, &currency)
+                    .await?
+            },
+        };
+
+        Ok(prices
+            .into_iter()
+            .filter_map(|(timestamp, price)| Some((DateTime::from_timestamp_millis(timestamp)?, price)))
+            .collect())
+    }
+}
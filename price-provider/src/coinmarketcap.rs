@@ -0,0 +1,98 @@
+use std::ops::Range;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use coinmarketcap_client::{
+    types::{CoinmarketcapError, PricesResponse},
+    CoinmarketcapClient,
+};
+use normdecimal::NormDecimal;
+use token_address::{ChainId, TokenAddress};
+
+use crate::PriceProvider;
+
+/// CMC id of the token, prices are requested by id as symbols are ambiguous
+struct Coin {
+    id: u64,
+}
+
+fn native_coin(chain: ChainId) -> Coin {
+    let id = match chain {
+        ChainId::Solana => 5426,
+        ChainId::Ethereum | ChainId::Arbitrum | ChainId::Base => 1027,
+        ChainId::Polygon => 3890,
+        ChainId::Bsc => 1839,
+        ChainId::Bitcoin => 1,
+        ChainId::Ton => 11419,
+    };
+
+    Coin { id }
+}
+
+async fn resolve(client: &CoinmarketcapClient, token: &TokenAddress) -> anyhow::Result<Option<Coin>> {
+    if let TokenAddress::Native(chain) = token {
+        return Ok(Some(native_coin(*chain)));
+    }
+
+    let info = match client.cryptocurrency_info(token.to_string()).await {
+        Ok(info) => info,
+        // Unknown addresses are reported as invalid values
+        Err(error) if error.is::<CoinmarketcapError>() => {
+            tracing::debug!(%token, %error, "token is not found");
+            return Ok(None);
+        },
+        Err(error) => return Err(error),
+    };
+
+    Ok(info.into_values().next().map(|info| Coin { id: info.id }))
+}
+
+#[async_trait]
+impl PriceProvider for CoinmarketcapClient {
+    fn name(&self) -> &str {
+        "Coinmarketcap"
+    }
+
+    async fn spot_price(&self, token: &TokenAddress, currency: &str) -> anyhow::Result<Option<NormDecimal>> {
+        let Some(coin) = resolve(self, token).await? else {
+            return Ok(None);
+        };
+
+        let currency = currency.to_uppercase();
+        let mut quotes = self.quotes_latest(&[coin.id], &currency).await?;
+
+        Ok(quotes
+            .remove(&coin.id.to_string())
+            .and_then(|mut quote| quote.quote.remove(&currency))
+            .map(|price| price.price))
+    }
+
+    async fn historical_prices(
+        &self,
+        token: &TokenAddress,
+        date_range: Range<NaiveDate>,
+        currency: &str,
+    ) -> anyhow::Result<Vec<(DateTime<Utc>, NormDecimal)>> {
+        let Some(coin) = resolve(self, token).await? else {
+            return Ok(vec![]);
+        };
+
+        let currency = currency.to_uppercase();
+        let mut prices = self
+            .historical_prices_by_ids(&[coin.id], date_range, &currency)
+            .await
+            .and_then(PricesResponse::into_data)
+            .context("Unable to get historical prices from Coinmarketcap")?;
+
+        Ok(prices
+            .remove(&coin.id.to_string())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|mut quotes| {
+                let price = quotes.quote.as_mut()?.remove(&currency)?;
+                Some((quotes.timestamp, price.price))
+            })
+            .collect())
+    }
+}
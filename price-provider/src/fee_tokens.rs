@@ -0,0 +1,235 @@
+//! Exchange rates of the fee tokens updated from a `PriceProvider`
+//!
+//! # Usage
+//! ```ignore
+//! let prices = PriceProviderChain::new().with(coingecko).with(coinmarketcap);
+//! let updater = FeeTokenRateUpdater::new(fee_tokens.clone(), prices)
+//!     .with_currency("sol")
+//!     .with_interval(Duration::from_secs(30));
+//! let _task = updater.spawn();
+//! ```
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Unit},
+    KeyValue,
+};
+use rust_utils::{error::UtilsResult, tokens::FeeTokenProvider};
+use solana_sdk::pubkey::Pubkey;
+use token_address::TokenAddress;
+use tokio::task::JoinHandle;
+
+use crate::PriceProvider;
+
+pub const DEFAULT_CURRENCY: &str = "usd";
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+/// Updates a token may miss before it's marked failed
+pub const DEFAULT_MAX_MISSES: u32 = 3;
+
+/// Updates the exchange rate of every fee token to its spot price in `currency`. A token keeps its rate when its
+/// price isn't found and is marked failed once it's missed `max_misses` updates in a row
+pub struct FeeTokenRateUpdater<P> {
+    tokens: FeeTokenProvider,
+    prices: P,
+    currency: String,
+    interval: Duration,
+    max_misses: u32,
+    misses: HashMap<Pubkey, u32>,
+    updated_at: HashMap<Pubkey, Instant>,
+    started_at: Instant,
+    metrics: UpdaterMetrics,
+}
+
+impl<P: PriceProvider + 'static> FeeTokenRateUpdater<P> {
+    pub fn new(tokens: FeeTokenProvider, prices: P) -> Self {
+        Self {
+            tokens,
+            prices,
+            currency: DEFAULT_CURRENCY.to_owned(),
+            interval: DEFAULT_INTERVAL,
+            max_misses: DEFAULT_MAX_MISSES,
+            misses: HashMap::new(),
+            updated_at: HashMap::new(),
+            started_at: Instant::now(),
+            metrics: UpdaterMetrics::new(),
+        }
+    }
+
+    pub fn with_currency(mut self, currency: impl Into<String>) -> Self {
+        self.currency = currency.into();
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_max_misses(mut self, max_misses: u32) -> Self {
+        self.max_misses = max_misses;
+        self
+    }
+
+    /// Updates the rates every `interval` until the task is aborted
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if let Err(error) = self.update().await {
+                    tracing::warn!(%error, "unable to update fee token exchange rates");
+                }
+            }
+        })
+    }
+
+    /// Fetches the prices of the tokens and updates their rates once
+    pub async fn update(&mut self) -> UtilsResult<()> {
+        let cx = opentelemetry::Context::current();
+        let tokens = self.tokens.read().await.clone();
+        let mut rates = HashMap::new();
+
+        for token in tokens.values() {
+            let address = TokenAddress::Spl(*token.mint());
+            let price = match self.prices.spot_price(&address, &self.currency).await {
+                Ok(price) => price.and_then(|price| f64::try_from(*price).ok()),
+                Err(error) => {
+                    tracing::warn!(token = token.name(), %error, "unable to get fee token price");
+                    None
+                },
+            };
+
+            // tokens failed before the updater started are still failed until their price is found
+            let failed_misses = if token.is_update_failed() { self.max_misses } else { 0 };
+            let misses = self.misses.entry(*token.mint()).or_insert(failed_misses);
+            let attributes = [KeyValue::new("token", token.name().to_owned())];
+            match price {
+                Some(price) => {
+                    *misses = 0;
+                    self.updated_at.insert(*token.mint(), Instant::now());
+                    rates.insert(token.name().to_owned(), price);
+                },
+                None => {
+                    *misses = misses.saturating_add(1);
+                    self.metrics.misses.add(&cx, 1, &attributes);
+                    if *misses < self.max_misses {
+                        rates.insert(token.name().to_owned(), token.exchange_rate());
+                    }
+                },
+            }
+
+            let updated_at = self.updated_at.get(token.mint()).unwrap_or(&self.started_at);
+            self.metrics
+                .age
+                .record(&cx, updated_at.elapsed().as_secs_f64(), &attributes);
+        }
+
+        self.misses.retain(|mint, _| tokens.contains_key(mint));
+        self.updated_at.retain(|mint, _| tokens.contains_key(mint));
+
+        self.tokens.update_exchange_rates(&rates).await
+    }
+}
+
+struct UpdaterMetrics {
+    misses: Counter<u64>,
+    age: Histogram<f64>,
+}
+
+impl UpdaterMetrics {
+    fn new() -> Self {
+        let meter = global::meter("price-provider");
+
+        Self {
+            misses: meter
+                .u64_counter("fee_tokens.rate.misses")
+                .with_description("Number of fee token rate updates without the price")
+                .init(),
+            age: meter
+                .f64_histogram("fee_tokens.rate.age")
+                .with_unit(Unit::new("s"))
+                .with_description("Time since the fee token price was found, recorded on every update")
+                .init(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ops::Range,
+        sync::{Arc, Mutex},
+    };
+
+    use async_trait::async_trait;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use normdecimal::NormDecimal;
+    use rust_utils::tokens::{FeeToken, FeeTokens};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Prices(Arc<Mutex<HashMap<TokenAddress, NormDecimal>>>);
+
+    #[async_trait]
+    impl PriceProvider for Prices {
+        fn name(&self) -> &str {
+            "Prices"
+        }
+
+        async fn spot_price(&self, token: &TokenAddress, _: &str) -> anyhow::Result<Option<NormDecimal>> {
+            Ok(self.0.lock().unwrap().get(token).copied())
+        }
+
+        async fn historical_prices(
+            &self,
+            _: &TokenAddress,
+            _: Range<NaiveDate>,
+            _: &str,
+        ) -> anyhow::Result<Vec<(DateTime<Utc>, NormDecimal)>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn mark_failed_after_misses() {
+        let token = FeeToken::new("usdc", "USDC", Pubkey::new_unique(), Pubkey::new_unique(), 1.0);
+        let mint = *token.mint();
+        let tokens = FeeTokenProvider::new(FeeTokens::from([(mint, token)]));
+        let prices = Prices::default();
+        let mut updater = FeeTokenRateUpdater::new(tokens.clone(), prices.clone()).with_max_misses(2);
+
+        prices
+            .0
+            .lock()
+            .unwrap()
+            .insert(TokenAddress::Spl(mint), NormDecimal::from(2));
+        updater.update().await.unwrap();
+        assert_eq!(tokens.get(&mint).await.unwrap().exchange_rate(), 2.0);
+
+        prices.0.lock().unwrap().clear();
+        updater.update().await.unwrap();
+        let token = tokens.get(&mint).await.unwrap();
+        assert_eq!(token.exchange_rate(), 2.0);
+        assert!(!token.is_update_failed());
+
+        updater.update().await.unwrap();
+        assert!(tokens.get(&mint).await.unwrap().is_update_failed());
+
+        prices
+            .0
+            .lock()
+            .unwrap()
+            .insert(TokenAddress::Spl(mint), NormDecimal::from(3));
+        updater.update().await.unwrap();
+        let token = tokens.get(&mint).await.unwrap();
+        assert_eq!(token.exchange_rate(), 3.0);
+        assert!(!token.is_update_failed());
+    }
+}
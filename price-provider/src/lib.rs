@@ -0,0 +1,33 @@
+use std::ops::Range;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use normdecimal::NormDecimal;
+use token_address::TokenAddress;
+
+pub mod chain;
+pub mod coingecko;
+pub mod coinmarketcap;
+#[cfg(feature = "fee-tokens")]
+pub mod fee_tokens;
+
+pub use chain::PriceProviderChain;
+#[cfg(feature = "fee-tokens")]
+pub use fee_tokens::FeeTokenRateUpdater;
+
+/// Source of token prices, `currency` is a lowercase fiat or crypto ticker like `usd`
+#[async_trait]
+pub trait PriceProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// `None` if the provider doesn't know the token
+    async fn spot_price(&self, token: &TokenAddress, currency: &str) -> anyhow::Result<Option<NormDecimal>>;
+
+    /// Daily prices, empty if the provider doesn't know the token
+    async fn historical_prices(
+        &self,
+        token: &TokenAddress,
+        date_range: Range<NaiveDate>,
+        currency: &str,
+    ) -> anyhow::Result<Vec<(DateTime<Utc>, NormDecimal)>>;
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hyper::{Body, Request, Response};
+use jsonrpsee::{
+    core::Error,
+    http_client::{
+        transport::{Error as TransportError, HttpBackend},
+        HeaderMap, HttpClient as JsonRpcClient, HttpClientBuilder,
+    },
+};
+use tower::{Layer, Service, ServiceBuilder};
+use tower_opentelemetry::{Layer as OpenTelemetryLayer, Service as OpenTelemetryService};
+
+use crate::rpc::RpcClientSettings;
+
+pub use ws::{ConnectionState, WsClient, WsSubscription};
+
+mod ws;
+
+pub type HttpClient = JsonRpcClient<OpenTelemetryService<RetryService<HttpBackend>>>;
+
+pub trait HttpClientExt {
+    fn from_url(url: impl AsRef<str>) -> Result<Self, Error>
+    where
+        Self: Sized;
+}
+
+pub trait HttpClientSettingsExt {
+    /// Client of `RpcClientSettings::address` with the timeouts, headers and retries of `settings`
+    fn from_settings(settings: &RpcClientSettings) -> Result<Self, Error>
+    where
+        Self: Sized;
+}
+
+impl HttpClientExt for HttpClient {
+    fn from_url(url: impl AsRef<str>) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let middleware = ServiceBuilder::default()
+            .layer(OpenTelemetryLayer::new())
+            .layer(RetryLayer::default());
+        let client = HttpClientBuilder::default().set_middleware(middleware).build(url)?;
+        Ok(client)
+    }
+}
+
+impl HttpClientSettingsExt for HttpClient {
+    fn from_settings(settings: &RpcClientSettings) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let middleware = ServiceBuilder::default()
+            .layer(OpenTelemetryLayer::new())
+            .layer(RetryLayer::from(settings));
+        HttpClientBuilder::default()
+            .with_settings(settings)?
+            .set_middleware(middleware)
+            .build(&settings.address)
+    }
+}
+
+/// Applies `RpcClientSettings` to a builder, custom middlewares can be set with `set_middleware` afterwards
+pub trait HttpClientBuilderExt: Sized {
+    fn with_settings(self, settings: &RpcClientSettings) -> Result<Self, Error>;
+}
+
+impl<L> HttpClientBuilderExt for HttpClientBuilder<L> {
+    fn with_settings(self, settings: &RpcClientSettings) -> Result<Self, Error> {
+        Ok(self
+            .request_timeout(settings.request_timeout)
+            .max_concurrent_requests(settings.max_concurrent_requests)
+            .set_headers(settings_headers(settings)?))
+    }
+}
+
+fn settings_headers(settings: &RpcClientSettings) -> Result<HeaderMap, Error> {
+    settings
+        .headers
+        .iter()
+        .map(|(name, value)| Ok((FromStr::from_str(name)?, FromStr::from_str(value.expose_secret())?)))
+        .collect::<Result<HeaderMap, Box<dyn std::error::Error>>>()
+        .map_err(|error| Error::Custom(format!("invalid header: {error}")))
+}
+
+/// Retries JSON-RPC requests of idempotent `methods` failed with a transport error or `5xx` status, requests
+/// of other methods are sent once
+#[derive(Debug, Clone, Default)]
+pub struct RetryLayer {
+    max_retries: usize,
+    delay: Duration,
+    methods: Arc<HashSet<String>>,
+}
+
+impl RetryLayer {
+    pub fn new(max_retries: usize, delay: Duration, methods: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            max_retries,
+            delay,
+            methods: Arc::new(methods.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Whether every call of the single or batch request is of a retried method
+    fn is_retried(&self, body: &[u8]) -> bool {
+        #[derive(serde::Deserialize)]
+        struct Call {
+            method: String,
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Calls {
+            Single(Call),
+            Batch(Vec<Call>),
+        }
+
+        match serde_json::from_slice(body) {
+            Ok(Calls::Single(call)) => self.methods.contains(&call.method),
+            Ok(Calls::Batch(calls)) => {
+                !calls.is_empty() && calls.iter().all(|call| self.methods.contains(&call.method))
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+impl From<&RpcClientSettings> for RetryLayer {
+    fn from(settings: &RpcClientSettings) -> Self {
+        Self::new(
+            settings.max_request_retries,
+            settings.request_retry_delay,
+            settings.retry_methods.iter().cloned(),
+        )
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = RetryService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RetryService {
+            layer: self.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryService<S> {
+    layer: RetryLayer,
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for RetryService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = TransportError> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if self.layer.max_retries == 0 || self.layer.methods.is_empty() {
+            return Box::pin(self.inner.call(request));
+        }
+
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            // the body is buffered to be sent again
+            let (parts, body) = request.into_parts();
+            let body = hyper::body::to_bytes(body)
+                .await
+                .map_err(|error| TransportError::Http(error.into()))?;
+            let max_retries = if layer.is_retried(&body) { layer.max_retries } else { 0 };
+
+            let mut attempt = 0;
+            loop {
+                let mut request = Request::new(Body::from(body.clone()));
+                *request.method_mut() = parts.method.clone();
+                *request.uri_mut() = parts.uri.clone();
+                *request.headers_mut() = parts.headers.clone();
+
+                let result = inner.call(request).await;
+                let failed = match &result {
+                    Ok(response) => response.status().is_server_error(),
+                    Err(_) => true,
+                };
+                if !failed || attempt >= max_retries {
+                    return result;
+                }
+
+                attempt += 1;
+                tracing::debug!(attempt, "retrying failed request in {:?}", layer.delay);
+                tokio::time::sleep(layer.delay).await;
+                std::future::poll_fn(|cx| inner.poll_ready(cx)).await?;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use hyper::StatusCode;
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn retry_server_errors() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let backend = {
+            let calls = calls.clone();
+            service_fn(move |request: Request<Body>| {
+                let calls = calls.clone();
+                async move {
+                    let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                    let mut response = Response::new(Body::from(body));
+                    if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                        *response.status_mut() = StatusCode::BAD_GATEWAY;
+                    }
+                    Ok::<_, TransportError>(response)
+                }
+            })
+        };
+        let retried = RetryLayer::new(3, Duration::ZERO, ["getBalance"]).layer(backend);
+        let call = |body: &'static str| retried.clone().oneshot(Request::new(Body::from(body)));
+
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"getBalance","params":[]}"#;
+        let response = call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), request);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // a transaction may have been sent by the failed request
+        calls.store(0, Ordering::SeqCst);
+        let response = call(r#"{"jsonrpc":"2.0","id":1,"method":"sendTransaction","params":[]}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        calls.store(0, Ordering::SeqCst);
+        let response = call(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"getBalance"},{"jsonrpc":"2.0","id":2,"method":"sendTransaction"}]"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,425 @@
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex, MutexGuard, RwLock, Weak},
+};
+
+use jsonrpsee::{
+    core::{
+        client::{ClientT, Subscription, SubscriptionClientT},
+        traits::ToRpcParams,
+        Error,
+    },
+    ws_client::{WsClient as JsonRpcWsClient, WsClientBuilder},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{value::RawValue, Value};
+use tokio::sync::{mpsc, oneshot, watch};
+
+use super::settings_headers;
+use crate::rpc::RpcClientSettings;
+
+/// Buffered notifications of a subscription, the oldest are not dropped, the connection waits instead
+const SUBSCRIPTION_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// Connection is lost, `attempt` of `RpcClientSettings::max_retries` to reconnect
+    Reconnecting {
+        attempt: usize,
+    },
+    /// Retries are exhausted, the client won't reconnect anymore
+    Disconnected,
+}
+
+/// Params serialized once, so they can be sent again on resubscription
+#[derive(Clone)]
+struct RawParams(Option<Box<RawValue>>);
+
+impl ToRpcParams for RawParams {
+    fn to_rpc_params(self) -> Result<Option<Box<RawValue>>, Error> {
+        Ok(self.0)
+    }
+}
+
+struct ActiveSubscription {
+    subscribe_method: String,
+    unsubscribe_method: String,
+    params: RawParams,
+    sender: mpsc::Sender<Value>,
+}
+
+struct Shared {
+    settings: RpcClientSettings,
+    client: RwLock<Arc<JsonRpcWsClient>>,
+    subscriptions: Mutex<Vec<ActiveSubscription>>,
+    state: watch::Sender<ConnectionState>,
+}
+
+/// WebSocket JSON-RPC client which reconnects every `RpcClientSettings::reconnect_timeout` up to
+/// `RpcClientSettings::max_retries` times (forever if not set) and resubscribes active subscriptions.
+/// Requests made while reconnecting fail
+#[derive(Clone)]
+pub struct WsClient {
+    shared: Arc<Shared>,
+    // stops reconnection once all the clones are dropped
+    _dropped: Arc<oneshot::Sender<()>>,
+}
+
+impl WsClient {
+    pub async fn connect(settings: &RpcClientSettings) -> Result<Self, Error> {
+        let client = Self::build(settings).await?;
+        let (state, _) = watch::channel(ConnectionState::Connected);
+
+        let shared = Arc::new(Shared {
+            settings: settings.clone(),
+            client: RwLock::new(Arc::new(client)),
+            subscriptions: Default::default(),
+            state,
+        });
+        let (dropped, on_drop) = oneshot::channel();
+        tokio::spawn(shared.clone().reconnect(on_drop));
+
+        Ok(Self {
+            shared,
+            _dropped: Arc::new(dropped),
+        })
+    }
+
+    async fn build(settings: &RpcClientSettings) -> Result<JsonRpcWsClient, Error> {
+        WsClientBuilder::default()
+            .request_timeout(settings.request_timeout)
+            .max_concurrent_requests(settings.max_concurrent_requests)
+            .set_headers(settings_headers(settings)?)
+            .build(&settings.address)
+            .await
+    }
+
+    /// Changes of the connection state
+    pub fn state(&self) -> watch::Receiver<ConnectionState> {
+        self.shared.state.subscribe()
+    }
+
+    pub async fn request<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: impl ToRpcParams + Send,
+    ) -> Result<R, Error> {
+        self.shared.client().request(method, params).await
+    }
+
+    /// Subscription is resubscribed with the same params after reconnection, notifications sent by
+    /// the server in between are lost
+    pub async fn subscribe<N: DeserializeOwned>(
+        &self,
+        subscribe_method: &str,
+        params: impl ToRpcParams + Send,
+        unsubscribe_method: &str,
+    ) -> Result<WsSubscription<N>, Error> {
+        let params = RawParams(params.to_rpc_params()?);
+        let client = self.shared.client();
+        let subscription = client
+            .subscribe(subscribe_method, params.clone(), unsubscribe_method)
+            .await?;
+
+        let (sender, receiver) = mpsc::channel(SUBSCRIPTION_BUFFER);
+        // pushed before it's forwarded, so a subscription which ends right away is removed
+        self.shared.push(ActiveSubscription {
+            subscribe_method: subscribe_method.to_owned(),
+            unsubscribe_method: unsubscribe_method.to_owned(),
+            params,
+            sender: sender.clone(),
+        });
+        tokio::spawn(forward(subscription, sender, client, Arc::downgrade(&self.shared)));
+
+        Ok(WsSubscription {
+            receiver,
+            _notification: PhantomData,
+        })
+    }
+}
+
+impl Shared {
+    fn client(&self) -> Arc<JsonRpcWsClient> {
+        self.client.read().unwrap_or_else(|error| error.into_inner()).clone()
+    }
+
+    fn subscriptions(&self) -> MutexGuard<'_, Vec<ActiveSubscription>> {
+        self.subscriptions.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn push(&self, subscription: ActiveSubscription) {
+        self.subscriptions().push(subscription);
+    }
+
+    fn remove(&self, sender: &mpsc::Sender<Value>) {
+        self.subscriptions()
+            .retain(|active| !active.sender.same_channel(sender));
+    }
+
+    /// Runs until the client is dropped or retries are exhausted
+    async fn reconnect(self: Arc<Self>, mut dropped: oneshot::Receiver<()>) {
+        let url = &self.settings.address;
+
+        loop {
+            let client = self.client();
+            tokio::select! {
+                _ = client.on_disconnect() => {},
+                _ = &mut dropped => return,
+            }
+            drop(client);
+            tracing::warn!(%url, "websocket connection lost");
+
+            let mut attempt = 0;
+            let client = loop {
+                attempt += 1;
+                if self.settings.max_retries.is_some_and(|max| attempt > max) {
+                    tracing::error!(%url, "failed to reconnect websocket");
+                    self.state.send_replace(ConnectionState::Disconnected);
+                    self.subscriptions().clear();
+                    return;
+                }
+
+                self.state.send_replace(ConnectionState::Reconnecting { attempt });
+                let reconnected = async {
+                    tokio::time::sleep(self.settings.reconnect_timeout).await;
+                    WsClient::build(&self.settings).await
+                };
+                tokio::select! {
+                    result = reconnected => match result {
+                        Ok(client) => break client,
+                        Err(error) => tracing::warn!(%url, %error, attempt, "failed to reconnect websocket"),
+                    },
+                    _ = &mut dropped => return,
+                }
+            };
+
+            let client = Arc::new(client);
+            self.resubscribe(&client).await;
+            *self.client.write().unwrap_or_else(|error| error.into_inner()) = client;
+            self.state.send_replace(ConnectionState::Connected);
+            tracing::info!(%url, "websocket reconnected");
+        }
+    }
+
+    async fn resubscribe(self: &Arc<Self>, client: &Arc<JsonRpcWsClient>) {
+        let subscriptions = std::mem::take(&mut *self.subscriptions());
+
+        for subscription in subscriptions {
+            // dropped by the user
+            if subscription.sender.is_closed() {
+                continue;
+            }
+
+            let result = client
+                .subscribe::<Value, _>(
+                    &subscription.subscribe_method,
+                    subscription.params.clone(),
+                    &subscription.unsubscribe_method,
+                )
+                .await;
+            match result {
+                Ok(stream) => {
+                    let sender = subscription.sender.clone();
+                    self.push(subscription);
+                    tokio::spawn(forward(stream, sender, client.clone(), Arc::downgrade(self)));
+                },
+                Err(error) => {
+                    // the sender is dropped, so the subscription ends
+                    tracing::warn!(method = %subscription.subscribe_method, %error, "failed to resubscribe");
+                },
+            }
+        }
+    }
+}
+
+/// Sends the notifications to the `WsSubscription` until the subscription ends. The subscription is
+/// resubscribed if it's ended by a lost connection, otherwise it's dropped by the user or closed by the server
+/// and it's removed
+async fn forward(
+    mut subscription: Subscription<Value>,
+    sender: mpsc::Sender<Value>,
+    client: Arc<JsonRpcWsClient>,
+    shared: Weak<Shared>,
+) {
+    loop {
+        tokio::select! {
+            notification = subscription.next() => match notification {
+                Some(Ok(notification)) => {
+                    if sender.send(notification).await.is_err() {
+                        break;
+                    }
+                },
+                Some(Err(error)) => tracing::warn!(%error, "invalid subscription notification"),
+                // the client is disconnected before its subscriptions end
+                None if !client.is_connected() => return,
+                None => break,
+            },
+            // unsubscribes on drop
+            _ = sender.closed() => break,
+        }
+    }
+
+    if let Some(shared) = shared.upgrade() {
+        shared.remove(&sender);
+    }
+}
+
+/// Notifications of `WsClient::subscribe`, unsubscribes on drop
+pub struct WsSubscription<N> {
+    receiver: mpsc::Receiver<Value>,
+    _notification: PhantomData<N>,
+}
+
+impl<N: DeserializeOwned> WsSubscription<N> {
+    /// `None` once the server closed the subscription, it failed to be resubscribed or the client gave up
+    /// reconnecting
+    pub async fn next(&mut self) -> Option<Result<N, Error>> {
+        let notification = self.receiver.recv().await?;
+        Some(serde_json::from_value(notification).map_err(Error::ParseError))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use jsonrpsee::{
+        rpc_params,
+        server::{ServerBuilder, ServerHandle},
+        RpcModule, SubscriptionMessage,
+    };
+
+    use super::*;
+
+    async fn start(address: &str) -> (SocketAddr, ServerHandle) {
+        let mut module = RpcModule::new(());
+        module
+            .register_method("version", |_, _| Ok::<_, jsonrpsee::types::ErrorObjectOwned>("1.0"))
+            .unwrap();
+        module
+            .register_subscription(
+                "subscribe_count",
+                "count",
+                "unsubscribe_count",
+                |_, pending, _| async move {
+                    let sink = pending.accept().await?;
+                    for i in 0..3 {
+                        sink.send(SubscriptionMessage::from_json(&i)?).await?;
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+        module
+            .register_subscription(
+                "subscribe_once",
+                "once",
+                "unsubscribe_once",
+                |_, pending, _| async move {
+                    let sink = pending.accept().await?;
+                    sink.send(SubscriptionMessage::from_json(&0)?).await?;
+                    // closes the subscription
+                    Err("done".into())
+                },
+            )
+            .unwrap();
+
+        let server = ServerBuilder::default().build(address).await.unwrap();
+        (server.local_addr().unwrap(), server.start(module).unwrap())
+    }
+
+    #[tokio::test]
+    async fn reconnect_and_resubscribe() {
+        let (address, server) = start("127.0.0.1:0").await;
+        let client = WsClient::connect(&RpcClientSettings {
+            address: format!("ws://{address}"),
+            reconnect_timeout: Duration::from_millis(50),
+            max_retries: Some(20),
+            max_request_retries: 0,
+            request_retry_delay: Duration::ZERO,
+            retry_methods: Default::default(),
+            request_timeout: Duration::from_secs(5),
+            max_concurrent_requests: 16,
+            headers: Default::default(),
+        })
+        .await
+        .unwrap();
+        let mut state = client.state();
+
+        let mut subscription = client
+            .subscribe::<u32>("subscribe_count", rpc_params![], "unsubscribe_count")
+            .await
+            .unwrap();
+        assert_eq!(subscription.next().await.unwrap().unwrap(), 0);
+
+        server.stop().unwrap();
+        server.stopped().await;
+        state
+            .wait_for(|state| matches!(state, ConnectionState::Reconnecting { .. }))
+            .await
+            .unwrap();
+
+        let (_, server) = start(&address.to_string()).await;
+        state
+            .wait_for(|state| *state == ConnectionState::Connected)
+            .await
+            .unwrap();
+
+        let version: String = client.request("version", rpc_params![]).await.unwrap();
+        assert_eq!(version, "1.0");
+        // the rest of the first subscription may be received before the new one
+        let mut notifications = vec![];
+        while notifications.len() < 3 || notifications[notifications.len() - 3..] != [0, 1, 2] {
+            let notification = tokio::time::timeout(Duration::from_secs(5), subscription.next())
+                .await
+                .unwrap();
+            notifications.push(notification.unwrap().unwrap());
+        }
+
+        server.stop().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn end_closed_subscription() {
+        let (address, server) = start("127.0.0.1:0").await;
+        let client = WsClient::connect(&RpcClientSettings {
+            address: format!("ws://{address}"),
+            reconnect_timeout: Duration::from_millis(50),
+            max_retries: Some(20),
+            max_request_retries: 0,
+            request_retry_delay: Duration::ZERO,
+            retry_methods: Default::default(),
+            request_timeout: Duration::from_secs(5),
+            max_concurrent_requests: 16,
+            headers: Default::default(),
+        })
+        .await
+        .unwrap();
+
+        let mut subscription = client
+            .subscribe::<u32>("subscribe_once", rpc_params![], "unsubscribe_once")
+            .await
+            .unwrap();
+        assert_eq!(subscription.next().await.unwrap().unwrap(), 0);
+        let next = tokio::time::timeout(Duration::from_secs(5), subscription.next()).await;
+        assert!(next.unwrap().is_none());
+        assert!(client.shared.subscriptions().is_empty());
+
+        let mut subscription = client
+            .subscribe::<u32>("subscribe_count", rpc_params![], "unsubscribe_count")
+            .await
+            .unwrap();
+        assert_eq!(subscription.next().await.unwrap().unwrap(), 0);
+        drop(subscription);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !client.shared.subscriptions().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        server.stop().unwrap();
+    }
+}
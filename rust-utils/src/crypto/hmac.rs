@@ -0,0 +1,60 @@
+//! HMAC-SHA256 signatures of shared secrets, e.g. of webhook payloads. Signatures are compared in
+//! constant time
+
+use ::hmac::{Hmac, Mac};
+use rustc_hex::{FromHex, ToHex};
+use sha2::Sha256;
+
+pub const SIGNATURE_LENGTH: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(secret: &[u8], message: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac
+}
+
+pub fn sign(secret: &[u8], message: &[u8]) -> [u8; SIGNATURE_LENGTH] {
+    mac(secret, message).finalize().into_bytes().into()
+}
+
+/// Lowercase hex signature
+pub fn sign_hex(secret: &[u8], message: &[u8]) -> String {
+    sign(secret, message).to_hex()
+}
+
+pub fn verify(secret: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    mac(secret, message).verify_slice(signature).is_ok()
+}
+
+/// Verify a hex signature in either case
+pub fn verify_hex(secret: &[u8], message: &[u8], signature: &str) -> bool {
+    signature
+        .from_hex::<Vec<u8>>()
+        .is_ok_and(|signature| verify(secret, message, &signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        // RFC 4231 test case 2
+        let signature = sign_hex(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            signature,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        assert!(verify_hex(
+            b"Jefe",
+            b"what do ya want for nothing?",
+            &signature.to_uppercase()
+        ));
+        assert!(!verify_hex(b"Jefe", b"what do ya want for nothing!", &signature));
+        assert!(!verify_hex(b"Jefe", b"what do ya want for nothing?", &signature[2..]));
+        assert!(!verify_hex(b"Jefe", b"what do ya want for nothing?", "not hex"));
+    }
+}
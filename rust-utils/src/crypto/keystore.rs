@@ -0,0 +1,324 @@
+//! Keypairs stored in files instead of environment variables: password-encrypted keystores (scrypt +
+//! AES-256-GCM) and plain Solana CLI JSON keypairs
+//!
+//! # Usage
+//! ```ignore
+//! // settings.toml
+//! // [keypair]
+//! // path = "/secrets/keystore.json"
+//! // password = "..."
+//!
+//! let keypair: Keypair = settings.keypair.load()?;
+//! ```
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, SignatureError, KEYPAIR_LENGTH, SECRET_KEY_LENGTH};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::secret::Secret;
+
+pub const KEYSTORE_VERSION: u32 = 1;
+const CIPHER: &str = "aes-256-gcm";
+const KDF: &str = "scrypt";
+/// Memory of scrypt is `128 * r * 2^log_n` bytes, keystores asking for more aren't decrypted
+const MAX_KDF_MEMORY: u64 = 1 << 30;
+const MAX_KDF_P: u32 = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeystoreError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported keystore: {0}")]
+    Unsupported(String),
+    #[error("invalid keystore: {0}")]
+    Invalid(&'static str),
+    #[error("wrong keystore password")]
+    WrongPassword,
+    #[error(transparent)]
+    Keypair(#[from] SignatureError),
+}
+
+/// Keypairs which are stored as 64 bytes of the secret and the public key
+pub trait KeypairBytes: Sized {
+    fn to_keypair_bytes(&self) -> [u8; KEYPAIR_LENGTH];
+    fn from_keypair_bytes(bytes: &[u8]) -> Result<Self, SignatureError>;
+}
+
+impl KeypairBytes for Keypair {
+    fn to_keypair_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+        self.to_bytes()
+    }
+
+    fn from_keypair_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        Keypair::from_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "solana")]
+impl KeypairBytes for solana_sdk::signer::keypair::Keypair {
+    fn to_keypair_bytes(&self) -> [u8; KEYPAIR_LENGTH] {
+        self.to_bytes()
+    }
+
+    fn from_keypair_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        Self::from_bytes(bytes)
+    }
+}
+
+/// Scrypt parameters, `n` is `2^log_n`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self { log_n: 15, r: 8, p: 1 }
+    }
+}
+
+impl KdfParams {
+    /// Params read from a file could take the memory and CPU of the host otherwise
+    fn is_bounded(&self) -> bool {
+        let memory = 1u64
+            .checked_shl(self.log_n.into())
+            .and_then(|n| n.checked_mul(128 * u64::from(self.r)));
+        memory.is_some_and(|memory| memory <= MAX_KDF_MEMORY) && self.p <= MAX_KDF_P
+    }
+}
+
+/// Keystore file content, binary fields are base58
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub pubkey: String,
+    pub cipher: String,
+    pub ciphertext: String,
+    pub nonce: String,
+    pub kdf: String,
+    pub kdf_params: KdfParams,
+    pub salt: String,
+}
+
+impl Keystore {
+    pub fn encrypt<K: KeypairBytes>(keypair: &K, password: &str) -> Result<Self, KeystoreError> {
+        Self::encrypt_with(keypair, password, KdfParams::default())
+    }
+
+    pub fn encrypt_with<K: KeypairBytes>(
+        keypair: &K,
+        password: &str,
+        kdf_params: KdfParams,
+    ) -> Result<Self, KeystoreError> {
+        let salt: [u8; 32] = rand::random();
+        let nonce: [u8; 12] = rand::random();
+
+        let bytes = Zeroizing::new(keypair.to_keypair_bytes());
+        let pubkey = bs58::encode(&bytes[KEYPAIR_LENGTH - 32..]).into_string();
+        let ciphertext = cipher(password, &salt, kdf_params)?.encrypt(Nonce::from_slice(&nonce), bytes.as_ref());
+
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            pubkey,
+            cipher: CIPHER.to_owned(),
+            ciphertext: bs58::encode(ciphertext.map_err(|_| KeystoreError::Invalid("keypair"))?).into_string(),
+            nonce: bs58::encode(nonce).into_string(),
+            kdf: KDF.to_owned(),
+            kdf_params,
+            salt: bs58::encode(salt).into_string(),
+        })
+    }
+
+    pub fn decrypt<K: KeypairBytes>(&self, password: &str) -> Result<K, KeystoreError> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::Unsupported(format!("version {}", self.version)));
+        }
+        if self.cipher != CIPHER || self.kdf != KDF {
+            return Err(KeystoreError::Unsupported(format!("{} with {}", self.cipher, self.kdf)));
+        }
+
+        let nonce = decode(&self.nonce, "nonce")?;
+        if nonce.len() != 12 {
+            return Err(KeystoreError::Invalid("nonce"));
+        }
+        let ciphertext = decode(&self.ciphertext, "ciphertext")?;
+
+        let bytes = Zeroizing::new(
+            cipher(password, &decode(&self.salt, "salt")?, self.kdf_params)?
+                .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+                .map_err(|_| KeystoreError::WrongPassword)?,
+        );
+
+        if bytes.len() != KEYPAIR_LENGTH {
+            return Err(KeystoreError::Invalid("keypair"));
+        }
+        // the public key isn't derived from the secret by `from_keypair_bytes`
+        let public = PublicKey::from(&SecretKey::from_bytes(&bytes[..SECRET_KEY_LENGTH])?);
+        if public.as_bytes() != &bytes[SECRET_KEY_LENGTH..]
+            || bs58::encode(public.as_bytes()).into_string() != self.pubkey
+        {
+            return Err(KeystoreError::Invalid("pubkey"));
+        }
+
+        Ok(K::from_keypair_bytes(&bytes)?)
+    }
+
+    pub fn read<K: KeypairBytes>(path: impl AsRef<Path>, password: &str) -> Result<K, KeystoreError> {
+        let keystore: Self = serde_json::from_slice(&fs::read(path)?)?;
+        keystore.decrypt(password)
+    }
+
+    /// Creates the file readable by the owner only, an existing file isn't overwritten
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), KeystoreError> {
+        create_private(path.as_ref(), &serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn create_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn cipher(password: &str, salt: &[u8], kdf_params: KdfParams) -> Result<Aes256Gcm, KeystoreError> {
+    if !kdf_params.is_bounded() {
+        return Err(KeystoreError::Unsupported(format!("kdf params {kdf_params:?}")));
+    }
+    let params = scrypt::Params::new(kdf_params.log_n, kdf_params.r, kdf_params.p)
+        .map_err(|_| KeystoreError::Invalid("kdf params"))?;
+
+    let mut key = Zeroizing::new([0; 32]);
+    scrypt::scrypt(password.as_bytes(), salt, &params, key.as_mut())
+        .map_err(|_| KeystoreError::Invalid("key length"))?;
+
+    Ok(Aes256Gcm::new(Key::from_slice(key.as_ref())))
+}
+
+fn decode(value: &str, field: &'static str) -> Result<Vec<u8>, KeystoreError> {
+    bs58::decode(value)
+        .into_vec()
+        .map_err(|_| KeystoreError::Invalid(field))
+}
+
+/// Keypair in the Solana CLI format, a JSON array of 64 bytes
+pub fn read_solana_keypair<K: KeypairBytes>(path: impl AsRef<Path>) -> Result<K, KeystoreError> {
+    let json = Zeroizing::new(fs::read(path)?);
+    let bytes: Zeroizing<Vec<u8>> = Zeroizing::new(serde_json::from_slice(&json)?);
+
+    Ok(K::from_keypair_bytes(&bytes)?)
+}
+
+/// Creates the file readable by the owner only, an existing file isn't overwritten
+pub fn write_solana_keypair<K: KeypairBytes>(path: impl AsRef<Path>, keypair: &K) -> Result<(), KeystoreError> {
+    let bytes = Zeroizing::new(keypair.to_keypair_bytes());
+    let json = Zeroizing::new(serde_json::to_vec(bytes.as_ref())?);
+
+    create_private(path.as_ref(), &json)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreSettings {
+    pub path: PathBuf,
+    /// Keystore password, the file is a Solana CLI keypair without it
+    #[serde(default)]
+    pub password: Option<Secret<String>>,
+}
+
+impl KeystoreSettings {
+    pub fn load<K: KeypairBytes>(&self) -> Result<K, KeystoreError> {
+        match &self.password {
+            Some(password) => Keystore::read(&self.path, password.expose_secret()),
+            None => read_solana_keypair(&self.path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{KeypairExt, PublicKeyExt};
+
+    #[test]
+    fn encrypt_keypair() {
+        let keypair = Keypair::new_rand();
+        let params = KdfParams { log_n: 4, r: 8, p: 1 };
+
+        let keystore = Keystore::encrypt_with(&keypair, "hunter2", params).unwrap();
+        assert_eq!(keystore.pubkey, keypair.public.to_base58());
+        let decrypted: Keypair = keystore.decrypt("hunter2").unwrap();
+        assert_eq!(decrypted.to_bytes(), keypair.to_bytes());
+        assert!(matches!(
+            keystore.decrypt::<Keypair>("hunter3"),
+            Err(KeystoreError::WrongPassword)
+        ));
+
+        let path = std::env::temp_dir().join(format!("keystore-{}.json", std::process::id()));
+        keystore.write(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        let settings = KeystoreSettings {
+            path: path.clone(),
+            password: Some("hunter2".into()),
+        };
+        assert_eq!(settings.load::<Keypair>().unwrap().to_bytes(), keypair.to_bytes());
+
+        assert!(matches!(
+            write_solana_keypair(&path, &keypair),
+            Err(KeystoreError::Io(error)) if error.kind() == io::ErrorKind::AlreadyExists
+        ));
+        fs::remove_file(&path).unwrap();
+        write_solana_keypair(&path, &keypair).unwrap();
+        let settings = KeystoreSettings { path, password: None };
+        assert_eq!(settings.load::<Keypair>().unwrap().to_bytes(), keypair.to_bytes());
+        fs::remove_file(settings.path).unwrap();
+    }
+
+    #[test]
+    fn reject_tampered_keystore() {
+        let keypair = Keypair::new_rand();
+        let params = KdfParams { log_n: 4, r: 8, p: 1 };
+        let keystore = Keystore::encrypt_with(&keypair, "hunter2", params).unwrap();
+
+        let other = Keystore {
+            pubkey: Keypair::new_rand().public.to_base58(),
+            ..keystore.clone()
+        };
+        assert!(matches!(
+            other.decrypt::<Keypair>("hunter2"),
+            Err(KeystoreError::Invalid("pubkey"))
+        ));
+
+        let expensive = Keystore {
+            kdf_params: KdfParams { log_n: 40, r: 8, p: 1 },
+            ..keystore
+        };
+        assert!(matches!(
+            expensive.decrypt::<Keypair>("hunter2"),
+            Err(KeystoreError::Unsupported(_))
+        ));
+    }
+}
@@ -4,6 +4,17 @@ use chrono::Utc;
 use ed25519_dalek::{Keypair, PublicKey, Signature, SignatureError, Signer, Verifier, PUBLIC_KEY_LENGTH};
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "crypto-hmac")]
+pub mod hmac;
+#[cfg(feature = "crypto-keystore")]
+pub mod keystore;
+#[cfg(feature = "crypto-secp256k1")]
+pub mod secp256k1;
+#[cfg(feature = "crypto-sign-in")]
+pub mod sign_in;
+#[cfg(feature = "crypto-signer")]
+pub mod signer;
+
 pub trait KeypairExt {
     type Signature;
     fn new_rand() -> Self;
@@ -57,7 +68,7 @@ impl PublicKeyExt<Signature> for PublicKey {
     }
 
     fn verify_slice(&self, message: &[u8], signature: &Signature) -> Result<(), SignatureError> {
-        self.verify(&message, signature)
+        self.verify(message, signature)
     }
 }
 
@@ -96,25 +107,57 @@ pub trait CheckSignature: GetSignatureTtl {
         msg: &T,
         timed_signature: &TimedSignature<&str>,
     ) -> Result<(), Error> {
-        if let Some(signature_ttl) = self.get_signature_ttl() {
-            // Debug mode if signature_ttl in settings is 0 we have option to skip checking signature
-            if signature_ttl == 0 && timed_signature.timestamp == 0 {
-                return Ok(());
-            }
+        if skip_signature_check(self.get_signature_ttl(), timed_signature.timestamp)? {
+            return Ok(());
+        }
 
-            let now = Utc::now().timestamp() as u64;
-            if now.abs_diff(timed_signature.timestamp) > signature_ttl {
-                return Err(Error::TTLExpired(signature_ttl, now));
-            }
+        verify_signature(pubkey, msg, timed_signature.signature)
+    }
+
+    /// Same as `check_signature` for `0x` prefixed hex Ethereum addresses and `personal_sign` signatures
+    #[cfg(feature = "crypto-secp256k1")]
+    fn check_ethereum_signature<T: borsh::ser::BorshSerialize>(
+        &self,
+        address: &str,
+        msg: &T,
+        timed_signature: &TimedSignature<&str>,
+    ) -> Result<(), Error> {
+        if skip_signature_check(self.get_signature_ttl(), timed_signature.timestamp)? {
+            return Ok(());
         }
 
-        let verifying_key = PublicKey::from_base58(pubkey).ok_or(Error::WrongUser(pubkey.to_string()))?;
+        let address = ethereum_types::Address::from_str(address).map_err(|_| Error::WrongUser(address.to_string()))?;
 
-        let signature = Signature::from_str(timed_signature.signature)
+        let signature = secp256k1::EthereumSignature::from_str(timed_signature.signature)
             .map_err(|_| Error::WrongSignature(timed_signature.signature.to_string()))?;
 
-        Ok(verifying_key.verify_borsh(msg, &signature)?)
+        Ok(address.verify_borsh(msg, &signature)?)
+    }
+}
+
+fn verify_signature<T: borsh::ser::BorshSerialize>(pubkey: &str, msg: &T, signature: &str) -> Result<(), Error> {
+    let verifying_key = PublicKey::from_base58(pubkey).ok_or(Error::WrongUser(pubkey.to_string()))?;
+
+    let signature = Signature::from_str(signature).map_err(|_| Error::WrongSignature(signature.to_string()))?;
+
+    Ok(verifying_key.verify_borsh(msg, &signature)?)
+}
+
+/// Whether the signature isn't checked, errors if its timestamp is out of the ttl
+fn skip_signature_check(signature_ttl: Option<u64>, timestamp: u64) -> Result<bool, Error> {
+    if let Some(signature_ttl) = signature_ttl {
+        // Debug mode if signature_ttl in settings is 0 we have option to skip checking signature
+        if signature_ttl == 0 && timestamp == 0 {
+            return Ok(true);
+        }
+
+        let now = Utc::now().timestamp() as u64;
+        if now.abs_diff(timestamp) > signature_ttl {
+            return Err(Error::TTLExpired(signature_ttl, now));
+        }
     }
+
+    Ok(false)
 }
 
 #[cfg(feature = "wrappers")]
@@ -228,7 +271,7 @@ mod solana {
         }
 
         fn verify_slice(&self, message: &[u8], signature: &Signature) -> Result<(), SignatureError> {
-            if signature.verify(self.as_ref(), &message) {
+            if signature.verify(self.as_ref(), message) {
                 Ok(())
             } else {
                 Err(SignatureError::new())
@@ -237,7 +280,7 @@ mod solana {
     }
 }
 
-#[cfg(all(test))]
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -267,6 +310,26 @@ mod tests {
             .unwrap();
     }
 
+    #[cfg(feature = "crypto-secp256k1")]
+    #[test]
+    fn test_ethereum_signature() {
+        let key = k256::ecdsa::SigningKey::new_rand();
+
+        let user = format!("{:#x}", secp256k1::address(&key.verifying_key()));
+        let timestamp = 1234567890;
+
+        let msg = (&user, timestamp);
+        let signature = key.sign_borsh(&msg).to_string();
+
+        let tests_service = TestService;
+        tests_service
+            .check_ethereum_signature(&user, &msg, &TimedSignature::new(timestamp, &signature))
+            .unwrap();
+        assert!(tests_service
+            .check_ethereum_signature(&user, &(&user, 0), &TimedSignature::new(timestamp, &signature))
+            .is_err());
+    }
+
     #[test]
     fn check_signing() {
         let keypair = Keypair::new_rand();
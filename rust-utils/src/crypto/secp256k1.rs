@@ -0,0 +1,173 @@
+//! Ethereum signatures: secp256k1 keys sign EIP-191 `personal_sign` hashes of messages, signers are
+//! identified by addresses recovered from signatures
+//!
+//! # Usage
+//! ```ignore
+//! let signature = signing_key.sign_borsh(&msg);
+//! address(&signing_key.verifying_key()).verify_borsh(&msg, &signature)?;
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use ed25519_dalek::SignatureError;
+use ethereum_types::Address;
+use k256::{
+    ecdsa::{recoverable, signature::DigestSigner, SigningKey, VerifyingKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    FieldBytes,
+};
+use rustc_hex::{FromHex, ToHex};
+use sha3::{Digest, Keccak256};
+
+use super::{KeypairExt, PublicKeyExt};
+
+pub const SIGNATURE_LENGTH: usize = 65;
+
+const EIP191_PREFIX: &str = "\x19Ethereum Signed Message:\n";
+
+/// Recoverable signature `r || s || v` as returned by `personal_sign`, `v` is 27 or 28
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EthereumSignature([u8; SIGNATURE_LENGTH]);
+
+impl EthereumSignature {
+    /// `v` may also be the recovery id 0 or 1
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SignatureError> {
+        let mut signature: [u8; SIGNATURE_LENGTH] = bytes.try_into().map_err(|_| SignatureError::new())?;
+        signature[64] = match signature[64] {
+            v @ (0 | 1) => v + 27,
+            v @ (27 | 28) => v,
+            _ => return Err(SignatureError::new()),
+        };
+        Ok(Self(signature))
+    }
+
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_LENGTH] {
+        self.0
+    }
+
+    fn to_recoverable(self) -> Result<recoverable::Signature, SignatureError> {
+        let mut bytes = self.0;
+        bytes[64] -= 27;
+        recoverable::Signature::try_from(&bytes[..]).map_err(|_| SignatureError::new())
+    }
+}
+
+impl FromStr for EthereumSignature {
+    type Err = SignatureError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let hex = value.strip_prefix("0x").unwrap_or(value);
+        let bytes: Vec<u8> = hex.from_hex().map_err(|_| SignatureError::new())?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// `0x` prefixed hex
+impl fmt::Display for EthereumSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{}", self.0.to_hex::<String>())
+    }
+}
+
+impl fmt::Debug for EthereumSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Keccak-256 of the message prefixed with `"\x19Ethereum Signed Message:\n" + len(message)`
+pub fn eip191_hash(message: &[u8]) -> [u8; 32] {
+    eip191_digest(message).finalize().into()
+}
+
+fn eip191_digest(message: &[u8]) -> Keccak256 {
+    Keccak256::new()
+        .chain(EIP191_PREFIX)
+        .chain(message.len().to_string())
+        .chain(message)
+}
+
+/// Last 20 bytes of Keccak-256 of the uncompressed public key
+pub fn address(key: &VerifyingKey) -> Address {
+    let point = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+    Address::from_slice(&hash[12..])
+}
+
+/// Address of the signer of the `personal_sign` signature of the message
+pub fn recover_address(message: &[u8], signature: &EthereumSignature) -> Result<Address, SignatureError> {
+    let key = signature
+        .to_recoverable()?
+        .recover_verify_key_from_digest_bytes(&FieldBytes::from(eip191_hash(message)))
+        .map_err(|_| SignatureError::new())?;
+    Ok(address(&key))
+}
+
+impl KeypairExt for SigningKey {
+    type Signature = EthereumSignature;
+
+    fn new_rand() -> Self {
+        loop {
+            // a random scalar is out of the curve order with negligible probability
+            if let Ok(key) = SigningKey::from_bytes(&rand::random::<[u8; 32]>()) {
+                return key;
+            }
+        }
+    }
+
+    fn sign_slice(&self, message: &[u8]) -> EthereumSignature {
+        let signature: recoverable::Signature = self.sign_digest(eip191_digest(message));
+        EthereumSignature::from_bytes(signature.as_ref()).expect("recovery id must be 0 or 1")
+    }
+}
+
+/// Signatures are verified by comparing the recovered address
+impl PublicKeyExt<EthereumSignature> for Address {
+    fn new_zeroed() -> Self {
+        Address::zero()
+    }
+
+    fn from_base58(value: &str) -> Option<Self> {
+        let bytes = bs58::decode(value).into_vec().ok()?;
+        (bytes.len() == Address::len_bytes()).then(|| Address::from_slice(&bytes))
+    }
+
+    fn to_base58(&self) -> String {
+        bs58::encode(self).into_string()
+    }
+
+    fn verify_slice(&self, message: &[u8], signature: &EthereumSignature) -> Result<(), SignatureError> {
+        if recover_address(message, signature)? == *self {
+            Ok(())
+        } else {
+            Err(SignatureError::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn personal_sign() {
+        let key = SigningKey::from_bytes(&[1; 32]).unwrap();
+        let signer = address(&key.verifying_key());
+        assert_eq!(format!("{signer:#x}"), "0x1a642f0e3c3af545e7acbd38b07251b3990914f1");
+
+        let signature = key.sign_slice(b"hello world");
+        assert_eq!(recover_address(b"hello world", &signature).unwrap(), signer);
+        assert_eq!(signature.to_string().parse::<EthereumSignature>().unwrap(), signature);
+
+        // web3.js `accounts.sign("Some data", key)` example
+        let web3: EthereumSignature = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c".parse().unwrap();
+        assert_eq!(
+            format!("{:#x}", recover_address(b"Some data", &web3).unwrap()),
+            "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+        );
+
+        let other = SigningKey::new_rand();
+        assert!(signer.verify_borsh(&"hello", &other.sign_borsh(&"hello")).is_err());
+        assert!(signer.verify_borsh(&"hello", &key.sign_borsh(&"hello")).is_ok());
+    }
+}
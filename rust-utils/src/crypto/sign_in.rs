@@ -0,0 +1,423 @@
+//! "Sign-In With Solana" and "Sign-In With Ethereum" (EIP-4361) messages
+//!
+//! # Usage
+//! ```ignore
+//! // issue a nonce, the client signs the message built from it
+//! let nonce = generate_nonce();
+//! let message = SignInMessage::new(SignInChain::Solana, "app.p2p.org", pubkey, "https://app.p2p.org", nonce)
+//!     .with_statement("Sign in to P2P")
+//!     .to_string();
+//!
+//! // check the signed message text as it was received
+//! let message = SignInMessage::verify(&text, &signature, "app.p2p.org", &nonce)?;
+//! log::info!("{} signed in", message.address);
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use ed25519_dalek::{PublicKey, Signature};
+use rand::{distributions::Alphanumeric, Rng};
+
+use super::{Error, PublicKeyExt};
+
+pub const VERSION: &str = "1";
+const NONCE_LENGTH: usize = 17;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignInError {
+    #[error("invalid sign-in message: {0}")]
+    Invalid(&'static str),
+    #[error("sign-in message is for domain '{0}'")]
+    WrongDomain(String),
+    #[error("sign-in message has a wrong nonce")]
+    WrongNonce,
+    #[error("sign-in message is expired")]
+    Expired,
+    #[error("sign-in message isn't valid yet")]
+    NotYetValid,
+    #[error("{0} sign-in messages aren't supported")]
+    Unsupported(SignInChain),
+    #[error(transparent)]
+    Signature(#[from] Error),
+}
+
+/// Chain of the signing account, Ethereum signatures are checked with the `crypto-secp256k1` feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignInChain {
+    Solana,
+    Ethereum,
+}
+
+impl SignInChain {
+    fn preamble(self) -> String {
+        format!(" wants you to sign in with your {self} account:")
+    }
+}
+
+impl fmt::Display for SignInChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SignInChain::Solana => "Solana",
+            SignInChain::Ethereum => "Ethereum",
+        })
+    }
+}
+
+/// Random alphanumeric nonce for a sign-in message
+pub fn generate_nonce() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(NONCE_LENGTH)
+        .collect()
+}
+
+/// Fields of a sign-in message, its text is built by `Display` and parsed by `FromStr`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignInMessage {
+    pub chain: SignInChain,
+    pub domain: String,
+    /// Base58 public key of Solana accounts, `0x` prefixed hex address of Ethereum ones
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    /// Required for Ethereum, e.g. `1`, Solana messages may have the cluster, e.g. `mainnet`
+    pub chain_id: Option<String>,
+    pub nonce: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration_time: Option<DateTime<Utc>>,
+    pub not_before: Option<DateTime<Utc>>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+impl SignInMessage {
+    /// Message issued now
+    pub fn new(
+        chain: SignInChain,
+        domain: impl Into<String>,
+        address: impl Into<String>,
+        uri: impl Into<String>,
+        nonce: impl Into<String>,
+    ) -> Self {
+        Self {
+            chain,
+            domain: domain.into(),
+            address: address.into(),
+            statement: None,
+            uri: uri.into(),
+            version: VERSION.to_owned(),
+            chain_id: None,
+            nonce: nonce.into(),
+            issued_at: Utc::now(),
+            expiration_time: None,
+            not_before: None,
+            request_id: None,
+            resources: Vec::new(),
+        }
+    }
+
+    pub fn with_statement(mut self, statement: impl Into<String>) -> Self {
+        self.statement = Some(statement.into());
+        self
+    }
+
+    pub fn with_chain_id(mut self, chain_id: impl Into<String>) -> Self {
+        self.chain_id = Some(chain_id.into());
+        self
+    }
+
+    pub fn with_issued_at(mut self, issued_at: DateTime<Utc>) -> Self {
+        self.issued_at = issued_at;
+        self
+    }
+
+    pub fn with_expiration_time(mut self, expiration_time: DateTime<Utc>) -> Self {
+        self.expiration_time = Some(expiration_time);
+        self
+    }
+
+    pub fn with_not_before(mut self, not_before: DateTime<Utc>) -> Self {
+        self.not_before = Some(not_before);
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn with_resource(mut self, resource: impl Into<String>) -> Self {
+        self.resources.push(resource.into());
+        self
+    }
+
+    /// Parse the signed message text, check it's for the domain and the nonce at the moment and verify
+    /// the signature of the text: base58 for Solana, `0x` prefixed hex for Ethereum
+    pub fn verify(message: &str, signature: &str, domain: &str, nonce: &str) -> Result<Self, SignInError> {
+        let parsed: Self = message.parse()?;
+        parsed.check(domain, nonce, Utc::now())?;
+        parsed.verify_signature(message.as_bytes(), signature)?;
+        Ok(parsed)
+    }
+
+    pub fn check(&self, domain: &str, nonce: &str, now: DateTime<Utc>) -> Result<(), SignInError> {
+        if self.domain != domain {
+            return Err(SignInError::WrongDomain(self.domain.clone()));
+        }
+        if self.nonce != nonce {
+            return Err(SignInError::WrongNonce);
+        }
+        if self
+            .expiration_time
+            .is_some_and(|expiration_time| now >= expiration_time)
+        {
+            return Err(SignInError::Expired);
+        }
+        if self.not_before.is_some_and(|not_before| now < not_before) {
+            return Err(SignInError::NotYetValid);
+        }
+        Ok(())
+    }
+
+    fn verify_signature(&self, message: &[u8], signature: &str) -> Result<(), SignInError> {
+        match self.chain {
+            SignInChain::Solana => {
+                let pubkey = PublicKey::from_base58(&self.address).ok_or(Error::WrongUser(self.address.clone()))?;
+                let signature = bs58::decode(signature)
+                    .into_vec()
+                    .ok()
+                    .and_then(|bytes| Signature::from_bytes(&bytes).ok())
+                    .ok_or(Error::WrongSignature(signature.to_owned()))?;
+                Ok(pubkey.verify_slice(message, &signature).map_err(Error::from)?)
+            },
+            #[cfg(feature = "crypto-secp256k1")]
+            SignInChain::Ethereum => {
+                use super::secp256k1::EthereumSignature;
+
+                let address = ethereum_types::Address::from_str(&self.address)
+                    .map_err(|_| Error::WrongUser(self.address.clone()))?;
+                let signature =
+                    EthereumSignature::from_str(signature).map_err(|_| Error::WrongSignature(signature.to_owned()))?;
+                Ok(address.verify_slice(message, &signature).map_err(Error::from)?)
+            },
+            #[cfg(not(feature = "crypto-secp256k1"))]
+            SignInChain::Ethereum => Err(SignInError::Unsupported(self.chain)),
+        }
+    }
+}
+
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+impl fmt::Display for SignInMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}{}", self.domain, self.chain.preamble())?;
+        writeln!(f, "{}", self.address)?;
+        writeln!(f)?;
+        if let Some(statement) = &self.statement {
+            writeln!(f, "{statement}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "URI: {}", self.uri)?;
+        write!(f, "Version: {}", self.version)?;
+        if let Some(chain_id) = &self.chain_id {
+            write!(f, "\nChain ID: {chain_id}")?;
+        }
+        write!(f, "\nNonce: {}", self.nonce)?;
+        write!(f, "\nIssued At: {}", format_time(&self.issued_at))?;
+        if let Some(expiration_time) = &self.expiration_time {
+            write!(f, "\nExpiration Time: {}", format_time(expiration_time))?;
+        }
+        if let Some(not_before) = &self.not_before {
+            write!(f, "\nNot Before: {}", format_time(not_before))?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, "\nRequest ID: {request_id}")?;
+        }
+        if !self.resources.is_empty() {
+            write!(f, "\nResources:")?;
+            for resource in &self.resources {
+                write!(f, "\n- {resource}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_time(value: &str, field: &'static str) -> Result<DateTime<Utc>, SignInError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| SignInError::Invalid(field))
+}
+
+impl FromStr for SignInMessage {
+    type Err = SignInError;
+
+    fn from_str(message: &str) -> Result<Self, Self::Err> {
+        let mut lines = message.split('\n');
+        let mut next = |field| lines.next().ok_or(SignInError::Invalid(field));
+
+        let header = next("header")?;
+        let (chain, domain) = [SignInChain::Solana, SignInChain::Ethereum]
+            .into_iter()
+            .find_map(|chain| Some((chain, header.strip_suffix(&chain.preamble())?)))
+            .ok_or(SignInError::Invalid("header"))?;
+        let address = next("address")?;
+        if !next("statement")?.is_empty() {
+            return Err(SignInError::Invalid("statement"));
+        }
+        let statement = match next("statement")? {
+            "" => None,
+            statement => {
+                if !next("statement")?.is_empty() {
+                    return Err(SignInError::Invalid("statement"));
+                }
+                Some(statement.to_owned())
+            },
+        };
+
+        let mut parsed = SignInMessage::new(chain, domain, address, "", "");
+        parsed.statement = statement;
+        let mut required = (None, None, None, None);
+        let mut in_resources = false;
+        for line in lines {
+            if in_resources {
+                let resource = line.strip_prefix("- ").ok_or(SignInError::Invalid("resources"))?;
+                parsed.resources.push(resource.to_owned());
+                continue;
+            }
+
+            let (field, value) = line.split_once(':').ok_or(SignInError::Invalid("field"))?;
+            if field == "Resources" && value.is_empty() {
+                in_resources = true;
+                continue;
+            }
+            let value = value.strip_prefix(' ').ok_or(SignInError::Invalid("field"))?;
+            match field {
+                "URI" => required.0 = Some(value.to_owned()),
+                "Version" => required.1 = Some(value.to_owned()),
+                "Chain ID" => parsed.chain_id = Some(value.to_owned()),
+                "Nonce" => required.2 = Some(value.to_owned()),
+                "Issued At" => required.3 = Some(parse_time(value, "issued at")?),
+                "Expiration Time" => parsed.expiration_time = Some(parse_time(value, "expiration time")?),
+                "Not Before" => parsed.not_before = Some(parse_time(value, "not before")?),
+                "Request ID" => parsed.request_id = Some(value.to_owned()),
+                _ => return Err(SignInError::Invalid("field")),
+            }
+        }
+
+        let (Some(uri), Some(version), Some(nonce), Some(issued_at)) = required else {
+            return Err(SignInError::Invalid("missing field"));
+        };
+        if version != VERSION {
+            return Err(SignInError::Invalid("version"));
+        }
+        if chain == SignInChain::Ethereum && parsed.chain_id.is_none() {
+            return Err(SignInError::Invalid("chain id"));
+        }
+
+        parsed.uri = uri;
+        parsed.version = version;
+        parsed.nonce = nonce;
+        parsed.issued_at = issued_at;
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use ed25519_dalek::Keypair;
+
+    use super::*;
+    use crate::crypto::KeypairExt;
+
+    #[test]
+    fn sign_in_with_solana() {
+        let keypair = Keypair::new_rand();
+        let nonce = generate_nonce();
+        let issued_at = DateTime::parse_from_rfc3339("2023-06-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let message = SignInMessage::new(
+            SignInChain::Solana,
+            "app.p2p.org",
+            keypair.public.to_base58(),
+            "https://app.p2p.org",
+            &nonce,
+        )
+        .with_statement("Sign in to P2P")
+        .with_chain_id("mainnet")
+        .with_issued_at(issued_at)
+        .with_expiration_time(issued_at + Duration::minutes(5))
+        .with_resource("https://app.p2p.org/terms");
+        let text = message.to_string();
+        assert!(text.starts_with(&format!(
+            "app.p2p.org wants you to sign in with your Solana account:\n{}\n\nSign in to P2P\n\nURI: https://app.p2p.org\nVersion: 1\nChain ID: mainnet\nNonce: {nonce}\nIssued At: 2023-06-01T10:00:00Z\n",
+            keypair.public.to_base58()
+        )));
+        assert_eq!(text.parse::<SignInMessage>().unwrap(), message);
+
+        let signature = bs58::encode(keypair.sign_slice(text.as_bytes())).into_string();
+        let parsed: SignInMessage = text.parse().unwrap();
+        parsed.verify_signature(text.as_bytes(), &signature).unwrap();
+        parsed.check("app.p2p.org", &nonce, issued_at).unwrap();
+        assert!(matches!(
+            parsed.check("evil.org", &nonce, issued_at),
+            Err(SignInError::WrongDomain(_))
+        ));
+        assert!(matches!(
+            parsed.check("app.p2p.org", &nonce, issued_at + Duration::minutes(6)),
+            Err(SignInError::Expired)
+        ));
+        assert!(parsed.verify_signature(b"other", &signature).is_err());
+    }
+
+    #[test]
+    fn parse_ethereum_message() {
+        // EIP-4361 example
+        let text = "service.org wants you to sign in with your Ethereum account:\n0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2\n\nI accept the ServiceOrg Terms of Service: https://service.org/tos\n\nURI: https://service.org/login\nVersion: 1\nChain ID: 1\nNonce: 32891756\nIssued At: 2021-09-30T16:25:24Z\nResources:\n- ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/\n- https://example.com/my-web2-claim.json";
+
+        let message: SignInMessage = text.parse().unwrap();
+        assert_eq!(message.chain, SignInChain::Ethereum);
+        assert_eq!(message.nonce, "32891756");
+        assert_eq!(message.resources.len(), 2);
+        assert_eq!(message.to_string(), text);
+
+        let without_statement = SignInMessage {
+            statement: None,
+            ..message
+        };
+        assert_eq!(
+            without_statement.to_string().parse::<SignInMessage>().unwrap(),
+            without_statement
+        );
+    }
+
+    #[cfg(feature = "crypto-secp256k1")]
+    #[test]
+    fn sign_in_with_ethereum() {
+        use crate::crypto::secp256k1::address;
+
+        let key = k256::ecdsa::SigningKey::new_rand();
+        let signer = format!("{:#x}", address(&key.verifying_key()));
+        let text = SignInMessage::new(
+            SignInChain::Ethereum,
+            "app.p2p.org",
+            &signer,
+            "https://app.p2p.org",
+            "nonce123",
+        )
+        .with_chain_id("1")
+        .to_string();
+
+        let signature = key.sign_slice(text.as_bytes()).to_string();
+        let message = SignInMessage::verify(&text, &signature, "app.p2p.org", "nonce123").unwrap();
+        assert_eq!(message.address, signer);
+        assert!(SignInMessage::verify(&text, &signature, "app.p2p.org", "nonce124").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Signing through external key custodians, e.g. Vault transit or a KMS, next to local keypairs
+//!
+//! # Usage
+//! ```ignore
+//! #[async_trait]
+//! impl SigningBackend for TransitClient {
+//!     async fn sign(&self, key: &str, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+//!         self.sign_ed25519(key, message).await
+//!     }
+//! }
+//!
+//! let signer = RemoteSigner::<_, Signature>::new(transit, "indexer");
+//! let signature = signer.sign_borsh(&message).await?;
+//! ```
+
+use std::marker::PhantomData;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use borsh::BorshSerialize;
+
+use super::{
+    skip_signature_check, verify_signature, CheckSignature, Error, GetSignatureTtl, KeypairExt, TimedSignature,
+};
+
+/// Async counterpart of `KeypairExt`, it's implemented for all keypairs
+#[async_trait]
+pub trait AsyncSigner: Send + Sync {
+    type Signature: Send;
+
+    async fn sign_slice(&self, message: &[u8]) -> anyhow::Result<Self::Signature>;
+
+    async fn sign_borsh<M: BorshSerialize + Sync>(&self, message: &M) -> anyhow::Result<Self::Signature> {
+        let message = borsh::to_vec(message)?;
+        self.sign_slice(&message).await
+    }
+}
+
+#[async_trait]
+impl<K> AsyncSigner for K
+where
+    K: KeypairExt + Send + Sync,
+    K::Signature: Send,
+{
+    type Signature = K::Signature;
+
+    async fn sign_slice(&self, message: &[u8]) -> anyhow::Result<Self::Signature> {
+        Ok(KeypairExt::sign_slice(self, message))
+    }
+}
+
+/// Service holding keys which signs messages with the named key and returns raw signatures
+#[async_trait]
+pub trait SigningBackend: Send + Sync {
+    async fn sign(&self, key: &str, message: &[u8]) -> anyhow::Result<Vec<u8>>;
+}
+
+/// Signer with a key of the backend, `S` is the signature parsed from the returned bytes
+pub struct RemoteSigner<B, S> {
+    backend: B,
+    key: String,
+    signature: PhantomData<fn() -> S>,
+}
+
+impl<B: SigningBackend, S> RemoteSigner<B, S> {
+    pub fn new(backend: B, key: impl Into<String>) -> Self {
+        Self {
+            backend,
+            key: key.into(),
+            signature: PhantomData,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+#[async_trait]
+impl<B, S> AsyncSigner for RemoteSigner<B, S>
+where
+    B: SigningBackend,
+    S: for<'a> TryFrom<&'a [u8]> + Send,
+{
+    type Signature = S;
+
+    async fn sign_slice(&self, message: &[u8]) -> anyhow::Result<S> {
+        let signature = self.backend.sign(&self.key, message).await?;
+        S::try_from(&signature).map_err(|_| anyhow!("malformed signature of key '{}'", self.key))
+    }
+}
+
+/// Async counterpart of `CheckSignature` for services which get the ttl from async sources, it's
+/// implemented for all `CheckSignature` services
+#[async_trait]
+pub trait AsyncCheckSignature: Send + Sync {
+    async fn get_signature_ttl(&self) -> Option<u64>;
+
+    async fn check_signature<T: BorshSerialize + Sync>(
+        &self,
+        pubkey: &str,
+        msg: &T,
+        timed_signature: &TimedSignature<&str>,
+    ) -> Result<(), Error> {
+        if skip_signature_check(self.get_signature_ttl().await, timed_signature.timestamp)? {
+            return Ok(());
+        }
+
+        verify_signature(pubkey, msg, timed_signature.signature)
+    }
+}
+
+#[async_trait]
+impl<C: CheckSignature + Send + Sync> AsyncCheckSignature for C {
+    async fn get_signature_ttl(&self) -> Option<u64> {
+        GetSignatureTtl::get_signature_ttl(self)
+    }
+
+    async fn check_signature<T: BorshSerialize + Sync>(
+        &self,
+        pubkey: &str,
+        msg: &T,
+        timed_signature: &TimedSignature<&str>,
+    ) -> Result<(), Error> {
+        CheckSignature::check_signature(self, pubkey, msg, timed_signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Keypair, Signature};
+
+    use super::*;
+    use crate::crypto::PublicKeyExt;
+
+    struct LocalBackend(Keypair);
+
+    #[async_trait]
+    impl SigningBackend for LocalBackend {
+        async fn sign(&self, _key: &str, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+            Ok(KeypairExt::sign_slice(&self.0, message).to_bytes().to_vec())
+        }
+    }
+
+    struct TestService;
+
+    impl GetSignatureTtl for TestService {
+        fn get_signature_ttl(&self) -> Option<u64> {
+            None
+        }
+    }
+
+    impl CheckSignature for TestService {}
+
+    #[tokio::test]
+    async fn sign_remotely() {
+        let keypair = Keypair::new_rand();
+        let user = keypair.public.to_base58();
+        let msg = (&user, 1u64);
+
+        let local = AsyncSigner::sign_borsh(&keypair, &msg).await.unwrap();
+        let remote =
+            RemoteSigner::<_, Signature>::new(LocalBackend(Keypair::from_bytes(&keypair.to_bytes()).unwrap()), "test")
+                .sign_borsh(&msg)
+                .await
+                .unwrap();
+        assert_eq!(local, remote);
+
+        let signature = remote.to_string();
+        AsyncCheckSignature::check_signature(&TestService, &user, &msg, &TimedSignature::new(1, &signature))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+
+use sqlx::migrate::MigrateError;
+
+/// SQLSTATE codes, see https://www.postgresql.org/docs/current/errcodes-appendix.html
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+const QUERY_CANCELED: &str = "57014";
+const ADMIN_SHUTDOWN: &str = "57P01";
+const CONNECTION_EXCEPTION_CLASS: &str = "08";
+
+/// `sqlx::Error` classified by its cause, so it can be mapped to the error of the service
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    #[error("unique violation: {source}")]
+    UniqueViolation {
+        constraint: Option<String>,
+        source: sqlx::Error,
+    },
+    #[error("foreign key violation: {source}")]
+    ForeignKeyViolation {
+        constraint: Option<String>,
+        source: sqlx::Error,
+    },
+    #[error("row not found")]
+    NotFound,
+    /// The pool had no idle connections in `connect_timeout` or the statement exceeded `statement_timeout`
+    #[error("timed out: {0}")]
+    Timeout(#[source] sqlx::Error),
+    #[error("connection failed: {0}")]
+    Connection(#[source] sqlx::Error),
+    #[error(transparent)]
+    Migrate(#[from] MigrateError),
+    #[error(transparent)]
+    Other(sqlx::Error),
+}
+
+impl DbError {
+    /// Name of the violated constraint
+    pub fn constraint(&self) -> Option<&str> {
+        match self {
+            Self::UniqueViolation { constraint, .. } | Self::ForeignKeyViolation { constraint, .. } => {
+                constraint.as_deref()
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether the operation may succeed once repeated
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Timeout(_) | Self::Connection(_))
+    }
+}
+
+impl From<sqlx::Error> for DbError {
+    fn from(error: sqlx::Error) -> Self {
+        let database = error.as_database_error();
+        let code = database.and_then(|database| database.code()).map(Cow::into_owned);
+        let constraint = database
+            .and_then(|database| database.constraint())
+            .map(ToOwned::to_owned);
+
+        match (&error, code.as_deref()) {
+            (sqlx::Error::RowNotFound, _) => Self::NotFound,
+            (_, Some(UNIQUE_VIOLATION)) => Self::UniqueViolation {
+                constraint,
+                source: error,
+            },
+            (_, Some(FOREIGN_KEY_VIOLATION)) => Self::ForeignKeyViolation {
+                constraint,
+                source: error,
+            },
+            (sqlx::Error::PoolTimedOut, _) | (_, Some(QUERY_CANCELED)) => Self::Timeout(error),
+            (sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed, _)
+            | (_, Some(ADMIN_SHUTDOWN)) => Self::Connection(error),
+            (_, Some(code)) if code.starts_with(CONNECTION_EXCEPTION_CLASS) => Self::Connection(error),
+            _ => Self::Other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_errors() {
+        assert!(matches!(DbError::from(sqlx::Error::RowNotFound), DbError::NotFound));
+        assert!(DbError::from(sqlx::Error::PoolTimedOut).is_transient());
+        assert!(DbError::from(sqlx::Error::PoolClosed).is_transient());
+        assert!(matches!(
+            DbError::from(sqlx::Error::ColumnNotFound("mint".to_owned())),
+            DbError::Other(_)
+        ));
+        assert_eq!(DbError::NotFound.constraint(), None);
+    }
+}
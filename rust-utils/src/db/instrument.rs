@@ -0,0 +1,197 @@
+//! Spans and metrics of queries executed on `&DbRepo` and `&mut DbAccess` of an instrumented repo.
+//! Queries executed on the dereferenced pool or transaction aren't instrumented
+
+use futures::{future::BoxFuture, stream::BoxStream};
+use sqlx::{
+    postgres::{PgQueryResult, PgRow},
+    Either, Error,
+};
+
+pub(super) type Step = Either<PgQueryResult, PgRow>;
+
+#[cfg(not(feature = "telemetry"))]
+pub(super) fn fetch_many<'e>(
+    _enabled: bool,
+    _sql: &str,
+    steps: BoxStream<'e, Result<Step, Error>>,
+) -> BoxStream<'e, Result<Step, Error>> {
+    steps
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub(super) fn fetch_optional<'e>(
+    _enabled: bool,
+    _sql: &str,
+    row: BoxFuture<'e, Result<Option<PgRow>, Error>>,
+) -> BoxFuture<'e, Result<Option<PgRow>, Error>> {
+    row
+}
+
+#[cfg(feature = "telemetry")]
+pub(super) use telemetry::{fetch_many, fetch_optional};
+
+#[cfg(feature = "telemetry")]
+mod telemetry {
+    use std::{sync::OnceLock, time::Instant};
+
+    use futures::{FutureExt, StreamExt};
+    use opentelemetry::{
+        global,
+        metrics::{Histogram, Unit},
+        KeyValue,
+    };
+    use tracing::{field::Empty, Instrument, Span};
+
+    use super::*;
+
+    /// Statements longer than this are truncated in spans
+    const MAX_STATEMENT_LEN: usize = 2048;
+
+    fn duration() -> &'static Histogram<f64> {
+        static DURATION: OnceLock<Histogram<f64>> = OnceLock::new();
+        DURATION.get_or_init(|| {
+            global::meter("db")
+                .f64_histogram("db.query.duration")
+                .with_unit(Unit::new("ms"))
+                .with_description("Duration of SQL queries")
+                .init()
+        })
+    }
+
+    /// Statement with collapsed whitespace
+    fn normalize(sql: &str) -> String {
+        let mut statement = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        if statement.len() > MAX_STATEMENT_LEN {
+            let end = (0..=MAX_STATEMENT_LEN)
+                .rev()
+                .find(|index| statement.is_char_boundary(*index))
+                .unwrap_or_default();
+            statement.truncate(end);
+            statement.push_str("...");
+        }
+        statement
+    }
+
+    /// Records the span fields and the duration once dropped
+    struct Query {
+        span: Span,
+        operation: String,
+        started_at: Instant,
+        returned: u64,
+        affected: u64,
+        failed: bool,
+    }
+
+    impl Query {
+        fn start(sql: &str) -> Self {
+            let statement = normalize(sql);
+            let operation = statement.split(' ').next().unwrap_or_default().to_uppercase();
+            let span = tracing::info_span!(
+                "db.query",
+                otel.name = %operation,
+                db.system = "postgresql",
+                db.operation = %operation,
+                db.statement = %statement,
+                db.rows = Empty,
+                error = Empty,
+            );
+
+            Self {
+                span,
+                operation,
+                started_at: Instant::now(),
+                returned: 0,
+                affected: 0,
+                failed: false,
+            }
+        }
+
+        fn observe_step(&mut self, step: &Result<Step, Error>) {
+            match step {
+                Ok(Either::Left(result)) => self.affected += result.rows_affected(),
+                Ok(Either::Right(_)) => self.returned += 1,
+                Err(error) => self.fail(error),
+            }
+        }
+
+        fn observe_row(&mut self, row: &Result<Option<PgRow>, Error>) {
+            match row {
+                Ok(row) => self.returned += row.is_some() as u64,
+                Err(error) => self.fail(error),
+            }
+        }
+
+        fn fail(&mut self, error: &Error) {
+            self.failed = true;
+            self.span.record("error", tracing::field::display(error));
+        }
+    }
+
+    impl Drop for Query {
+        fn drop(&mut self) {
+            self.span.record("db.rows", self.returned.max(self.affected));
+
+            let cx = opentelemetry::Context::current();
+            duration().record(&cx, self.started_at.elapsed().as_secs_f64() * 1000.0, &[
+                KeyValue::new("operation", self.operation.clone()),
+                KeyValue::new("success", !self.failed),
+            ]);
+        }
+    }
+
+    pub(in crate::db) fn fetch_many<'e>(
+        enabled: bool,
+        sql: &str,
+        steps: BoxStream<'e, Result<Step, Error>>,
+    ) -> BoxStream<'e, Result<Step, Error>> {
+        if !enabled {
+            return steps;
+        }
+
+        let mut query = Query::start(sql);
+        steps
+            .map(move |step| {
+                query.observe_step(&step);
+                step
+            })
+            .boxed()
+    }
+
+    pub(in crate::db) fn fetch_optional<'e>(
+        enabled: bool,
+        sql: &str,
+        row: BoxFuture<'e, Result<Option<PgRow>, Error>>,
+    ) -> BoxFuture<'e, Result<Option<PgRow>, Error>> {
+        if !enabled {
+            return row;
+        }
+
+        let mut query = Query::start(sql);
+        let span = query.span.clone();
+        async move {
+            let row = row.await;
+            query.observe_row(&row);
+            row
+        }
+        .instrument(span)
+        .boxed()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn normalize_statement() {
+            assert_eq!(
+                normalize("SELECT *\n    FROM tokens\n    WHERE mint = $1"),
+                "SELECT * FROM tokens WHERE mint = $1"
+            );
+
+            let long = format!("SELECT '{}'", "é".repeat(MAX_STATEMENT_LEN));
+            let statement = normalize(&long);
+            assert!(statement.len() <= MAX_STATEMENT_LEN + 3);
+            assert!(statement.ends_with("..."));
+        }
+    }
+}
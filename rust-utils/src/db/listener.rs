@@ -0,0 +1,202 @@
+//! Postgres LISTEN/NOTIFY with JSON payloads
+//!
+//! # Usage
+//! ```ignore
+//! #[async_trait]
+//! impl NotificationHandler for PermissionsCache {
+//!     type Payload = PermissionsChanged;
+//!
+//!     async fn handle_notification(&self, _channel: &str, changed: PermissionsChanged) -> anyhow::Result<()> {
+//!         self.reload(changed.account).await
+//!     }
+//!
+//!     async fn reconnected(&self) -> anyhow::Result<()> {
+//!         self.reload_all().await
+//!     }
+//! }
+//!
+//! let listener = repo.listen(["permissions_changed"], cache);
+//! // NOTIFY permissions_changed, '{"account": "..."}'
+//! listener.cancel_and_wait().await;
+//! ```
+
+use anyhow::Context;
+use async_trait::async_trait;
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use serde::de::DeserializeOwned;
+use sqlx::{postgres::PgListener, Error, PgPool};
+use stream_cancel::{Trigger, Tripwire};
+
+use super::DbRepo;
+
+#[async_trait]
+pub trait NotificationHandler {
+    type Payload: DeserializeOwned + Send;
+
+    async fn handle_notification(&self, channel: &str, payload: Self::Payload) -> anyhow::Result<()>;
+
+    /// Called once the connection is restored, notifications sent while it was lost aren't delivered
+    async fn reconnected(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct DbListenerCancellation {
+    trigger: Trigger,
+    stopped: Tripwire,
+}
+
+impl DbListenerCancellation {
+    pub fn cancel(self) {
+        self.trigger.cancel();
+    }
+
+    /// Cancel listening and wait until the current notification is handled and the connection is closed
+    pub async fn cancel_and_wait(self) {
+        self.trigger.cancel();
+        self.stopped.await;
+    }
+}
+
+enum Session {
+    Cancelled,
+    PoolClosed,
+}
+
+impl DbRepo {
+    /// Listen to the channels on a dedicated connection until cancelled or the pool is closed. The
+    /// connection is restored with exponential backoff, malformed payloads are logged and skipped
+    pub fn listen<Handler>(
+        &self,
+        channels: impl IntoIterator<Item = impl Into<String>>,
+        handler: Handler,
+    ) -> DbListenerCancellation
+    where
+        Handler: NotificationHandler + Send + Sync + 'static,
+    {
+        let pool = self.pool.clone();
+        let channels: Vec<String> = channels.into_iter().map(Into::into).collect();
+        let (trigger, cancelled) = Tripwire::new();
+        let (stopped_trigger, stopped) = Tripwire::new();
+
+        tokio::spawn(async move {
+            let mut backoff = ExponentialBackoff {
+                max_elapsed_time: None,
+                ..Default::default()
+            };
+            let mut connected_before = false;
+            loop {
+                match listen(
+                    &pool,
+                    &channels,
+                    &handler,
+                    &mut backoff,
+                    &mut connected_before,
+                    cancelled.clone(),
+                )
+                .await
+                {
+                    Ok(Session::Cancelled) => break,
+                    Ok(Session::PoolClosed) => {
+                        log::warn!("Stopped listening to {channels:?}, the pool is closed");
+                        break;
+                    },
+                    Err(error) => {
+                        let delay = backoff.next_backoff().unwrap_or(backoff.max_interval);
+                        log::warn!("Failed to listen to {channels:?}: {error:?}, retrying in {delay:?}");
+                        tokio::select! {
+                            _ = tokio::time::sleep(delay) => {},
+                            _ = cancelled.clone() => break,
+                        }
+                    },
+                }
+            }
+            stopped_trigger.cancel();
+        });
+
+        DbListenerCancellation { trigger, stopped }
+    }
+}
+
+async fn listen<Handler: NotificationHandler + Sync>(
+    pool: &PgPool,
+    channels: &[String],
+    handler: &Handler,
+    backoff: &mut ExponentialBackoff,
+    connected_before: &mut bool,
+    cancelled: Tripwire,
+) -> anyhow::Result<Session> {
+    let mut listener = match PgListener::connect_with(pool).await {
+        Err(Error::PoolClosed) => return Ok(Session::PoolClosed),
+        connected => connected.context("Failed to connect")?,
+    };
+    listener
+        .listen_all(channels.iter().map(String::as_str))
+        .await
+        .context("Failed to listen")?;
+    backoff.reset();
+    log::trace!("Listening to {channels:?}");
+
+    if std::mem::replace(connected_before, true) {
+        if let Err(error) = handler.reconnected().await {
+            log::error!("Failed to handle reconnect to {channels:?}: {error:?}");
+        }
+    }
+
+    loop {
+        let notification = tokio::select! {
+            notification = listener.try_recv() => notification,
+            _ = cancelled.clone() => return Ok(Session::Cancelled),
+        };
+        match notification {
+            Ok(Some(notification)) => dispatch(handler, notification.channel(), notification.payload()).await,
+            Ok(None) => anyhow::bail!("Connection is lost"),
+            Err(Error::PoolClosed) => return Ok(Session::PoolClosed),
+            Err(error) => return Err(error).context("Failed to receive notification"),
+        }
+    }
+}
+
+async fn dispatch<Handler: NotificationHandler + Sync>(handler: &Handler, channel: &str, payload: &str) {
+    let payload = match serde_json::from_str(payload) {
+        Ok(payload) => payload,
+        Err(error) => {
+            log::warn!("Failed to deserialize notification of {channel}: {error}");
+            return;
+        },
+    };
+
+    if let Err(error) = handler.handle_notification(channel, payload).await {
+        log::error!("Failed to handle notification of {channel}: {error:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Invalidated(Arc<Mutex<Vec<(String, u64)>>>);
+
+    #[async_trait]
+    impl NotificationHandler for Invalidated {
+        type Payload = u64;
+
+        async fn handle_notification(&self, channel: &str, account: u64) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push((channel.to_owned(), account));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_json_payloads() {
+        let handler = Invalidated::default();
+
+        dispatch(&handler, "permissions_changed", "7").await;
+        dispatch(&handler, "permissions_changed", "{malformed").await;
+
+        assert_eq!(*handler.0.lock().unwrap(), vec![("permissions_changed".to_owned(), 7)]);
+    }
+}
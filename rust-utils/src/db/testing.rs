@@ -0,0 +1,158 @@
+//! Isolated databases for integration tests of services
+//!
+//! Every `TestDb` is a new schema of the database at `TEST_DATABASE_URL`, the default `DbSettings` URL if
+//! it's not set. The schema is dropped with the `TestDb`, so tests can run concurrently on one server.
+//!
+//! # Usage
+//! ```ignore
+//! use rust_utils::db::testing::TestDb;
+//!
+//! #[tokio::test]
+//! async fn stores_verdicts() {
+//!     let db = TestDb::new(&MIGRATOR).await.unwrap();
+//!     let store = VerdictStore::new(db.repo().clone());
+//!     ...
+//! }
+//! ```
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sqlx::{postgres::PgConnectOptions, Connection, Executor, PgConnection};
+
+use super::{DbError, DbRepo, DbSettings, Migrations};
+
+/// Variable with the URL of the server the schemas are created on
+pub const TEST_DATABASE_URL: &str = "TEST_DATABASE_URL";
+
+static SCHEMAS: AtomicUsize = AtomicUsize::new(0);
+
+/// Schema with the migrations applied and the repo using it, the schema is dropped on drop
+pub struct TestDb {
+    repo: DbRepo,
+    schema: String,
+    options: PgConnectOptions,
+}
+
+impl TestDb {
+    pub async fn new(migrations: impl Into<Migrations>) -> Result<Self, DbError> {
+        let settings = match std::env::var(TEST_DATABASE_URL) {
+            Ok(url) => DbSettings::from_url(url),
+            Err(_) => DbSettings::default(),
+        };
+        Self::with_settings(&settings, Some(migrations.into())).await
+    }
+
+    pub async fn with_settings(settings: &DbSettings, migrations: Option<Migrations>) -> Result<Self, DbError> {
+        let options = settings.connect_options()?;
+        let schema = schema_name();
+
+        let mut connection = PgConnection::connect_with(&options).await?;
+        connection
+            .execute(format!(r#"CREATE SCHEMA "{schema}""#).as_str())
+            .await?;
+        connection.close().await?;
+
+        // unqualified tables are created in the schema, extensions of `public` are still found
+        let pool = settings
+            .pool_options()
+            .connect_with(options.clone().options([("search_path", format!("{schema},public"))]))
+            .await?;
+        let db = Self {
+            repo: DbRepo::from(pool),
+            schema,
+            options,
+        };
+        if let Some(migrations) = migrations {
+            db.repo.migrate(migrations).await?;
+        }
+
+        Ok(db)
+    }
+
+    pub fn repo(&self) -> &DbRepo {
+        &self.repo
+    }
+
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// Remove the rows of every table of the schema except applied migrations
+    pub async fn truncate(&self) -> Result<(), DbError> {
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT format('%I.%I', schemaname, tablename) FROM pg_tables
+            WHERE schemaname = $1 AND tablename <> '_sqlx_migrations'",
+        )
+        .bind(&self.schema)
+        .fetch_all(&self.repo.pool)
+        .await?;
+
+        if !tables.is_empty() {
+            sqlx::query(&format!("TRUNCATE {} RESTART IDENTITY CASCADE", tables.join(", ")))
+                .execute(&self.repo.pool)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let schema = std::mem::take(&mut self.schema);
+        let options = self.options.clone();
+
+        // the runtime of the test may be shutting down, the schema is dropped on a runtime of its own
+        let dropped = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|error| error.to_string())?;
+            runtime.block_on(async {
+                let mut connection = PgConnection::connect_with(&options)
+                    .await
+                    .map_err(|error| error.to_string())?;
+                connection
+                    .execute(format!(r#"DROP SCHEMA "{schema}" CASCADE"#).as_str())
+                    .await
+                    .map_err(|error| error.to_string())?;
+                connection.close().await.map_err(|error| error.to_string())
+            })
+        })
+        .join();
+
+        match dropped {
+            Ok(Ok(())) => {},
+            Ok(Err(error)) => log::warn!("Failed to drop test schema: {error}"),
+            Err(_) => log::warn!("Failed to drop test schema: cleanup panicked"),
+        }
+    }
+}
+
+/// Name unique among the processes of a test run and the previous runs
+fn schema_name() -> String {
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    format!(
+        "test_{started_at}_{}_{}",
+        std::process::id(),
+        SCHEMAS.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_schema_names() {
+        let (first, second) = (schema_name(), schema_name());
+        assert_ne!(first, second);
+        assert!(first.starts_with("test_"));
+        assert!(first.chars().all(|char| char.is_ascii_alphanumeric() || char == '_'));
+    }
+}
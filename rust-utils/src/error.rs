@@ -1,5 +1,6 @@
-use std::io;
+use std::{fmt::Display, io};
 
+use jsonrpsee::types::ErrorObjectOwned;
 use serde::{ser::SerializeTupleVariant, Serialize, Serializer};
 use strum::AsStaticRef;
 use strum_macros::AsStaticStr;
@@ -7,13 +8,67 @@ use thiserror::Error;
 
 pub type UtilsResult<T> = Result<T, UtilsError>;
 
+/// JSON-RPC error object of a domain error
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl From<RpcError> for ErrorObjectOwned {
+    fn from(error: RpcError) -> Self {
+        ErrorObjectOwned::owned(error.code, error.message, error.data)
+    }
+}
+
+/// Maps an error into `RpcError` with a stable code, the message is its `Display`.
+/// Usually implemented with `impl_into_rpc_error!`
+pub trait IntoRpcError: Display {
+    fn rpc_code(&self) -> i32;
+
+    fn rpc_data(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    fn to_rpc_error(&self) -> RpcError {
+        RpcError {
+            code: self.rpc_code(),
+            message: self.to_string(),
+            data: self.rpc_data(),
+        }
+    }
+
+    fn into_error_object(self) -> ErrorObjectOwned
+    where
+        Self: Sized,
+    {
+        self.to_rpc_error().into()
+    }
+}
+
+#[doc(hidden)]
+pub use serde_json;
+
+#[doc(hidden)]
+pub fn serialize_rpc_data(error: &impl Serialize) -> Option<serde_json::Value> {
+    serde_json::to_value(error).ok()
+}
+
 #[derive(Debug, Error, Serialize)]
 pub enum FeeTokenProviderError {
     #[error("Duplicate token mint: {0}")]
     DuplicateTokenMint(String),
 
-    #[error("Poison error of {0}")]
-    PoisonError(String),
+    #[error("Unsupported snapshot version: {0}")]
+    UnsupportedSnapshotVersion(String),
+
+    #[error("Tokens are modified concurrently: {0}")]
+    ConcurrentModification(String),
+
+    #[error("Invalid interval: {0}")]
+    InvalidInterval(String),
 }
 
 #[derive(Debug, Error, AsStaticStr)]
@@ -26,6 +81,10 @@ pub enum UtilsError {
 
     #[error("JSON serialize error: {0}")]
     JsonSerializeError(#[from] serde_json::Error),
+
+    #[cfg(feature = "db")]
+    #[error("Database error: {0}")]
+    DbError(#[from] crate::db::DbError),
 }
 
 impl UtilsError {
@@ -34,15 +93,19 @@ impl UtilsError {
             Self::IoError(..) => 0,
             Self::JsonSerializeError(..) => 1,
             Self::FeeTokenProviderError(..) => 2,
+            #[cfg(feature = "db")]
+            Self::DbError(..) => 3,
         }
     }
+}
+
+impl IntoRpcError for UtilsError {
+    fn rpc_code(&self) -> i32 {
+        self.as_u32() as i32
+    }
 
-    pub fn to_json(&self) -> serde_json::Value {
-        serde_json::json!({
-            "code": self.as_u32(),
-            "message": self.to_string(),
-            "data": self,
-        })
+    fn rpc_data(&self) -> Option<serde_json::Value> {
+        serialize_rpc_data(self)
     }
 }
 
@@ -59,7 +122,10 @@ impl Serialize for UtilsError {
             UtilsError::FeeTokenProviderError(code) => {
                 let mut s = ser.serialize_tuple_variant(error_type_name, variant_index, variant_name, 2)?;
                 s.serialize_field(match code {
-                    FeeTokenProviderError::DuplicateTokenMint(msg) | FeeTokenProviderError::PoisonError(msg) => msg,
+                    FeeTokenProviderError::DuplicateTokenMint(msg)
+                    | FeeTokenProviderError::UnsupportedSnapshotVersion(msg)
+                    | FeeTokenProviderError::ConcurrentModification(msg)
+                    | FeeTokenProviderError::InvalidInterval(msg) => msg,
                 })?;
                 s.end()
             },
@@ -74,6 +140,12 @@ impl Serialize for UtilsError {
                 s.serialize_field(&format!("{:?}", error))?;
                 s.end()
             },
+            #[cfg(feature = "db")]
+            UtilsError::DbError(error) => {
+                let mut s = ser.serialize_tuple_variant(error_type_name, variant_index, variant_name, 1)?;
+                s.serialize_field(&format!("{}", error))?;
+                s.end()
+            },
         }
     }
 }
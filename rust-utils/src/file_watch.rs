@@ -0,0 +1,62 @@
+//! Watching files which are replaced rather than written, shared by `settings::watch` and the fee tokens reload
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+/// Changes within this time are reported at once, editors and config map updates write files in several steps
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Changes of the watched files, the directories are watched until it's dropped
+pub(crate) struct FileChanges {
+    _watcher: RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<()>,
+}
+
+impl FileChanges {
+    /// Watch `directories` for changes of the files accepted by `is_watched`, `name` describes the files in logs
+    pub(crate) fn watch(
+        directories: impl IntoIterator<Item = PathBuf>,
+        is_watched: impl Fn(&Path) -> bool + Send + 'static,
+        name: &'static str,
+    ) -> notify::Result<Self> {
+        let (changed, changes) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                if event.paths.iter().any(|path| is_watched(path)) {
+                    let _ = changed.send(());
+                }
+            },
+            Ok(_) => {},
+            Err(error) => log::warn!("Failed to watch {name}: {error}"),
+        })?;
+        for directory in directories {
+            watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Waits for a change and the changes following it within `DEBOUNCE`, `None` if the watcher is stopped
+    pub(crate) async fn changed(&mut self) -> Option<()> {
+        self.changes.recv().await?;
+        tokio::time::sleep(DEBOUNCE).await;
+        while self.changes.try_recv().is_ok() {}
+        Some(())
+    }
+}
+
+/// Directory watched for the file, files are often replaced rather than written
+pub(crate) fn directory(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
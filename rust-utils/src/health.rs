@@ -0,0 +1,170 @@
+//! Readiness probes of the service components
+//!
+//! # Usage
+//! ```ignore
+//! use rust_utils::health::HealthRegistry;
+//!
+//! let health = HealthRegistry::default()
+//!     .with_probe("db", db_repo.clone())
+//!     .with_probe("coingecko", move || {
+//!         let client = client.clone();
+//!         async move { client.ping().await }
+//!     });
+//!
+//! let mut module = RpcModule::new(());
+//! module.merge(health.into_rpc())?;
+//! // `GET /readiness` of `server::Server` is proxied to `system_readiness`
+//! ```
+
+use std::{collections::BTreeMap, future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use jsonrpsee::{types::ErrorObjectOwned, RpcModule};
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// JSON-RPC error code of `system_readiness` with unhealthy probes, the data is `HealthStatus`
+pub const UNHEALTHY_CODE: i32 = -32003;
+
+#[async_trait]
+pub trait HealthCheck: Send + Sync + 'static {
+    async fn check(&self) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl<F, Fut> HealthCheck for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send,
+{
+    async fn check(&self) -> anyhow::Result<()> {
+        self().await
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ProbeStatus {
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct HealthStatus {
+    pub healthy: bool,
+    pub probes: BTreeMap<String, ProbeStatus>,
+}
+
+#[derive(Clone)]
+struct Probe {
+    name: String,
+    check: Arc<dyn HealthCheck>,
+    timeout: Duration,
+}
+
+/// Probes checked concurrently by `system_readiness`, each one fails if it doesn't finish in its timeout
+#[derive(Clone)]
+pub struct HealthRegistry {
+    probes: Vec<Probe>,
+    timeout: Duration,
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self {
+            probes: vec![],
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl HealthRegistry {
+    /// Timeout of the probes registered after it
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_probe(mut self, name: impl Into<String>, check: impl HealthCheck) -> Self {
+        self.register(name, check);
+        self
+    }
+
+    pub fn register(&mut self, name: impl Into<String>, check: impl HealthCheck) {
+        self.probes.push(Probe {
+            name: name.into(),
+            check: Arc::new(check),
+            timeout: self.timeout,
+        });
+    }
+
+    pub async fn status(&self) -> HealthStatus {
+        let probes = join_all(self.probes.iter().map(|probe| async move {
+            let started_at = Instant::now();
+            let error = match tokio::time::timeout(probe.timeout, probe.check.check()).await {
+                Ok(Ok(())) => None,
+                Ok(Err(error)) => Some(format!("{error:#}")),
+                Err(_) => Some(format!("timed out after {:?}", probe.timeout)),
+            };
+
+            let status = ProbeStatus {
+                healthy: error.is_none(),
+                error,
+                duration_ms: started_at.elapsed().as_millis() as u64,
+            };
+            (probe.name.clone(), status)
+        }))
+        .await;
+
+        HealthStatus {
+            healthy: probes.iter().all(|(_, status)| status.healthy),
+            probes: probes.into_iter().collect(),
+        }
+    }
+
+    /// `system_liveness` always answers `"ok"`, `system_readiness` answers `HealthStatus` or fails with
+    /// `UNHEALTHY_CODE`
+    pub fn into_rpc(self) -> RpcModule<Self> {
+        let mut module = RpcModule::new(self);
+
+        module
+            .register_method("system_liveness", |_, _| Ok::<_, ErrorObjectOwned>("ok"))
+            .expect("method name is unique");
+        module
+            .register_async_method("system_readiness", |_, registry| async move {
+                let status = registry.status().await;
+                if status.healthy {
+                    Ok(status)
+                } else {
+                    Err(ErrorObjectOwned::owned(UNHEALTHY_CODE, "Unhealthy", Some(status)))
+                }
+            })
+            .expect("method name is unique");
+
+        module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn aggregate_probes() {
+        let registry = HealthRegistry::default()
+            .with_probe("ok", || async { Ok(()) })
+            .with_probe("failed", || async { Err(anyhow::anyhow!("connection refused")) })
+            .with_timeout(Duration::from_secs(1))
+            .with_probe("stuck", std::future::pending);
+
+        let status = registry.status().await;
+
+        assert!(!status.healthy);
+        assert!(status.probes["ok"].healthy);
+        assert_eq!(status.probes["failed"].error.as_deref(), Some("connection refused"));
+        assert_eq!(status.probes["stuck"].error.as_deref(), Some("timed out after 1s"));
+        assert_eq!(status.probes["stuck"].duration_ms, 1000);
+    }
+}
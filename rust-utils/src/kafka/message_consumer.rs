@@ -0,0 +1,206 @@
+use anyhow::Context;
+use futures::StreamExt as _;
+use rdkafka::{
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    message::BorrowedMessage,
+    Message,
+};
+use serde::de::DeserializeOwned;
+use stream_cancel::{StreamExt as _, Tripwire};
+#[cfg(feature = "telemetry")]
+use tracing::Instrument;
+
+use super::KafkaSettings;
+use crate::messaging::{retry_transient, spawn_with_reconnect, ConsumerCancellation, MessageHandler, PermanentError};
+
+/// Consumes topics by a `MessageHandler` in a consumer group. Offsets are committed once a message is
+/// handled or failed permanently, other failures are retried with backoff for `KafkaSettings::retry_timeout`,
+/// so the consumer is not evicted from the group for not polling. The consumer is recreated on receive errors
+/// and on messages still failing after the retries, it resumes from the last committed offset
+#[derive(Clone)]
+pub struct KafkaMessageConsumer<Handler> {
+    settings: KafkaSettings,
+    group_id: String,
+    topics: Vec<String>,
+    handler: Handler,
+    tripwire: Tripwire,
+}
+
+impl<Handler> KafkaMessageConsumer<Handler>
+where
+    Handler: MessageHandler + Clone + Send + Sync + 'static,
+    Handler::Message: DeserializeOwned + Send + Sync + 'static,
+{
+    pub fn try_connect_and_consume(
+        settings: &KafkaSettings,
+        group_id: &str,
+        topics: &[&str],
+        handler: Handler,
+    ) -> ConsumerCancellation {
+        let (trigger, tripwire) = Tripwire::new();
+
+        let consumer = Self {
+            settings: settings.clone(),
+            group_id: group_id.to_owned(),
+            topics: topics.iter().map(ToString::to_string).collect(),
+            handler,
+            tripwire,
+        };
+        let handle = spawn_with_reconnect(move || consumer.clone().connect_and_consume());
+
+        ConsumerCancellation::new(trigger, handle)
+    }
+
+    async fn connect_and_consume(self) -> anyhow::Result<()> {
+        let consumer: StreamConsumer = self
+            .settings
+            .client_config()
+            .set("group.id", &self.group_id)
+            .set("enable.auto.offset.store", "false")
+            .create()
+            .context("Failed to create kafka consumer")?;
+        let topics: Vec<&str> = self.topics.iter().map(String::as_str).collect();
+        consumer
+            .subscribe(&topics)
+            .context("Failed to subscribe to kafka topics")?;
+
+        let mut messages = consumer.stream().take_until_if(self.tripwire.clone());
+        while let Some(message) = messages.next().await {
+            let message = message.context("Failed to receive message from kafka")?;
+
+            #[cfg(not(feature = "telemetry"))]
+            let processed = {
+                log::trace!("received message {}/{}", message.partition(), message.offset());
+                retry_transient(
+                    || self.process_message(&message),
+                    self.settings.retry_timeout,
+                    self.tripwire.clone(),
+                )
+                .await
+            };
+
+            #[cfg(feature = "telemetry")]
+            let processed = {
+                use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+                let span = tracing::info_span!(
+                    "process_message",
+                    partition = message.partition(),
+                    offset = message.offset()
+                );
+                span.set_parent(extract_context(&message));
+                retry_transient(
+                    || self.process_message(&message).instrument(span.clone()),
+                    self.settings.retry_timeout,
+                    self.tripwire.clone(),
+                )
+                .await
+            };
+
+            match Outcome::of(processed) {
+                Outcome::Store => consumer
+                    .store_offset_from_message(&message)
+                    .context("Failed to store kafka offset")?,
+                Outcome::Cancelled => break,
+                Outcome::Failed(error) => {
+                    commit(&consumer);
+                    return Err(error.context("Failed to process kafka message, the consumer is recreated"));
+                },
+            }
+        }
+
+        commit(&consumer);
+        log::info!("Have received close request (cancellation trigger)");
+
+        Ok(())
+    }
+
+    async fn process_message(&self, message: &BorrowedMessage<'_>) -> anyhow::Result<()> {
+        if let Some(routing_key) = Handler::ROUTING_KEY {
+            if message.key() != Some(routing_key.as_bytes()) {
+                log::warn!("Unsupported key {:?}", message.key().map(String::from_utf8_lossy));
+                return Ok(());
+            }
+        }
+
+        let payload = serde_json::from_slice::<Handler::Message>(message.payload().unwrap_or_default())
+            .map_err(|error| {
+                log::warn!("Failed to deserialize message: {error:?}");
+                error
+            })
+            .context(PermanentError)?;
+        self.handler.handle_message(payload).await.map_err(|error| {
+            log::warn!("Failed to handle message: {error:?}");
+            error
+        })
+    }
+}
+
+/// What is done with a received message once its processing is finished
+#[derive(Debug)]
+enum Outcome {
+    /// Handled or failed permanently, the offset is stored
+    Store,
+    /// Consumption is cancelled, the message is received again
+    Cancelled,
+    /// Still failing after the retries, the message is received again by the recreated consumer
+    Failed(anyhow::Error),
+}
+
+impl Outcome {
+    fn of(processed: Option<anyhow::Result<()>>) -> Self {
+        match processed {
+            None => Outcome::Cancelled,
+            Some(Err(error)) if !error.is::<PermanentError>() => Outcome::Failed(error),
+            Some(_) => Outcome::Store,
+        }
+    }
+}
+
+/// Commit offsets of the handled messages, so a recreated consumer doesn't receive them again
+fn commit(consumer: &StreamConsumer) {
+    if let Err(error) = consumer.commit_consumer_state(CommitMode::Sync) {
+        log::warn!("Failed to commit kafka offsets: {error}");
+    }
+}
+
+#[cfg(feature = "telemetry")]
+fn extract_context(message: &BorrowedMessage<'_>) -> opentelemetry::Context {
+    use rdkafka::message::Headers;
+
+    let headers: std::collections::HashMap<String, String> = message
+        .headers()
+        .map(|headers| {
+            headers
+                .iter()
+                .filter_map(|header| {
+                    let value = std::str::from_utf8(header.value?).ok()?;
+                    Some((header.key.to_owned(), value.to_owned()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    opentelemetry::global::get_text_map_propagator(|propagator| propagator.extract(&headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn outcome() {
+        assert!(matches!(Outcome::of(Some(Ok(()))), Outcome::Store));
+        assert!(matches!(
+            Outcome::of(Some(Err(anyhow!("malformed").context(PermanentError)))),
+            Outcome::Store
+        ));
+        assert!(matches!(Outcome::of(None), Outcome::Cancelled));
+        assert!(matches!(
+            Outcome::of(Some(Err(anyhow!("unavailable")))),
+            Outcome::Failed(_)
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use rdkafka::{
+    message::{Header, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+};
+
+use super::KafkaSettings;
+use crate::messaging::{MessagePublisher, PublishOptions};
+
+/// Time to wait for space in the producer queue when it's full
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Messages are published to the topic named by `exchange` with `routing_key` as the key, an empty
+/// routing key publishes without a key. Delivery retries are configured by librdkafka properties
+#[derive(Clone)]
+pub struct KafkaMessagePublisher {
+    producer: FutureProducer,
+}
+
+impl KafkaMessagePublisher {
+    pub fn try_connect(settings: &KafkaSettings) -> anyhow::Result<Self> {
+        let producer = settings
+            .client_config()
+            .create()
+            .context("Failed to create kafka producer")?;
+
+        Ok(Self { producer })
+    }
+
+    /// Custom headers of the options, the content type and the trace context
+    fn headers(options: &PublishOptions) -> OwnedHeaders {
+        let mut headers: Vec<(String, String)> = options.headers.clone().into_iter().collect();
+        if let Some(content_type) = &options.content_type {
+            headers.push(("content-type".to_owned(), content_type.clone()));
+        }
+
+        #[cfg(feature = "telemetry")]
+        {
+            use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+            let mut trace_headers = std::collections::HashMap::new();
+            let cx = tracing::Span::current().context();
+            opentelemetry::global::get_text_map_propagator(|propagator| {
+                propagator.inject_context(&cx, &mut trace_headers)
+            });
+            headers.extend(trace_headers);
+        }
+
+        headers
+            .iter()
+            .fold(OwnedHeaders::new_with_capacity(headers.len()), |owned, (key, value)| {
+                owned.insert(Header {
+                    key,
+                    value: Some(value.as_str()),
+                })
+            })
+    }
+}
+
+#[async_trait]
+impl MessagePublisher for KafkaMessagePublisher {
+    /// Kafka has neither priorities nor per-message expiration, such options fail the publish.
+    /// Messages are always persistent
+    #[cfg_attr(
+        feature = "telemetry",
+        tracing::instrument(level = "debug", skip(self, payload, options))
+    )]
+    async fn publish_payload_with_properties(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(options.priority.is_none(), "Message priority isn't supported by kafka");
+        anyhow::ensure!(
+            options.expiration.is_none(),
+            "Message expiration isn't supported by kafka"
+        );
+
+        let mut record: FutureRecord<str, [u8]> = FutureRecord::to(exchange)
+            .payload(payload)
+            .headers(Self::headers(options));
+        if !routing_key.is_empty() {
+            record = record.key(routing_key);
+        }
+
+        self.producer
+            .send(record, QUEUE_TIMEOUT)
+            .await
+            .map_err(|(error, _)| error)
+            .with_context(|| format!("Failed to publish to {exchange}/{routing_key}"))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+//! Kafka implementations of `messaging::MessagePublisher` and consumption by `messaging::MessageHandler`,
+//! so handlers stay the same for both brokers.
+//! A topic takes the place of the exchange and the message key takes the place of the routing key
+
+pub mod message_consumer;
+pub mod message_publisher;
+
+use std::{collections::HashMap, time::Duration};
+
+use rdkafka::ClientConfig;
+use serde::Deserialize;
+use serde_with::serde_as;
+
+use crate::wrappers::serde::DurationMs;
+
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct KafkaSettings {
+    /// Comma separated `host:port` list of `bootstrap.servers`
+    pub brokers: String,
+    /// Other librdkafka properties, e.g. `security.protocol` or `message.timeout.ms`
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+    /// How long a consumer retries a failing message before rejoining the group to receive it again.
+    /// Must stay below `max.poll.interval.ms` (5 minutes by default), the consumer doesn't poll meanwhile
+    #[serde(rename = "retry_timeout_ms", default = "KafkaSettings::default_retry_timeout")]
+    #[serde_as(as = "DurationMs")]
+    pub retry_timeout: Duration,
+}
+
+impl KafkaSettings {
+    fn default_retry_timeout() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn client_config(&self) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &self.brokers);
+        for (key, value) in &self.properties {
+            config.set(key, value);
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_settings() {
+        let settings: KafkaSettings = serde_json::from_value(serde_json::json!({
+            "brokers": "localhost:9092",
+            "properties": {"security.protocol": "ssl"},
+        }))
+        .unwrap();
+        assert_eq!(settings.retry_timeout, Duration::from_secs(60));
+
+        let config = settings.client_config();
+        assert_eq!(config.get("bootstrap.servers"), Some("localhost:9092"));
+        assert_eq!(config.get("security.protocol"), Some("ssl"));
+
+        let settings: KafkaSettings = serde_json::from_value(serde_json::json!({
+            "brokers": "localhost:9092",
+            "retry_timeout_ms": 1500,
+        }))
+        .unwrap();
+        assert_eq!(settings.retry_timeout, Duration::from_millis(1500));
+    }
+}
@@ -6,16 +6,28 @@ pub mod crypto;
 pub mod db;
 #[cfg(feature = "error")]
 pub mod error;
+#[cfg(feature = "file-watch")]
+mod file_watch;
+#[cfg(feature = "health")]
+pub mod health;
+#[cfg(feature = "kafka")]
+pub mod kafka;
 #[cfg(feature = "logger")]
 pub mod logger;
 #[cfg(feature = "macros")]
 pub mod macros;
+#[cfg(feature = "messaging")]
+pub mod messaging;
 #[cfg(feature = "rabbitmq")]
 pub mod rabbitmq;
+#[cfg(feature = "secret")]
+pub mod secret;
 #[cfg(feature = "server")]
 pub mod server;
 #[cfg(feature = "settings")]
 pub mod settings;
+#[cfg(feature = "shutdown")]
+pub mod shutdown;
 #[cfg(feature = "telemetry")]
 pub mod telemetry;
 #[cfg(feature = "tokens")]
@@ -37,3 +49,6 @@ pub extern crate config;
 
 #[cfg(feature = "settings")]
 pub extern crate paste;
+
+#[cfg(feature = "settings")]
+pub extern crate toml;
@@ -8,6 +8,40 @@ macro_rules! matches_opt {
     };
 }
 
+/// Implements `error::IntoRpcError` with a code for each pattern, `Serialize` errors are sent as data
+///
+/// ```ignore
+/// impl_into_rpc_error!(ServiceError: Serialize {
+///     Self::NotFound(..) => 1001,
+///     Self::Db(..) | Self::Rpc(..) => 1002,
+/// });
+/// ```
+#[macro_export]
+macro_rules! impl_into_rpc_error {
+    ($error:ty { $($pattern:pat => $code:expr),+ $(,)? }) => {
+        impl $crate::error::IntoRpcError for $error {
+            fn rpc_code(&self) -> i32 {
+                match self {
+                    $($pattern => $code,)+
+                }
+            }
+        }
+    };
+    ($error:ty: Serialize { $($pattern:pat => $code:expr),+ $(,)? }) => {
+        impl $crate::error::IntoRpcError for $error {
+            fn rpc_code(&self) -> i32 {
+                match self {
+                    $($pattern => $code,)+
+                }
+            }
+
+            fn rpc_data(&self) -> Option<$crate::error::serde_json::Value> {
+                $crate::error::serialize_rpc_data(self)
+            }
+        }
+    };
+}
+
 #[test]
 fn test_matches_opt() {
     #[derive(Debug, Eq, PartialEq)]
@@ -30,3 +64,31 @@ fn test_matches_opt() {
     let value = matches_opt!(5, x @ 0..=1 | x @ 3..=10 => x * 2);
     assert_eq!(value, Some(10));
 }
+
+#[cfg(feature = "error")]
+#[test]
+fn test_impl_into_rpc_error() {
+    use crate::error::{IntoRpcError, RpcError};
+
+    #[derive(Debug, thiserror::Error, serde::Serialize)]
+    enum Error {
+        #[error("not found: {0}")]
+        NotFound(String),
+        #[error("invalid")]
+        Invalid { reason: String },
+    }
+    impl_into_rpc_error!(Error: Serialize {
+        Self::NotFound(..) => 1001,
+        Self::Invalid { .. } => 1002,
+    });
+
+    assert_eq!(Error::NotFound("token".into()).to_rpc_error(), RpcError {
+        code: 1001,
+        message: "not found: token".into(),
+        data: Some(serde_json::json!({ "NotFound": "token" })),
+    });
+
+    let error = Error::Invalid { reason: "empty".into() }.into_error_object();
+    assert_eq!(error.code(), 1002);
+    assert_eq!(error.message(), "invalid");
+}
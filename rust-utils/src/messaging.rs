@@ -0,0 +1,316 @@
+//! Broker-neutral parts of the message consumers and publishers, shared by `rabbitmq` and `kafka` so
+//! handlers and publishing code stay the same for both brokers
+
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use backoff::{future::retry_notify, ExponentialBackoff};
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use stream_cancel::{Trigger, Tripwire};
+
+use crate::wrappers::serde::DurationMs;
+
+/// Context of errors which fail the message for good, such messages are neither redelivered nor retried
+#[derive(Debug, Clone, Copy)]
+pub struct PermanentError;
+
+impl std::fmt::Display for PermanentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("PermanentError")
+    }
+}
+
+#[async_trait]
+pub trait CancelConsume {
+    fn cancel(self);
+    async fn wait(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn wait_or_panic(&self) {
+        self.wait().await.expect("Failed to wait for consumer cancellation")
+    }
+}
+
+#[async_trait]
+pub trait MessageHandler {
+    type Message;
+    const ROUTING_KEY: Option<&'static str> = None;
+    async fn handle_message(&self, message: Self::Message) -> anyhow::Result<()>;
+}
+
+pub struct ConsumerCancellation {
+    trigger: Trigger,
+    tripwire: Tripwire,
+}
+
+impl ConsumerCancellation {
+    pub fn new(trigger: Trigger, tripwire: Tripwire) -> Self {
+        Self { trigger, tripwire }
+    }
+
+    /// Cancel consumption and wait until the current message is processed and the consumer is closed
+    pub async fn cancel_and_wait(self) {
+        self.trigger.cancel();
+        self.tripwire.await;
+    }
+}
+
+#[async_trait]
+impl CancelConsume for ConsumerCancellation {
+    fn cancel(self) {
+        self.trigger.cancel();
+    }
+
+    async fn wait(&self) -> anyhow::Result<()> {
+        if self.tripwire.clone().await {
+            Err(anyhow!("Stopped"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Backoff of reconnects, a consumer never gives up
+fn backoff() -> ExponentialBackoff {
+    ExponentialBackoff {
+        max_elapsed_time: None,
+        ..Default::default()
+    }
+}
+
+/// Run `consume` until it succeeds, reconnecting with backoff on errors. The returned tripwire is
+/// triggered once it's finished
+pub fn spawn_with_reconnect<F, Fut>(consume: F) -> Tripwire
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send,
+{
+    let (trigger, tripwire) = Tripwire::new();
+    tokio::spawn(async move {
+        log::trace!("try connect and consume");
+        let retry_status = retry_notify(
+            backoff(),
+            || async { consume().await.map_err(Into::into) },
+            |err, duration| {
+                log::warn!("failed to connect and consume: {err:?}, retrying in {duration:?}");
+            },
+        )
+        .await;
+        if let Err(error) = retry_status {
+            log::error!("Reconnect logic failed: {error}");
+        }
+        trigger.cancel();
+    });
+    tripwire
+}
+
+/// Run `process` until it succeeds, fails with a `PermanentError` or keeps failing for `max_elapsed`, backing
+/// off between the other failures. The last error is returned once `max_elapsed` passes. `None` if `tripwire`
+/// is triggered meanwhile
+pub async fn retry_transient<F, Fut, T>(
+    process: F,
+    max_elapsed: Duration,
+    tripwire: Tripwire,
+) -> Option<anyhow::Result<T>>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let backoff = ExponentialBackoff {
+        max_elapsed_time: Some(max_elapsed),
+        ..Default::default()
+    };
+    let retried = retry_notify(
+        backoff,
+        || async {
+            process().await.map_err(|error| {
+                if error.is::<PermanentError>() {
+                    backoff::Error::permanent(error)
+                } else {
+                    backoff::Error::transient(error)
+                }
+            })
+        },
+        |_, duration| log::warn!("Failed to process message, retrying in {duration:?}"),
+    );
+
+    tokio::select! {
+        result = retried => Some(result),
+        true = tripwire => None,
+    }
+}
+
+#[async_trait]
+pub trait MessagePublisher {
+    async fn publish_payload_with_properties(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> anyhow::Result<()>;
+
+    async fn publish_payload(&self, exchange: &str, routing_key: &str, payload: &[u8]) -> anyhow::Result<()> {
+        self.publish_payload_with_properties(exchange, routing_key, payload, &PublishOptions::default())
+            .await
+    }
+
+    async fn publish<T>(&self, exchange: &str, routing_key: &str, message: &T) -> anyhow::Result<()>
+    where
+        T: Serialize + Sync,
+    {
+        self.publish_payload(exchange, routing_key, serde_json::to_vec(message)?.as_ref())
+            .await
+    }
+
+    async fn publish_with_properties<T>(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        message: &T,
+        options: &PublishOptions,
+    ) -> anyhow::Result<()>
+    where
+        T: Serialize + Sync,
+    {
+        self.publish_payload_with_properties(exchange, routing_key, serde_json::to_vec(message)?.as_ref(), options)
+            .await
+    }
+}
+
+/// Properties of a published message, by default it's transient without priority and expiration
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct PublishOptions {
+    /// Only applies to queues declared with `x-max-priority`
+    #[serde(default)]
+    pub priority: Option<u8>,
+    /// Message is discarded or dead-lettered if it stays in a queue longer than this
+    #[serde(rename = "expiration_ms", default)]
+    #[serde_as(as = "Option<DurationMs>")]
+    pub expiration: Option<Duration>,
+    /// Persistent messages survive a broker restart if they are routed to durable queues
+    #[serde(default)]
+    pub persistent: bool,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl PublishOptions {
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn with_expiration(mut self, expiration: Duration) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    pub fn persistent(mut self) -> Self {
+        self.persistent = true;
+        self
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn reconnect_forever() {
+        assert_eq!(backoff().max_elapsed_time, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_transient_errors() {
+        let (_trigger, tripwire) = Tripwire::new();
+        let attempts = AtomicUsize::new(0);
+        let result = retry_transient(
+            || async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err(anyhow!("unavailable")),
+                    attempt => Ok(attempt),
+                }
+            },
+            Duration::from_secs(60),
+            tripwire.clone(),
+        )
+        .await;
+        assert_eq!(result.unwrap().unwrap(), 2);
+
+        attempts.store(0, Ordering::SeqCst);
+        let result = retry_transient(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(anyhow!("malformed").context(PermanentError))
+            },
+            Duration::from_secs(60),
+            tripwire,
+        )
+        .await;
+        assert!(result.unwrap().is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn give_up_after_max_elapsed() {
+        let (_trigger, tripwire) = Tripwire::new();
+        let attempts = AtomicUsize::new(0);
+        let started_at = std::time::Instant::now();
+        let result = retry_transient(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(anyhow!("unavailable"))
+            },
+            Duration::from_secs(1),
+            tripwire,
+        )
+        .await;
+
+        let error = result.unwrap().unwrap_err();
+        assert!(!error.is::<PermanentError>());
+        assert!(attempts.load(Ordering::SeqCst) > 1);
+        assert!(started_at.elapsed() < Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_retries_once_cancelled() {
+        let (trigger, tripwire) = Tripwire::new();
+        let attempts = AtomicUsize::new(0);
+        let retried = retry_transient(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(anyhow!("unavailable"))
+            },
+            Duration::from_secs(600),
+            tripwire,
+        );
+        let cancel = async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            trigger.cancel();
+        };
+
+        let (result, ()) = tokio::join!(retried, cancel);
+        assert!(result.is_none());
+        assert!(attempts.load(Ordering::SeqCst) > 1);
+    }
+}
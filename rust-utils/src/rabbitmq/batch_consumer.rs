@@ -0,0 +1,405 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::prelude::*;
+use lapin::{
+    message::Delivery,
+    options::{BasicAckOptions, BasicNackOptions},
+    topology::TopologyDefinition,
+    types::DeliveryTag,
+    Channel,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_with::serde_as;
+use stream_cancel::{StreamExt as _, Tripwire};
+use tokio::time::Instant;
+
+use super::{
+    message_consumer::{cancel_consumer, connect, handle_failure, PermanentError, RabbitConsumerCancellation},
+    retry::RetryPolicy,
+};
+use crate::{messaging::spawn_with_reconnect, wrappers::serde::DurationMs};
+
+#[async_trait]
+pub trait BatchMessageHandler {
+    type Message;
+    const ROUTING_KEY: Option<&'static str> = None;
+    /// The whole batch is acked if it succeeds or fails with a `PermanentError`. Messages of a failed batch are
+    /// retried by the `RetryPolicy` of the consumer, without it they're requeued once and then nacked without
+    /// requeue, so they're dropped or dead-lettered by the queue
+    async fn handle_batch(&self, messages: Vec<Self::Message>) -> anyhow::Result<()>;
+}
+
+/// A batch is dispatched once it has `max_size` messages or `max_wait` after its first message.
+/// Prefetch count of the consumer channel has to be at least `max_size`
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct BatchSettings {
+    #[serde(default = "BatchSettings::default_max_size")]
+    pub max_size: usize,
+    #[serde(rename = "max_wait_ms", default = "BatchSettings::default_max_wait")]
+    #[serde_as(as = "DurationMs")]
+    pub max_wait: Duration,
+}
+
+impl BatchSettings {
+    fn default_max_size() -> usize {
+        100
+    }
+
+    fn default_max_wait() -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        Self {
+            max_size: Self::default_max_size(),
+            max_wait: Self::default_max_wait(),
+        }
+    }
+}
+
+struct Batch<Message> {
+    messages: Vec<Message>,
+    deliveries: Vec<Delivery>,
+    deadline: Option<Instant>,
+}
+
+impl<Message> Batch<Message> {
+    fn take(&mut self) -> Option<(Vec<Message>, Vec<Delivery>)> {
+        self.deadline = None;
+        if self.deliveries.is_empty() {
+            return None;
+        }
+        Some((std::mem::take(&mut self.messages), std::mem::take(&mut self.deliveries)))
+    }
+}
+
+/// Collects the deliveries into batches until the stream ends, `dispatch` is called with every full or expired
+/// batch and with the rest once the stream ends
+async fn collect_batches<Message, S, F, Fut>(
+    mut deliveries: S,
+    settings: BatchSettings,
+    deserialize: impl Fn(&Delivery) -> Option<Message>,
+    mut dispatch: F,
+) -> anyhow::Result<()>
+where
+    S: Stream<Item = lapin::Result<Delivery>> + Unpin,
+    F: FnMut(Vec<Message>, Vec<Delivery>) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut batch = Batch {
+        messages: Vec::with_capacity(settings.max_size),
+        deliveries: Vec::with_capacity(settings.max_size),
+        deadline: None,
+    };
+
+    loop {
+        let next = match batch.deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, deliveries.next()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    if let Some((messages, deliveries)) = batch.take() {
+                        dispatch(messages, deliveries).await?;
+                    }
+                    continue;
+                },
+            },
+            None => deliveries.next().await,
+        };
+        let Some(delivery) = next else {
+            break;
+        };
+        let delivery = delivery.context("Failed to receive message from consumer")?;
+
+        let Some(message) = deserialize(&delivery) else {
+            delivery
+                .ack(Default::default())
+                .await
+                .context("Failed to ack rabbitmq msg")?;
+            continue;
+        };
+
+        batch.messages.push(message);
+        batch.deliveries.push(delivery);
+        batch.deadline.get_or_insert_with(|| Instant::now() + settings.max_wait);
+        if batch.messages.len() >= settings.max_size {
+            if let Some((messages, deliveries)) = batch.take() {
+                dispatch(messages, deliveries).await?;
+            }
+        }
+    }
+
+    match batch.take() {
+        Some((messages, deliveries)) => dispatch(messages, deliveries).await,
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Settlement {
+    /// Every delivery up to the tag is acked at once
+    AckUpTo(DeliveryTag),
+    Nack {
+        delivery_tag: DeliveryTag,
+        requeue: bool,
+    },
+}
+
+/// Settlement of a batch handled without a retry policy, messages redelivered once aren't requeued again
+fn settlements(deliveries: &[Delivery], result: &anyhow::Result<()>) -> Vec<Settlement> {
+    let Some(last) = deliveries.last() else {
+        return vec![];
+    };
+
+    match result {
+        Err(error) if !error.is::<PermanentError>() => deliveries
+            .iter()
+            .map(|delivery| Settlement::Nack {
+                delivery_tag: delivery.delivery_tag,
+                requeue: !delivery.redelivered,
+            })
+            .collect(),
+        _ => vec![Settlement::AckUpTo(last.delivery_tag)],
+    }
+}
+
+#[derive(Clone)]
+pub struct RabbitBatchConsumer<Handler> {
+    url: String,
+    topology_definition: TopologyDefinition,
+    handler: Handler,
+    settings: BatchSettings,
+    tripwire: Tripwire,
+    retry: Option<RetryPolicy>,
+}
+
+impl<Handler> RabbitBatchConsumer<Handler>
+where
+    Handler: BatchMessageHandler + Clone + Send + Sync + 'static,
+    Handler::Message: DeserializeOwned + Send + Sync + 'static,
+{
+    pub fn try_connect_and_consume(
+        url: &str,
+        topology_definition: TopologyDefinition,
+        handler: Handler,
+        settings: BatchSettings,
+    ) -> RabbitConsumerCancellation {
+        Self::consume(url, topology_definition, handler, settings, None)
+    }
+
+    /// Same as `try_connect_and_consume`, but messages of failed batches are retried and dead-lettered by `retry`
+    /// one by one. Retry queues are declared on connect
+    pub fn try_connect_and_consume_with_retries(
+        url: &str,
+        topology_definition: TopologyDefinition,
+        handler: Handler,
+        settings: BatchSettings,
+        retry: RetryPolicy,
+    ) -> RabbitConsumerCancellation {
+        Self::consume(url, topology_definition, handler, settings, Some(retry))
+    }
+
+    fn consume(
+        url: &str,
+        topology_definition: TopologyDefinition,
+        handler: Handler,
+        settings: BatchSettings,
+        retry: Option<RetryPolicy>,
+    ) -> RabbitConsumerCancellation {
+        let (trigger, tripwire) = Tripwire::new();
+
+        let consumer = Self {
+            url: url.to_owned(),
+            topology_definition,
+            handler,
+            settings,
+            tripwire,
+            retry,
+        };
+        let handle = spawn_with_reconnect(move || consumer.clone().connect_and_consume());
+
+        RabbitConsumerCancellation::new(trigger, handle)
+    }
+
+    async fn connect_and_consume(self) -> anyhow::Result<()> {
+        let topology = connect(&self.url, self.topology_definition.clone()).await?;
+        let channel = topology.channel(0).into_inner();
+        let consumer = topology.channel(0).consumer(0);
+        let queue = consumer.queue();
+
+        if let Some(retry) = &self.retry {
+            retry
+                .declare_topology(&channel, queue.as_str())
+                .await
+                .context("Failed to declare retry queues")?;
+        }
+
+        collect_batches(
+            consumer.clone().take_until_if(self.tripwire.clone()),
+            self.settings,
+            Self::deserialize,
+            |messages, deliveries| self.dispatch(&channel, queue.as_str(), messages, deliveries),
+        )
+        .await?;
+
+        cancel_consumer(&channel, &consumer).await
+    }
+
+    /// Unsupported and malformed messages are acked and skipped
+    fn deserialize(delivery: &Delivery) -> Option<Handler::Message> {
+        if let Some(routing_key) = Handler::ROUTING_KEY {
+            if delivery.routing_key.as_str() != routing_key {
+                log::warn!("Unsupported routing key {}", delivery.routing_key);
+                return None;
+            }
+        }
+
+        serde_json::from_slice(delivery.data.as_ref())
+            .map_err(|error| log::warn!("Failed to deserialize message {}: {error:?}", delivery.delivery_tag))
+            .ok()
+    }
+
+    async fn dispatch(
+        &self,
+        channel: &Channel,
+        queue: &str,
+        messages: Vec<Handler::Message>,
+        deliveries: Vec<Delivery>,
+    ) -> anyhow::Result<()> {
+        let size = messages.len();
+
+        #[cfg(feature = "telemetry")]
+        let result = {
+            use tracing::Instrument;
+            self.handler
+                .handle_batch(messages)
+                .instrument(tracing::info_span!("process_batch", size))
+                .await
+        };
+        #[cfg(not(feature = "telemetry"))]
+        let result = self.handler.handle_batch(messages).await;
+
+        if let Err(error) = &result {
+            log::warn!("Failed to handle batch of {size} messages: {error:?}");
+
+            if let Some(retry) = &self.retry {
+                for delivery in &deliveries {
+                    if handle_failure(Some(retry), channel, queue, delivery, error).await {
+                        delivery.ack(Default::default()).await
+                    } else {
+                        delivery.nack(Default::default()).await
+                    }
+                    .context("Failed to settle rabbitmq msg")?;
+                }
+                return Ok(());
+            }
+        }
+
+        for settlement in settlements(&deliveries, &result) {
+            match settlement {
+                Settlement::AckUpTo(delivery_tag) => channel
+                    .basic_ack(delivery_tag, BasicAckOptions { multiple: true })
+                    .await
+                    .context("Failed to ack rabbitmq batch")?,
+                Settlement::Nack { delivery_tag, requeue } => channel
+                    .basic_nack(delivery_tag, BasicNackOptions {
+                        multiple: false,
+                        requeue,
+                    })
+                    .await
+                    .context("Failed to nack rabbitmq msg")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use lapin::{acker::Acker, BasicProperties};
+
+    use super::*;
+
+    fn delivery(delivery_tag: DeliveryTag, redelivered: bool) -> Delivery {
+        Delivery {
+            delivery_tag,
+            exchange: "".into(),
+            routing_key: "events".into(),
+            redelivered,
+            properties: BasicProperties::default(),
+            data: delivery_tag.to_string().into_bytes(),
+            acker: Acker::default(),
+        }
+    }
+
+    #[test]
+    fn settle_batches() {
+        let deliveries = [delivery(1, false), delivery(2, true)];
+
+        assert_eq!(settlements(&deliveries, &Ok(())), [Settlement::AckUpTo(2)]);
+        assert_eq!(
+            settlements(&deliveries, &Err(anyhow::anyhow!("malformed").context(PermanentError))),
+            [Settlement::AckUpTo(2)]
+        );
+        assert_eq!(settlements(&deliveries, &Err(anyhow::anyhow!("unavailable"))), [
+            Settlement::Nack {
+                delivery_tag: 1,
+                requeue: true
+            },
+            Settlement::Nack {
+                delivery_tag: 2,
+                requeue: false
+            },
+        ]);
+        assert!(settlements(&[], &Ok(())).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dispatch_full_and_expired_batches() {
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let batches = Arc::new(Mutex::new(vec![]));
+
+        let collected = tokio::spawn({
+            let batches = batches.clone();
+            collect_batches(
+                receiver,
+                BatchSettings {
+                    max_size: 2,
+                    max_wait: Duration::from_secs(1),
+                },
+                |delivery| serde_json::from_slice::<u64>(&delivery.data).ok(),
+                move |messages, deliveries| {
+                    assert_eq!(messages.len(), deliveries.len());
+                    batches.lock().unwrap().push(messages);
+                    async { Ok(()) }
+                },
+            )
+        });
+
+        for tag in 1..=3 {
+            sender.unbounded_send(Ok(delivery(tag, false))).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(*batches.lock().unwrap(), [vec![1, 2]]);
+
+        // the last message is dispatched once the batch expires
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(*batches.lock().unwrap(), [vec![1, 2], vec![3]]);
+
+        // malformed messages are skipped, the rest is dispatched once the stream ends
+        let mut malformed = delivery(4, false);
+        malformed.data = b"{".to_vec();
+        sender.unbounded_send(Ok(malformed)).unwrap();
+        sender.unbounded_send(Ok(delivery(5, false))).unwrap();
+        drop(sender);
+        collected.await.unwrap().unwrap();
+        assert_eq!(*batches.lock().unwrap(), [vec![1, 2], vec![3], vec![5]]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Messages wrapped with their type and schema version, so consumers pick the payload format they know
+//!
+//! # Usage
+//! ```ignore
+//! impl VersionedMessage for TransferV2 {
+//!     const TYPE: &'static str = "transfer";
+//!     const VERSION: u32 = 2;
+//! }
+//!
+//! publisher.publish_envelope("events", "transfer", "indexer", &transfer).await?;
+//!
+//! let dispatcher = EnvelopeDispatcher::default()
+//!     .with_handler(TransferV1Handler)
+//!     .with_handler(TransferV2Handler);
+//! RabbitMessageConsumer::try_connect_and_consume(url, topology, dispatcher);
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use lapin::{message::Delivery, Channel};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    message_consumer::{AutoAck, MessageHandler, MessageProcessor, PermanentError},
+    message_publisher::MessagePublisher,
+};
+
+/// Payload format identified by its type name and schema version
+pub trait VersionedMessage {
+    const TYPE: &'static str;
+    const VERSION: u32;
+}
+
+impl<T: VersionedMessage> VersionedMessage for &T {
+    const TYPE: &'static str = T::TYPE;
+    const VERSION: u32 = T::VERSION;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Envelope<T> {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub version: u32,
+    /// Name of the publishing service
+    pub producer: String,
+    /// Unix time of the publish in milliseconds
+    pub timestamp_ms: u64,
+    /// Trace of the publish, set with `telemetry`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    pub payload: T,
+}
+
+impl<T: VersionedMessage> Envelope<T> {
+    pub fn new(producer: impl Into<String>, payload: T) -> Self {
+        Self {
+            message_type: T::TYPE.to_owned(),
+            version: T::VERSION,
+            producer: producer.into(),
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+            trace_id: current_trace_id(),
+            payload,
+        }
+    }
+}
+
+#[cfg(feature = "telemetry")]
+fn current_trace_id() -> Option<String> {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let cx = tracing::Span::current().context();
+    let span_context = cx.span().span_context().clone();
+    span_context.is_valid().then(|| span_context.trace_id().to_string())
+}
+
+#[cfg(not(feature = "telemetry"))]
+fn current_trace_id() -> Option<String> {
+    None
+}
+
+#[async_trait]
+pub trait PublishEnvelope {
+    async fn publish_envelope<T>(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        producer: &str,
+        message: &T,
+    ) -> anyhow::Result<()>
+    where
+        T: VersionedMessage + Serialize + Sync;
+}
+
+#[async_trait]
+impl<P: MessagePublisher + Sync> PublishEnvelope for P {
+    async fn publish_envelope<T>(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        producer: &str,
+        message: &T,
+    ) -> anyhow::Result<()>
+    where
+        T: VersionedMessage + Serialize + Sync,
+    {
+        self.publish(exchange, routing_key, &Envelope::new(producer, message))
+            .await
+    }
+}
+
+type Handle = Arc<dyn Fn(serde_json::Value) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> + Send + Sync>;
+
+/// Processor of enveloped messages, the payload is handled by the handler of its type and version. Without
+/// one, the handler of the closest older version reads it, so unknown fields of newer versions are ignored.
+/// Messages of unknown types or older versions only are acked and skipped
+#[derive(Clone, Default)]
+pub struct EnvelopeDispatcher {
+    handlers: BTreeMap<(String, u32), Handle>,
+}
+
+impl EnvelopeDispatcher {
+    pub fn with_handler<Handler>(mut self, handler: Handler) -> Self
+    where
+        Handler: MessageHandler + Clone + Send + Sync + 'static,
+        Handler::Message: VersionedMessage + DeserializeOwned + Send + Sync + 'static,
+    {
+        let handle: Handle = Arc::new(move |payload| {
+            let handler = handler.clone();
+            Box::pin(async move {
+                let message = serde_json::from_value::<Handler::Message>(payload).context(PermanentError)?;
+                handler.handle_message(message).await
+            })
+        });
+        self.handlers
+            .insert((Handler::Message::TYPE.to_owned(), Handler::Message::VERSION), handle);
+        self
+    }
+
+    /// Handler of the version or the closest older one
+    fn handler(&self, message_type: &str, version: u32) -> Option<&Handle> {
+        self.handlers
+            .range((message_type.to_owned(), 0)..=(message_type.to_owned(), version))
+            .next_back()
+            .map(|(_, handle)| handle)
+    }
+
+    pub async fn dispatch(&self, envelope: Envelope<serde_json::Value>) -> anyhow::Result<()> {
+        let Some(handle) = self.handler(&envelope.message_type, envelope.version) else {
+            log::warn!(
+                "No handler of {} version {} from {}",
+                envelope.message_type,
+                envelope.version,
+                envelope.producer
+            );
+            return Ok(());
+        };
+
+        handle(envelope.payload).await
+    }
+}
+
+#[async_trait]
+impl MessageProcessor for EnvelopeDispatcher {
+    async fn process_message(&self, delivery: &Delivery, _channel: &Channel) -> anyhow::Result<AutoAck> {
+        let envelope = serde_json::from_slice(delivery.data.as_ref())
+            .map_err(|error| {
+                log::warn!("Failed to deserialize envelope {}: {error:?}", delivery.delivery_tag);
+                error
+            })
+            .context(PermanentError)?;
+
+        self.dispatch(envelope).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::rabbitmq::testing::InMemoryBus;
+
+    #[derive(Serialize, Deserialize)]
+    struct TransferV1 {
+        amount: u64,
+    }
+
+    impl VersionedMessage for TransferV1 {
+        const TYPE: &'static str = "transfer";
+        const VERSION: u32 = 1;
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct TransferV3 {
+        amount: u64,
+        memo: String,
+    }
+
+    impl VersionedMessage for TransferV3 {
+        const TYPE: &'static str = "transfer";
+        const VERSION: u32 = 3;
+    }
+
+    #[derive(Clone, Default)]
+    struct Received(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl MessageHandler for Received {
+        type Message = TransferV1;
+
+        async fn handle_message(&self, message: TransferV1) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(format!("v1 {}", message.amount));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_by_version() {
+        let received = Received::default();
+        let dispatcher = EnvelopeDispatcher::default().with_handler(received.clone());
+        let bus = InMemoryBus::default();
+
+        bus.publish_envelope("events", "", "indexer", &TransferV3 {
+            amount: 5,
+            memo: "fee".to_owned(),
+        })
+        .await
+        .unwrap();
+        let envelope = bus.published()[0].message::<Envelope<serde_json::Value>>().unwrap();
+        assert_eq!((envelope.message_type.as_str(), envelope.version), ("transfer", 3));
+        assert_eq!(envelope.producer, "indexer");
+
+        // v3 is read by the v1 handler, v0 has no handler
+        dispatcher.dispatch(envelope.clone()).await.unwrap();
+        dispatcher.dispatch(Envelope { version: 0, ..envelope }).await.unwrap();
+
+        assert_eq!(*received.0.lock().unwrap(), vec!["v1 5"]);
+    }
+}
@@ -1,6 +1,5 @@
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use async_trait::async_trait;
-use backoff::{future::retry_notify, ExponentialBackoff};
 
 use futures::prelude::*;
 use lapin::{
@@ -8,36 +7,22 @@ use lapin::{
     options::BasicCancelOptions,
     topology::{RestoredTopology, TopologyDefinition},
     types::DeliveryTag,
-    Channel, Connection, ConnectionProperties, Consumer, ConsumerState,
+    Channel, Consumer, ConsumerState,
 };
 use serde::de::DeserializeOwned;
 
-use stream_cancel::{StreamExt, Trigger, Tripwire};
+use stream_cancel::{StreamExt, Tripwire};
+
+use super::{connection::RabbitConnectionManager, retry::RetryPolicy};
+use crate::messaging::spawn_with_reconnect;
+pub use crate::messaging::{CancelConsume, ConsumerCancellation, MessageHandler, PermanentError};
 #[cfg(feature = "telemetry")]
 use tracing::Instrument;
 
-#[derive(Debug, Clone, Copy)]
-pub struct PermanentError;
-
 pub type AutoAck = bool;
 
-impl std::fmt::Display for PermanentError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.write_str("PermanentError")
-    }
-}
-
-#[async_trait]
-pub trait CancelConsume {
-    fn cancel(self);
-    async fn wait(&self) -> anyhow::Result<()> {
-        Ok(())
-    }
-
-    async fn wait_or_panic(&self) {
-        self.wait().await.expect("Failed to wait for consumer cancellation")
-    }
-}
+/// Cancellation of the rabbitmq consumers
+pub type RabbitConsumerCancellation = ConsumerCancellation;
 
 pub trait MessageConsumer<MsgProcessor> {
     type Cancellation: CancelConsume;
@@ -53,13 +38,6 @@ pub trait MessageProcessor {
     async fn process_message(&self, delivery: &Delivery, channel: &Channel) -> anyhow::Result<AutoAck>;
 }
 
-#[async_trait]
-pub trait MessageHandler {
-    type Message;
-    const ROUTING_KEY: Option<&'static str> = None;
-    async fn handle_message(&self, message: Self::Message) -> anyhow::Result<()>;
-}
-
 #[cfg(not(feature = "telemetry"))]
 macro_rules! tagged_warn {
     (tag = $tag:expr; $($arg:tt)*) => {
@@ -101,32 +79,13 @@ where
     }
 }
 
-pub struct RabbitConsumerCancellation {
-    trigger: Trigger,
-    tripwire: Tripwire,
-}
-
-#[async_trait]
-impl CancelConsume for RabbitConsumerCancellation {
-    fn cancel(self) {
-        self.trigger.cancel();
-    }
-
-    async fn wait(&self) -> anyhow::Result<()> {
-        if self.tripwire.clone().await {
-            Err(anyhow!("Stopped"))
-        } else {
-            Ok(())
-        }
-    }
-}
-
 #[derive(Clone)]
 pub struct RabbitMessageConsumer<MsgProcessor> {
     url: String,
     topology_definition: TopologyDefinition,
     processor: MsgProcessor,
     tripwire: Tripwire,
+    retry: Option<RetryPolicy>,
 }
 
 impl<MsgProcessor: MessageProcessor + Clone + Send + Sync + 'static> MessageConsumer<MsgProcessor>
@@ -139,6 +98,28 @@ impl<MsgProcessor: MessageProcessor + Clone + Send + Sync + 'static> MessageCons
         topology_definition: TopologyDefinition,
         processor: MsgProcessor,
     ) -> Self::Cancellation {
+        Self::consume(url, topology_definition, processor, None)
+    }
+}
+
+impl<MsgProcessor: MessageProcessor + Clone + Send + Sync + 'static> RabbitMessageConsumer<MsgProcessor> {
+    /// Same as `try_connect_and_consume`, but failed messages are retried and dead-lettered by `retry`
+    /// instead of being nacked. Retry queues are declared on connect
+    pub fn try_connect_and_consume_with_retries(
+        url: &str,
+        topology_definition: TopologyDefinition,
+        processor: MsgProcessor,
+        retry: RetryPolicy,
+    ) -> RabbitConsumerCancellation {
+        Self::consume(url, topology_definition, processor, Some(retry))
+    }
+
+    fn consume(
+        url: &str,
+        topology_definition: TopologyDefinition,
+        processor: MsgProcessor,
+        retry: Option<RetryPolicy>,
+    ) -> RabbitConsumerCancellation {
         let (trigger, tripwire) = Tripwire::new();
 
         let handle = RabbitMessageConsumer {
@@ -146,35 +127,15 @@ impl<MsgProcessor: MessageProcessor + Clone + Send + Sync + 'static> MessageCons
             topology_definition,
             processor,
             tripwire,
+            retry,
         }
         .try_connect_and_consume_core();
 
-        Self::Cancellation {
-            trigger,
-            tripwire: handle,
-        }
+        RabbitConsumerCancellation::new(trigger, handle)
     }
-}
 
-impl<MsgProcessor: MessageProcessor + Clone + Send + Sync + 'static> RabbitMessageConsumer<MsgProcessor> {
     fn try_connect_and_consume_core(self) -> Tripwire {
-        let (trigger, tripwire) = Tripwire::new();
-        tokio::spawn(async move {
-            log::trace!("try connect and consume");
-            let retry_status = retry_notify(
-                ExponentialBackoff::default(),
-                || async { self.clone().connect_and_consume().await.map_err(Into::into) },
-                |err, duration| {
-                    log::warn!("failed to connect and consume: {err:?}, retrying in {duration:?}");
-                },
-            )
-            .await;
-            if let Err(error) = retry_status {
-                log::error!("Reconnect logic failed: {error}");
-            }
-            trigger.cancel();
-        });
-        tripwire
+        spawn_with_reconnect(move || self.clone().connect_and_consume())
     }
 
     async fn connect_and_consume(self) -> anyhow::Result<()> {
@@ -183,101 +144,33 @@ impl<MsgProcessor: MessageProcessor + Clone + Send + Sync + 'static> RabbitMessa
             topology_definition,
             processor,
             tripwire,
+            retry,
         } = self;
 
-        let options = ConnectionProperties::default()
-            // Use tokio executor and reactor.
-            // At the moment the reactor is only available for unix.
-            .with_executor(tokio_executor_trait::Tokio::current());
+        let topology = connect(&url, topology_definition).await?;
 
-        #[cfg(unix)]
-        let options = options.with_reactor(tokio_reactor_trait::Tokio);
-
-        let connection = Connection::connect(&url, options)
-            .await
-            .context("Failed to connect to rabbitmq")?;
-        log::trace!("Connected to rabbitmq");
-
-        let topology = connection
-            .restore(topology_definition)
-            .await
-            .context("Failed to restore topology")?;
-
-        let mut consumer = Self::consumer(&topology).take_until_if(tripwire);
+        let consumer = Self::consumer(&topology);
+        let queue = consumer.queue();
         let channel = Self::channel(&topology);
 
-        while let Some(delivery) = consumer.next().await {
-            let delivery = delivery.context("Failed to receive message from consumer")?;
-
-            #[cfg(feature = "telemetry")]
-            let (delivery, span) = {
-                let span = tracing::info_span!("process_message", delivery = %delivery.delivery_tag);
-                (span.in_scope(|| correlate_trace_from_delivery(delivery)), span)
-            };
-
-            #[cfg(not(feature = "telemetry"))]
-            let ack = {
-                log::trace!("received message {}", delivery.delivery_tag);
-
-                // actual message handler should return non-permanent error if it wants to nack message
-                match processor.process_message(&delivery, &channel).await {
-                    Ok(true) => true,
-                    Ok(false) => continue,
-                    Err(error) => {
-                        // here we will send nack for failed message processing (e.g. can't deserialize, can't send
-                        // through tx, etc)
-                        log::warn!("Failed to process message: {error}");
-                        error.is::<PermanentError>()
-                    },
-                }
-            };
-
-            #[cfg(feature = "telemetry")]
-            let ack = {
-                // actual message handler should return non-permanent error if it wants to nack message
-                match processor
-                    .process_message(&delivery, &channel)
-                    .instrument(span.clone())
-                    .await
-                {
-                    Ok(true) => true,
-                    Ok(false) => continue,
-                    Err(error) => {
-                        // here we will send nack for failed message processing (e.g. can't deserialize, can't send
-                        // through tx, etc)
-                        tracing::warn!(parent: &span, error = ?error, delivery_tag = %delivery.delivery_tag, "Failed to process message");
-                        error.is::<PermanentError>()
-                    },
-                }
-            };
-
-            if ack {
-                delivery
-                    .ack(Default::default())
-                    .await
-                    .context("Failed to ack rabbitmq msg")?;
-            } else {
-                delivery
-                    .nack(Default::default())
-                    .await
-                    .context("Failed to nack rabbitmq msg")?;
-            }
-        }
-
-        // Consumer will be cancelled on error, otherwise cancellation trigger
-        // has been fired and it has to be cancelled by hand
-        let channel = Self::channel(&topology);
-        let consumer = Self::consumer(&topology);
-        if consumer.state() != ConsumerState::Canceled {
-            channel
-                .basic_cancel(consumer.tag().as_str(), BasicCancelOptions::default())
+        if let Some(retry) = &retry {
+            retry
+                .declare_topology(&channel, queue.as_str())
                 .await
-                .context("Failed to cancel rabbitmq consumer")?;
+                .context("Failed to declare retry queues")?;
         }
 
-        log::info!("Have received close request (cancellation trigger)");
-
-        Ok(())
+        consume_deliveries(
+            consumer.clone().take_until_if(tripwire),
+            &url,
+            &channel,
+            queue.as_str(),
+            &processor,
+            retry.as_ref(),
+        )
+        .await?;
+
+        cancel_consumer(&channel, &consumer).await
     }
 
     fn consumer(topology: &RestoredTopology) -> Consumer {
@@ -289,6 +182,151 @@ impl<MsgProcessor: MessageProcessor + Clone + Send + Sync + 'static> RabbitMessa
     }
 }
 
+/// Process deliveries of `queue` until the stream ends, acking or nacking each one by the processor result.
+/// The depth of the queue is polled on a channel of its own to `url`
+pub(super) async fn consume_deliveries<P, S>(
+    mut deliveries: S,
+    url: &str,
+    channel: &Channel,
+    queue: &str,
+    processor: &P,
+    retry: Option<&RetryPolicy>,
+) -> anyhow::Result<()>
+where
+    P: MessageProcessor + Sync + ?Sized,
+    S: Stream<Item = lapin::Result<Delivery>> + Unpin,
+{
+    #[cfg(feature = "telemetry")]
+    let (metrics, _queue_depth) = (
+        super::metrics::ConsumerMetrics::new(queue),
+        super::metrics::QueueDepthPoller::spawn(url, queue),
+    );
+    #[cfg(not(feature = "telemetry"))]
+    let _ = url;
+
+    while let Some(delivery) = deliveries.next().await {
+        let delivery = delivery.context("Failed to receive message from consumer")?;
+
+        #[cfg(feature = "telemetry")]
+        metrics.record_delivery(delivery.redelivered);
+
+        #[cfg(feature = "telemetry")]
+        let (delivery, span) = {
+            let span = tracing::info_span!("process_message", delivery = %delivery.delivery_tag);
+            (span.in_scope(|| correlate_trace_from_delivery(delivery)), span)
+        };
+
+        #[cfg(not(feature = "telemetry"))]
+        let ack = {
+            log::trace!("received message {}", delivery.delivery_tag);
+
+            // actual message handler should return non-permanent error if it wants to nack message
+            match processor.process_message(&delivery, channel).await {
+                Ok(true) => true,
+                Ok(false) => continue,
+                Err(error) => {
+                    // here we will send nack for failed message processing (e.g. can't deserialize, can't send
+                    // through tx, etc)
+                    log::warn!("Failed to process message: {error}");
+                    handle_failure(retry, channel, queue, &delivery, &error).await
+                },
+            }
+        };
+
+        #[cfg(feature = "telemetry")]
+        let ack = {
+            let started_at = std::time::Instant::now();
+            let result = processor
+                .process_message(&delivery, channel)
+                .instrument(span.clone())
+                .await;
+            metrics.record_processed(started_at, result.is_ok());
+
+            // actual message handler should return non-permanent error if it wants to nack message
+            match result {
+                Ok(true) => true,
+                Ok(false) => continue,
+                Err(error) => {
+                    // here we will send nack for failed message processing (e.g. can't deserialize, can't send
+                    // through tx, etc)
+                    tracing::warn!(parent: &span, error = ?error, delivery_tag = %delivery.delivery_tag, "Failed to process message");
+                    handle_failure(retry, channel, queue, &delivery, &error)
+                        .instrument(span.clone())
+                        .await
+                },
+            }
+        };
+
+        #[cfg(feature = "telemetry")]
+        metrics.record_acknowledged(ack);
+
+        if ack {
+            delivery
+                .ack(Default::default())
+                .await
+                .context("Failed to ack rabbitmq msg")?;
+        } else {
+            delivery
+                .nack(Default::default())
+                .await
+                .context("Failed to nack rabbitmq msg")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the failed message should be acked: permanent errors are, others are nacked. With the retry
+/// policy the message is acked once it's republished to the retry or dead-letter queue
+pub(super) async fn handle_failure(
+    retry: Option<&RetryPolicy>,
+    channel: &Channel,
+    queue: &str,
+    delivery: &Delivery,
+    error: &anyhow::Error,
+) -> bool {
+    let permanent = error.is::<PermanentError>();
+    let Some(retry) = retry else {
+        return permanent;
+    };
+
+    match retry.republish(channel, queue, delivery, error, permanent).await {
+        Ok(()) => true,
+        Err(error) => {
+            log::warn!("Failed to republish message {}: {error}", delivery.delivery_tag);
+            false
+        },
+    }
+}
+
+/// Restore the topology on the connection of `RabbitConnectionManager::global`
+pub(super) async fn connect(url: &str, topology_definition: TopologyDefinition) -> anyhow::Result<RestoredTopology> {
+    let connection = RabbitConnectionManager::global()
+        .connection(url)
+        .await
+        .context("Failed to connect to rabbitmq")?;
+
+    connection
+        .restore(topology_definition)
+        .await
+        .context("Failed to restore topology")
+}
+
+/// Consumer will be cancelled on error, otherwise cancellation trigger
+/// has been fired and it has to be cancelled by hand
+pub(super) async fn cancel_consumer(channel: &Channel, consumer: &Consumer) -> anyhow::Result<()> {
+    if consumer.state() != ConsumerState::Canceled {
+        channel
+            .basic_cancel(consumer.tag().as_str(), BasicCancelOptions::default())
+            .await
+            .context("Failed to cancel rabbitmq consumer")?;
+    }
+
+    log::info!("Have received close request (cancellation trigger)");
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct Ackable {
     delivery_tag: DeliveryTag,
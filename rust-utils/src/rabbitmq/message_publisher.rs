@@ -1,95 +1,186 @@
 use anyhow::Context;
 use async_trait::async_trait;
-use backoff::ExponentialBackoff;
+use backoff::{backoff::Backoff, ExponentialBackoff};
 use lapin::{
-    options::BasicPublishOptions, topology::TopologyDefinition, BasicProperties, Channel, Connection,
-    ConnectionProperties,
+    options::BasicPublishOptions,
+    publisher_confirm::Confirmation,
+    topology::TopologyDefinition,
+    types::{AMQPValue, FieldTable, ShortString},
+    BasicProperties,
 };
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use serde_with::serde_as;
+use std::{collections::BTreeMap, time::Duration};
 
-#[cfg(feature = "telemetry")]
-use lapin::types::FieldTable;
-use serde::Serialize;
-#[cfg(feature = "telemetry")]
-use std::collections::BTreeMap;
+use super::connection::RabbitConnectionManager;
+pub use crate::messaging::{MessagePublisher, PublishOptions};
+use crate::wrappers::serde::DurationMs;
+
+use serde::Deserialize;
 #[cfg(feature = "telemetry")]
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-#[async_trait]
-pub trait MessagePublisher {
-    async fn publish_payload(&self, exchange: &str, routing_key: &str, payload: &[u8]) -> anyhow::Result<()>;
-
-    async fn publish<T>(&self, exchange: &str, routing_key: &str, message: &T) -> anyhow::Result<()>
-    where
-        T: Serialize + Sync,
-    {
-        self.publish_payload(exchange, routing_key, serde_json::to_vec(message)?.as_ref())
-            .await
+impl PublishOptions {
+    fn amqp_headers(&self) -> BTreeMap<ShortString, AMQPValue> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str().into(), AMQPValue::LongString(value.as_str().into())))
+            .collect()
+    }
+
+    /// Properties with `headers`, which include custom headers of the options
+    fn properties(&self, headers: BTreeMap<ShortString, AMQPValue>) -> BasicProperties {
+        let mut properties = BasicProperties::default();
+        if let Some(priority) = self.priority {
+            properties = properties.with_priority(priority);
+        }
+        if let Some(expiration) = self.expiration {
+            properties = properties.with_expiration(expiration.as_millis().to_string().into());
+        }
+        if self.persistent {
+            properties = properties.with_delivery_mode(2);
+        }
+        if let Some(content_type) = &self.content_type {
+            properties = properties.with_content_type(content_type.as_str().into());
+        }
+        if !headers.is_empty() {
+            properties = properties.with_headers(FieldTable::from(headers));
+        }
+        properties
+    }
+}
+
+/// Retries of failed publishes, every publish is confirmed by the broker
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct PublishPolicy {
+    /// Attempts before the message is dropped, the channel is reconnected after connection errors. A message is
+    /// published at least once even if it's `0`
+    #[serde(default = "PublishPolicy::default_max_attempts")]
+    pub max_attempts: usize,
+    #[serde(rename = "initial_backoff_ms", default = "PublishPolicy::default_initial_backoff")]
+    #[serde_as(as = "DurationMs")]
+    pub initial_backoff: Duration,
+    #[serde(rename = "max_backoff_ms", default = "PublishPolicy::default_max_backoff")]
+    #[serde_as(as = "DurationMs")]
+    pub max_backoff: Duration,
+    /// Messages which can't be routed to any queue are returned by the broker and fail with
+    /// `PublishError::Unroutable` instead of being silently discarded
+    #[serde(default)]
+    pub mandatory: bool,
+}
+
+impl PublishPolicy {
+    fn default_max_attempts() -> usize {
+        5
+    }
+
+    fn default_initial_backoff() -> Duration {
+        Duration::from_millis(100)
+    }
+
+    fn default_max_backoff() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn attempts(&self) -> usize {
+        self.max_attempts.max(1)
     }
+
+    fn backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoff {
+            initial_interval: self.initial_backoff,
+            current_interval: self.initial_backoff,
+            max_interval: self.max_backoff,
+            max_elapsed_time: None,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PublishPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Self::default_max_attempts(),
+            initial_backoff: Self::default_initial_backoff(),
+            max_backoff: Self::default_max_backoff(),
+            mandatory: false,
+        }
+    }
+}
+
+/// Permanent failure of a publish, the message is dropped
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PublishError {
+    /// Returned by the broker, see `PublishPolicy::mandatory`
+    #[error("Unroutable message: {reply_code} {reply_text}")]
+    Unroutable { reply_code: u16, reply_text: String },
+    #[error("Failed to publish in {0} attempts")]
+    AttemptsExhausted(usize),
 }
 
+/// Publishes on pooled channels of a `RabbitConnectionManager`, concurrent publishes don't wait for each other
 #[derive(Clone)]
 pub struct RabbitMessagePublisher {
     url: String,
-    channel: Arc<RwLock<Channel>>,
+    manager: RabbitConnectionManager,
     topology: TopologyDefinition,
+    policy: PublishPolicy,
 }
 
 #[cfg(not(feature = "telemetry"))]
 #[async_trait]
 impl MessagePublisher for RabbitMessagePublisher {
-    async fn publish_payload(&self, exchange: &str, routing_key: &str, payload: &[u8]) -> anyhow::Result<()> {
-        while self.basic_publish(exchange, routing_key, payload).await.is_err() {
-            self.reconnect().await?;
-        }
-        Ok(())
+    async fn publish_payload_with_properties(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> anyhow::Result<()> {
+        self.publish_with_policy(exchange, routing_key, payload, options).await
     }
 }
 
 #[cfg(feature = "telemetry")]
 #[async_trait]
 impl MessagePublisher for RabbitMessagePublisher {
-    #[tracing::instrument(level = "debug", skip(self, payload))]
-    async fn publish_payload(&self, exchange: &str, routing_key: &str, payload: &[u8]) -> anyhow::Result<()> {
-        while self.basic_publish(exchange, routing_key, payload).await.is_err() {
-            self.reconnect().await?;
-        }
-        Ok(())
+    #[tracing::instrument(level = "debug", skip(self, payload, options))]
+    async fn publish_payload_with_properties(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> anyhow::Result<()> {
+        self.publish_with_policy(exchange, routing_key, payload, options).await
     }
 }
 
 impl RabbitMessagePublisher {
+    /// Connect with `RabbitConnectionManager::global`, sharing the connection with consumers
     pub async fn try_connect(url: &str, topology: &TopologyDefinition) -> anyhow::Result<Self> {
-        Self::connect(url, topology)
-            .await
-            .map(|channel| Self {
-                url: url.to_owned(),
-                channel: Arc::new(RwLock::new(channel)),
-                topology: topology.clone(),
-            })
-            .context("failed to connect")
+        Self::try_connect_with_manager(RabbitConnectionManager::global(), url, topology).await
     }
 
-    async fn connect(url: &str, topology: &TopologyDefinition) -> lapin::Result<Channel> {
-        let options = ConnectionProperties::default()
-            // Use tokio executor and reactor.
-            // At the moment the reactor is only available for unix.
-            .with_executor(tokio_executor_trait::Tokio::current());
+    pub async fn try_connect_with_manager(
+        manager: RabbitConnectionManager,
+        url: &str,
+        topology: &TopologyDefinition,
+    ) -> anyhow::Result<Self> {
+        let publisher = Self {
+            url: url.to_owned(),
+            manager,
+            topology: topology.clone(),
+            policy: PublishPolicy::default(),
+        };
+        publisher.reconnect().await.context("failed to connect")?;
 
-        #[cfg(unix)]
-        let options = options.with_reactor(tokio_reactor_trait::Tokio);
-
-        let connection = Connection::connect(url, options).await?;
-        log::trace!("Connected to rabbitmq");
-
-        let _ = connection.restore(topology.clone()).await.map_err(|error| {
-            log::warn!("Failed to restore topology: {error:?}");
-            error
-        })?;
-        log::trace!("Restored topology");
+        Ok(publisher)
+    }
 
-        connection.create_channel().await
+    pub fn with_policy(mut self, policy: PublishPolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     fn topology_definition(topology: &[u8]) -> TopologyDefinition {
@@ -103,41 +194,122 @@ impl RabbitMessagePublisher {
         }
     }
 
+    /// Restore the topology, the connection is opened again if it's closed
     async fn reconnect(&self) -> lapin::Result<()> {
-        let channel = backoff::future::retry(ExponentialBackoff::default(), || async {
-            let channel = Self::connect(&self.url, &self.topology).await?;
-            Ok(channel)
-        })
-        .await?;
-
-        let mut channel_guard = self.channel.write().await;
-        *channel_guard = channel;
+        let connection = self.manager.connection(&self.url).await?;
+        connection.restore(self.topology.clone()).await.map_err(|error| {
+            log::warn!("Failed to restore topology: {error:?}");
+            error
+        })?;
+        log::trace!("Restored topology");
 
         Ok(())
     }
 
+    /// Publish with retries of `PublishPolicy`, nacked messages are published again
+    async fn publish_with_policy(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> anyhow::Result<()> {
+        let mut backoff = self.policy.backoff();
+
+        for attempt in 1..=self.policy.attempts() {
+            if attempt > 1 {
+                tokio::time::sleep(backoff.next_backoff().unwrap_or(self.policy.max_backoff)).await;
+            }
+
+            match self.basic_publish(exchange, routing_key, payload, options).await {
+                Ok(Confirmation::Ack(Some(returned))) => {
+                    let error = PublishError::Unroutable {
+                        reply_code: returned.reply_code,
+                        reply_text: returned.reply_text.to_string(),
+                    };
+                    self.record_dropped(exchange, routing_key, "unroutable");
+                    return Err(error.into());
+                },
+                Ok(Confirmation::Ack(None) | Confirmation::NotRequested) => return Ok(()),
+                Ok(Confirmation::Nack(_)) => {
+                    log::warn!("Message to {exchange}/{routing_key} is nacked, attempt {attempt}");
+                },
+                Err(error) => {
+                    log::warn!("Failed to publish to {exchange}/{routing_key}, attempt {attempt}: {error}");
+                    if let Err(error) = self.reconnect().await {
+                        log::warn!("Failed to reconnect: {error}");
+                    }
+                },
+            }
+        }
+
+        self.record_dropped(exchange, routing_key, "attempts_exhausted");
+        Err(PublishError::AttemptsExhausted(self.policy.attempts()).into())
+    }
+
     #[cfg(not(feature = "telemetry"))]
-    async fn basic_publish(&self, exchange: &str, routing_key: &str, payload: &[u8]) -> lapin::Result<()> {
-        let _ = self
-            .channel
-            .read()
-            .await
+    fn record_dropped(&self, exchange: &str, routing_key: &str, reason: &'static str) {
+        log::error!("Dropped message to {exchange}/{routing_key}: {reason}");
+    }
+
+    /// Dropped messages are counted as `rabbitmq.publisher.dropped`
+    #[cfg(feature = "telemetry")]
+    fn record_dropped(&self, exchange: &str, routing_key: &str, reason: &'static str) {
+        use opentelemetry::{global, metrics::Counter, KeyValue};
+        use std::sync::OnceLock;
+
+        static DROPPED: OnceLock<Counter<u64>> = OnceLock::new();
+
+        tracing::error!(exchange, routing_key, reason, "Dropped message");
+        DROPPED
+            .get_or_init(|| {
+                global::meter("rabbitmq")
+                    .u64_counter("rabbitmq.publisher.dropped")
+                    .with_description("Number of messages dropped by the publisher")
+                    .init()
+            })
+            .add(&opentelemetry::Context::current(), 1, &[
+                KeyValue::new("exchange", exchange.to_owned()),
+                KeyValue::new("routing_key", routing_key.to_owned()),
+                KeyValue::new("reason", reason),
+            ]);
+    }
+
+    #[cfg(not(feature = "telemetry"))]
+    async fn basic_publish(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> lapin::Result<Confirmation> {
+        self.manager
+            .channel(&self.url)
+            .await?
             .basic_publish(
                 exchange,
                 routing_key,
-                BasicPublishOptions::default(),
+                BasicPublishOptions {
+                    mandatory: self.policy.mandatory,
+                    ..Default::default()
+                },
                 payload,
-                BasicProperties::default(),
+                options.properties(options.amqp_headers()),
             )
             .await?
-            .await?;
-        Ok(())
+            .await
     }
 
     #[cfg(feature = "telemetry")]
-    #[tracing::instrument(level = "debug", skip(self, payload))]
-    async fn basic_publish(&self, exchange: &str, routing_key: &str, payload: &[u8]) -> lapin::Result<()> {
-        let mut amqp_headers = BTreeMap::new();
+    #[tracing::instrument(level = "debug", skip(self, payload, options))]
+    async fn basic_publish(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> lapin::Result<Confirmation> {
+        let mut amqp_headers = options.amqp_headers();
 
         // retrieve the current span
         let span = tracing::Span::current();
@@ -148,25 +320,27 @@ impl RabbitMessagePublisher {
             propagator.inject_context(&cx, &mut AmqpClientCarrier::new(&mut amqp_headers))
         });
 
-        let _ = self
-            .channel
-            .read()
-            .await
+        self.manager
+            .channel(&self.url)
+            .await?
             .basic_publish(
                 exchange,
                 routing_key,
-                BasicPublishOptions::default(),
+                BasicPublishOptions {
+                    mandatory: self.policy.mandatory,
+                    ..Default::default()
+                },
                 payload,
-                BasicProperties::default().with_headers(FieldTable::from(amqp_headers)),
+                options.properties(amqp_headers),
             )
             .await?
-            .await?;
-        Ok(())
+            .await
     }
 
     pub async fn purge(&self, queue: &str) -> anyhow::Result<()> {
-        let guard = self.channel.read().await;
-        guard
+        self.manager
+            .channel(&self.url)
+            .await?
             .queue_purge(queue, lapin::options::QueuePurgeOptions::default())
             .await?;
         log::debug!("purge queues");
@@ -174,6 +348,14 @@ impl RabbitMessagePublisher {
     }
 }
 
+#[cfg(feature = "health")]
+#[async_trait]
+impl crate::health::HealthCheck for RabbitMessagePublisher {
+    async fn check(&self) -> anyhow::Result<()> {
+        self.manager.check(&self.url).await
+    }
+}
+
 #[cfg(feature = "telemetry")]
 mod telemetry {
     use lapin::types::{AMQPValue, ShortString};
@@ -199,3 +381,61 @@ mod telemetry {
 
 #[cfg(feature = "telemetry")]
 use telemetry::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_properties() {
+        let options = PublishOptions::default()
+            .with_priority(5)
+            .with_expiration(Duration::from_secs(30))
+            .persistent()
+            .with_content_type("application/json")
+            .with_header("source", "indexer");
+
+        let properties = options.properties(options.amqp_headers());
+
+        assert_eq!(properties.priority(), &Some(5));
+        assert_eq!(properties.expiration().as_ref().map(ShortString::as_str), Some("30000"));
+        assert_eq!(properties.delivery_mode(), &Some(2));
+        assert_eq!(
+            properties.content_type().as_ref().map(ShortString::as_str),
+            Some("application/json")
+        );
+        assert_eq!(
+            properties.headers().as_ref().unwrap().inner().get("source"),
+            Some(&AMQPValue::LongString("indexer".into()))
+        );
+        assert_eq!(
+            PublishOptions::default().properties(BTreeMap::new()),
+            BasicProperties::default()
+        );
+    }
+
+    #[test]
+    fn publish_at_least_once() {
+        let policy: PublishPolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(policy, PublishPolicy::default());
+        assert_eq!(policy.attempts(), 5);
+
+        let policy: PublishPolicy = serde_json::from_str(r#"{"max_attempts": 0, "initial_backoff_ms": 50}"#).unwrap();
+        assert_eq!(policy.attempts(), 1);
+        assert_eq!(policy.backoff().initial_interval, Duration::from_millis(50));
+        assert_eq!(policy.backoff().max_elapsed_time, None);
+    }
+
+    #[test]
+    fn publish_error_message() {
+        let error = PublishError::Unroutable {
+            reply_code: 312,
+            reply_text: "NO_ROUTE".to_owned(),
+        };
+        assert_eq!(error.to_string(), "Unroutable message: 312 NO_ROUTE");
+        assert_eq!(
+            PublishError::AttemptsExhausted(1).to_string(),
+            "Failed to publish in 1 attempts"
+        );
+    }
+}
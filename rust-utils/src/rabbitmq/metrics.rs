@@ -0,0 +1,216 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use lapin::{options::QueueDeclareOptions, types::FieldTable, Channel};
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Unit},
+    KeyValue,
+};
+use tokio::task::JoinHandle;
+
+use super::connection::RabbitConnectionManager;
+
+/// Interval of passive declares polling `rabbitmq.consumer.queue_depth`
+const QUEUE_DEPTH_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Depth of a queue polled by every consumer of the queue in the process
+#[derive(Default)]
+struct QueueDepth {
+    pollers: usize,
+    depth: Option<u64>,
+}
+
+type QueueDepths = Arc<Mutex<HashMap<String, QueueDepth>>>;
+
+static QUEUE_DEPTHS: OnceLock<QueueDepths> = OnceLock::new();
+
+/// Depths of the consumed queues, reported by a single gauge callback
+fn queue_depths() -> &'static QueueDepths {
+    QUEUE_DEPTHS.get_or_init(|| {
+        let depths = QueueDepths::default();
+        let meter = global::meter("rabbitmq");
+        let gauge = meter
+            .u64_observable_gauge("rabbitmq.consumer.queue_depth")
+            .with_description("Number of messages ready for delivery in the consumed queue")
+            .init();
+
+        let latest = depths.clone();
+        let registered = meter.register_callback(move |cx| {
+            let depths = latest.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            for (queue, depth) in depths.iter() {
+                if let Some(depth) = depth.depth {
+                    gauge.observe(cx, depth, &[KeyValue::new("queue", queue.clone())]);
+                }
+            }
+        });
+        if let Err(error) = registered {
+            log::warn!("Failed to register queue depth gauge: {error}");
+        }
+
+        depths
+    })
+}
+
+/// Counts consumed messages as `rabbitmq.consumer.consumed` and `rabbitmq.consumer.redelivered`, acks and
+/// nacks as `rabbitmq.consumer.acknowledged` and records processing latency as `rabbitmq.consumer.duration`
+pub(crate) struct ConsumerMetrics {
+    queue: KeyValue,
+    consumed: Counter<u64>,
+    redelivered: Counter<u64>,
+    acknowledged: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl ConsumerMetrics {
+    pub(crate) fn new(queue: &str) -> Self {
+        let meter = global::meter("rabbitmq");
+
+        Self {
+            queue: KeyValue::new("queue", queue.to_owned()),
+            consumed: meter
+                .u64_counter("rabbitmq.consumer.consumed")
+                .with_description("Number of received messages")
+                .init(),
+            redelivered: meter
+                .u64_counter("rabbitmq.consumer.redelivered")
+                .with_description("Number of received messages which were delivered before")
+                .init(),
+            acknowledged: meter
+                .u64_counter("rabbitmq.consumer.acknowledged")
+                .with_description("Number of acked and nacked messages")
+                .init(),
+            duration: meter
+                .f64_histogram("rabbitmq.consumer.duration")
+                .with_unit(Unit::new("ms"))
+                .with_description("Duration of message processing")
+                .init(),
+        }
+    }
+
+    pub(crate) fn record_delivery(&self, redelivered: bool) {
+        let cx = opentelemetry::Context::current();
+        let attributes = [self.queue.clone()];
+
+        self.consumed.add(&cx, 1, &attributes);
+        if redelivered {
+            self.redelivered.add(&cx, 1, &attributes);
+        }
+    }
+
+    pub(crate) fn record_processed(&self, started_at: Instant, success: bool) {
+        let cx = opentelemetry::Context::current();
+        self.duration
+            .record(&cx, started_at.elapsed().as_secs_f64() * 1000.0, &[
+                self.queue.clone(),
+                KeyValue::new("success", success),
+            ]);
+    }
+
+    pub(crate) fn record_acknowledged(&self, ack: bool) {
+        let cx = opentelemetry::Context::current();
+        let outcome = if ack { "ack" } else { "nack" };
+        self.acknowledged
+            .add(&cx, 1, &[self.queue.clone(), KeyValue::new("outcome", outcome)]);
+    }
+}
+
+fn lock_depths(depths: &QueueDepths) -> MutexGuard<'_, HashMap<String, QueueDepth>> {
+    depths.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Polls the depth of a queue until dropped, the depth is reported until every poller of the queue is dropped
+pub(crate) struct QueueDepthPoller {
+    queue: String,
+    task: JoinHandle<()>,
+}
+
+impl QueueDepthPoller {
+    /// A failed passive declare closes its channel, so the queue is polled on a channel of its own to `url`
+    pub(crate) fn spawn(url: &str, queue: &str) -> Self {
+        let depths = queue_depths().clone();
+        lock_depths(&depths).entry(queue.to_owned()).or_default().pollers += 1;
+        let (url, name) = (url.to_owned(), queue.to_owned());
+
+        let task = tokio::spawn(async move {
+            let options = QueueDeclareOptions {
+                passive: true,
+                ..Default::default()
+            };
+            let mut channel = None;
+            let mut ticker = tokio::time::interval(QUEUE_DEPTH_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let polling = match channel.take().filter(|channel: &Channel| channel.status().connected()) {
+                    Some(channel) => channel,
+                    None => match open_channel(&url).await {
+                        Ok(channel) => channel,
+                        Err(error) => {
+                            log::warn!("Failed to open channel polling depth of queue {name}: {error}");
+                            continue;
+                        },
+                    },
+                };
+
+                match polling.queue_declare(&name, options, FieldTable::default()).await {
+                    Ok(declared) => {
+                        if let Some(depth) = lock_depths(&depths).get_mut(&name) {
+                            depth.depth = Some(declared.message_count().into());
+                        }
+                    },
+                    Err(error) => log::warn!("Failed to poll depth of queue {name}: {error}"),
+                }
+                channel = Some(polling);
+            }
+        });
+
+        Self {
+            queue: queue.to_owned(),
+            task,
+        }
+    }
+}
+
+async fn open_channel(url: &str) -> lapin::Result<Channel> {
+    RabbitConnectionManager::global()
+        .connection(url)
+        .await?
+        .create_channel()
+        .await
+}
+
+impl Drop for QueueDepthPoller {
+    fn drop(&mut self) {
+        self.task.abort();
+        let mut depths = lock_depths(queue_depths());
+        if let Some(depth) = depths.get_mut(&self.queue) {
+            depth.pollers -= 1;
+            if depth.pollers == 0 {
+                depths.remove(&self.queue);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn report_depth_until_last_poller_dropped() {
+        let queue = "queue-depth-test";
+        let (first, second) = (
+            QueueDepthPoller::spawn("amqp://127.0.0.1:1", queue),
+            QueueDepthPoller::spawn("amqp://127.0.0.1:1", queue),
+        );
+        lock_depths(queue_depths()).get_mut(queue).unwrap().depth = Some(3);
+
+        drop(first);
+        assert_eq!(lock_depths(queue_depths())[queue].depth, Some(3));
+        drop(second);
+        assert!(!lock_depths(queue_depths()).contains_key(queue));
+    }
+}
@@ -1,2 +1,11 @@
+pub mod batch_consumer;
+pub mod connection;
+pub mod envelope;
 pub mod message_consumer;
 pub mod message_publisher;
+#[cfg(feature = "telemetry")]
+mod metrics;
+pub mod multi_queue_consumer;
+pub mod retry;
+#[cfg(any(test, feature = "rabbitmq-testing"))]
+pub mod testing;
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::bail;
+use futures::future::try_join_all;
+use lapin::topology::{RestoredTopology, TopologyDefinition};
+use stream_cancel::{StreamExt, Trigger, Tripwire};
+
+use super::message_consumer::{
+    cancel_consumer, connect, consume_deliveries, MessageProcessor, RabbitConsumerCancellation,
+};
+use crate::messaging::spawn_with_reconnect;
+
+pub type SharedProcessor = Arc<dyn MessageProcessor + Send + Sync>;
+
+#[derive(Clone)]
+struct QueueConsumer {
+    queue: String,
+    /// Indexes of the channel and its consumer in the topology definition
+    channel: usize,
+    consumer: usize,
+    processor: SharedProcessor,
+    tripwire: Tripwire,
+    stopped: Arc<Mutex<Option<Trigger>>>,
+}
+
+impl QueueConsumer {
+    fn stop(&self) {
+        let trigger = self.stopped.lock().unwrap_or_else(|error| error.into_inner()).take();
+        if let Some(trigger) = trigger {
+            trigger.cancel();
+        }
+    }
+
+    async fn consume(&self, url: &str, topology: &RestoredTopology) -> anyhow::Result<()> {
+        let channel = topology.channel(self.channel).into_inner();
+        let consumer = topology.channel(self.channel).consumer(self.consumer);
+
+        consume_deliveries(
+            consumer.clone().take_until_if(self.tripwire.clone()),
+            url,
+            &channel,
+            &self.queue,
+            self.processor.as_ref(),
+            None,
+        )
+        .await?;
+
+        cancel_consumer(&channel, &consumer).await?;
+        self.stop();
+        Ok(())
+    }
+}
+
+/// Consumes every queue of the topology with its own processor over a single connection.
+/// On a connection error all the consumers are restored, cancelled ones stay cancelled
+#[derive(Clone)]
+pub struct RabbitMultiQueueConsumer {
+    url: String,
+    topology_definition: TopologyDefinition,
+    consumers: Vec<QueueConsumer>,
+}
+
+impl RabbitMultiQueueConsumer {
+    /// Each consumer of the topology must have a processor registered by its queue name.
+    /// Consumption of the queues is cancelled independently
+    pub fn try_connect_and_consume(
+        url: &str,
+        topology_definition: TopologyDefinition,
+        mut processors: HashMap<String, SharedProcessor>,
+    ) -> anyhow::Result<HashMap<String, RabbitConsumerCancellation>> {
+        let mut consumers = vec![];
+        let mut cancellations = HashMap::new();
+
+        for (channel, definition) in topology_definition.channels.iter().enumerate() {
+            for (consumer, definition) in definition.consumers.iter().enumerate() {
+                let queue = definition.queue.to_string();
+                let Some(processor) = processors.remove(&queue) else {
+                    bail!("No processor for queue {queue}");
+                };
+
+                let (trigger, tripwire) = Tripwire::new();
+                let (stopped, handle) = Tripwire::new();
+                consumers.push(QueueConsumer {
+                    queue: queue.clone(),
+                    channel,
+                    consumer,
+                    processor,
+                    tripwire,
+                    stopped: Arc::new(Mutex::new(Some(stopped))),
+                });
+                cancellations.insert(queue, RabbitConsumerCancellation::new(trigger, handle));
+            }
+        }
+
+        if let Some(queue) = processors.keys().next() {
+            bail!("No consumer of queue {queue} in the topology");
+        }
+
+        let consumer = Self {
+            url: url.to_owned(),
+            topology_definition,
+            consumers,
+        };
+        let handle = spawn_with_reconnect({
+            let consumer = consumer.clone();
+            move || consumer.clone().connect_and_consume()
+        });
+
+        // consumers left running are stopped together with the connection
+        tokio::spawn(async move {
+            handle.await;
+            consumer.consumers.iter().for_each(QueueConsumer::stop);
+        });
+
+        Ok(cancellations)
+    }
+
+    async fn connect_and_consume(self) -> anyhow::Result<()> {
+        let topology = connect(&self.url, self.topology_definition).await?;
+
+        try_join_all(
+            self.consumers
+                .iter()
+                .map(|consumer| consumer.consume(&self.url, &topology)),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use lapin::topology::{ChannelDefinition, ConsumerDefinition};
+
+    use super::*;
+    use crate::rabbitmq::message_consumer::MessageHandler;
+
+    struct Handler;
+
+    #[async_trait]
+    impl MessageHandler for Handler {
+        type Message = ();
+
+        async fn handle_message(&self, _message: ()) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn match_processors_with_consumers() {
+        let topology = TopologyDefinition {
+            channels: vec![ChannelDefinition {
+                consumers: vec![ConsumerDefinition {
+                    queue: "events".into(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let processors = |queues: &[&str]| {
+            queues
+                .iter()
+                .map(|queue| (queue.to_string(), Arc::new(Handler) as SharedProcessor))
+                .collect()
+        };
+
+        let error = RabbitMultiQueueConsumer::try_connect_and_consume("", topology.clone(), processors(&[]))
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "No processor for queue events");
+
+        let error =
+            RabbitMultiQueueConsumer::try_connect_and_consume("", topology, processors(&["events", "transfers"]))
+                .err()
+                .unwrap();
+        assert_eq!(error.to_string(), "No consumer of queue transfers in the topology");
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use lapin::{
+    message::Delivery,
+    options::{BasicPublishOptions, QueueDeclareOptions},
+    types::{AMQPValue, FieldTable, ShortString},
+    Channel,
+};
+use serde::Deserialize;
+use serde_with::serde_as;
+
+use crate::wrappers::serde::DurationMs;
+
+/// Number of failed processing attempts of a message
+pub const ATTEMPT_HEADER: &str = "x-retry-attempt";
+/// Error of the last attempt of a dead-lettered message
+pub const ERROR_HEADER: &str = "x-retry-error";
+
+/// Failed messages are republished to `<queue>.retry` and return to the queue after `delay`. After
+/// `max_attempts` or a `PermanentError` they are republished to `<queue>.dlq`, or to `dead_letter_exchange`
+/// with the original routing key
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    #[serde(default = "RetryPolicy::default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(rename = "delay_ms", default = "RetryPolicy::default_delay")]
+    #[serde_as(as = "DurationMs")]
+    pub delay: Duration,
+    #[serde(default)]
+    pub dead_letter_exchange: Option<String>,
+}
+
+impl RetryPolicy {
+    fn default_max_attempts() -> u32 {
+        5
+    }
+
+    fn default_delay() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub fn retry_queue(queue: &str) -> String {
+        format!("{queue}.retry")
+    }
+
+    pub fn dead_letter_queue(queue: &str) -> String {
+        format!("{queue}.dlq")
+    }
+
+    /// Declare `<queue>.retry` dead-lettering back to `queue` and `<queue>.dlq` if there is no
+    /// `dead_letter_exchange`
+    pub async fn declare_topology(&self, channel: &Channel, queue: &str) -> lapin::Result<()> {
+        let options = QueueDeclareOptions {
+            durable: true,
+            ..Default::default()
+        };
+
+        let mut arguments = FieldTable::default();
+        arguments.insert(
+            "x-message-ttl".into(),
+            AMQPValue::LongLongInt(self.delay.as_millis() as i64),
+        );
+        arguments.insert("x-dead-letter-exchange".into(), AMQPValue::LongString("".into()));
+        arguments.insert("x-dead-letter-routing-key".into(), AMQPValue::LongString(queue.into()));
+        channel
+            .queue_declare(&Self::retry_queue(queue), options, arguments)
+            .await?;
+
+        if self.dead_letter_exchange.is_none() {
+            channel
+                .queue_declare(&Self::dead_letter_queue(queue), options, FieldTable::default())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Republish a failed message of `queue`, it should be acked if this succeeds
+    pub(crate) async fn republish(
+        &self,
+        channel: &Channel,
+        queue: &str,
+        delivery: &Delivery,
+        error: &anyhow::Error,
+        permanent: bool,
+    ) -> lapin::Result<()> {
+        let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+        let attempt = attempt(&headers) + 1;
+        headers.insert(ATTEMPT_HEADER.into(), AMQPValue::LongLongInt(attempt.into()));
+
+        let (exchange, routing_key) = if permanent || attempt >= self.max_attempts {
+            headers.insert(ERROR_HEADER.into(), AMQPValue::LongString(format!("{error:#}").into()));
+            match &self.dead_letter_exchange {
+                Some(exchange) => (exchange.clone(), delivery.routing_key.to_string()),
+                None => (String::new(), Self::dead_letter_queue(queue)),
+            }
+        } else {
+            (String::new(), Self::retry_queue(queue))
+        };
+
+        channel
+            .basic_publish(
+                &exchange,
+                &routing_key,
+                BasicPublishOptions::default(),
+                &delivery.data,
+                delivery.properties.clone().with_headers(headers),
+            )
+            .await?
+            .await?;
+        Ok(())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Self::default_max_attempts(),
+            delay: Self::default_delay(),
+            dead_letter_exchange: None,
+        }
+    }
+}
+
+/// Failed attempts of a delivery, headers may be re-encoded by the broker
+pub fn attempt(headers: &FieldTable) -> u32 {
+    let Some(value) = headers.inner().get(&ShortString::from(ATTEMPT_HEADER)) else {
+        return 0;
+    };
+
+    value
+        .as_long_long_int()
+        .or_else(|| value.as_long_int().map(Into::into))
+        .or_else(|| value.as_long_uint().map(Into::into))
+        .and_then(|attempt| u32::try_from(attempt).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_attempt() {
+        let mut headers = FieldTable::default();
+        assert_eq!(attempt(&headers), 0);
+
+        headers.insert(ATTEMPT_HEADER.into(), AMQPValue::LongLongInt(2));
+        assert_eq!(attempt(&headers), 2);
+
+        headers.insert(ATTEMPT_HEADER.into(), AMQPValue::LongInt(3));
+        assert_eq!(attempt(&headers), 3);
+    }
+}
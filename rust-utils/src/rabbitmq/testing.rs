@@ -0,0 +1,241 @@
+//! Broker-less messaging for unit tests of services, enabled by the `rabbitmq-testing` feature of dev-dependencies
+//!
+//! # Usage
+//! ```ignore
+//! use rust_utils::rabbitmq::testing::InMemoryBus;
+//!
+//! let bus = InMemoryBus::default().with_handler("events", EventsHandler::new(repo.clone()));
+//!
+//! // the handler has finished once the publish returns
+//! service_under_test(bus.clone()).run().await?;
+//!
+//! assert_eq!(bus.published()[0].message::<Event>()?, expected);
+//! assert!(bus.failed().is_empty());
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::{future::BoxFuture, FutureExt};
+use serde::de::DeserializeOwned;
+
+use super::{
+    message_consumer::{MessageHandler, PermanentError},
+    message_publisher::{MessagePublisher, PublishOptions},
+};
+
+type Deliver = Arc<dyn Fn(String, Vec<u8>) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedMessage {
+    pub exchange: String,
+    pub routing_key: String,
+    pub payload: Vec<u8>,
+    pub options: PublishOptions,
+}
+
+impl PublishedMessage {
+    pub fn message<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.payload)
+    }
+}
+
+/// Message which a handler failed to process, it's not redelivered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedMessage {
+    pub message: PublishedMessage,
+    pub error: String,
+    /// Whether the error is a `PermanentError`, e.g. the message can't be deserialized
+    pub permanent: bool,
+}
+
+#[derive(Default)]
+struct State {
+    handlers: Vec<(String, Deliver)>,
+    published: Vec<PublishedMessage>,
+    failed: Vec<FailedMessage>,
+    publish_faults: usize,
+}
+
+/// `MessagePublisher` delivering messages to the handlers registered for the exchange before the publish
+/// returns. Handlers see messages as the rabbitmq consumer does: unsupported routing keys are skipped and
+/// malformed payloads fail permanently
+#[derive(Clone, Default)]
+pub struct InMemoryBus(Arc<Mutex<State>>);
+
+impl InMemoryBus {
+    pub fn with_handler<Handler>(self, exchange: impl Into<String>, handler: Handler) -> Self
+    where
+        Handler: MessageHandler + Clone + Send + Sync + 'static,
+        Handler::Message: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.register(exchange, handler);
+        self
+    }
+
+    pub fn register<Handler>(&self, exchange: impl Into<String>, handler: Handler)
+    where
+        Handler: MessageHandler + Clone + Send + Sync + 'static,
+        Handler::Message: DeserializeOwned + Send + Sync + 'static,
+    {
+        let deliver: Deliver = Arc::new(move |routing_key, payload| {
+            let handler = handler.clone();
+            async move { deliver(&handler, &routing_key, &payload).await }.boxed()
+        });
+        self.state().handlers.push((exchange.into(), deliver));
+    }
+
+    /// Fail the next `count` publishes as if the broker was unavailable, failed messages aren't captured
+    pub fn fail_next_publishes(&self, count: usize) {
+        self.state().publish_faults = count;
+    }
+
+    pub fn published(&self) -> Vec<PublishedMessage> {
+        self.state().published.clone()
+    }
+
+    /// Published messages, the captured ones are cleared
+    pub fn take_published(&self) -> Vec<PublishedMessage> {
+        std::mem::take(&mut self.state().published)
+    }
+
+    pub fn failed(&self) -> Vec<FailedMessage> {
+        self.state().failed.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+async fn deliver<Handler>(handler: &Handler, routing_key: &str, payload: &[u8]) -> anyhow::Result<()>
+where
+    Handler: MessageHandler,
+    Handler::Message: DeserializeOwned,
+{
+    if let Some(expected) = Handler::ROUTING_KEY {
+        if routing_key != expected {
+            return Ok(());
+        }
+    }
+
+    let message = serde_json::from_slice(payload).context(PermanentError)?;
+    handler.handle_message(message).await
+}
+
+#[async_trait]
+impl MessagePublisher for InMemoryBus {
+    async fn publish_payload_with_properties(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        payload: &[u8],
+        options: &PublishOptions,
+    ) -> anyhow::Result<()> {
+        // handlers may publish too, the state isn't locked while they run
+        let (message, handlers) = {
+            let mut state = self.state();
+            if state.publish_faults > 0 {
+                state.publish_faults -= 1;
+                anyhow::bail!("Injected failure of publish to {exchange}/{routing_key}");
+            }
+
+            let message = PublishedMessage {
+                exchange: exchange.to_owned(),
+                routing_key: routing_key.to_owned(),
+                payload: payload.to_vec(),
+                options: options.clone(),
+            };
+            state.published.push(message.clone());
+
+            let handlers: Vec<Deliver> = state
+                .handlers
+                .iter()
+                .filter(|(handler_exchange, _)| handler_exchange == exchange)
+                .map(|(_, deliver)| deliver.clone())
+                .collect();
+            (message, handlers)
+        };
+
+        for deliver in handlers {
+            if let Err(error) = deliver(routing_key.to_owned(), payload.to_vec()).await {
+                self.state().failed.push(FailedMessage {
+                    message: message.clone(),
+                    permanent: error.is::<PermanentError>(),
+                    error: format!("{error:#}"),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Unhealthy while publishes are failed by `InMemoryBus::fail_next_publishes`, as a publisher without a broker
+#[cfg(feature = "health")]
+#[async_trait]
+impl crate::health::HealthCheck for InMemoryBus {
+    async fn check(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.state().publish_faults == 0, "connection is not open");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Transfers(Arc<Mutex<Vec<u64>>>);
+
+    #[async_trait]
+    impl MessageHandler for Transfers {
+        type Message = u64;
+
+        const ROUTING_KEY: Option<&'static str> = Some("transfer");
+
+        async fn handle_message(&self, amount: u64) -> anyhow::Result<()> {
+            anyhow::ensure!(amount > 0, "empty transfer");
+            self.0.lock().unwrap().push(amount);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn deliver_to_handlers() {
+        let transfers = Transfers::default();
+        let bus = InMemoryBus::default().with_handler("events", transfers.clone());
+
+        bus.publish("events", "transfer", &10).await.unwrap();
+        bus.publish("events", "mint", &20).await.unwrap();
+        bus.publish("events", "transfer", &0).await.unwrap();
+        bus.publish("events", "transfer", &"malformed").await.unwrap();
+        bus.fail_next_publishes(1);
+        assert!(bus.publish("events", "transfer", &30).await.is_err());
+
+        assert_eq!(*transfers.0.lock().unwrap(), vec![10]);
+        assert_eq!(bus.take_published().len(), 4);
+        assert!(bus.published().is_empty());
+
+        let failed = bus.failed();
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0].message.message::<u64>().unwrap(), 0);
+        assert_eq!(failed[0].error, "empty transfer");
+        assert!(!failed[0].permanent);
+        assert!(failed[1].permanent);
+    }
+
+    #[cfg(feature = "health")]
+    #[tokio::test]
+    async fn report_health_of_publishes() {
+        use crate::health::HealthCheck;
+
+        let bus = InMemoryBus::default();
+        bus.fail_next_publishes(1);
+        assert!(bus.check().await.is_err());
+
+        assert!(bus.publish("events", "transfer", &10).await.is_err());
+        bus.check().await.unwrap();
+    }
+}
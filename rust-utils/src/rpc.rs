@@ -1,32 +1,74 @@
-use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use gcloud_env::GCloudRunEnv;
 use lazy_static::lazy_static;
 use serde::Deserialize;
-use serde_with::{serde_as, DurationMilliSeconds};
+use serde_with::serde_as;
+
+use crate::{secret::Secret, wrappers::serde::DurationMs};
 
 lazy_static! {
     pub static ref GCLOUD_ENV: Option<GCloudRunEnv> = GCloudRunEnv::from_env().ok();
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct RpcClientSettings {
     pub address: String,
     #[serde(
         rename = "reconnect_timeout_ms",
         default = "RpcClientSettings::default_reconnect_timeout"
     )]
-    #[serde_as(as = "DurationMilliSeconds")]
+    #[serde_as(as = "DurationMs")]
     pub reconnect_timeout: Duration,
+    /// Reconnects of a WebSocket client, `reconnect_timeout` apart, forever if not set
     #[serde(default)]
     pub max_retries: Option<usize>,
+    /// Retries of HTTP requests of `retry_methods` failed with `5xx` or a transport error
+    #[serde(default)]
+    pub max_request_retries: usize,
+    #[serde(
+        rename = "request_retry_delay_ms",
+        default = "RpcClientSettings::default_request_retry_delay"
+    )]
+    #[serde_as(as = "DurationMs")]
+    pub request_retry_delay: Duration,
+    /// Idempotent methods which are retried, e.g. `getBalance`. Other methods and batches with them are never
+    /// retried, the failed request may have been executed
+    #[serde(default)]
+    pub retry_methods: HashSet<String>,
+    #[serde(
+        rename = "request_timeout_ms",
+        default = "RpcClientSettings::default_request_timeout"
+    )]
+    #[serde_as(as = "DurationMs")]
+    pub request_timeout: Duration,
+    #[serde(default = "RpcClientSettings::default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Headers sent with every request, e.g. `authorization`. Values are redacted in logs
+    #[serde(default)]
+    pub headers: HashMap<String, Secret<String>>,
 }
 
 impl RpcClientSettings {
     fn default_reconnect_timeout() -> Duration {
         Duration::from_secs(1)
     }
+
+    fn default_request_retry_delay() -> Duration {
+        Duration::from_millis(500)
+    }
+
+    fn default_request_timeout() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_max_concurrent_requests() -> usize {
+        256
+    }
 }
 
 pub fn default_bind_address() -> String {
@@ -0,0 +1,91 @@
+//! Settings values which must not get into logs, e.g. API keys and database URLs with passwords. Values which can't be
+//! zeroized, e.g. keypairs, are wrapped in `wrappers::Redacted` instead
+//!
+//! # Usage
+//! ```ignore
+//! #[derive(Debug, Deserialize)]
+//! pub struct ClientSettings {
+//!     pub api_key: Secret<String>,
+//! }
+//!
+//! log::info!("{settings:?}"); // ClientSettings { api_key: [REDACTED] }
+//! request.header("x-api-key", settings.api_key.expose_secret());
+//! ```
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// Replaces the value in `Debug`, `Display` and serialized settings
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Value which is redacted when formatted or serialized and zeroed on drop. It's deserialized as is
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T: Zeroize> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T: Zeroize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de, T: Zeroize + Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_secret() {
+        let secret: Secret<String> = serde_json::from_str(r#""postgres://indexer:hunter2@db/indexer""#).unwrap();
+
+        assert_eq!(secret.expose_secret(), "postgres://indexer:hunter2@db/indexer");
+        assert_eq!(format!("{secret} {secret:?}"), "[REDACTED] [REDACTED]");
+        assert_eq!(serde_json::to_string(&Some(secret)).unwrap(), r#""[REDACTED]""#);
+    }
+}
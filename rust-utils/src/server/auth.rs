@@ -0,0 +1,187 @@
+use std::{
+    collections::HashSet,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use http::{header::AUTHORIZATION, HeaderMap, HeaderName, Method, Request, Response, StatusCode};
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use tower::{Layer, Service};
+
+use super::body::error_response;
+
+/// JSON-RPC error code of unauthenticated calls, from the range reserved for implementation-defined server errors
+pub const UNAUTHORIZED_CODE: i32 = -32001;
+
+/// Paths proxied to health and version methods, they are called by probes without credentials
+pub(crate) const PUBLIC_PATHS: [&str; 3] = ["/liveness", "/readiness", "/version"];
+
+/// `GET` requests to the public paths, which `ProxyGetRequestLayer` rewrites. JSON-RPC calls are accepted on any path,
+/// so other methods are checked
+pub(crate) fn is_public<B>(request: &Request<B>) -> bool {
+    request.method() == Method::GET && PUBLIC_PATHS.contains(&request.uri().path())
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuthSettings {
+    /// Header with the API key
+    #[serde(default = "AuthSettings::default_header")]
+    pub header: String,
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+}
+
+impl AuthSettings {
+    fn default_header() -> String {
+        "x-api-key".into()
+    }
+}
+
+/// Validates tokens of `Authorization: Bearer <token>` header
+#[async_trait]
+pub trait TokenValidator: Send + Sync + 'static {
+    async fn validate(&self, token: &str) -> bool;
+}
+
+/// Static list of tokens
+#[async_trait]
+impl TokenValidator for HashSet<String> {
+    async fn validate(&self, token: &str) -> bool {
+        contains_secret(self, token)
+    }
+}
+
+/// Compares `secret` with every key in constant time, so the timing doesn't reveal how much of a key matches
+fn contains_secret<'a>(keys: impl IntoIterator<Item = &'a String>, secret: &str) -> bool {
+    keys.into_iter()
+        .fold(subtle::Choice::from(0), |found, key| {
+            found | key.as_bytes().ct_eq(secret.as_bytes())
+        })
+        .into()
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Auth {
+    api_key: Option<(HeaderName, Vec<String>)>,
+    validator: Option<Arc<dyn TokenValidator>>,
+}
+
+impl Auth {
+    pub(crate) fn new(
+        settings: Option<&AuthSettings>,
+        validator: Option<Arc<dyn TokenValidator>>,
+    ) -> Result<Option<Self>, http::header::InvalidHeaderName> {
+        let api_key = settings
+            .map(|settings| {
+                Ok::<_, http::header::InvalidHeaderName>((
+                    HeaderName::from_bytes(settings.header.as_bytes())?,
+                    settings.api_keys.clone(),
+                ))
+            })
+            .transpose()?;
+
+        if api_key.is_none() && validator.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self { api_key, validator }))
+    }
+
+    /// Any configured method is enough
+    async fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        if let Some((header, keys)) = &self.api_key {
+            let key = headers.get(header).and_then(|key| key.to_str().ok());
+            if key.is_some_and(|key| contains_secret(keys, key)) {
+                return true;
+            }
+        }
+
+        if let Some(validator) = &self.validator {
+            let token = headers
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            if let Some(token) = token {
+                return validator.validate(token).await;
+            }
+        }
+
+        false
+    }
+}
+
+/// Rejects requests without valid credentials with `401` and a JSON-RPC error
+#[derive(Clone)]
+pub(crate) struct AuthLayer(pub(crate) Auth);
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            auth: self.0.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AuthService<S> {
+    auth: Auth,
+    inner: S,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for AuthService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+    ResBody: From<String>,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        // the ready service is taken, a clone is left for the next call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let auth = self.auth.clone();
+
+        Box::pin(async move {
+            if is_public(&request) || auth.is_authorized(request.headers()).await {
+                return inner.call(request).await;
+            }
+
+            Ok(error_response(
+                StatusCode::UNAUTHORIZED,
+                UNAUTHORIZED_CODE,
+                "Unauthorized",
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_secrets() {
+        let keys = ["first".to_owned(), "second".to_owned()];
+
+        assert!(contains_secret(&keys, "second"));
+        assert!(!contains_secret(&keys, "secon"));
+        assert!(!contains_secret(&keys, "seconds"));
+        assert!(!contains_secret(&keys, ""));
+        assert!(!contains_secret(&[], "first"));
+    }
+}
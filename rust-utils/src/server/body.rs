@@ -0,0 +1,44 @@
+use http::{header::CONTENT_TYPE, HeaderValue, Response, StatusCode};
+use hyper::{body::HttpBody, Body};
+use jsonrpsee::types::error::{OVERSIZED_REQUEST_CODE, PARSE_ERROR_CODE};
+
+/// HTTP response with a JSON-RPC error, for requests rejected before they are dispatched
+pub(crate) fn error_response<ResBody: From<String>>(status: StatusCode, code: i32, message: &str) -> Response<ResBody> {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": message },
+        "id": null,
+    });
+
+    let mut response = Response::new(ResBody::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// Read the whole body for layers which need to inspect it, the error is the response to return
+pub(crate) async fn read_body(body: &mut Body, max_size: usize) -> Result<Vec<u8>, Response<Body>> {
+    let mut bytes = Vec::new();
+
+    while let Some(chunk) = body.data().await {
+        let Ok(chunk) = chunk else {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                PARSE_ERROR_CODE,
+                "Unable to read request body",
+            ));
+        };
+        if bytes.len() + chunk.len() > max_size {
+            return Err(error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                OVERSIZED_REQUEST_CODE,
+                "Request body is too large",
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
@@ -0,0 +1,173 @@
+use std::{
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::OnceLock,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use http::{header::CONTENT_TYPE, HeaderValue, Method, Request, Response};
+use jsonrpsee::server::logger::{HttpRequest, Logger, MethodKind, Params, TransportProtocol};
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Meter, MeterProvider, Unit},
+    sdk::{
+        export::metrics::aggregation,
+        metrics::{controllers, processors, selectors},
+    },
+    KeyValue,
+};
+use opentelemetry_prometheus::PrometheusExporter;
+use prometheus::{Encoder, TextEncoder};
+use tower::{Layer, Service};
+
+pub(crate) const METRICS_PATH: &str = "/metrics";
+
+/// Buckets of `rpc.server.duration` in milliseconds
+const DURATION_BUCKETS: [f64; 12] = [
+    1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+static EXPORTER: OnceLock<PrometheusExporter> = OnceLock::new();
+
+/// Prometheus exporter with its own registry and meter provider. The global meter provider is left untouched,
+/// so `/metrics` serves only the server metrics
+fn exporter() -> &'static PrometheusExporter {
+    EXPORTER.get_or_init(|| {
+        let controller = controllers::basic(
+            processors::factory(
+                selectors::simple::histogram(DURATION_BUCKETS),
+                aggregation::cumulative_temporality_selector(),
+            )
+            .with_memory(true),
+        )
+        .build();
+
+        // `init` always installs the exporter as the global meter provider, so the previous one is put back
+        let global_provider = global::meter_provider();
+        let exporter = opentelemetry_prometheus::exporter(controller).init();
+        global::set_meter_provider(global_provider);
+
+        exporter
+    })
+}
+
+/// Meter of the server instruments served on `/metrics`
+pub(crate) fn meter() -> Meter {
+    exporter()
+        .meter_provider()
+        .expect("Prometheus controller is poisoned")
+        .meter("rpc-server")
+}
+
+/// Counts JSON-RPC calls as `rpc.server.calls` and records their latency as `rpc.server.duration`
+/// by method and result
+#[derive(Debug, Clone)]
+pub struct MetricsLogger {
+    calls: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl Default for MetricsLogger {
+    fn default() -> Self {
+        let meter = meter();
+
+        Self {
+            calls: meter
+                .u64_counter("rpc.server.calls")
+                .with_description("Number of JSON-RPC calls")
+                .init(),
+            duration: meter
+                .f64_histogram("rpc.server.duration")
+                .with_unit(Unit::new("ms"))
+                .with_description("Duration of JSON-RPC calls")
+                .init(),
+        }
+    }
+}
+
+impl Logger for MetricsLogger {
+    type Instant = Instant;
+
+    fn on_connect(&self, _remote_addr: SocketAddr, _request: &HttpRequest, _transport: TransportProtocol) {}
+
+    fn on_request(&self, _transport: TransportProtocol) -> Self::Instant {
+        Instant::now()
+    }
+
+    fn on_call(&self, _method_name: &str, _params: Params, _kind: MethodKind, _transport: TransportProtocol) {}
+
+    fn on_result(&self, method_name: &str, success: bool, started_at: Self::Instant, transport: TransportProtocol) {
+        let cx = opentelemetry::Context::current();
+        let attributes = [
+            KeyValue::new("method", method_name.to_owned()),
+            KeyValue::new("success", success),
+            KeyValue::new("transport", transport.to_string()),
+        ];
+
+        self.calls.add(&cx, 1, &attributes);
+        self.duration
+            .record(&cx, started_at.elapsed().as_secs_f64() * 1000.0, &attributes);
+    }
+
+    fn on_response(&self, _result: &str, _started_at: Self::Instant, _transport: TransportProtocol) {}
+
+    fn on_disconnect(&self, _remote_addr: SocketAddr, _transport: TransportProtocol) {}
+}
+
+/// Serves `GET /metrics` in Prometheus text format
+#[derive(Clone)]
+pub(crate) struct MetricsLayer;
+
+impl MetricsLayer {
+    pub(crate) fn new() -> Self {
+        exporter();
+        Self
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService(inner)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct MetricsService<S>(S);
+
+impl<S, B, ResBody> Service<Request<B>> for MetricsService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    ResBody: From<String> + Send + 'static,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        if request.method() != Method::GET || request.uri().path() != METRICS_PATH {
+            return Box::pin(self.0.call(request));
+        }
+
+        let encoder = TextEncoder::new();
+        let mut body = Vec::new();
+        if let Err(error) = encoder.encode(&exporter().registry().gather(), &mut body) {
+            tracing::warn!(%error, "failed to encode metrics");
+        }
+
+        let mut response = Response::new(ResBody::from(String::from_utf8_lossy(&body).into_owned()));
+        if let Ok(content_type) = HeaderValue::from_str(encoder.format_type()) {
+            response.headers_mut().insert(CONTENT_TYPE, content_type);
+        }
+
+        Box::pin(async move { Ok(response) })
+    }
+}
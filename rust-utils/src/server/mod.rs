@@ -0,0 +1,654 @@
+use crate::wrappers::serde::DurationMs;
+use auth::{Auth, AuthLayer};
+use axum_tracing_opentelemetry::opentelemetry_tracing_layer;
+use gcloud_env::GCloudRunEnv;
+use http::{header::CONTENT_TYPE, HeaderValue, Method};
+use jsonrpsee::{
+    core::error::Error,
+    server::{
+        middleware::proxy_get_request::ProxyGetRequestLayer, AllowHosts, BatchRequestConfig,
+        ServerBuilder as RpcServerBuilder, ServerHandle,
+    },
+    Methods,
+};
+use lazy_static::lazy_static;
+use metrics::MetricsLayer;
+use rate_limit::RateLimitLayer;
+use router::RouterLayer;
+use serde::Deserialize;
+use serde_with::serde_as;
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{net::ToSocketAddrs, task::JoinHandle};
+use tower::ServiceBuilder;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    timeout::TimeoutLayer,
+};
+
+pub use crate::shutdown::signal as shutdown_signal;
+pub use auth::{AuthSettings, TokenValidator, UNAUTHORIZED_CODE};
+pub use metrics::MetricsLogger;
+pub use rate_limit::{RateLimitSettings, RATE_LIMITED_CODE};
+#[cfg(feature = "crypto")]
+pub use signature::{sign_request, AuthenticatedPubkey, PUBKEY_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+#[cfg(feature = "crypto-hmac")]
+pub use webhook::{WebhookSignatureLayer, WebhookSignatureService};
+
+mod auth;
+mod body;
+mod metrics;
+mod rate_limit;
+mod router;
+#[cfg(feature = "crypto")]
+mod signature;
+#[cfg(feature = "crypto-hmac")]
+mod webhook;
+
+lazy_static! {
+    pub static ref GCLOUD_ENV: Option<GCloudRunEnv> = GCloudRunEnv::from_env().ok();
+}
+
+/// Protocols accepted by the server, subscriptions require WebSocket
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    #[default]
+    Http,
+    Ws,
+    /// HTTP and WebSocket on the same port
+    Both,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ServerSettings {
+    #[serde(default = "ServerSettings::default_address")]
+    pub address: String,
+    #[serde(default)]
+    pub transport: Transport,
+    /// Maximum size of a request body in bytes
+    #[serde(default = "ServerSettings::default_max_body_size")]
+    pub max_request_size: u32,
+    /// Maximum size of a response body in bytes
+    #[serde(default = "ServerSettings::default_max_body_size")]
+    pub max_response_size: u32,
+    #[serde(default = "ServerSettings::default_max_connections")]
+    pub max_connections: u32,
+    /// Maximum number of calls in a batch, `0` disables batches, unlimited if not set
+    #[serde(default)]
+    pub batch_request_limit: Option<u32>,
+    /// Allowed CORS origins, any origin is allowed if empty
+    #[serde(default)]
+    pub cors_allowlist: Vec<String>,
+    /// HTTP requests taking longer are answered with `408 Request Timeout`
+    #[serde(rename = "request_timeout_ms", default = "ServerSettings::default_request_timeout")]
+    #[serde_as(as = "DurationMs")]
+    pub request_timeout: Duration,
+    /// Interval of WebSocket pings
+    #[serde(rename = "ping_interval_ms", default = "ServerSettings::default_ping_interval")]
+    #[serde_as(as = "DurationMs")]
+    pub ping_interval: Duration,
+    /// Static API keys, bearer tokens are validated by `Builder::with_token_validator`
+    #[serde(default)]
+    pub auth: Option<AuthSettings>,
+    /// Serve the JSON-RPC and rate limit metrics on `GET /metrics` in Prometheus format
+    #[serde(default)]
+    pub metrics: bool,
+    /// Requests over the limits are answered with `429 Too Many Requests`
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitSettings>,
+}
+
+impl ServerSettings {
+    fn default_address() -> String {
+        Server::default_bind_address(None)
+    }
+
+    fn default_max_body_size() -> u32 {
+        10 * 1024 * 1024
+    }
+
+    fn default_max_connections() -> u32 {
+        100
+    }
+
+    fn default_request_timeout() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_ping_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            address: Self::default_address(),
+            transport: Transport::default(),
+            max_request_size: Self::default_max_body_size(),
+            max_response_size: Self::default_max_body_size(),
+            max_connections: Self::default_max_connections(),
+            batch_request_limit: None,
+            cors_allowlist: vec![],
+            request_timeout: Self::default_request_timeout(),
+            ping_interval: Self::default_ping_interval(),
+            auth: None,
+            metrics: false,
+            rate_limit: None,
+        }
+    }
+}
+
+/// Builder of `Server`, created by `Server::builder`
+#[derive(Clone, Default)]
+pub struct Builder {
+    settings: ServerSettings,
+    validator: Option<Arc<dyn TokenValidator>>,
+    #[cfg(feature = "crypto")]
+    signature: Option<signature::SignatureLayer>,
+    router: RouterLayer,
+    /// Set by the `Server` constructors, they keep serving without `ServerSettings::request_timeout`
+    without_timeout: bool,
+}
+
+impl Builder {
+    pub fn with_settings(mut self, settings: ServerSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn with_address(mut self, address: impl Into<String>) -> Self {
+        self.settings.address = address.into();
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.settings.transport = transport;
+        self
+    }
+
+    /// Require `Authorization: Bearer <token>` accepted by `validator`, or an API key from `ServerSettings::auth`
+    pub fn with_token_validator(mut self, validator: impl TokenValidator) -> Self {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Require requests to be signed, see `sign_request`. The pubkey of the signer is available to the inner
+    /// layers as `AuthenticatedPubkey` request extension
+    #[cfg(feature = "crypto")]
+    pub fn with_signature_check(mut self, checker: impl crate::crypto::CheckSignature + Send + Sync + 'static) -> Self {
+        self.signature = Some(signature::SignatureLayer::new(checker));
+        self
+    }
+
+    /// Serve the routes of `router` on the same listener, other requests are handled by JSON-RPC.
+    /// The routes bypass the server middleware (auth, rate limits, timeouts), add the layers to `router` if needed
+    pub fn with_router(mut self, router: axum::Router) -> Self {
+        self.router = RouterLayer(router);
+        self
+    }
+
+    /// Bind the address and start serving `service`
+    pub async fn start(self, service: impl Into<Methods>) -> Result<Server, Error> {
+        let address = self.settings.address.clone();
+        self.start_at(address, service).await
+    }
+
+    /// Same as `start`, but binds `address` instead of `ServerSettings::address`
+    pub async fn start_at(self, address: impl ToSocketAddrs, service: impl Into<Methods>) -> Result<Server, Error> {
+        let Builder {
+            settings,
+            validator,
+            #[cfg(feature = "crypto")]
+            signature,
+            router,
+            without_timeout,
+        } = self;
+        let service = service.into();
+
+        #[cfg(feature = "crypto")]
+        let signature = signature.map(|layer| layer.with_max_body_size(settings.max_request_size));
+        #[cfg(not(feature = "crypto"))]
+        let signature: Option<tower::layer::util::Identity> = None;
+
+        let auth = Auth::new(settings.auth.as_ref(), validator)
+            .map_err(|error| Error::Custom(format!("invalid API key header: {error}")))?;
+
+        let rate_limit = settings
+            .rate_limit
+            .map(|limits| RateLimitLayer::new(limits, settings.max_request_size))
+            .transpose()
+            .map_err(|error| Error::Custom(format!("invalid rate limit: {error}")))?;
+
+        let cors = if settings.cors_allowlist.is_empty() {
+            CorsLayer::permissive()
+        } else {
+            let origins = settings
+                .cors_allowlist
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| Error::Custom(format!("invalid CORS origin: {error}")))?;
+
+            CorsLayer::new()
+                .allow_origin(AllowOrigin::list(origins))
+                .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+                .allow_headers([CONTENT_TYPE])
+        };
+
+        let middleware = ServiceBuilder::default()
+            .layer(router)
+            .layer(opentelemetry_tracing_layer())
+            .layer(cors)
+            .option_layer(settings.metrics.then(MetricsLayer::new))
+            .option_layer((!without_timeout).then(|| TimeoutLayer::new(settings.request_timeout)))
+            .option_layer(rate_limit)
+            .option_layer(auth.map(AuthLayer))
+            .option_layer(signature)
+            .option_layer(
+                service
+                    .method("system_liveness")
+                    .map(|_| ProxyGetRequestLayer::new("/liveness", "system_liveness").unwrap()),
+            )
+            .option_layer(
+                service
+                    .method("system_readiness")
+                    .map(|_| ProxyGetRequestLayer::new("/readiness", "system_readiness").unwrap()),
+            )
+            .option_layer(
+                service
+                    .method("version")
+                    .map(|_| ProxyGetRequestLayer::new("/version", "version").unwrap()),
+            );
+
+        let batch_requests = match settings.batch_request_limit {
+            None => BatchRequestConfig::Unlimited,
+            Some(0) => BatchRequestConfig::Disabled,
+            Some(limit) => BatchRequestConfig::Limit(limit),
+        };
+
+        let builder = RpcServerBuilder::default()
+            .set_host_filtering(AllowHosts::Any)
+            .max_request_body_size(settings.max_request_size)
+            .max_response_body_size(settings.max_response_size)
+            .max_connections(settings.max_connections)
+            .set_batch_request_config(batch_requests)
+            .ping_interval(settings.ping_interval)
+            .set_logger(MetricsLogger::default())
+            .set_middleware(middleware);
+        let builder = match settings.transport {
+            Transport::Http => builder.http_only(),
+            Transport::Ws => builder.ws_only(),
+            Transport::Both => builder,
+        };
+        let server = builder.build(address).await?;
+
+        Ok(Server {
+            address: server.local_addr()?,
+            handle: server.start(service)?,
+        })
+    }
+}
+
+pub struct Server {
+    address: SocketAddr,
+    handle: ServerHandle,
+}
+
+impl Server {
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    pub fn default_bind_address(port: Option<&str>) -> String {
+        if let Some(gcloud) = &*GCLOUD_ENV {
+            format!("0.0.0.0:{}", gcloud.port)
+        } else {
+            format!("127.0.0.1:{}", port.unwrap_or("9999"))
+        }
+    }
+
+    pub fn from_handle(address: SocketAddr, handle: ServerHandle) -> Self {
+        Self { address, handle }
+    }
+
+    /// The constructors serve without request timeout, the other limits are the defaults of `jsonrpsee`.
+    /// Use `Server::builder` to configure them
+    fn constructor() -> Builder {
+        Builder {
+            without_timeout: true,
+            ..Default::default()
+        }
+    }
+
+    pub async fn with_address(address: impl ToSocketAddrs, service: impl Into<Methods>) -> Result<Self, Error> {
+        Self::constructor().start_at(address, service).await
+    }
+
+    /// Open WebSocket sessions are closed by `stop`
+    pub async fn with_transport(
+        address: impl ToSocketAddrs,
+        service: impl Into<Methods>,
+        transport: Transport,
+    ) -> Result<Self, Error> {
+        Self::constructor()
+            .with_transport(transport)
+            .start_at(address, service)
+            .await
+    }
+
+    /// Serve REST routes of `router` alongside JSON-RPC `service`, see `Builder::with_router`
+    pub async fn with_router(
+        address: impl ToSocketAddrs,
+        service: impl Into<Methods>,
+        router: axum::Router,
+    ) -> Result<Self, Error> {
+        Self::constructor().with_router(router).start_at(address, service).await
+    }
+
+    pub async fn stop(self) -> Result<(), Error> {
+        self.handle.stop()?;
+        self.handle.stopped().await;
+        Ok(())
+    }
+
+    pub async fn with_graceful_shutdown<F>(self, signal: F)
+    where
+        F: Future<Output = ()>,
+    {
+        signal.await;
+        match self.stop().await {
+            Ok(_) => {
+                tracing::info!("server stopped successfully");
+            },
+            Err(error) => {
+                tracing::warn!("failed to stop the server: {error}");
+            },
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.handle.stopped())
+    }
+
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::{
+        core::client::{ClientT, SubscriptionClientT},
+        http_client::HttpClientBuilder,
+        rpc_params,
+        ws_client::WsClientBuilder,
+        RpcModule, SubscriptionMessage,
+    };
+
+    use super::*;
+
+    fn module() -> RpcModule<()> {
+        let mut module = RpcModule::new(());
+        module
+            .register_method("version", |_, _| Ok::<_, jsonrpsee::types::ErrorObjectOwned>("1.0"))
+            .unwrap();
+        module
+            .register_subscription(
+                "subscribe_count",
+                "count",
+                "unsubscribe_count",
+                |_, pending, _| async move {
+                    let sink = pending.accept().await?;
+                    for i in 0..3 {
+                        sink.send(SubscriptionMessage::from_json(&i)?).await?;
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+        module
+    }
+
+    #[test]
+    fn deserialize_settings() {
+        let settings: ServerSettings = serde_json::from_str(
+            r#"{"address": "0.0.0.0:8080", "transport": "both", "batch_request_limit": 0, "request_timeout_ms": 500}"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings, ServerSettings {
+            address: "0.0.0.0:8080".into(),
+            transport: Transport::Both,
+            batch_request_limit: Some(0),
+            request_timeout: Duration::from_millis(500),
+            ..Default::default()
+        });
+    }
+
+    #[tokio::test]
+    async fn request_timeout() {
+        let mut module = module();
+        module
+            .register_async_method("slow", |_, _| async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok::<_, jsonrpsee::types::ErrorObjectOwned>("done")
+            })
+            .unwrap();
+        let settings = ServerSettings {
+            address: "127.0.0.1:0".into(),
+            request_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+
+        let server = Server::builder()
+            .with_settings(settings.clone())
+            .start(module.clone())
+            .await
+            .unwrap();
+        let http = HttpClientBuilder::default()
+            .build(format!("http://{}", server.address()))
+            .unwrap();
+        let result: Result<String, _> = http.request("slow", rpc_params![]).await;
+        assert!(result.is_err());
+        server.stop().await.unwrap();
+
+        // the constructors keep serving without timeout
+        let server = Builder {
+            settings,
+            ..Server::constructor()
+        }
+        .start(module)
+        .await
+        .unwrap();
+        let http = HttpClientBuilder::default()
+            .build(format!("http://{}", server.address()))
+            .unwrap();
+        let result: String = http.request("slow", rpc_params![]).await.unwrap();
+        assert_eq!(result, "done");
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_cors_origin() {
+        let result = Server::builder()
+            .with_settings(ServerSettings {
+                address: "127.0.0.1:0".into(),
+                cors_allowlist: vec!["https://example.com\n".into()],
+                ..Default::default()
+            })
+            .start(module())
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn authentication() {
+        let server = Server::builder()
+            .with_settings(ServerSettings {
+                address: "127.0.0.1:0".into(),
+                auth: Some(AuthSettings {
+                    header: "x-api-key".into(),
+                    api_keys: vec!["key".into()],
+                }),
+                ..Default::default()
+            })
+            .with_token_validator(std::collections::HashSet::from(["token".to_owned()]))
+            .start(module())
+            .await
+            .unwrap();
+        let url = format!("http://{}", server.address());
+
+        let call = |headers: http::HeaderMap| HttpClientBuilder::default().set_headers(headers).build(&url).unwrap();
+        let header = |name: &'static str, value: &'static str| {
+            http::HeaderMap::from_iter([(http::HeaderName::from_static(name), HeaderValue::from_static(value))])
+        };
+
+        let result: Result<String, _> = call(Default::default()).request("version", rpc_params![]).await;
+        assert!(result.is_err());
+        let result: Result<String, _> = call(header("x-api-key", "wrong"))
+            .request("version", rpc_params![])
+            .await;
+        assert!(result.is_err());
+
+        let version: String = call(header("x-api-key", "key"))
+            .request("version", rpc_params![])
+            .await
+            .unwrap();
+        assert_eq!(version, "1.0");
+        let version: String = call(header("authorization", "Bearer token"))
+            .request("version", rpc_params![])
+            .await
+            .unwrap();
+        assert_eq!(version, "1.0");
+
+        // probes get the version without credentials, JSON-RPC calls on its path are authenticated
+        let address = *server.address();
+        let request = |request: String| async move {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            tokio::io::AsyncWriteExt::write_all(&mut stream, request.as_bytes())
+                .await
+                .unwrap();
+            let mut response = String::new();
+            tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+                .await
+                .unwrap();
+            response
+        };
+        let response = request("GET /version HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n".into()).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"version","params":[]}"#;
+        let response = request(format!(
+            "POST /version HTTP/1.1\r\nhost: localhost\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: \
+             close\r\n\r\n{body}",
+            body.len()
+        ))
+        .await;
+        assert!(response.starts_with("HTTP/1.1 401"), "{response}");
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn metrics() {
+        // Every server of the process records into the same exporter, so the method is unique to this test
+        let mut module = module();
+        module
+            .register_method("metrics_probe", |_, _| {
+                Ok::<_, jsonrpsee::types::ErrorObjectOwned>("ok")
+            })
+            .unwrap();
+        let server = Server::builder()
+            .with_settings(ServerSettings {
+                address: "127.0.0.1:0".into(),
+                metrics: true,
+                ..Default::default()
+            })
+            .start(module)
+            .await
+            .unwrap();
+        let address = *server.address();
+
+        let http = HttpClientBuilder::default().build(format!("http://{address}")).unwrap();
+        let _: String = http.request("metrics_probe", rpc_params![]).await.unwrap();
+
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(
+            &mut stream,
+            b"GET /metrics HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let mut response = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+            .await
+            .unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        let calls = response
+            .lines()
+            .find(|line| line.starts_with(r#"rpc_server_calls{method="metrics_probe""#))
+            .unwrap();
+        assert!(calls.contains(r#"success="true""#));
+        assert!(calls.ends_with(" 1"));
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rest_routes() {
+        let router = axum::Router::new().route("/healthz", axum::routing::get(|| async { "healthy" }));
+        let server = Server::with_router("127.0.0.1:0", module(), router).await.unwrap();
+        let address = *server.address();
+
+        let http = HttpClientBuilder::default().build(format!("http://{address}")).unwrap();
+        let version: String = http.request("version", rpc_params![]).await.unwrap();
+        assert_eq!(version, "1.0");
+
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(
+            &mut stream,
+            b"GET /healthz HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+        let mut response = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+            .await
+            .unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("healthy"));
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn http_and_ws() {
+        let server = Server::with_transport("127.0.0.1:0", module(), Transport::Both)
+            .await
+            .unwrap();
+        let address = *server.address();
+
+        let http = HttpClientBuilder::default().build(format!("http://{address}")).unwrap();
+        let version: String = http.request("version", rpc_params![]).await.unwrap();
+        assert_eq!(version, "1.0");
+
+        let ws = WsClientBuilder::default()
+            .build(format!("ws://{address}"))
+            .await
+            .unwrap();
+        let mut subscription = ws
+            .subscribe::<u32, _>("subscribe_count", rpc_params![], "unsubscribe_count")
+            .await
+            .unwrap();
+        assert_eq!(subscription.next().await.unwrap().unwrap(), 0);
+
+        server.stop().await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), ws.on_disconnect())
+            .await
+            .expect("websocket session must be closed");
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use http::{HeaderMap, Method, Request, Response, StatusCode};
+use hyper::Body;
+use opentelemetry::{metrics::Counter, KeyValue};
+use serde::Deserialize;
+use tower::{Layer, Service};
+
+use super::{
+    auth::is_public,
+    body::{error_response, read_body},
+    metrics::meter,
+};
+
+/// JSON-RPC error code of rate limited requests, mirrors HTTP `429 Too Many Requests`
+pub const RATE_LIMITED_CODE: i32 = -32029;
+
+/// New clients share the bucket of unknown clients once there are this many keys, until idle ones are swept
+const MAX_KEYS: usize = 10_000;
+/// Buckets of idle clients are dropped this often
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+/// Key of the clients without a known address
+const SHARED_KEY: &str = "";
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct RateLimitSettings {
+    /// Requests per second of a client. WebSocket calls are only limited on connect
+    #[serde(default)]
+    pub per_ip: Option<f64>,
+    /// Calls per second of a method over all clients, calls of a batch are counted separately
+    #[serde(default)]
+    pub per_method: HashMap<String, f64>,
+    /// Proxies in front of the server appending to `X-Forwarded-For`, the client is the address appended by the
+    /// outermost one since the addresses before it are set by the client. The peer address isn't available to the
+    /// middleware, so requests without the address share one bucket
+    #[serde(default = "RateLimitSettings::default_trusted_proxies")]
+    pub trusted_proxies: usize,
+}
+
+impl RateLimitSettings {
+    fn default_trusted_proxies() -> usize {
+        1
+    }
+
+    /// Rates must be positive, a bucket of a zero or negative rate rejects every request after the first one
+    fn validate(&self) -> Result<(), String> {
+        let is_valid = |rate: f64| rate.is_finite() && rate > 0.0;
+
+        if let Some(rate) = self.per_ip.filter(|rate| !is_valid(*rate)) {
+            return Err(format!("per_ip rate {rate} must be positive"));
+        }
+        if let Some((method, rate)) = self.per_method.iter().find(|(_, rate)| !is_valid(**rate)) {
+            return Err(format!("per_method rate {rate} of {method} must be positive"));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            per_ip: None,
+            per_method: HashMap::new(),
+            trusted_proxies: Self::default_trusted_proxies(),
+        }
+    }
+}
+
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn capacity(&self) -> f64 {
+        self.rate.max(1.0)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity());
+        self.updated_at = now;
+    }
+}
+
+/// Token buckets by key, bursts up to one second of `rate` are allowed
+#[derive(Default)]
+struct Buckets(Mutex<HashMap<String, Bucket>>);
+
+impl Buckets {
+    fn try_acquire(&self, key: &str, rate: f64, now: Instant) -> bool {
+        let mut buckets = self.0.lock().unwrap_or_else(|error| error.into_inner());
+
+        let key = if buckets.len() >= MAX_KEYS && !buckets.contains_key(key) {
+            SHARED_KEY
+        } else {
+            key
+        };
+        let bucket = buckets.entry(key.to_owned()).or_insert_with(|| Bucket {
+            rate,
+            tokens: rate.max(1.0),
+            updated_at: now,
+        });
+        bucket.refill(now);
+
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Drops the buckets refilled up to the capacity
+    fn sweep(&self, now: Instant) {
+        let mut buckets = self.0.lock().unwrap_or_else(|error| error.into_inner());
+        buckets.retain(|_, bucket| {
+            bucket.refill(now);
+            bucket.tokens < bucket.capacity()
+        });
+    }
+}
+
+#[derive(Deserialize)]
+struct Call {
+    method: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Calls {
+    Single(Call),
+    Batch(Vec<Call>),
+}
+
+/// Client address appended to `X-Forwarded-For` by the outermost of the trusted proxies
+fn client_ip(headers: &HeaderMap, trusted_proxies: usize) -> Option<&str> {
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+
+    let index = forwarded.len().checked_sub(trusted_proxies)?;
+    forwarded.get(index).copied().filter(|ip| !ip.is_empty())
+}
+
+struct Limits {
+    settings: RateLimitSettings,
+    by_ip: Buckets,
+    by_method: Buckets,
+    max_body_size: usize,
+    rejected: Counter<u64>,
+}
+
+/// Answers requests over the limits of `RateLimitSettings` with `429` and a JSON-RPC error,
+/// rejections are counted as `rpc.server.rate_limited`
+#[derive(Clone)]
+pub(crate) struct RateLimitLayer(Arc<Limits>);
+
+impl RateLimitLayer {
+    /// Spawns the task sweeping idle clients until the layer is dropped. Fails if a rate isn't positive
+    pub(crate) fn new(settings: RateLimitSettings, max_body_size: u32) -> Result<Self, String> {
+        settings.validate()?;
+
+        let limits = Arc::new(Limits {
+            settings,
+            by_ip: Buckets::default(),
+            by_method: Buckets::default(),
+            max_body_size: max_body_size as usize,
+            rejected: meter()
+                .u64_counter("rpc.server.rate_limited")
+                .with_description("Number of requests rejected by rate limits")
+                .init(),
+        });
+
+        let sweep = Arc::downgrade(&limits);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(limits) = sweep.upgrade() else { break };
+                limits.by_ip.sweep(Instant::now());
+            }
+        });
+
+        Ok(Self(limits))
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            limits: self.0.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RateLimitService<S> {
+    limits: Arc<Limits>,
+    inner: S,
+}
+
+impl Limits {
+    fn reject(&self, limit: &'static str, method: Option<&str>) -> Response<Body> {
+        let cx = opentelemetry::Context::current();
+        let mut attributes = vec![KeyValue::new("limit", limit)];
+        if let Some(method) = method {
+            attributes.push(KeyValue::new("method", method.to_owned()));
+        }
+        self.rejected.add(&cx, 1, &attributes);
+
+        error_response(StatusCode::TOO_MANY_REQUESTS, RATE_LIMITED_CODE, "Too many requests")
+    }
+
+    /// Method of the first call over its limit
+    fn limited_method(&self, body: &[u8], now: Instant) -> Option<String> {
+        let calls = match serde_json::from_slice(body).ok()? {
+            Calls::Single(call) => vec![call],
+            Calls::Batch(calls) => calls,
+        };
+
+        calls.into_iter().map(|call| call.method).find(|method| {
+            self.settings
+                .per_method
+                .get(method)
+                .is_some_and(|rate| !self.by_method.try_acquire(method, *rate, now))
+        })
+    }
+}
+
+impl<S> Service<Request<Body>> for RateLimitService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limits = self.limits.clone();
+
+        Box::pin(async move {
+            if is_public(&request) {
+                return inner.call(request).await;
+            }
+
+            let now = Instant::now();
+            if let Some(rate) = limits.settings.per_ip {
+                let ip = client_ip(request.headers(), limits.settings.trusted_proxies).unwrap_or(SHARED_KEY);
+                if !limits.by_ip.try_acquire(ip, rate, now) {
+                    return Ok(limits.reject("ip", None));
+                }
+            }
+
+            if limits.settings.per_method.is_empty() || request.method() != Method::POST {
+                return inner.call(request).await;
+            }
+
+            // methods are only known from the body, it is read and passed on as is
+            let (parts, mut body) = request.into_parts();
+            let bytes = match read_body(&mut body, limits.max_body_size).await {
+                Ok(bytes) => bytes,
+                Err(response) => return Ok(response),
+            };
+
+            if let Some(method) = limits.limited_method(&bytes, now) {
+                return Ok(limits.reject("method", Some(&method)));
+            }
+
+            inner.call(Request::from_parts(parts, Body::from(bytes))).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    #[test]
+    fn refill_tokens() {
+        let buckets = Buckets::default();
+        let now = Instant::now();
+
+        assert!(buckets.try_acquire("a", 2.0, now));
+        assert!(buckets.try_acquire("a", 2.0, now));
+        assert!(!buckets.try_acquire("a", 2.0, now));
+        assert!(buckets.try_acquire("b", 2.0, now));
+
+        assert!(buckets.try_acquire("a", 2.0, now + Duration::from_millis(500)));
+        assert!(!buckets.try_acquire("a", 2.0, now + Duration::from_millis(500)));
+
+        buckets.sweep(now + Duration::from_secs(2));
+        assert!(buckets.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn forwarded_client_ip() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_ip(&headers, 1), None);
+
+        headers.append("x-forwarded-for", "6.6.6.6, 1.1.1.1".parse().unwrap());
+        headers.append("x-forwarded-for", "10.0.0.1".parse().unwrap());
+        assert_eq!(client_ip(&headers, 1), Some("10.0.0.1"));
+        assert_eq!(client_ip(&headers, 2), Some("1.1.1.1"));
+        assert_eq!(client_ip(&headers, 4), None);
+        assert_eq!(client_ip(&headers, 0), None);
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_rates() {
+        let settings = |per_ip, per_method| RateLimitSettings {
+            per_ip,
+            per_method: HashMap::from([("send".to_owned(), per_method)]),
+            ..Default::default()
+        };
+
+        assert!(RateLimitLayer::new(settings(Some(0.5), 1.0), 1024).is_ok());
+        for (per_ip, per_method) in [(Some(0.0), 1.0), (Some(-1.0), 1.0), (Some(f64::NAN), 1.0), (None, 0.0)] {
+            assert!(RateLimitLayer::new(settings(per_ip, per_method), 1024).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_over_limits() {
+        let layer = RateLimitLayer::new(
+            RateLimitSettings {
+                per_ip: Some(2.0),
+                per_method: HashMap::from([("send".to_owned(), 1.0)]),
+                trusted_proxies: 1,
+            },
+            1024,
+        )
+        .unwrap();
+        let service = layer.layer(service_fn(|request: Request<Body>| async move {
+            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+            Ok::<_, Infallible>(Response::new(Body::from(body)))
+        }));
+        let call = |ip: &'static str, body: &'static str| {
+            let request = Request::post("/")
+                .header("x-forwarded-for", format!("6.6.6.6, {ip}"))
+                .body(Body::from(body))
+                .unwrap();
+            service.clone().oneshot(request)
+        };
+        let send = r#"{"jsonrpc":"2.0","id":1,"method":"send"}"#;
+        let version = r#"[{"jsonrpc":"2.0","id":1,"method":"version"}]"#;
+
+        let response = call("1.1.1.1", send).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hyper::body::to_bytes(response.into_body()).await.unwrap(), send);
+
+        let response = call("1.1.1.1", send).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(call("2.2.2.2", version).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(
+            call("1.1.1.1", version).await.unwrap().status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // clients without the address are limited together
+        for status in [StatusCode::OK, StatusCode::OK, StatusCode::TOO_MANY_REQUESTS] {
+            let request = Request::post("/").body(Body::from(version)).unwrap();
+            assert_eq!(service.clone().oneshot(request).await.unwrap().status(), status);
+        }
+    }
+}
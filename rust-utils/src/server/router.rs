@@ -0,0 +1,52 @@
+use std::{
+    convert::Infallible,
+    task::{Context, Poll},
+};
+
+use axum::{error_handling::HandleError, response::IntoResponse, routing::future::RouteFuture, Router};
+use futures::{future::MapErr, TryFutureExt};
+use http::{Request, StatusCode};
+use hyper::Body;
+use tower::{BoxError, Layer, Service};
+
+/// Serves the routes of `Router`, other requests fall back to JSON-RPC
+#[derive(Clone, Default)]
+pub(crate) struct RouterLayer(pub(crate) Router);
+
+impl<S> Layer<S> for RouterLayer
+where
+    S: Service<Request<Body>> + Clone + Send + 'static,
+    S::Response: IntoResponse + Send,
+    S::Error: std::fmt::Display + Send,
+    S::Future: Send,
+{
+    type Service = RouterService;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let fallback = HandleError::new(inner, |error: S::Error| async move {
+            tracing::warn!(%error, "failed to serve request");
+            StatusCode::INTERNAL_SERVER_ERROR
+        });
+
+        RouterService(self.0.clone().fallback_service(fallback))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RouterService(Router);
+
+impl Service<Request<Body>> for RouterService {
+    type Error = BoxError;
+    type Future = MapErr<RouteFuture<Body, Infallible>, fn(Infallible) -> BoxError>;
+    type Response = axum::response::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Service::<Request<Body>>::poll_ready(&mut self.0, cx).map_err(BoxError::from)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        self.0
+            .call(request)
+            .map_err(BoxError::from as fn(Infallible) -> BoxError)
+    }
+}
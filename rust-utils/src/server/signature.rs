@@ -0,0 +1,199 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use ed25519_dalek::Keypair;
+use http::{HeaderMap, Request, Response, StatusCode};
+use hyper::Body;
+use tower::{Layer, Service};
+
+use super::{
+    auth::{is_public, UNAUTHORIZED_CODE},
+    body::{error_response, read_body},
+};
+use crate::crypto::{self, CheckSignature, KeypairExt, TimedSignature};
+
+/// Base58 public key of the signer
+pub const PUBKEY_HEADER: &str = "x-pubkey";
+/// Hex signature of `borsh((timestamp, body))`
+pub const SIGNATURE_HEADER: &str = "x-signature";
+/// Unix timestamp in seconds, checked against `GetSignatureTtl::get_signature_ttl`
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+
+/// Public key of the verified signer, inserted into request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedPubkey(pub String);
+
+/// Sign request `body` the way `Builder::with_signature_check` verifies it
+pub fn sign_request(keypair: &Keypair, timestamp: u64, body: &[u8]) -> String {
+    keypair.sign_borsh(&(timestamp, body)).to_string()
+}
+
+type Verify = dyn Fn(&str, u64, &[u8], &str) -> Result<(), crypto::Error> + Send + Sync;
+
+#[derive(Clone)]
+pub(crate) struct SignatureLayer {
+    verify: Arc<Verify>,
+    max_body_size: usize,
+}
+
+impl SignatureLayer {
+    pub(crate) fn new(checker: impl CheckSignature + Send + Sync + 'static) -> Self {
+        Self {
+            verify: Arc::new(move |pubkey, timestamp, body, signature| {
+                checker.check_signature(pubkey, &(timestamp, body), &TimedSignature::new(timestamp, signature))
+            }),
+            max_body_size: usize::MAX,
+        }
+    }
+
+    pub(crate) fn with_max_body_size(mut self, max_body_size: u32) -> Self {
+        self.max_body_size = max_body_size as usize;
+        self
+    }
+}
+
+impl<S> Layer<S> for SignatureLayer {
+    type Service = SignatureService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SignatureService {
+            layer: self.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SignatureService<S> {
+    layer: SignatureLayer,
+    inner: S,
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+impl<S> Service<Request<Body>> for SignatureService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            if is_public(&request) {
+                return inner.call(request).await;
+            }
+
+            let (mut parts, mut body) = request.into_parts();
+
+            let credentials = (
+                header(&parts.headers, PUBKEY_HEADER),
+                header(&parts.headers, SIGNATURE_HEADER),
+                header(&parts.headers, TIMESTAMP_HEADER).and_then(|timestamp| timestamp.parse::<u64>().ok()),
+            );
+            let (Some(pubkey), Some(signature), Some(timestamp)) = credentials else {
+                return Ok(error_response(
+                    StatusCode::UNAUTHORIZED,
+                    UNAUTHORIZED_CODE,
+                    "Missing signature headers",
+                ));
+            };
+
+            // the body is signed, so it has to be read before the call is dispatched
+            let bytes = match read_body(&mut body, layer.max_body_size).await {
+                Ok(bytes) => bytes,
+                Err(response) => return Ok(response),
+            };
+
+            if let Err(error) = (layer.verify)(pubkey, timestamp, &bytes, signature) {
+                return Ok(error_response(
+                    StatusCode::UNAUTHORIZED,
+                    UNAUTHORIZED_CODE,
+                    &error.to_string(),
+                ));
+            }
+
+            let pubkey = AuthenticatedPubkey(pubkey.to_owned());
+            parts.extensions.insert(pubkey);
+
+            inner.call(Request::from_parts(parts, Body::from(bytes))).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+    use crate::crypto::{GetSignatureTtl, PublicKeyExt};
+
+    struct Checker;
+
+    impl GetSignatureTtl for Checker {
+        fn get_signature_ttl(&self) -> Option<u64> {
+            None
+        }
+    }
+
+    impl CheckSignature for Checker {}
+
+    fn service() -> impl Service<Request<Body>, Response = Response<Body>, Error = Infallible> {
+        SignatureLayer::new(Checker)
+            .with_max_body_size(1024)
+            .layer(service_fn(|request: Request<Body>| async move {
+                let pubkey = request.extensions().get::<AuthenticatedPubkey>().unwrap().0.clone();
+                Ok::<_, Infallible>(Response::new(Body::from(pubkey)))
+            }))
+    }
+
+    fn request(keypair: &Keypair, body: &'static str, signed_body: &[u8]) -> Request<Body> {
+        Request::post("/")
+            .header(PUBKEY_HEADER, keypair.public.to_base58())
+            .header(SIGNATURE_HEADER, sign_request(keypair, 1, signed_body))
+            .header(TIMESTAMP_HEADER, "1")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn verify_signature() {
+        let keypair = Keypair::new_rand();
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"version"}"#;
+
+        let response = service()
+            .oneshot(request(&keypair, body, body.as_bytes()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let pubkey = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(pubkey, keypair.public.to_base58().as_bytes());
+
+        let response = service().oneshot(request(&keypair, body, b"other")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = service()
+            .oneshot(Request::post("/").body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
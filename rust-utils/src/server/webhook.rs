@@ -0,0 +1,166 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{Request, Response, StatusCode};
+use hyper::Body;
+use tower::{Layer, Service};
+
+use super::{
+    auth::UNAUTHORIZED_CODE,
+    body::{error_response, read_body},
+    ServerSettings,
+};
+use crate::crypto::hmac;
+
+/// Rejects requests without a valid hex HMAC-SHA256 signature of the body in the header, e.g. callbacks of
+/// providers sharing a secret. Add it to the routes of `Builder::with_router` receiving the webhooks
+///
+/// # Usage
+/// ```ignore
+/// let webhooks = axum::Router::new()
+///     .route("/webhooks/payments", post(payment_callback))
+///     .layer(WebhookSignatureLayer::new(settings.webhook_secret.expose_secret(), "x-signature").with_prefix("sha256="));
+/// ```
+#[derive(Clone)]
+pub struct WebhookSignatureLayer {
+    secret: Arc<[u8]>,
+    header: Arc<str>,
+    prefix: Arc<str>,
+    max_body_size: usize,
+}
+
+impl WebhookSignatureLayer {
+    pub fn new(secret: impl AsRef<[u8]>, header: &str) -> Self {
+        Self {
+            secret: secret.as_ref().into(),
+            header: header.into(),
+            prefix: "".into(),
+            max_body_size: ServerSettings::default_max_body_size() as usize,
+        }
+    }
+
+    /// Prefix of the signature in the header, e.g. `sha256=`
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn with_max_body_size(mut self, max_body_size: u32) -> Self {
+        self.max_body_size = max_body_size as usize;
+        self
+    }
+}
+
+impl<S> Layer<S> for WebhookSignatureLayer {
+    type Service = WebhookSignatureService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        WebhookSignatureService {
+            layer: self.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WebhookSignatureService<S> {
+    layer: WebhookSignatureLayer,
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for WebhookSignatureService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Response = S::Response;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let (parts, mut body) = request.into_parts();
+
+            let signature = parts
+                .headers
+                .get(&*layer.header)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix(&*layer.prefix))
+                .map(str::to_owned);
+            let Some(signature) = signature else {
+                return Ok(error_response(
+                    StatusCode::UNAUTHORIZED,
+                    UNAUTHORIZED_CODE,
+                    "Missing webhook signature",
+                ));
+            };
+
+            let bytes = match read_body(&mut body, layer.max_body_size).await {
+                Ok(bytes) => bytes,
+                Err(response) => return Ok(response),
+            };
+
+            if !hmac::verify_hex(&layer.secret, &bytes, &signature) {
+                return Ok(error_response(
+                    StatusCode::UNAUTHORIZED,
+                    UNAUTHORIZED_CODE,
+                    "Invalid webhook signature",
+                ));
+            }
+
+            inner.call(Request::from_parts(parts, Body::from(bytes))).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tower::{service_fn, ServiceExt};
+
+    use super::*;
+
+    fn request(signature: &str) -> Request<Body> {
+        Request::post("/webhooks")
+            .header("x-signature", signature)
+            .body(Body::from(r#"{"event":"paid"}"#))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn verify_webhook_signature() {
+        let service = WebhookSignatureLayer::new("secret", "x-signature")
+            .with_prefix("sha256=")
+            .layer(service_fn(|request: Request<Body>| async move {
+                Ok::<_, Infallible>(Response::new(request.into_body()))
+            }));
+        let signature = hmac::sign_hex(b"secret", br#"{"event":"paid"}"#);
+
+        let response = service
+            .clone()
+            .oneshot(request(&format!("sha256={signature}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"event":"paid"}"#.as_bytes());
+
+        for signature in [signature.as_str(), "sha256=00"] {
+            let response = service.clone().oneshot(request(signature)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use config::{ConfigError, Map, Source, Value, ValueKind};
+
+/// Case of settings keys read from environment variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyCase {
+    /// `APP__DB_SETTINGS__URL` sets `db_settings.url`, names of variables are case-insensitive
+    #[default]
+    Lower,
+    /// `APP__retryOn` sets `retryOn`, for keys renamed to other cases
+    Preserve,
+}
+
+/// Mapping of environment variables to settings keys: variables are `<prefix><prefix separator><key>`,
+/// keys of nested structs are joined by the separator. Values of list keys are split into lists
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvMapping {
+    prefix: String,
+    prefix_separator: Option<String>,
+    separator: String,
+    key_case: KeyCase,
+    list_separator: String,
+    list_keys: Vec<String>,
+}
+
+impl EnvMapping {
+    /// `__` separated keys of variables with the prefix, lists are comma-separated
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            prefix_separator: None,
+            separator: "__".to_owned(),
+            key_case: KeyCase::default(),
+            list_separator: ",".to_owned(),
+            list_keys: Vec::new(),
+        }
+    }
+
+    pub fn with_separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Separator after the prefix, `separator` if not set
+    pub fn with_prefix_separator(mut self, separator: impl Into<String>) -> Self {
+        self.prefix_separator = Some(separator.into());
+        self
+    }
+
+    pub fn with_key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    pub fn with_list_separator(mut self, separator: impl Into<String>) -> Self {
+        self.list_separator = separator.into();
+        self
+    }
+
+    /// Key of a list, e.g. `http_client.retry_on`, its value is split by the list separator
+    pub fn with_list_key(mut self, key: impl Into<String>) -> Self {
+        self.list_keys.push(key.into());
+        self
+    }
+
+    /// Settings key of the variable if it has the prefix
+    fn key(&self, variable: &str) -> Option<String> {
+        let prefix = format!(
+            "{}{}",
+            self.prefix,
+            self.prefix_separator.as_deref().unwrap_or(&self.separator)
+        );
+        let head = variable.get(..prefix.len())?;
+        if !head.eq_ignore_ascii_case(&prefix) {
+            return None;
+        }
+
+        let key = variable[prefix.len()..]
+            .split(self.separator.as_str())
+            .collect::<Vec<_>>()
+            .join(".");
+        Some(match self.key_case {
+            KeyCase::Lower => key.to_lowercase(),
+            KeyCase::Preserve => key,
+        })
+    }
+
+    fn collect_from(&self, variables: impl Iterator<Item = (String, String)>) -> Map<String, Value> {
+        let origin = "the environment".to_owned();
+        variables
+            .filter_map(|(variable, value)| {
+                let key = self.key(&variable)?;
+                let value = if self
+                    .list_keys
+                    .iter()
+                    .any(|list_key| list_key.eq_ignore_ascii_case(&key))
+                {
+                    let items = value
+                        .split(self.list_separator.as_str())
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(|item| Value::new(Some(&origin), item))
+                        .collect();
+                    ValueKind::Array(items)
+                } else {
+                    ValueKind::String(value)
+                };
+                Some((key, Value::new(Some(&origin), value)))
+            })
+            .collect()
+    }
+}
+
+impl Source for EnvMapping {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        Ok(self.collect_from(std::env::vars()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_variables() {
+        let mapping = EnvMapping::new("SVC").with_list_key("http.retry_on");
+        let variables = [
+            ("SVC__DB_SETTINGS__URL", "postgres://db"),
+            ("svc__http__RETRY_ON", "429, 503"),
+            ("SVCX__IGNORED", "1"),
+        ]
+        .map(|(variable, value)| (variable.to_owned(), value.to_owned()));
+
+        let mut keys = mapping.collect_from(variables.into_iter());
+        assert_eq!(keys.len(), 2);
+        assert_eq!(
+            keys.remove("db_settings.url").unwrap().into_string().unwrap(),
+            "postgres://db"
+        );
+        let retry_on: Vec<u16> = keys.remove("http.retry_on").unwrap().try_deserialize().unwrap();
+        assert_eq!(retry_on, vec![429, 503]);
+
+        let preserved = EnvMapping::new("SVC").with_key_case(KeyCase::Preserve);
+        assert_eq!(preserved.key("SVC__http__retryOn").as_deref(), Some("http.retryOn"));
+        assert_eq!(preserved.key("SVC_http").as_deref(), None);
+    }
+}
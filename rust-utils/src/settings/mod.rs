@@ -1,9 +1,113 @@
-use config::{Config, ConfigError};
+mod env;
+#[doc(hidden)]
+pub mod template;
+#[cfg(feature = "settings-watch")]
+mod watch;
+
+use std::path::Path;
+
+use config::{builder::DefaultState, Config, ConfigBuilder, ConfigError};
 use serde::de::DeserializeOwned;
 use thiserror::Error;
 
+pub use self::env::{EnvMapping, KeyCase};
+#[cfg(feature = "settings-watch")]
+pub use self::watch::watch;
+
 pub static DEFAULT_SETTINGS_FILE: &str = "settings.toml";
 
+/// Variable with the profile selecting override files, e.g. `staging` reads `settings.staging.toml`
+pub static APP_ENV: &str = "APP_ENV";
+
+/// Ordered settings sources, the later ones override the earlier ones: every file followed by its
+/// override of the profile, then environment variables. Missing files are skipped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingsSources {
+    files: Vec<String>,
+    profile: Option<String>,
+    env: EnvMapping,
+}
+
+impl SettingsSources {
+    /// Sources without files, the profile is read from `APP_ENV`
+    pub fn new(env_prefix: impl Into<String>) -> Self {
+        Self {
+            files: Vec::new(),
+            profile: std::env::var(APP_ENV).ok().filter(|profile| !profile.is_empty()),
+            env: EnvMapping::new(env_prefix),
+        }
+    }
+
+    /// Mapping of environment variables replacing the `__` separated keys of `env_prefix` variables
+    pub fn with_env_mapping(mut self, env: EnvMapping) -> Self {
+        self.env = env;
+        self
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.files.push(file.into());
+        self
+    }
+
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    pub fn without_profile(mut self) -> Self {
+        self.profile = None;
+        self
+    }
+
+    /// Files and their overrides of the profile in the order they are read
+    pub fn files(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .flat_map(|file| {
+                let profile_file = self.profile.as_ref().map(|profile| profile_file(file, profile));
+                std::iter::once(file.clone()).chain(profile_file)
+            })
+            .collect()
+    }
+
+    /// Builder with the sources, sources added to it override environment variables
+    pub fn builder(&self) -> ConfigBuilder<DefaultState> {
+        self.files()
+            .iter()
+            .fold(Config::builder(), |builder, file| {
+                builder.add_source(config::File::with_name(file).required(false))
+            })
+            .add_source(self.env.clone())
+    }
+
+    pub fn read<T, E>(&self) -> Result<T, E>
+    where
+        T: DeserializeOwned,
+        E: From<ConfigError>,
+    {
+        self.builder()
+            .build()
+            .and_then(Config::try_deserialize)
+            .map_err(Into::into)
+    }
+}
+
+/// `settings.toml` of `staging` is `settings.staging.toml`
+fn profile_file(file: &str, profile: &str) -> String {
+    let path = Path::new(file);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}.{profile}.{}",
+                stem.to_string_lossy(),
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{file}.{profile}"),
+    }
+}
+
 /// Returns settings file name from first argument (args[1]) or a default file name "settings.toml"
 /// #[deprecated(note = "use impl_settings")]
 pub fn get_settings_file() -> String {
@@ -28,12 +132,7 @@ where
     T: DeserializeOwned,
     E: From<ConfigError>,
 {
-    Config::builder()
-        .add_source(config::File::with_name(file).required(false))
-        .add_source(config::Environment::with_prefix(env_prefix).separator("__"))
-        .build()
-        .and_then(Config::try_deserialize)
-        .map_err(Into::into)
+    SettingsSources::new(env_prefix).with_file(file).read()
 }
 
 /// #[deprecated(note = "use impl_settings")]
@@ -84,6 +183,9 @@ pub enum SettingsError {
     Json(#[from] serde_json::Error),
     #[error("bad application secret")]
     BadSecret,
+    #[cfg(feature = "settings-watch")]
+    #[error("Watch error: {0}")]
+    Watch(#[from] notify::Error),
 }
 
 /// Macro for simple initialization of Settings structures.
@@ -175,17 +277,43 @@ macro_rules! impl_settings {
             }
 
 
+            /// Reads the file, its override of the `APP_ENV` profile and environment variables
             pub fn try_read_file_config<E>(file: &str, env_prefix: &str) -> Result<Self, E>
             where
                 E: From<$crate::config::ConfigError>,
             {
-                $crate::config::Config::builder()
-                    .add_source($crate::config::File::with_name(file).required(false))
-                    .add_source($crate::config::Environment::with_prefix(env_prefix)
-                    .separator("__"))
-                    .build()
-                    .and_then($crate::config::Config::try_deserialize)
-                    .map_err(Into::into)
+                Self::try_read_sources(&$crate::settings::SettingsSources::new(env_prefix).with_file(file))
+            }
+
+            pub fn try_read_sources<E>(sources: &$crate::settings::SettingsSources) -> Result<Self, E>
+            where
+                E: From<$crate::config::ConfigError>,
+            {
+                sources.read()
+            }
+
+            /// TOML of the default settings with the environment variable of every value in comments
+            pub fn to_template_toml(env_prefix: &str) -> String {
+                #[allow(unused_imports)]
+                use $crate::settings::template::{NoTemplate as _, SerializeTemplate as _, Template};
+
+                let default = Self::default();
+                match (&Template(&default)).to_template() {
+                    Some($crate::toml::Value::Table(table)) => $crate::settings::template::template(env_prefix, &table),
+                    _ => $crate::settings::template::fields_template(env_prefix, vec![$(
+                        $crate::settings::template::FieldTemplate {
+                            name: stringify!($field),
+                            type_name: stringify!($type),
+                            default: stringify!($def),
+                            value: (&Template(&default.$field)).to_template(),
+                        },
+                    )+]),
+                }
+            }
+
+            #[allow(dead_code)]
+            pub fn print_schema() {
+                println!("{}", Self::to_template_toml(APP_ENV_PREFIX));
             }
 
             #[allow(dead_code)]
@@ -205,6 +333,7 @@ macro_rules! impl_settings {
 mod tests {
     use std::sync::Mutex;
 
+    use super::{profile_file, SettingsSources};
     use crate::{db::DbSettings, logger::LoggerSettings};
     use lazy_static::lazy_static;
     use serde::Deserialize;
@@ -255,7 +384,7 @@ mod tests {
         };
 
         assert_eq!(expected_settings, default_settings);
-        assert_eq!(&expected_settings.db_settings.url, DB_URL);
+        assert_eq!(expected_settings.db_settings.url.expose_secret(), DB_URL);
     }
 
     #[test]
@@ -301,4 +430,27 @@ mod tests {
 
         assert_eq!(expected_settings, settings);
     }
+
+    #[test]
+    fn check_profile_override() {
+        let _locker = NO_PARALLEL_TEST.lock();
+        let dir = std::env::temp_dir().join(format!("settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("settings.toml").to_string_lossy().into_owned();
+        std::fs::write(&file, "field_1 = 3\nfield_2 = \"base\"").unwrap();
+        std::fs::write(dir.join("settings.staging.toml"), "field_2 = \"staging\"").unwrap();
+        std::env::set_var("TESTS__field_1", "4");
+
+        let sources = SettingsSources::new(APP_ENV_PREFIX).with_file(&file);
+        let base = TestSettings::try_read_sources::<config::ConfigError>(&sources.clone().without_profile());
+        let staging = TestSettings::try_read_sources::<config::ConfigError>(&sources.with_profile("staging"));
+
+        std::env::remove_var("TESTS__field_1");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(profile_file("conf/settings.toml", "prod"), "conf/settings.prod.toml");
+        assert_eq!(base.unwrap().field_2, "base");
+        let staging = staging.unwrap();
+        assert_eq!((staging.field_1, staging.field_2.as_str()), (4, "staging"));
+    }
 }
@@ -0,0 +1,178 @@
+//! TOML templates of settings generated by `impl_settings!`
+
+use std::fmt::Write;
+
+use serde::Serialize;
+use toml::{value::Table, Value};
+
+/// Default of a settings struct or field, rendered to TOML if it's `Serialize`
+#[doc(hidden)]
+pub struct Template<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait SerializeTemplate {
+    fn to_template(&self) -> Option<Value>;
+}
+
+impl<T: Serialize> SerializeTemplate for Template<'_, T> {
+    fn to_template(&self) -> Option<Value> {
+        Value::try_from(self.0).ok()
+    }
+}
+
+/// Fallback of `SerializeTemplate`, it's picked by autoref only if the value isn't `Serialize`
+#[doc(hidden)]
+pub trait NoTemplate {
+    fn to_template(&self) -> Option<Value> {
+        None
+    }
+}
+
+impl<T> NoTemplate for &Template<'_, T> {}
+
+/// Field of a settings struct which isn't `Serialize`
+#[doc(hidden)]
+pub struct FieldTemplate {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub default: &'static str,
+    pub value: Option<Value>,
+}
+
+/// Template of a struct which isn't `Serialize` from its fields, keys of renamed fields may differ
+#[doc(hidden)]
+pub fn fields_template(env_prefix: &str, fields: Vec<FieldTemplate>) -> String {
+    let mut table = Table::new();
+    let mut unrendered = String::new();
+    for field in fields {
+        match field.value {
+            Some(value) => {
+                table.insert(field.name.to_owned(), value);
+            },
+            None => {
+                let _ = writeln!(
+                    unrendered,
+                    "# {}: {} = {}\n# env: {}\n",
+                    field.name,
+                    field.type_name,
+                    field.default,
+                    env_var(env_prefix, &[field.name])
+                );
+            },
+        }
+    }
+
+    unrendered + &template(env_prefix, &table)
+}
+
+/// Values of the table with their environment variables in comments, values go before tables as
+/// TOML requires
+#[doc(hidden)]
+pub fn template(env_prefix: &str, table: &Table) -> String {
+    let mut template = String::new();
+    write_table(&mut template, env_prefix, &mut Vec::new(), table);
+    template
+}
+
+fn write_table<'a>(template: &mut String, env_prefix: &str, path: &mut Vec<&'a str>, table: &'a Table) {
+    let (tables, values): (Vec<_>, Vec<_>) = table.iter().partition(|(_, value)| value.is_table());
+
+    for (key, value) in values {
+        path.push(key);
+        let _ = writeln!(template, "# env: {}", env_var(env_prefix, path));
+        if is_secret(value) {
+            // the redacted default would be loaded as the secret, it's left for the user to set
+            let _ = writeln!(template, "# secret\n# {} = \"\"\n", toml_key(key));
+        } else {
+            let _ = writeln!(template, "{} = {value}\n", toml_key(key));
+        }
+        path.pop();
+    }
+
+    for (key, value) in tables {
+        path.push(key);
+        let header: Vec<_> = path.iter().map(|key| toml_key(key)).collect();
+        let _ = writeln!(template, "[{}]", header.join("."));
+        if let Value::Table(table) = value {
+            write_table(template, env_prefix, path, table);
+        }
+        path.pop();
+    }
+}
+
+/// Whether the value is a serialized `Secret`
+#[cfg(feature = "secret")]
+fn is_secret(value: &Value) -> bool {
+    value.as_str() == Some(crate::secret::REDACTED)
+}
+
+#[cfg(not(feature = "secret"))]
+fn is_secret(_: &Value) -> bool {
+    false
+}
+
+/// Key as is if it's bare, quoted otherwise
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+    {
+        key.to_owned()
+    } else {
+        Value::String(key.to_owned()).to_string()
+    }
+}
+
+fn env_var(env_prefix: &str, path: &[&str]) -> String {
+    std::iter::once(env_prefix)
+        .chain(path.iter().copied())
+        .collect::<Vec<_>>()
+        .join("__")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_before_tables() {
+        let table: Table = toml::from_str(
+            r#"
+            url = "postgres://db"
+            [rabbitmq]
+            queues = ["events"]
+            "#,
+        )
+        .unwrap();
+
+        let template = template("APP", &table);
+        assert_eq!(
+            template,
+            "# env: APP__url\nurl = \"postgres://db\"\n\n[rabbitmq]\n# env: APP__rabbitmq__queues\nqueues = [\"events\"]\n\n"
+        );
+        assert_eq!(toml::from_str::<Table>(&template).unwrap(), table);
+    }
+
+    #[cfg(feature = "secret")]
+    #[test]
+    fn comment_out_secrets() {
+        #[derive(Serialize)]
+        struct Settings {
+            url: crate::secret::Secret<String>,
+            pool_size: u32,
+        }
+
+        let table = Value::try_from(Settings {
+            url: "postgres://indexer:hunter2@db/indexer".into(),
+            pool_size: 10,
+        })
+        .unwrap();
+        let template = template("APP", table.as_table().unwrap());
+        assert_eq!(
+            template,
+            "# env: APP__pool_size\npool_size = 10\n\n# env: APP__url\n# secret\n# url = \"\"\n\n"
+        );
+        assert!(!toml::from_str::<Table>(&template).unwrap().contains_key("url"));
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use serde::de::DeserializeOwned;
+use tokio::sync::watch;
+
+use super::{SettingsError, SettingsSources};
+use crate::file_watch::{self, FileChanges};
+
+/// Extensions `config::File::with_name` tries for names without one
+const EXTENSIONS: &[&str] = &["toml", "json", "yaml", "yml", "ini", "ron", "json5"];
+
+/// Settings read from the sources and read again once their files change. If the changed files can't be
+/// read, the previous settings are kept. Files are watched until the receivers are dropped, it has to be
+/// called within a tokio runtime
+///
+/// # Usage
+/// ```ignore
+/// let mut tracing = settings::watch::<TracingSettings>(SettingsSources::new("APP").with_file("tracing.toml"))?;
+/// while tracing.changed().await.is_ok() {
+///     reload_handle.reload(&tracing.borrow().spec)?;
+/// }
+/// ```
+pub fn watch<T>(sources: SettingsSources) -> Result<watch::Receiver<T>, SettingsError>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    let (sender, receiver) = watch::channel(sources.read::<T, SettingsError>()?);
+
+    let files = sources.files();
+    let names: Vec<OsString> = files
+        .iter()
+        .filter_map(|file| Path::new(file).file_name().map(ToOwned::to_owned))
+        .collect();
+    let mut changes = FileChanges::watch(
+        directories(&files),
+        move |path| is_watched(&names, path),
+        "settings files",
+    )?;
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                change = changes.changed() => if change.is_none() { break },
+                _ = sender.closed() => break,
+            }
+
+            match sources.read::<T, SettingsError>() {
+                Ok(settings) => {
+                    log::info!("Reloaded settings from {:?}", sources.files());
+                    sender.send_replace(settings);
+                },
+                Err(error) => log::warn!("Failed to reload settings, the previous ones are kept: {error}"),
+            }
+        }
+    });
+
+    Ok(receiver)
+}
+
+/// Whether the path is one of the files, the name may have no extension as in `config::File::with_name`
+fn is_watched(names: &[OsString], path: &Path) -> bool {
+    let Some(file_name) = path.file_name().map(|name| name.to_string_lossy()) else {
+        return false;
+    };
+    names.iter().any(|name| {
+        let name = name.to_string_lossy();
+        file_name == name
+            || file_name
+                .strip_prefix(name.as_ref())
+                .and_then(|rest| rest.strip_prefix('.'))
+                .is_some_and(|extension| EXTENSIONS.contains(&extension))
+    })
+}
+
+/// Existing directories of the files
+fn directories(files: &[String]) -> BTreeSet<PathBuf> {
+    files
+        .iter()
+        .map(|file| file_watch::directory(Path::new(file)))
+        .filter(|directory| directory.is_dir())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Eq)]
+    struct TracingSettings {
+        spec: String,
+    }
+
+    #[tokio::test]
+    async fn reload_changed_file() {
+        let dir = std::env::temp_dir().join(format!("settings-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("tracing.toml");
+        std::fs::write(&file, r#"spec = "info""#).unwrap();
+
+        let sources = SettingsSources::new("SETTINGS_WATCH")
+            .without_profile()
+            .with_file(file.to_string_lossy());
+        let mut settings = watch::<TracingSettings>(sources).unwrap();
+        assert_eq!(settings.borrow().spec, "info");
+
+        std::fs::write(&file, "spec = [").unwrap();
+        std::fs::write(&file, r#"spec = "debug""#).unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), settings.changed()).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        changed.unwrap().unwrap();
+        assert_eq!(settings.borrow().spec, "debug");
+        assert!(is_watched(
+            &["settings.staging".into()],
+            Path::new("./settings.staging.toml")
+        ));
+        assert!(!is_watched(&["settings.toml".into()], Path::new("./settings.toml.swp")));
+    }
+}
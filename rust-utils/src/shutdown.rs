@@ -0,0 +1,180 @@
+//! Graceful shutdown of the service components
+//!
+//! # Usage
+//! ```ignore
+//! use rust_utils::shutdown::ShutdownCoordinator;
+//!
+//! let mut shutdown = ShutdownCoordinator::new(Duration::from_secs(30)).with_telemetry(telemetry);
+//!
+//! let token = shutdown.token();
+//! shutdown.spawn("indexer", async move {
+//!     // run until `token.cancelled()`
+//! });
+//! shutdown.register_server(server);
+//! shutdown.register_consumer("events", cancellation);
+//!
+//! // stop everything on Ctrl+C or SIGTERM, telemetry is flushed last
+//! shutdown.run_until_signal().await;
+//! ```
+
+use std::{future::Future, time::Duration};
+
+use futures::{future::BoxFuture, stream::FuturesUnordered, FutureExt, StreamExt};
+use tokio::signal;
+pub use tokio_util::sync::CancellationToken;
+
+/// Cancels `token` and waits for the registered components to stop, at most `deadline`
+pub struct ShutdownCoordinator {
+    token: CancellationToken,
+    deadline: Duration,
+    components: Vec<(String, BoxFuture<'static, anyhow::Result<()>>)>,
+    #[cfg(feature = "telemetry")]
+    telemetry: Option<crate::telemetry::Telemetry>,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(deadline: Duration) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            deadline,
+            components: vec![],
+            #[cfg(feature = "telemetry")]
+            telemetry: None,
+        }
+    }
+
+    /// Flush telemetry after all the components are stopped
+    #[cfg(feature = "telemetry")]
+    pub fn with_telemetry(mut self, telemetry: crate::telemetry::Telemetry) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Token cancelled when the shutdown starts
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Wait for `stopped` after the token is cancelled, it's not polled before that
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        stopped: impl Future<Output = anyhow::Result<()>> + Send + 'static,
+    ) {
+        self.components.push((name.into(), stopped.boxed()));
+    }
+
+    /// Spawn a background task which is expected to return once `token` is cancelled
+    pub fn spawn(&mut self, name: impl Into<String>, task: impl Future<Output = ()> + Send + 'static) {
+        let handle = tokio::spawn(task);
+        self.register(name, async move { Ok(handle.await?) });
+    }
+
+    #[cfg(feature = "server")]
+    pub fn register_server(&mut self, server: crate::server::Server) {
+        self.register("server", async move { Ok(server.stop().await?) });
+    }
+
+    /// Cancel consumption and wait for the consumer to stop
+    #[cfg(feature = "messaging")]
+    pub fn register_consumer(&mut self, name: impl Into<String>, cancellation: crate::messaging::ConsumerCancellation) {
+        self.register(name, async move {
+            cancellation.cancel_and_wait().await;
+            Ok(())
+        });
+    }
+
+    /// Start the shutdown on Ctrl+C, SIGTERM or cancellation of the token
+    pub async fn run_until_signal(self) -> bool {
+        tokio::select! {
+            _ = signal() => {},
+            _ = self.token.cancelled() => {},
+        }
+
+        self.shutdown().await
+    }
+
+    /// Stop all the components, `false` if some of them failed or didn't stop before the deadline
+    pub async fn shutdown(self) -> bool {
+        self.token.cancel();
+
+        let mut pending: FuturesUnordered<_> = self
+            .components
+            .into_iter()
+            .map(|(name, stopped)| stopped.map(move |result| (name, result)))
+            .collect();
+        let mut stopped = true;
+
+        let wait = async {
+            while let Some((name, result)) = pending.next().await {
+                match result {
+                    Ok(()) => tracing::info!(component = %name, "stopped"),
+                    Err(error) => {
+                        tracing::warn!(component = %name, ?error, "failed to stop");
+                        stopped = false;
+                    },
+                }
+            }
+        };
+        if tokio::time::timeout(self.deadline, wait).await.is_err() {
+            tracing::warn!(pending = pending.len(), "components didn't stop in {:?}", self.deadline);
+            stopped = false;
+        }
+
+        #[cfg(feature = "telemetry")]
+        if let Some(telemetry) = self.telemetry {
+            telemetry.shutdown().await;
+        }
+
+        stopped
+    }
+}
+
+/// Wait for Ctrl+C or SIGTERM
+pub async fn signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::warn!("signal received, starting graceful shutdown");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stop_components() {
+        let mut shutdown = ShutdownCoordinator::new(Duration::from_secs(1));
+        let token = shutdown.token();
+        shutdown.spawn("task", async move { token.cancelled().await });
+        shutdown.register("component", async { Ok(()) });
+
+        assert!(shutdown.shutdown().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadline() {
+        let mut shutdown = ShutdownCoordinator::new(Duration::from_secs(1));
+        shutdown.spawn("stuck", std::future::pending());
+        shutdown.register("failed", async { Err(anyhow::anyhow!("failed")) });
+
+        assert!(!shutdown.shutdown().await);
+    }
+}
@@ -1,27 +1,114 @@
-// TODO: when solana-rpc-client crate will be released, we should use it for backoff.
-// 1) Build an RpcClient with custom HttpSender that will be depend on sender from crate repo.
-// 2) add all backoff stuff around HttpSender and avoid any extra functions while RpcClient requests calling.
-
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
+use async_trait::async_trait;
 use backoff::ExponentialBackoff;
 use futures::Future;
-use solana_client::client_error::{ClientError, ClientErrorKind};
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Unit},
+    KeyValue,
+};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_custom_error::{
+        JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE, JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+        JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    },
+    rpc_request::{RpcError, RpcRequest},
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::sync::Notify;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Delay before retrying a rate limited request when the endpoint didn't tell how long to wait
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+/// Longest `Retry-After` honored, the same cap `HttpSender` applies to the header
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+const DEFAULT_MAX_CONCURRENCY: usize = 32;
+
+/// Whether the endpoint throttles us, either with HTTP 429 or with a "rate limited" RPC error payload
+fn is_rate_limited(err: &ClientError) -> bool {
+    match &err.kind {
+        ClientErrorKind::Reqwest(err) => err.status().is_some_and(|status| status.as_u16() == 429),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code: 429 | -32429, .. }) => true,
+        ClientErrorKind::RpcError(
+            RpcError::RpcResponseError { message, .. }
+            | RpcError::RpcRequestError(message)
+            | RpcError::ForUser(message),
+        ) => {
+            let message = message.to_lowercase();
+            message.contains("rate limit") || message.contains("too many requests")
+        },
+        _ => false,
+    }
+}
+
+/// Errors of a node which is unhealthy or behind, the node answers any other error the same way on a retry, e.g. a
+/// failed preflight of a transaction
+fn is_transient_response(code: i64) -> bool {
+    matches!(
+        code,
+        JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+            | JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE
+            | JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET
+            | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+    )
+}
+
+/// Seconds to wait parsed from a `Retry-After` hint in the error message, e.g. "rate limited, retry after 5"
+///
+/// `HttpSender` already honors the `Retry-After` header of 429 responses a few times before giving up, so this
+/// only covers the endpoints passing the hint in the payload.
+fn retry_after(err: &ClientError) -> Option<Duration> {
+    let message = err.to_string().to_lowercase();
+    let start = message.find("retry after").or_else(|| message.find("retry-after"))? + "retry after".len();
+    let hint = message[start..].trim_start_matches([':', ' ']);
+    let end = hint.find(|c: char| !c.is_ascii_digit()).unwrap_or(hint.len());
+    let secs = hint[..end].parse().ok()?;
+
+    Some(Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
 
+/// Classifies the error of the call, rate limited calls are retried after the delay the endpoint asked for unless it
+/// would pass the deadline
 async fn call<I: std::fmt::Debug>(
     fut: impl Future<Output = Result<I, ClientError>>,
+    deadline: Option<Instant>,
 ) -> Result<I, backoff::Error<ClientError>> {
-    fut.await.map_err(|err| match &err.kind {
-        ClientErrorKind::Io(_)
-        | ClientErrorKind::Reqwest(_)
-        | ClientErrorKind::RpcError(_)
-        | ClientErrorKind::Custom(_) => {
-            tracing::warn!(?err, "Transient error happened while SolanaRpc call");
-            backoff::Error::transient(err)
-        },
-        ClientErrorKind::SerdeJson(_) | ClientErrorKind::SigningError(_) | ClientErrorKind::TransactionError(_) => {
-            backoff::Error::permanent(err)
-        },
+    fut.await.map_err(|err| {
+        if is_rate_limited(&err) {
+            let delay = retry_after(&err).unwrap_or(DEFAULT_RETRY_AFTER);
+            if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+                return backoff::Error::permanent(err);
+            }
+            tracing::warn!(?err, ?delay, "SolanaRpc call is rate limited");
+            return backoff::Error::retry_after(err, delay);
+        }
+
+        match &err.kind {
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) if !is_transient_response(*code) => {
+                backoff::Error::permanent(err)
+            },
+            ClientErrorKind::Io(_)
+            | ClientErrorKind::Reqwest(_)
+            | ClientErrorKind::RpcError(_)
+            | ClientErrorKind::Custom(_) => {
+                tracing::warn!(?err, "Transient error happened while SolanaRpc call");
+                backoff::Error::transient(err)
+            },
+            ClientErrorKind::SerdeJson(_) | ClientErrorKind::SigningError(_) | ClientErrorKind::TransactionError(_) => {
+                backoff::Error::permanent(err)
+            },
+        }
     })
 }
 
@@ -29,12 +116,13 @@ pub async fn call_with_backoff<I: std::fmt::Debug, Fut: Future<Output = Result<I
     timeout: Option<Duration>,
     fut: impl Fn() -> Fut,
 ) -> Result<I, ClientError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
     backoff::future::retry(
         ExponentialBackoff {
             max_elapsed_time: timeout,
             ..Default::default()
         },
-        || async { call(fut()).await },
+        || async { call(fut(), deadline).await },
     )
     .await
 }
@@ -42,5 +130,392 @@ pub async fn call_with_backoff<I: std::fmt::Debug, Fut: Future<Output = Result<I
 pub async fn call_with_backoff_default_timeout<I: std::fmt::Debug, Fut: Future<Output = Result<I, ClientError>>>(
     fut: impl Fn() -> Fut,
 ) -> Result<I, ClientError> {
-    call_with_backoff(Some(Duration::from_secs(30)), fut).await
+    call_with_backoff(Some(DEFAULT_TIMEOUT), fut).await
+}
+
+/// Counts requests as `solana.rpc.requests`, failed attempts as `solana.rpc.retries` and rate limited attempts as
+/// `solana.rpc.rate_limited` and records the duration of requests including the retries as `solana.rpc.duration`, all
+/// by `method`
+struct SenderMetrics {
+    requests: Counter<u64>,
+    retries: Counter<u64>,
+    rate_limited: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl SenderMetrics {
+    fn new() -> Self {
+        let meter = global::meter("solana");
+
+        Self {
+            requests: meter
+                .u64_counter("solana.rpc.requests")
+                .with_description("Number of RPC requests by outcome")
+                .init(),
+            retries: meter
+                .u64_counter("solana.rpc.retries")
+                .with_description("Number of failed attempts of RPC requests")
+                .init(),
+            rate_limited: meter
+                .u64_counter("solana.rpc.rate_limited")
+                .with_description("Number of RPC request attempts rate limited by the endpoint")
+                .init(),
+            duration: meter
+                .f64_histogram("solana.rpc.duration")
+                .with_unit(Unit::new("ms"))
+                .with_description("Duration of RPC requests including the retries")
+                .init(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ConcurrencyState {
+    limit: usize,
+    in_flight: usize,
+    successes: usize,
+}
+
+/// Limits the requests in flight to an endpoint, halving the limit when the endpoint throttles us and growing it back
+/// by one after `limit` successful requests in a row
+struct AdaptiveConcurrency {
+    max: usize,
+    state: Mutex<ConcurrencyState>,
+    notify: Notify,
+}
+
+struct ConcurrencyPermit<'a>(&'a AdaptiveConcurrency);
+
+impl AdaptiveConcurrency {
+    fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            state: Mutex::new(ConcurrencyState {
+                limit: max,
+                in_flight: 0,
+                successes: 0,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    async fn acquire(&self) -> ConcurrencyPermit<'_> {
+        loop {
+            // created before checking the state to not miss the notification in between
+            let notified = self.notify.notified();
+            {
+                let mut state = self.state.lock().expect("poisoned");
+                if state.in_flight < state.limit {
+                    state.in_flight += 1;
+                    return ConcurrencyPermit(self);
+                }
+            }
+            notified.await;
+        }
+    }
+
+    fn on_success(&self) {
+        let mut state = self.state.lock().expect("poisoned");
+        state.successes += 1;
+        if state.successes >= state.limit && state.limit < self.max {
+            state.limit += 1;
+            state.successes = 0;
+            self.notify.notify_waiters();
+        }
+    }
+
+    fn on_rate_limited(&self) {
+        let mut state = self.state.lock().expect("poisoned");
+        state.limit = (state.limit / 2).max(1);
+        state.successes = 0;
+        tracing::debug!(limit = state.limit, "Decreased SolanaRpc concurrency");
+    }
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().expect("poisoned").in_flight -= 1;
+        self.0.notify.notify_waiters();
+    }
+}
+
+/// `RpcSender` retrying transient errors of the inner sender with an exponential backoff until the timeout of the
+/// method elapses
+///
+/// Rate limited requests are retried after the delay the endpoint asked for, and the number of requests in flight to
+/// the endpoint adapts to how hard it throttles us.
+pub struct BackoffSender<S = HttpSender> {
+    inner: S,
+    timeout: Option<Duration>,
+    method_timeouts: HashMap<RpcRequest, Duration>,
+    concurrency: AdaptiveConcurrency,
+    metrics: SenderMetrics,
+}
+
+impl BackoffSender {
+    pub fn new(url: impl ToString) -> Self {
+        Self::new_with_sender(HttpSender::new(url))
+    }
+}
+
+impl<S: RpcSender> BackoffSender<S> {
+    pub fn new_with_sender(inner: S) -> Self {
+        Self {
+            inner,
+            timeout: Some(DEFAULT_TIMEOUT),
+            method_timeouts: HashMap::new(),
+            concurrency: AdaptiveConcurrency::new(DEFAULT_MAX_CONCURRENCY),
+            metrics: SenderMetrics::new(),
+        }
+    }
+
+    /// Time the requests are retried for, `None` retries them forever
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Overrides the timeout for the method, e.g. a shorter one for `sendTransaction`
+    pub fn with_method_timeout(mut self, method: RpcRequest, timeout: Duration) -> Self {
+        self.method_timeouts.insert(method, timeout);
+        self
+    }
+
+    /// Upper bound of the requests in flight to the endpoint, the actual limit is lowered while it rate limits us
+    pub fn with_max_concurrency(mut self, max: usize) -> Self {
+        self.concurrency = AdaptiveConcurrency::new(max);
+        self
+    }
+}
+
+#[async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for BackoffSender<S> {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> Result<serde_json::Value, ClientError> {
+        let method = KeyValue::new("method", request.to_string());
+        let timeout = self.method_timeouts.get(&request).copied().or(self.timeout);
+        let started = Instant::now();
+        let deadline = timeout.map(|timeout| started + timeout);
+        let cx = opentelemetry::Context::current();
+
+        let result = backoff::future::retry(
+            ExponentialBackoff {
+                max_elapsed_time: timeout,
+                ..Default::default()
+            },
+            || async {
+                let _permit = self.concurrency.acquire().await;
+                let result = self.inner.send(request, params.clone()).await;
+                match &result {
+                    Ok(_) => self.concurrency.on_success(),
+                    Err(err) if is_rate_limited(err) => {
+                        self.concurrency.on_rate_limited();
+                        self.metrics.rate_limited.add(&cx, 1, std::slice::from_ref(&method));
+                    },
+                    Err(_) => {},
+                }
+
+                let result = call(async { result }, deadline).await;
+                if result.is_err() {
+                    self.metrics.retries.add(&cx, 1, std::slice::from_ref(&method));
+                }
+                result
+            },
+        )
+        .await;
+
+        let outcome = KeyValue::new("outcome", if result.is_ok() { "success" } else { "failure" });
+        self.metrics.requests.add(&cx, 1, &[method.clone(), outcome]);
+        self.metrics
+            .duration
+            .record(&cx, started.elapsed().as_secs_f64() * 1000.0, &[method]);
+        result
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+/// `RpcClient` whose requests are all sent with `BackoffSender`, so that call sites don't need `call_with_backoff`
+pub struct BackoffRpcClient(RpcClient);
+
+impl BackoffRpcClient {
+    pub fn new(url: impl ToString) -> Self {
+        Self::new_with_sender(BackoffSender::new(url), CommitmentConfig::default())
+    }
+
+    pub fn new_with_sender<S: RpcSender + Send + Sync + 'static>(
+        sender: BackoffSender<S>,
+        commitment: CommitmentConfig,
+    ) -> Self {
+        Self(RpcClient::new_sender(
+            sender,
+            RpcClientConfig::with_commitment(commitment),
+        ))
+    }
+
+    pub fn into_inner(self) -> RpcClient {
+        self.0
+    }
+}
+
+impl Deref for BackoffRpcClient {
+    type Target = RpcClient;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use serde_json::json;
+    use solana_client::rpc_request::RpcResponseErrorData;
+
+    use super::*;
+
+    /// Fails with the error the first `failures` times
+    struct FlakySender {
+        failures: usize,
+        attempts: AtomicUsize,
+        error: fn() -> ClientErrorKind,
+    }
+
+    #[async_trait]
+    impl RpcSender for FlakySender {
+        async fn send(&self, request: RpcRequest, _: serde_json::Value) -> Result<serde_json::Value, ClientError> {
+            if request == RpcRequest::GetVersion {
+                return Ok(json!({"solana-core": "1.18.26"}));
+            }
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err((self.error)().into());
+            }
+            Ok(json!(42))
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "flaky".to_owned()
+        }
+    }
+
+    fn flaky_client(failures: usize, error: fn() -> ClientErrorKind) -> BackoffRpcClient {
+        let sender = BackoffSender::new_with_sender(FlakySender {
+            failures,
+            attempts: AtomicUsize::new(0),
+            error,
+        })
+        .with_method_timeout(RpcRequest::GetBalance, Duration::from_millis(1));
+        BackoffRpcClient::new_with_sender(sender, CommitmentConfig::confirmed())
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let client = flaky_client(2, || ClientErrorKind::Custom("unavailable".to_owned()));
+        assert_eq!(client.get_slot().await.unwrap(), 42);
+
+        let client = flaky_client(1, || {
+            ClientErrorKind::SerdeJson(serde_json::from_str::<u64>("").unwrap_err())
+        });
+        assert!(client.get_slot().await.is_err());
+    }
+
+    fn response_error(code: i64) -> ClientErrorKind {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code,
+            message: "error".to_owned(),
+            data: RpcResponseErrorData::Empty,
+        })
+    }
+
+    #[tokio::test]
+    async fn retries_unhealthy_nodes_only() {
+        let client = flaky_client(1, || response_error(JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY));
+        assert_eq!(client.get_slot().await.unwrap(), 42);
+
+        // preflight failure
+        let client = flaky_client(1, || response_error(-32002));
+        assert!(client.get_slot().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn method_timeout_overrides() {
+        let client = flaky_client(usize::MAX, || ClientErrorKind::Custom("unavailable".to_owned()));
+        assert!(client.get_balance(&Default::default()).await.is_err());
+    }
+
+    fn rate_limited() -> ClientErrorKind {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code: -32005,
+            message: "Rate limited, retry after 5".to_owned(),
+            data: RpcResponseErrorData::Empty,
+        })
+    }
+
+    #[test]
+    fn detects_rate_limits() {
+        let err = ClientError::from(rate_limited());
+        assert!(is_rate_limited(&err));
+        assert_eq!(retry_after(&err), Some(Duration::from_secs(5)));
+
+        let err = ClientError::from(ClientErrorKind::Custom("retry-after: 1000".to_owned()));
+        assert!(!is_rate_limited(&err));
+        assert_eq!(retry_after(&err), Some(MAX_RETRY_AFTER));
+
+        let err = ClientError::from(RpcError::ForUser(
+            "Too many requests for a specific RPC call".to_owned(),
+        ));
+        assert!(is_rate_limited(&err));
+        assert_eq!(retry_after(&err), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn honors_retry_after() {
+        let client = flaky_client(1, rate_limited);
+        let started = tokio::time::Instant::now();
+        assert_eq!(client.get_slot().await.unwrap(), 42);
+        assert!(started.elapsed() >= Duration::from_secs(5));
+
+        // the delay would pass the timeout of the method
+        let client = flaky_client(1, rate_limited);
+        assert!(client.get_balance(&Default::default()).await.is_err());
+    }
+
+    #[test]
+    fn adapts_concurrency() {
+        let concurrency = AdaptiveConcurrency::new(8);
+        concurrency.on_rate_limited();
+        concurrency.on_rate_limited();
+        assert_eq!(concurrency.state.lock().unwrap().limit, 2);
+
+        concurrency.on_success();
+        assert_eq!(concurrency.state.lock().unwrap().limit, 2);
+        concurrency.on_success();
+        assert_eq!(concurrency.state.lock().unwrap().limit, 3);
+
+        (0..100).for_each(|_| concurrency.on_success());
+        assert_eq!(concurrency.state.lock().unwrap().limit, 8);
+    }
+
+    #[tokio::test]
+    async fn limits_requests_in_flight() {
+        let concurrency = AdaptiveConcurrency::new(1);
+        let permit = concurrency.acquire().await;
+        assert!(tokio::time::timeout(Duration::from_millis(10), concurrency.acquire())
+            .await
+            .is_err());
+
+        drop(permit);
+        let _permit = concurrency.acquire().await;
+    }
 }
@@ -0,0 +1,386 @@
+//! Boilerplate for setting up and initializing tracing and OpenTelemetry
+//!
+//! This module contains methods for setting up and initializing tracing and OpenTelemetry.
+//! We are using the `tracing` crate instead of `log` for logging
+//!
+//! # Usage
+//! ```ignore
+//! use rust_utils::telemetry::Telemetry;
+//! use rust_utils::telemetry::TracingSettings;
+//!
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let tracing = TracingSettings::default(); // or use your own settings
+//!
+//!     let (telemetry, subscriber) = Telemetry::init("service-name".into(), tracing)?;
+//!     Telemetry::init_subscriber(subscriber)?;
+//!
+//!     // ...
+//!
+//!     // record panics and flush them before the process dies
+//!     telemetry.install_panic_hook();
+//!
+//!     // ...
+//!
+//!     // proper flush of telemetry data
+//!     telemetry.shutdown().await;
+//!     Ok(())
+//! }
+//! ```
+
+use std::{collections::HashMap, panic, sync::mpsc, thread, time::Duration};
+
+use anyhow::Context as anyhowContext;
+use opentelemetry::{
+    global, runtime,
+    sdk::{propagation::TraceContextPropagator, trace as sdktrace, Resource},
+};
+#[cfg(feature = "telemetry-otlp")]
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_semantic_conventions as semcov;
+use sentry::ClientInitGuard;
+use serde::Deserialize;
+use serde_with::serde_as;
+use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
+use tracing_log::LogTracer;
+use tracing_stackdriver::Stackdriver;
+use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
+
+use tracing::{subscriber::set_global_default, Subscriber};
+
+use crate::{secret::Secret, wrappers::serde::DurationMs};
+
+pub use sampler::{RateLimitedSampler, SamplerSettings};
+
+pub mod runtime_metrics;
+mod sampler;
+
+pub struct Telemetry {
+    // Sentry guard is held to keep the client alive until telemetry is dropped
+    sentry_guard: Option<ClientInitGuard>,
+    // Holds only a weak reference to the provider, so it doesn't prevent the global shutdown
+    tracer: sdktrace::Tracer,
+    flush_timeout: Duration,
+}
+
+macro_rules! tracer {
+    ($resource:ident, $sampler:ident, $pipeline:expr) => {{
+        let mut pipeline = $pipeline;
+        if let Some(ref name) = $resource.get(semcov::resource::SERVICE_NAME) {
+            pipeline = pipeline.with_service_name(name.to_string());
+        }
+
+        pipeline = pipeline.with_trace_config(sdktrace::config().with_resource($resource).with_sampler($sampler));
+
+        pipeline.install_batch(runtime::Tokio)?
+    }};
+}
+
+impl Telemetry {
+    /// Compose multiple layers into a `tracing`'s subscriber.
+    ///
+    /// # Implementation Notes
+    ///
+    /// We are using `impl Subscriber` as return type to avoid having to spell out the actual
+    /// type of the returned subscriber, which is indeed quite complex.
+    pub fn init(
+        resource: Resource,
+        tracing_settings: TracingSettings,
+    ) -> anyhow::Result<(Self, impl Subscriber + Sync + Send)> {
+        global::set_text_map_propagator(TraceContextPropagator::default());
+
+        let name = resource.get(semcov::resource::SERVICE_NAME);
+        let sampler = RateLimitedSampler::new(
+            (&tracing_settings.sampler).into(),
+            tracing_settings.sampling_rate_limits,
+        );
+
+        let tracer = match (tracing_settings.exporter, tracing_settings.jaeger_collector) {
+            #[cfg(feature = "telemetry-otlp")]
+            (Exporter::Otlp { endpoint, protocol }, _) => {
+                let exporter: opentelemetry_otlp::SpanExporterBuilder = match protocol {
+                    OtlpProtocol::Grpc => opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint)
+                        .into(),
+                    OtlpProtocol::Http => opentelemetry_otlp::new_exporter().http().with_endpoint(endpoint).into(),
+                };
+
+                opentelemetry_otlp::new_pipeline()
+                    .tracing()
+                    .with_exporter(exporter)
+                    .with_trace_config(sdktrace::config().with_resource(resource).with_sampler(sampler))
+                    .install_batch(runtime::Tokio)?
+            },
+            #[cfg(not(feature = "telemetry-otlp"))]
+            (Exporter::Otlp { .. }, _) => anyhow::bail!("OTLP exporter requires `telemetry-otlp` feature"),
+            (Exporter::Jaeger, Some(collector_endpoint)) => {
+                let pipeline = opentelemetry_jaeger::new_collector_pipeline()
+                    .with_reqwest()
+                    .with_endpoint(collector_endpoint);
+
+                tracer!(resource, sampler, pipeline)
+            },
+            // No explicit Jaeger collector set up, but we have environment
+            // obviously set up to Jaeger collector
+            (Exporter::Jaeger, None) if std::env::var("OTEL_EXPORTER_JAEGER_ENDPOINT").is_ok() => {
+                let pipeline = opentelemetry_jaeger::new_collector_pipeline().with_reqwest();
+
+                tracer!(resource, sampler, pipeline)
+            },
+            (Exporter::Jaeger, None) => {
+                let pipeline = opentelemetry_jaeger::new_agent_pipeline();
+
+                tracer!(resource, sampler, pipeline)
+            },
+        };
+
+        let layer = tracing_opentelemetry::layer().with_tracer(tracer.clone());
+
+        let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&tracing_settings.spec));
+
+        let format = if tracing_settings.gclogs {
+            LogFormat::Stackdriver
+        } else {
+            tracing_settings.format
+        };
+
+        // Google Cloud Operations Suite structured logging (formerly Stackdriver).
+        // https://cloud.google.com/logging/docs/structured-logging
+        let stackdriver = (format == LogFormat::Stackdriver).then(Stackdriver::layer);
+
+        let name = name.map(|it| it.to_string()).unwrap_or_default();
+
+        // We are using BunyanFormattingLayer by default instead of tracing_subscriber::fmt because
+        // fmt does not implement metadata inheritance
+        let formatting_layer = (format == LogFormat::Bunyan).then(|| BunyanFormattingLayer::new(name, std::io::stdout));
+
+        // Fields of the event are flattened, fields of the current span are nested under `span`
+        let json_layer = (format == LogFormat::Json).then(|| {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+        });
+
+        let (sentry_layer, sentry_guard) = if let Some(sentry_url) = tracing_settings.sentry_server {
+            let guard = Some(sentry::init((
+                sentry_url.expose_secret().as_str(),
+                sentry::ClientOptions {
+                    release: sentry::release_name!(),
+                    ..Default::default()
+                },
+            )));
+            let layer = Some(sentry_tracing::layer());
+            (layer, guard)
+        } else {
+            (None, None)
+        };
+
+        let subscriber = Registry::default()
+            .with(env_filter)
+            .with(JsonStorageLayer)
+            .with(layer)
+            .with(sentry_layer)
+            .with(formatting_layer)
+            .with(json_layer)
+            .with(stackdriver);
+
+        Ok((
+            Self {
+                sentry_guard,
+                tracer,
+                flush_timeout: tracing_settings.flush_timeout,
+            },
+            subscriber,
+        ))
+    }
+
+    /// Register a subscriber as global default to process span data.
+    ///
+    /// It should only be called once!
+    pub fn init_subscriber(subscriber: impl Subscriber + Sync + Send) -> anyhow::Result<()> {
+        LogTracer::init().context("Failed to set logger")?;
+        set_global_default(subscriber).context("Failed to set subscriber")?;
+        Ok(())
+    }
+
+    /// Record panics as error spans and flush spans and Sentry events before the panic unwinds further.
+    ///
+    /// Sentry event itself is captured by the Sentry panic integration, so the hook should be installed
+    /// after `init`. Previous hook is called first, flush is bounded by `TracingSettings::flush_timeout`.
+    pub fn install_panic_hook(&self) {
+        let tracer = self.tracer.clone();
+        let flush_timeout = self.flush_timeout;
+        let previous = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+
+            let message = info
+                .payload()
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("Box<dyn Any>");
+            let location = info.location().map(ToString::to_string).unwrap_or_default();
+
+            tracing::error_span!("panic", %location).in_scope(|| tracing::error!(%location, "{message}"));
+
+            if let Some(provider) = tracer.provider() {
+                let flushed = with_timeout(flush_timeout, move || {
+                    provider.force_flush();
+                });
+                if !flushed {
+                    eprintln!("Failed to flush spans in {flush_timeout:?}");
+                }
+            }
+
+            if let Some(client) = sentry::Hub::current().client() {
+                client.flush(Some(flush_timeout));
+            }
+        }));
+    }
+
+    /// Flush and shut down the tracer provider and Sentry client, waits at most `TracingSettings::flush_timeout`
+    pub async fn shutdown(self) {
+        let flush_timeout = self.flush_timeout;
+        let shutdown = tokio::task::spawn_blocking(move || {
+            global::shutdown_tracer_provider();
+            if let Some(guard) = self.sentry_guard {
+                guard.close(Some(flush_timeout));
+            }
+        });
+
+        if tokio::time::timeout(flush_timeout, shutdown).await.is_err() {
+            tracing::warn!("Telemetry wasn't flushed in {flush_timeout:?}");
+        }
+    }
+}
+
+/// Run blocking `f` on a separate thread, `false` if it didn't finish in `timeout`
+fn with_timeout(timeout: Duration, f: impl FnOnce() + Send + 'static) -> bool {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        f();
+        let _ = sender.send(());
+    });
+
+    receiver.recv_timeout(timeout).is_ok()
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct TracingSettings {
+    #[serde(default = "default_spec")]
+    pub spec: String,
+
+    /// Overrides `format` with `LogFormat::Stackdriver`
+    #[serde(default)]
+    pub gclogs: bool,
+
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// DSN with the key of the project, redacted
+    #[serde(default)]
+    pub sentry_server: Option<Secret<String>>,
+
+    #[serde(default)]
+    pub jaeger_collector: Option<String>,
+
+    #[serde(default)]
+    pub exporter: Exporter,
+
+    #[serde(default)]
+    pub sampler: SamplerSettings,
+
+    /// Maximum number of sampled root spans per second by target prefix, e.g. `{"my_service::rpc": 10}`
+    #[serde(default)]
+    pub sampling_rate_limits: HashMap<String, u32>,
+
+    /// Maximum time to wait for spans and Sentry events to be sent on panic or shutdown
+    #[serde(rename = "flush_timeout_ms", default = "default_flush_timeout")]
+    #[serde_as(as = "DurationMs")]
+    pub flush_timeout: Duration,
+}
+
+/// Format of the logs written to stdout
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Bunyan JSON with `v`, `hostname` and `pid` fields
+    #[default]
+    Bunyan,
+    /// Plain JSON of `tracing_subscriber::fmt` with flattened event fields
+    Json,
+    /// Google Cloud structured logging
+    Stackdriver,
+}
+
+/// Where spans are exported to
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Exporter {
+    /// Jaeger collector from `jaeger_collector` or `OTEL_EXPORTER_JAEGER_ENDPOINT`, Jaeger agent otherwise
+    #[default]
+    Jaeger,
+    /// OpenTelemetry Collector or any other OTLP receiver, requires `telemetry-otlp` feature
+    Otlp {
+        endpoint: String,
+        #[serde(default)]
+        protocol: OtlpProtocol,
+    },
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OtlpProtocol {
+    #[default]
+    Grpc,
+    Http,
+}
+
+impl Default for TracingSettings {
+    fn default() -> Self {
+        Self {
+            spec: default_spec(),
+            gclogs: false,
+            format: LogFormat::default(),
+            sentry_server: None,
+            jaeger_collector: None,
+            exporter: Exporter::default(),
+            sampler: SamplerSettings::default(),
+            sampling_rate_limits: HashMap::new(),
+            flush_timeout: default_flush_timeout(),
+        }
+    }
+}
+
+fn default_spec() -> String {
+    "info".into()
+}
+
+fn default_flush_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// call with service name and version
+///
+/// ```ignore
+/// use axum_tracing_opentelemetry::make_resource;
+/// # fn main() {
+/// let r = make_resource(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+/// # }
+/// ```
+pub fn make_resource<S>(service_name: S, service_version: S) -> Resource
+where
+    S: Into<String>,
+{
+    Resource::new(vec![
+        semcov::resource::SERVICE_NAME.string(service_name.into()),
+        semcov::resource::SERVICE_VERSION.string(service_version.into()),
+    ])
+}
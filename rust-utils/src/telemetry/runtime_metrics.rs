@@ -0,0 +1,181 @@
+//! Tokio runtime metrics exported as OpenTelemetry gauges
+//!
+//! ```ignore
+//! let _metrics = rust_utils::telemetry::runtime_metrics::spawn(Duration::from_secs(10))?;
+//! ```
+//!
+//! Blocking pool metrics are only available with `RUSTFLAGS="--cfg tokio_unstable"`.
+
+use std::{
+    sync::{Mutex, MutexGuard, OnceLock},
+    time::Duration,
+};
+
+use opentelemetry::{global, metrics::ObservableGauge};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+/// Latest values of the runtime metrics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    /// Tasks scheduled from outside of the runtime and waiting in the global queue
+    pub global_queue_depth: usize,
+    pub blocking_threads: Option<usize>,
+    pub idle_blocking_threads: Option<usize>,
+    pub blocking_queue_depth: Option<usize>,
+}
+
+impl Sample {
+    pub fn collect(handle: &Handle) -> Self {
+        let metrics = handle.metrics();
+
+        #[cfg(tokio_unstable)]
+        let (blocking_threads, idle_blocking_threads, blocking_queue_depth) = (
+            Some(metrics.num_blocking_threads()),
+            Some(metrics.num_idle_blocking_threads()),
+            Some(metrics.blocking_queue_depth()),
+        );
+        #[cfg(not(tokio_unstable))]
+        let (blocking_threads, idle_blocking_threads, blocking_queue_depth) = (None, None, None);
+
+        Self {
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+            blocking_threads,
+            idle_blocking_threads,
+            blocking_queue_depth,
+        }
+    }
+}
+
+struct Gauges {
+    workers: ObservableGauge<u64>,
+    alive_tasks: ObservableGauge<u64>,
+    global_queue_depth: ObservableGauge<u64>,
+    blocking_threads: ObservableGauge<u64>,
+    idle_blocking_threads: ObservableGauge<u64>,
+    blocking_queue_depth: ObservableGauge<u64>,
+}
+
+impl Gauges {
+    fn new() -> Self {
+        let meter = global::meter("tokio");
+        let gauge = |name: &str, description: &str| {
+            meter
+                .u64_observable_gauge(format!("tokio.runtime.{name}"))
+                .with_description(description)
+                .init()
+        };
+
+        Self {
+            workers: gauge("workers", "Number of worker threads"),
+            alive_tasks: gauge("alive_tasks", "Number of alive tasks"),
+            global_queue_depth: gauge("global_queue_depth", "Number of tasks in the global queue"),
+            blocking_threads: gauge("blocking_threads", "Number of threads in the blocking pool"),
+            idle_blocking_threads: gauge("idle_blocking_threads", "Number of idle threads in the blocking pool"),
+            blocking_queue_depth: gauge("blocking_queue_depth", "Number of tasks waiting for a blocking thread"),
+        }
+    }
+}
+
+/// Latest sample of the running sampler, `None` if it's stopped
+static SAMPLE: Mutex<Option<Sample>> = Mutex::new(None);
+
+fn latest() -> MutexGuard<'static, Option<Sample>> {
+    SAMPLE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Gauges are observed by one callback for all samplers, the meter in use can't unregister it
+fn register_callback() -> anyhow::Result<()> {
+    static REGISTERED: OnceLock<()> = OnceLock::new();
+    if REGISTERED.get().is_some() {
+        return Ok(());
+    }
+
+    let gauges = Gauges::new();
+    global::meter("tokio").register_callback(move |cx| {
+        let Some(sample) = *latest() else {
+            return;
+        };
+
+        gauges.workers.observe(cx, sample.workers as u64, &[]);
+        gauges.alive_tasks.observe(cx, sample.alive_tasks as u64, &[]);
+        gauges
+            .global_queue_depth
+            .observe(cx, sample.global_queue_depth as u64, &[]);
+        for (gauge, value) in [
+            (&gauges.blocking_threads, sample.blocking_threads),
+            (&gauges.idle_blocking_threads, sample.idle_blocking_threads),
+            (&gauges.blocking_queue_depth, sample.blocking_queue_depth),
+        ] {
+            if let Some(value) = value {
+                gauge.observe(cx, value as u64, &[]);
+            }
+        }
+    })?;
+    let _ = REGISTERED.set(());
+    Ok(())
+}
+
+/// Running sampler, it's stopped and the gauges aren't reported anymore once it's dropped
+#[derive(Debug)]
+pub struct RuntimeMetrics {
+    sampler: JoinHandle<()>,
+}
+
+impl Drop for RuntimeMetrics {
+    fn drop(&mut self) {
+        self.sampler.abort();
+        *latest() = None;
+    }
+}
+
+/// Spawn a task sampling metrics of the current runtime every `interval`, gauges report the latest
+/// sample. Only one sampler runs at a time, it fails if another one isn't dropped yet
+pub fn spawn(interval: Duration) -> anyhow::Result<RuntimeMetrics> {
+    anyhow::ensure!(!interval.is_zero(), "Sampling interval must be positive");
+    let handle = Handle::try_current()?;
+
+    let mut sample = latest();
+    anyhow::ensure!(sample.is_none(), "Runtime metrics are already sampled");
+    // under the lock of the sample so it's registered once
+    register_callback()?;
+    *sample = Some(Sample::collect(&handle));
+    drop(sample);
+
+    let sampler = handle.clone().spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            // the sampler may be stopped meanwhile
+            if let Some(sample) = latest().as_mut() {
+                *sample = Sample::collect(&handle);
+            }
+        }
+    });
+    Ok(RuntimeMetrics { sampler })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn collect() {
+        assert!(spawn(Duration::ZERO).is_err());
+
+        let sampler = spawn(Duration::from_millis(10)).unwrap();
+        let sample = Sample::collect(&Handle::current());
+
+        assert_eq!(sample.workers, 2);
+        assert!(sample.alive_tasks >= 1);
+        assert_eq!(latest().unwrap().workers, 2);
+        assert!(spawn(Duration::from_millis(10)).is_err());
+
+        drop(sampler);
+        assert_eq!(*latest(), None);
+        drop(spawn(Duration::from_millis(10)).unwrap());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{
+    sdk::{
+        trace::{Sampler, ShouldSample},
+        InstrumentationLibrary,
+    },
+    trace::{Link, OrderMap, SamplingDecision, SamplingResult, SpanKind, TraceContextExt, TraceId},
+    Context, Key, Value,
+};
+use serde::Deserialize;
+
+/// Module path of the span set by `tracing-opentelemetry`
+const TARGET_KEY: &str = "code.namespace";
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerSettings {
+    #[default]
+    AlwaysOn,
+    AlwaysOff,
+    /// Share of traces to sample, from 0.0 to 1.0
+    Ratio(f64),
+    /// Follow the decision of the parent span, use the inner sampler for root spans
+    ParentBased(Box<SamplerSettings>),
+}
+
+impl From<&SamplerSettings> for Sampler {
+    fn from(settings: &SamplerSettings) -> Self {
+        match settings {
+            SamplerSettings::AlwaysOn => Sampler::AlwaysOn,
+            SamplerSettings::AlwaysOff => Sampler::AlwaysOff,
+            SamplerSettings::Ratio(ratio) => Sampler::TraceIdRatioBased(*ratio),
+            SamplerSettings::ParentBased(root) => Sampler::ParentBased(Box::new(Sampler::from(root.as_ref()))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    sampled: u32,
+}
+
+/// Limits number of sampled root spans per second for targets starting with the configured prefixes.
+/// The longest matching prefix wins, child spans follow their parent.
+#[derive(Debug, Clone)]
+pub struct RateLimitedSampler {
+    inner: Sampler,
+    limits: Vec<(String, u32)>,
+    windows: Arc<Mutex<HashMap<String, Window>>>,
+}
+
+impl RateLimitedSampler {
+    pub fn new(inner: Sampler, limits: HashMap<String, u32>) -> Self {
+        let mut limits: Vec<_> = limits.into_iter().collect();
+        limits.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        Self {
+            inner,
+            limits,
+            windows: Default::default(),
+        }
+    }
+
+    fn limit(&self, target: &str) -> Option<&(String, u32)> {
+        self.limits
+            .iter()
+            .find(|(prefix, _)| target.starts_with(prefix.as_str()))
+    }
+
+    /// Count the span in the current window of the prefix, `false` if the limit is reached
+    fn acquire(&self, prefix: &str, limit: u32, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let window = windows.entry(prefix.to_owned()).or_insert(Window {
+            started: now,
+            sampled: 0,
+        });
+
+        if now.duration_since(window.started) >= Duration::from_secs(1) {
+            *window = Window {
+                started: now,
+                sampled: 0,
+            };
+        }
+
+        if window.sampled >= limit {
+            return false;
+        }
+
+        window.sampled += 1;
+        true
+    }
+}
+
+impl ShouldSample for RateLimitedSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &OrderMap<Key, Value>,
+        links: &[Link],
+        instrumentation_library: &InstrumentationLibrary,
+    ) -> SamplingResult {
+        let result = self.inner.should_sample(
+            parent_context,
+            trace_id,
+            name,
+            span_kind,
+            attributes,
+            links,
+            instrumentation_library,
+        );
+
+        let is_root = parent_context.is_none_or(|cx| !cx.has_active_span());
+        if !is_root || result.decision != SamplingDecision::RecordAndSample {
+            return result;
+        }
+
+        let target = attributes
+            .get(&Key::from_static_str(TARGET_KEY))
+            .map(|target| target.as_str());
+        let Some((prefix, limit)) = target.as_deref().and_then(|target| self.limit(target)) else {
+            return result;
+        };
+
+        if self.acquire(prefix, *limit, Instant::now()) {
+            result
+        } else {
+            SamplingResult {
+                decision: SamplingDecision::Drop,
+                ..result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let settings: SamplerSettings = serde_json::from_str(r#"{"parent_based": {"ratio": 0.1}}"#).unwrap();
+        assert_eq!(
+            settings,
+            SamplerSettings::ParentBased(Box::new(SamplerSettings::Ratio(0.1)))
+        );
+    }
+
+    #[test]
+    fn rate_limit() {
+        let sampler = RateLimitedSampler::new(
+            Sampler::AlwaysOn,
+            [("service".to_owned(), 100), ("service::rpc".to_owned(), 2)]
+                .into_iter()
+                .collect(),
+        );
+
+        let (prefix, limit) = sampler.limit("service::rpc::handler").unwrap();
+        assert_eq!((prefix.as_str(), *limit), ("service::rpc", 2));
+        assert!(sampler.limit("other").is_none());
+
+        let now = Instant::now();
+        assert!(sampler.acquire(prefix, *limit, now));
+        assert!(sampler.acquire(prefix, *limit, now));
+        assert!(!sampler.acquire(prefix, *limit, now));
+        assert!(sampler.acquire(prefix, *limit, now + Duration::from_secs(1)));
+    }
+}
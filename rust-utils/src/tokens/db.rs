@@ -0,0 +1,285 @@
+//! Fee tokens shared between replicas in the `fee_tokens` table
+//!
+//! Every row has a version incremented on its update, so a row changed by another replica since it was read isn't
+//! overwritten: the change is applied to the fresh rows instead.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{postgres::PgRow, Row};
+
+use super::{FeeToken, FeeTokens};
+use crate::{
+    db::{Access, DbAccess, DbError, DbRepo, Repo},
+    error::{FeeTokenProviderError, UtilsResult},
+    wrappers::AsString,
+};
+
+/// Schema of the table, applied by `FeeTokenTable::create` or the migrations of the service
+pub const FEE_TOKENS_TABLE: &str = "CREATE TABLE IF NOT EXISTS fee_tokens (
+    mint TEXT PRIMARY KEY,
+    account TEXT NOT NULL,
+    name TEXT NOT NULL,
+    code TEXT NOT NULL,
+    exchange_rate DOUBLE PRECISION NOT NULL,
+    is_update_failed BOOLEAN NOT NULL DEFAULT FALSE,
+    version BIGINT NOT NULL DEFAULT 0,
+    rate_updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Attempts to modify the tokens while other replicas modify them
+const MAX_ATTEMPTS: usize = 5;
+
+pub struct FeeTokenTable {
+    repo: DbRepo,
+}
+
+impl FeeTokenTable {
+    pub fn new(repo: DbRepo) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create(&self) -> Result<(), DbError> {
+        sqlx::query(FEE_TOKENS_TABLE).execute(&self.repo).await?;
+        Ok(())
+    }
+
+    pub async fn fetch(&self) -> Result<FeeTokens, DbError> {
+        Ok(self
+            .fetch_versioned()
+            .await?
+            .into_iter()
+            .map(|(mint, (token, _))| (mint, token))
+            .collect())
+    }
+
+    /// Time the exchange rate of every token has changed at
+    pub async fn rates_updated_at(&self) -> Result<HashMap<Pubkey, DateTime<Utc>>, DbError> {
+        sqlx::query("SELECT mint, rate_updated_at FROM fee_tokens")
+            .try_map(|row: PgRow| {
+                let mint: AsString<Pubkey> = row.try_get("mint")?;
+                Ok((mint.0, row.try_get("rate_updated_at")?))
+            })
+            .fetch_all(&self.repo)
+            .await
+            .map(|rates| rates.into_iter().collect())
+            .map_err(Into::into)
+    }
+
+    /// Applies `f` to the tokens of the table and writes the changed ones, `f` is applied again if another replica
+    /// has written them meanwhile
+    pub(super) async fn modify(&self, f: impl Fn(&mut FeeTokens)) -> UtilsResult<FeeTokens> {
+        for _ in 0..MAX_ATTEMPTS {
+            let stored = self.fetch_versioned().await?;
+            let mut tokens = stored.iter().map(|(mint, (token, _))| (*mint, token.clone())).collect();
+            f(&mut tokens);
+
+            let changes = changes(&stored, &tokens);
+            if changes.is_empty() {
+                return Ok(tokens);
+            }
+
+            let mut access = self.repo.access().await?;
+            if write(&mut access, changes).await? {
+                access.done().await?;
+                return Ok(tokens);
+            }
+            log::debug!("Fee tokens are modified by another replica, retrying");
+        }
+
+        Err(FeeTokenProviderError::ConcurrentModification(format!("gave up after {MAX_ATTEMPTS} attempts")).into())
+    }
+
+    async fn fetch_versioned(&self) -> Result<HashMap<Pubkey, (FeeToken, i64)>, DbError> {
+        sqlx::query("SELECT mint, account, name, code, exchange_rate, is_update_failed, version FROM fee_tokens")
+            .try_map(|row: PgRow| {
+                let mint: AsString<Pubkey> = row.try_get("mint")?;
+                let account: AsString<Pubkey> = row.try_get("account")?;
+                let token = FeeToken {
+                    name: row.try_get("name")?,
+                    code: row.try_get("code")?,
+                    mint: mint.0,
+                    account: account.0,
+                    exchange_rate: row.try_get("exchange_rate")?,
+                    is_update_failed: row.try_get("is_update_failed")?,
+                };
+                Ok((mint.0, (token, row.try_get("version")?)))
+            })
+            .fetch_all(&self.repo)
+            .await
+            .map(|tokens| tokens.into_iter().collect())
+            .map_err(Into::into)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Change<'a> {
+    Insert(&'a FeeToken),
+    Update(&'a FeeToken, i64),
+    Delete(Pubkey, i64),
+}
+
+fn changes<'a>(stored: &HashMap<Pubkey, (FeeToken, i64)>, tokens: &'a FeeTokens) -> Vec<Change<'a>> {
+    let deleted = stored
+        .iter()
+        .filter(|(mint, _)| !tokens.contains_key(mint))
+        .map(|(mint, (_, version))| Change::Delete(*mint, *version));
+    let written = tokens.values().filter_map(|token| match stored.get(token.mint()) {
+        None => Some(Change::Insert(token)),
+        Some((stored, version)) if stored != token => Some(Change::Update(token, *version)),
+        Some(_) => None,
+    });
+
+    deleted.chain(written).collect()
+}
+
+/// Returns whether every change is written, the rows may be written by another replica since they were read
+async fn write(access: &mut DbAccess, changes: Vec<Change<'_>>) -> Result<bool, DbError> {
+    for change in changes {
+        let query = match change {
+            Change::Insert(token) => sqlx::query(
+                "INSERT INTO fee_tokens (mint, account, name, code, exchange_rate, is_update_failed)
+                VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (mint) DO NOTHING",
+            )
+            .bind(token.mint.to_string())
+            .bind(token.account.to_string())
+            .bind(&token.name)
+            .bind(&token.code)
+            .bind(token.exchange_rate)
+            .bind(token.is_update_failed),
+            Change::Update(token, version) => sqlx::query(
+                "UPDATE fee_tokens SET account = $2, name = $3, code = $4, exchange_rate = $5, is_update_failed = $6,
+                rate_updated_at = CASE WHEN exchange_rate <> $5 THEN now() ELSE rate_updated_at END,
+                version = version + 1, updated_at = now()
+                WHERE mint = $1 AND version = $7",
+            )
+            .bind(token.mint.to_string())
+            .bind(token.account.to_string())
+            .bind(&token.name)
+            .bind(&token.code)
+            .bind(token.exchange_rate)
+            .bind(token.is_update_failed)
+            .bind(version),
+            Change::Delete(mint, version) => sqlx::query("DELETE FROM fee_tokens WHERE mint = $1 AND version = $2")
+                .bind(mint.to_string())
+                .bind(version),
+        };
+
+        if query.execute(&mut *access).await?.rows_affected() == 0 {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_rows() {
+        let token = |name: &str, exchange_rate| {
+            FeeToken::new(name, name, Pubkey::new_unique(), Pubkey::new_unique(), exchange_rate)
+        };
+        let (kept, updated, deleted) = (token("kept", 1.0), token("updated", 2.0), token("deleted", 3.0));
+        let stored = HashMap::from([
+            (kept.mint, (kept.clone(), 0)),
+            (updated.mint, (updated.clone(), 4)),
+            (deleted.mint, (deleted.clone(), 1)),
+        ]);
+
+        let inserted = token("inserted", 4.0);
+        let mut tokens: FeeTokens = stored.iter().map(|(mint, (token, _))| (*mint, token.clone())).collect();
+        tokens.remove(&deleted.mint);
+        tokens.get_mut(&updated.mint).unwrap().exchange_rate = 5.0;
+        tokens.insert(inserted.mint, inserted.clone());
+
+        let mut changes = changes(&stored, &tokens);
+        changes.sort_by_key(|change| match change {
+            Change::Delete(..) => 0,
+            Change::Insert(..) => 1,
+            Change::Update(..) => 2,
+        });
+        assert_eq!(changes, vec![
+            Change::Delete(deleted.mint, 1),
+            Change::Insert(&inserted),
+            Change::Update(&tokens[&updated.mint], 4),
+        ]);
+    }
+
+    #[cfg(feature = "db-testing")]
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs postgres"]
+    async fn modify_concurrently() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::{
+            db::{
+                testing::{TestDb, TEST_DATABASE_URL},
+                DbSettings,
+            },
+            tokens::FeeTokenProvider,
+        };
+
+        let settings = std::env::var(TEST_DATABASE_URL)
+            .map(DbSettings::from_url)
+            .unwrap_or_default();
+        let db = TestDb::with_settings(&settings, None).await.unwrap();
+        let (table, other) = (
+            FeeTokenTable::new(db.repo().clone()),
+            FeeTokenTable::new(db.repo().clone()),
+        );
+        table.create().await.unwrap();
+
+        let token = FeeToken::new("usdc", "USDC", Pubkey::new_unique(), Pubkey::new_unique(), 1.0);
+        let mint = token.mint;
+        table
+            .modify(|tokens| {
+                tokens.insert(mint, token.clone());
+            })
+            .await
+            .unwrap();
+
+        // the row is inserted by another replica meanwhile
+        let mut access = db.repo().access().await.unwrap();
+        assert!(!write(&mut access, vec![Change::Insert(&token)]).await.unwrap());
+        drop(access);
+
+        // the row is updated by another replica after it's read, the change is applied to the updated row
+        let attempts = AtomicUsize::new(0);
+        let tokens = table
+            .modify(|tokens| {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    tokio::task::block_in_place(|| {
+                        tokio::runtime::Handle::current().block_on(other.modify(|tokens| {
+                            tokens.get_mut(&mint).unwrap().exchange_rate = 2.0;
+                        }))
+                    })
+                    .unwrap();
+                }
+                tokens.get_mut(&mint).unwrap().is_update_failed = true;
+            })
+            .await
+            .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(tokens[&mint].exchange_rate, 2.0);
+        assert!(tokens[&mint].is_update_failed);
+        assert_eq!(table.fetch().await.unwrap(), tokens);
+
+        // replicas see the changes of each other once refreshed
+        let (replica, other) = (
+            FeeTokenProvider::from_table(table).await.unwrap(),
+            FeeTokenProvider::from_table(other).await.unwrap(),
+        );
+        replica
+            .update_exchange_rates(&HashMap::from([("usdc".to_owned(), 3.0)]))
+            .await
+            .unwrap();
+        assert!(other.refresh().await.unwrap());
+        assert_eq!(other.get(&mint).await.unwrap().exchange_rate(), 3.0);
+        assert!(!other.refresh().await.unwrap());
+    }
+}
@@ -0,0 +1,416 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::{watch, Mutex, RwLock, RwLockReadGuard};
+
+#[cfg(feature = "tokens-db")]
+use super::db::FeeTokenTable;
+use super::persistence::{read_snapshot, write_snapshot};
+use crate::{error::UtilsResult, wrappers::serde::PubkeyAsBase58};
+
+pub type FeeTokens = HashMap<Pubkey, FeeToken>;
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FeeToken {
+    pub(super) name: String,
+
+    pub(super) code: String,
+
+    #[serde_as(as = "PubkeyAsBase58")]
+    pub(super) mint: Pubkey,
+
+    #[serde_as(as = "PubkeyAsBase58")]
+    pub(super) account: Pubkey,
+
+    pub(super) exchange_rate: f64,
+
+    pub(super) is_update_failed: bool,
+}
+
+impl FeeToken {
+    pub fn new(
+        name: impl Into<String>,
+        code: impl Into<String>,
+        mint: Pubkey,
+        account: Pubkey,
+        exchange_rate: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            code: code.into(),
+            mint,
+            account,
+            exchange_rate,
+            is_update_failed: false,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn mint(&self) -> &Pubkey {
+        &self.mint
+    }
+
+    pub fn account(&self) -> &Pubkey {
+        &self.account
+    }
+
+    pub fn exchange_rate(&self) -> f64 {
+        self.exchange_rate
+    }
+
+    pub fn is_update_failed(&self) -> bool {
+        self.is_update_failed
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    tokens: FeeTokens,
+    /// Incremented on every change of the tokens
+    revision: u64,
+    /// Revision of the tokens in the file they were loaded from or saved to
+    persisted_revision: u64,
+}
+
+/// Fee tokens shared between tasks, clones share the tokens
+///
+/// Subscribers get a snapshot of the tokens whenever the rates or the set of tokens change. With a table the tokens
+/// are shared between replicas, changes are written to the table and the tokens are its cache.
+#[derive(Clone)]
+pub struct FeeTokenProvider {
+    state: Arc<RwLock<State>>,
+    changes: Arc<watch::Sender<Arc<FeeTokens>>>,
+    /// Saves are serialized, so a snapshot of an older revision isn't written after a newer one
+    saving: Arc<Mutex<()>>,
+    /// Changes are serialized without locking the tokens, so they're read while a change is written to the table
+    writing: Arc<Mutex<()>>,
+    #[cfg(feature = "tokens-db")]
+    table: Option<Arc<FeeTokenTable>>,
+}
+
+impl Default for FeeTokenProvider {
+    fn default() -> Self {
+        Self::new(FeeTokens::new())
+    }
+}
+
+impl FeeTokenProvider {
+    pub fn new(tokens: FeeTokens) -> Self {
+        let (changes, _) = watch::channel(Arc::new(tokens.clone()));
+        Self {
+            state: Arc::new(RwLock::new(State {
+                tokens,
+                ..Default::default()
+            })),
+            changes: Arc::new(changes),
+            saving: Arc::default(),
+            writing: Arc::default(),
+            #[cfg(feature = "tokens-db")]
+            table: None,
+        }
+    }
+
+    /// Tokens stored in the table, changes of other replicas are fetched by `refresh`
+    #[cfg(feature = "tokens-db")]
+    pub async fn from_table(table: FeeTokenTable) -> UtilsResult<Self> {
+        let tokens = table.fetch().await?;
+        Ok(Self {
+            table: Some(Arc::new(table)),
+            ..Self::new(tokens)
+        })
+    }
+
+    /// Fetches the tokens changed by other replicas from the table, returns whether the tokens are changed
+    #[cfg(feature = "tokens-db")]
+    pub async fn refresh(&self) -> UtilsResult<bool> {
+        let Some(table) = &self.table else {
+            return Ok(false);
+        };
+        let _writing = self.writing.lock().await;
+        let tokens = table.fetch().await?;
+        Ok(self.replace(&mut *self.state.write().await, tokens))
+    }
+
+    /// Replaces the tokens with the snapshot in the file, it may be a plain JSON array of the tokens as well. The
+    /// tokens of the table are replaced as well
+    pub async fn load(&self, config_path: impl AsRef<Path>) -> UtilsResult<()> {
+        self.load_snapshot(config_path, false).await.map(|_| ())
+    }
+
+    /// Same as `load` unless the tokens are the same or newer than the snapshot in the file, returns whether the
+    /// tokens are replaced
+    pub async fn reload(&self, config_path: impl AsRef<Path>) -> UtilsResult<bool> {
+        self.load_snapshot(config_path, true).await
+    }
+
+    async fn load_snapshot(&self, config_path: impl AsRef<Path>, only_newer: bool) -> UtilsResult<bool> {
+        let snapshot = read_snapshot(config_path.as_ref()).await?;
+
+        let _writing = self.writing.lock().await;
+        let revision = {
+            let state = self.state.read().await;
+            let revision = snapshot.revision.unwrap_or(state.revision + 1);
+            if only_newer && (revision <= state.revision || snapshot.tokens == state.tokens) {
+                return Ok(false);
+            }
+            revision
+        };
+
+        let tokens = self.store(|tokens| *tokens = snapshot.tokens.clone()).await?;
+        let mut state = self.state.write().await;
+        self.replace(&mut state, tokens);
+        state.revision = revision;
+        state.persisted_revision = revision;
+
+        Ok(true)
+    }
+
+    /// Atomically replaces the file with a snapshot of the tokens, the file is synced to the disk before
+    pub async fn save(&self, config_path: impl AsRef<Path>) -> UtilsResult<()> {
+        let _saving = self.saving.lock().await;
+        let (tokens, revision) = {
+            let state = self.state.read().await;
+            (state.tokens.clone(), state.revision)
+        };
+        write_snapshot(config_path.as_ref(), revision, &tokens).await?;
+
+        let mut state = self.state.write().await;
+        state.persisted_revision = state.persisted_revision.max(revision);
+
+        Ok(())
+    }
+
+    /// Incremented on every change of the tokens, persisted in the snapshots
+    pub async fn revision(&self) -> u64 {
+        self.state.read().await.revision
+    }
+
+    /// Whether the tokens have changed since they were loaded or saved
+    pub async fn is_dirty(&self) -> bool {
+        let state = self.state.read().await;
+        state.revision != state.persisted_revision
+    }
+
+    /// Receives a snapshot of the tokens on every change of the rates or the set of tokens
+    pub fn subscribe(&self) -> watch::Receiver<Arc<FeeTokens>> {
+        self.changes.subscribe()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.read().await.is_empty()
+    }
+
+    pub async fn get(&self, mint: &Pubkey) -> Option<FeeToken> {
+        self.read().await.get(mint).cloned()
+    }
+
+    pub async fn get_by_account(&self, account: &Pubkey) -> Option<FeeToken> {
+        self.read()
+            .await
+            .values()
+            .find(|token| token.account == *account)
+            .cloned()
+    }
+
+    pub async fn update_exchange_rates(&self, tokens_price: &HashMap<String, f64>) -> UtilsResult<()> {
+        self.modify(|tokens| {
+            tokens
+                .values_mut()
+                .for_each(|fee_token| match tokens_price.get(fee_token.name()) {
+                    Some(new_exchange_rate) => {
+                        fee_token.exchange_rate = *new_exchange_rate;
+                        fee_token.is_update_failed = false;
+                    },
+                    None => {
+                        log::error!(
+                            "Unable to update exchange_rate for {}: token not found",
+                            fee_token.name()
+                        );
+                        fee_token.is_update_failed = true
+                    },
+                })
+        })
+        .await
+    }
+
+    pub async fn read(&self) -> RwLockReadGuard<'_, FeeTokens> {
+        RwLockReadGuard::map(self.state.read().await, |state| &state.tokens)
+    }
+
+    pub async fn contains_token(&self, mint: &Pubkey) -> bool {
+        self.read().await.contains_key(mint)
+    }
+
+    pub async fn contains_active_token(&self, mint: &Pubkey) -> bool {
+        self.read().await.get(mint).is_some_and(|token| !token.is_update_failed)
+    }
+
+    async fn modify(&self, f: impl Fn(&mut FeeTokens) + Send + Sync) -> UtilsResult<()> {
+        let _writing = self.writing.lock().await;
+        let tokens = self.store(f).await?;
+        self.replace(&mut *self.state.write().await, tokens);
+        Ok(())
+    }
+
+    /// Applies `f` to the tokens and returns them, with a table `f` is applied to its tokens and retried if they're
+    /// modified by another replica meanwhile. Called with `writing` locked
+    async fn store(&self, f: impl Fn(&mut FeeTokens) + Send + Sync) -> UtilsResult<FeeTokens> {
+        #[cfg(feature = "tokens-db")]
+        if let Some(table) = &self.table {
+            return table.modify(f).await;
+        }
+
+        let mut tokens = self.read().await.clone();
+        f(&mut tokens);
+        Ok(tokens)
+    }
+
+    /// Notifies the subscribers if the tokens are changed, returns whether they are
+    fn replace(&self, state: &mut State, tokens: FeeTokens) -> bool {
+        if state.tokens == tokens {
+            return false;
+        }
+
+        state.tokens = tokens;
+        state.revision += 1;
+        self.changes.send_replace(Arc::new(state.tokens.clone()));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{FeeToken, FeeTokenProvider};
+
+    fn init_fee_token_provider(is_update_failed: bool) -> FeeTokenProvider {
+        let mut fee_tokens = HashMap::new();
+        for i in 0..3 {
+            let mint = Pubkey::new_unique();
+            let fee_token = FeeToken {
+                name: format!("token{i}"),
+                code: format!("tkn{i}"),
+                mint,
+                account: Pubkey::new_unique(),
+                exchange_rate: i as f64,
+                is_update_failed,
+            };
+            fee_tokens.insert(mint, fee_token);
+        }
+
+        FeeTokenProvider::new(fee_tokens)
+    }
+
+    #[tokio::test]
+    async fn update_exchange_rates_successfully_3_out_of_3() {
+        let fee_token_provider = init_fee_token_provider(false);
+
+        let new_prices = HashMap::from([
+            ("token0".to_string(), 1f64),
+            ("token1".to_string(), 2f64),
+            ("token2".to_string(), 3f64),
+        ]);
+
+        fee_token_provider.update_exchange_rates(&new_prices).await.unwrap();
+
+        fee_token_provider.read().await.iter().for_each(|(_, fee_token)| {
+            match fee_token.name() {
+                "token0" => {
+                    assert_eq!("tkn0", fee_token.code());
+                    assert_eq!(1f64, fee_token.exchange_rate());
+                },
+                "token1" => {
+                    assert_eq!("tkn1", fee_token.code());
+                    assert_eq!(2f64, fee_token.exchange_rate());
+                },
+                "token2" => {
+                    assert_eq!("tkn2", fee_token.code());
+                    assert_eq!(3f64, fee_token.exchange_rate());
+                },
+                _ => panic!("Fee token with name '{}' not found", fee_token.name()),
+            }
+            assert!(!fee_token.is_update_failed());
+        });
+    }
+
+    #[tokio::test]
+    async fn update_exchange_rates_successfully_2_out_of_3() {
+        let fee_token_provider = init_fee_token_provider(true);
+
+        let new_prices = HashMap::from([("token0".to_string(), 1f64), ("token2".to_string(), 3f64)]);
+
+        fee_token_provider.update_exchange_rates(&new_prices).await.unwrap();
+
+        fee_token_provider
+            .read()
+            .await
+            .iter()
+            .for_each(|(_, fee_token)| match fee_token.name() {
+                "token0" => {
+                    assert_eq!("tkn0", fee_token.code());
+                    assert_eq!(1f64, fee_token.exchange_rate());
+                    assert!(!fee_token.is_update_failed());
+                },
+                "token1" => {
+                    assert_eq!("tkn1", fee_token.code());
+                    assert_eq!(1f64, fee_token.exchange_rate());
+                    assert!(fee_token.is_update_failed());
+                },
+                "token2" => {
+                    assert_eq!("tkn2", fee_token.code());
+                    assert_eq!(3f64, fee_token.exchange_rate());
+                    assert!(!fee_token.is_update_failed());
+                },
+                _ => panic!("Fee token with name '{}' not found", fee_token.name()),
+            });
+    }
+
+    #[tokio::test]
+    async fn notifies_subscribers_on_changes() {
+        let fee_token_provider = init_fee_token_provider(false);
+        let mut changes = fee_token_provider.subscribe();
+
+        let prices = HashMap::from([
+            ("token0".to_string(), 0f64),
+            ("token1".to_string(), 1f64),
+            ("token2".to_string(), 2f64),
+        ]);
+        fee_token_provider.update_exchange_rates(&prices).await.unwrap();
+        assert!(!changes.has_changed().unwrap());
+
+        let prices = HashMap::from([("token0".to_string(), 5f64)]);
+        fee_token_provider.update_exchange_rates(&prices).await.unwrap();
+        assert!(changes.has_changed().unwrap());
+        let tokens = changes.borrow_and_update().clone();
+        assert_eq!(tokens.values().filter(|token| token.is_update_failed()).count(), 2);
+        assert!(tokens.values().any(|token| token.exchange_rate() == 5f64));
+    }
+
+    #[tokio::test]
+    async fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("fee_tokens_{}.json", Pubkey::new_unique()));
+        let fee_token_provider = init_fee_token_provider(false);
+        fee_token_provider.save(&path).await.unwrap();
+
+        let loaded = FeeTokenProvider::default();
+        let changes = loaded.subscribe();
+        loaded.load(&path).await.unwrap();
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(*loaded.read().await, *fee_token_provider.read().await);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
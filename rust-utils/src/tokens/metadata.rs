@@ -0,0 +1,243 @@
+//! Metaplex Token Metadata accounts
+//! https://developers.metaplex.com/token-metadata
+//!
+//! Fields were appended to the account over the program versions, so an account created by an earlier version ends
+//! before them and the missing ones are decoded as `None`.
+
+use std::io;
+
+use borsh::BorshDeserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+pub static METADATA_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// `Key::MetadataV1` of the program, the first byte of a metadata account
+const METADATA_KEY: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,
+    /// Trailing zeros of the padded strings are trimmed
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub creators: Vec<Creator>,
+    pub primary_sale_happened: bool,
+    pub is_mutable: bool,
+    pub edition_nonce: Option<u8>,
+    pub token_standard: Option<TokenStandard>,
+    pub collection: Option<Collection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    /// Percentage of the royalties
+    pub share: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize)]
+pub enum TokenStandard {
+    NonFungible,
+    FungibleAsset,
+    Fungible,
+    NonFungibleEdition,
+    ProgrammableNonFungible,
+    ProgrammableNonFungibleEdition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collection {
+    pub verified: bool,
+    pub key: Pubkey,
+}
+
+#[derive(BorshDeserialize)]
+struct RawCreator {
+    address: [u8; 32],
+    verified: bool,
+    share: u8,
+}
+
+#[derive(BorshDeserialize)]
+struct RawCollection {
+    verified: bool,
+    key: [u8; 32],
+}
+
+impl TokenMetadata {
+    /// Address of the metadata account of the mint
+    pub fn address(mint: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.as_ref()],
+            &METADATA_PROGRAM_ID,
+        )
+        .0
+    }
+
+    /// Decodes the account data, the optional fields missing or malformed in the account are `None`
+    pub fn decode(data: &[u8]) -> io::Result<Self> {
+        let buf = &mut &data[..];
+
+        let key = u8::deserialize(buf)?;
+        if key != METADATA_KEY {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("not a metadata account, key {key}"),
+            ));
+        }
+
+        let update_authority = Pubkey::new_from_array(<[u8; 32]>::deserialize(buf)?);
+        let mint = Pubkey::new_from_array(<[u8; 32]>::deserialize(buf)?);
+        let name = trim(String::deserialize(buf)?);
+        let symbol = trim(String::deserialize(buf)?);
+        let uri = trim(String::deserialize(buf)?);
+        let seller_fee_basis_points = u16::deserialize(buf)?;
+        let creators = Option::<Vec<RawCreator>>::deserialize(buf)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|creator| Creator {
+                address: Pubkey::new_from_array(creator.address),
+                verified: creator.verified,
+                share: creator.share,
+            })
+            .collect();
+        let primary_sale_happened = bool::deserialize(buf)?;
+        let is_mutable = bool::deserialize(buf)?;
+
+        // once a field is missing the following ones are missing too
+        let edition_nonce = optional::<u8>(buf);
+        let token_standard = edition_nonce.as_ref().and(optional::<TokenStandard>(buf));
+        let collection = token_standard
+            .as_ref()
+            .and(optional::<RawCollection>(buf))
+            .flatten()
+            .map(|collection| Collection {
+                verified: collection.verified,
+                key: Pubkey::new_from_array(collection.key),
+            });
+
+        Ok(Self {
+            update_authority,
+            mint,
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points,
+            creators,
+            primary_sale_happened,
+            is_mutable,
+            edition_nonce: edition_nonce.flatten(),
+            token_standard: token_standard.flatten(),
+            collection,
+        })
+    }
+}
+
+/// `Some` with the field if it's in the account, the slice is left as it is otherwise
+fn optional<T: BorshDeserialize>(buf: &mut &[u8]) -> Option<Option<T>> {
+    let mut rest = *buf;
+    let field = Option::<T>::deserialize(&mut rest).ok()?;
+    *buf = rest;
+    Some(field)
+}
+
+fn trim(value: String) -> String {
+    value.trim_end_matches('\0').to_owned()
+}
+
+pub async fn get_token_metadata(client: &RpcClient, mint: &Pubkey) -> anyhow::Result<TokenMetadata> {
+    let data = client.get_account_data(&TokenMetadata::address(mint)).await?;
+    Ok(TokenMetadata::decode(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str, padded: usize) -> Vec<u8> {
+        let mut bytes = (padded as u32).to_le_bytes().to_vec();
+        bytes.extend(value.as_bytes());
+        bytes.resize(4 + padded, 0);
+        bytes
+    }
+
+    fn account(update_authority: &Pubkey, mint: &Pubkey, creator: &Pubkey) -> Vec<u8> {
+        let mut data = vec![METADATA_KEY];
+        data.extend(update_authority.as_ref());
+        data.extend(mint.as_ref());
+        data.extend(string("USD Coin", 32));
+        data.extend(string("USDC", 10));
+        data.extend(string("https://example.com/usdc.json", 200));
+        data.extend(500u16.to_le_bytes());
+        data.extend([1, 1, 0, 0, 0]);
+        data.extend(creator.as_ref());
+        data.extend([1, 100]);
+        // primary sale happened, mutable
+        data.extend([0, 1]);
+        data
+    }
+
+    #[test]
+    fn decode_metadata() {
+        let (update_authority, mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let collection = Pubkey::new_unique();
+
+        let mut data = account(&update_authority, &mint, &creator);
+        // edition nonce, token standard, collection
+        data.extend([1, 254, 1, 2, 1, 1]);
+        data.extend(collection.as_ref());
+        // uses and the rest of the fields, then padding
+        data.extend([0; 64]);
+
+        let metadata = TokenMetadata::decode(&data).unwrap();
+        assert_eq!(metadata, TokenMetadata {
+            update_authority,
+            mint,
+            name: "USD Coin".to_owned(),
+            symbol: "USDC".to_owned(),
+            uri: "https://example.com/usdc.json".to_owned(),
+            seller_fee_basis_points: 500,
+            creators: vec![Creator {
+                address: creator,
+                verified: true,
+                share: 100,
+            }],
+            primary_sale_happened: false,
+            is_mutable: true,
+            edition_nonce: Some(254),
+            token_standard: Some(TokenStandard::Fungible),
+            collection: Some(Collection {
+                verified: true,
+                key: collection,
+            }),
+        });
+    }
+
+    #[test]
+    fn decode_truncated_metadata() {
+        let (update_authority, mint, creator) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        // created before edition nonces
+        let mut data = account(&update_authority, &mint, &creator);
+        let metadata = TokenMetadata::decode(&data).unwrap();
+        assert_eq!(metadata.symbol, "USDC");
+        assert_eq!(metadata.edition_nonce, None);
+        assert_eq!(metadata.token_standard, None);
+
+        // malformed token standard
+        data.extend([1, 7, 1, 9]);
+        let metadata = TokenMetadata::decode(&data).unwrap();
+        assert_eq!(metadata.edition_nonce, Some(7));
+        assert_eq!(metadata.token_standard, None);
+        assert_eq!(metadata.collection, None);
+
+        assert!(TokenMetadata::decode(&data[..100]).is_err());
+        data[0] = 1;
+        assert!(TokenMetadata::decode(&data).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+#[cfg(feature = "tokens-db")]
+mod db;
+mod fee_tokens;
+pub mod metadata;
+mod persistence;
+mod storage;
+
+use std::collections::HashMap;
+
+use anyhow::bail;
+use futures::{stream, StreamExt};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::MAX_MULTIPLE_ACCOUNTS};
+use solana_client_extensions::accounts::GetMultipleAccountsChunked;
+use solana_sdk::pubkey::Pubkey;
+
+#[cfg(feature = "tokens-db")]
+pub use self::db::{FeeTokenTable, FEE_TOKENS_TABLE};
+pub use self::{
+    fee_tokens::{FeeToken, FeeTokenProvider, FeeTokens},
+    metadata::{get_token_metadata, TokenMetadata},
+    persistence::SNAPSHOT_VERSION,
+    storage::FeeTokenStorage,
+};
+
+/// Get token symbol by mint for token-list
+/// Deprecated since 2022-06
+pub async fn get_token_symbol_by_mint_from_json(mint: &str) -> anyhow::Result<String> {
+    let chain_id = "101"; // MAIN NET
+    let target = format!("https://cdn.jsdelivr.net/gh/CLBExchange/certified-token-list/{chain_id}/{mint}.json");
+
+    #[derive(Deserialize)]
+    struct Response {
+        symbol: String,
+    }
+
+    let response = reqwest::get(target).await?;
+
+    match response.status() {
+        StatusCode::NOT_FOUND => bail!("token not found"),
+        StatusCode::OK => Ok(response.json::<Response>().await.map(|x: Response| x.symbol)?),
+        _ => bail!("Unable to get token symbol: {}", response.status()),
+    }
+}
+
+/// Get token symbol from Metaplex Fungible Token Metadata
+/// https://docs.metaplex.com/programs/token-metadata/accounts#metadata
+/// Recommended method since 2022-06
+pub async fn get_token_symbol_by_mint_from_metadata(client: &RpcClient, mint: &Pubkey) -> anyhow::Result<String> {
+    Ok(get_token_metadata(client, mint).await?.symbol)
+}
+
+pub async fn get_token_symbol_by_mint(client: &RpcClient, mint: &Pubkey) -> anyhow::Result<String> {
+    match get_token_symbol_by_mint_from_metadata(client, mint).await {
+        Ok(symbol) => Ok(symbol),
+        Err(error) => {
+            log::warn!(
+                "unable to get token name for mint '{}' from on-chain metadata, fallback to token-list: {error}",
+                mint
+            );
+            get_token_symbol_by_mint_from_json(&mint.to_string()).await
+        },
+    }
+}
+
+/// Number of token-list requests sent at the same time
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Token resolved by `get_token_metadata_batch`, only the symbol is known for tokens without on-chain metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolvedToken {
+    Metadata(TokenMetadata),
+    TokenList { symbol: String },
+}
+
+impl ResolvedToken {
+    pub fn symbol(&self) -> &str {
+        match self {
+            Self::Metadata(metadata) => &metadata.symbol,
+            Self::TokenList { symbol } => symbol,
+        }
+    }
+}
+
+/// Resolves the tokens by their on-chain metadata fetched in chunks of `getMultipleAccounts`, the token-list is
+/// requested only for the mints without metadata. Mints found in neither are missing in the result
+pub async fn get_token_metadata_batch(
+    client: &RpcClient,
+    mints: &[Pubkey],
+) -> anyhow::Result<HashMap<Pubkey, ResolvedToken>> {
+    let addresses: Vec<Pubkey> = mints.iter().map(TokenMetadata::address).collect();
+    let accounts = client
+        .get_multiple_accounts_chunked(&addresses, MAX_MULTIPLE_ACCOUNTS)
+        .await?;
+
+    let mut tokens = HashMap::with_capacity(mints.len());
+    let mut misses = Vec::new();
+    for (mint, account) in mints.iter().zip(accounts) {
+        match account.map(|account| TokenMetadata::decode(&account.data)) {
+            Some(Ok(metadata)) => {
+                tokens.insert(*mint, ResolvedToken::Metadata(metadata));
+            },
+            Some(Err(error)) => {
+                log::warn!("unable to decode metadata of mint '{mint}', fallback to token-list: {error}");
+                misses.push(*mint);
+            },
+            None => misses.push(*mint),
+        }
+    }
+
+    let mut symbols = stream::iter(misses)
+        .map(|mint| async move { (mint, get_token_symbol_by_mint_from_json(&mint.to_string()).await) })
+        .buffer_unordered(MAX_CONCURRENT_REQUESTS);
+    while let Some((mint, symbol)) = symbols.next().await {
+        match symbol {
+            Ok(symbol) => {
+                tokens.insert(mint, ResolvedToken::TokenList { symbol });
+            },
+            Err(error) => log::debug!("unable to get token symbol for mint '{mint}' from token-list: {error}"),
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::{assert_err, assert_ok_eq};
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use std::str::FromStr;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use crate::tokens::{
+        get_token_metadata_batch, get_token_symbol_by_mint, get_token_symbol_by_mint_from_json,
+        get_token_symbol_by_mint_from_metadata, ResolvedToken,
+    };
+
+    #[tokio::test]
+    async fn get_tokens_symbol_by_mint_from_json() {
+        assert_ok_eq!(
+            get_token_symbol_by_mint_from_json("7vfCXTUXx5WJV5JADk17DUJ4ksgau7utNKj4b963voxs").await,
+            "ETH".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint_from_json("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").await,
+            "USDC".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint_from_json("9n4nbM75f5Ui33ZbPYXn59EwSgE8CGsHtAeTH5YFeJ9E").await,
+            "BTC".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint_from_json("EzfnjRUKtc5vweE1GCLdHV4MkDQ3ebSpQXLobSKgQ9RB").await,
+            "CSM".to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn get_tokens_symbol_by_mint_from_metadata() {
+        let solana_client = RpcClient::new("https://api.mainnet-beta.solana.com".into());
+
+        // token-list has ETH where as metadata hs WETH
+        assert_ok_eq!(
+            get_token_symbol_by_mint_from_metadata(
+                &solana_client,
+                &Pubkey::from_str("7vfCXTUXx5WJV5JADk17DUJ4ksgau7utNKj4b963voxs").unwrap()
+            )
+            .await,
+            "WETH".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint_from_metadata(
+                &solana_client,
+                &Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap()
+            )
+            .await,
+            "USDC".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint_from_metadata(
+                &solana_client,
+                &Pubkey::from_str("9n4nbM75f5Ui33ZbPYXn59EwSgE8CGsHtAeTH5YFeJ9E").unwrap()
+            )
+            .await,
+            "BTC".to_string()
+        );
+
+        // This mint doesn't have metadata
+        assert_err!(
+            get_token_symbol_by_mint_from_metadata(
+                &solana_client,
+                &Pubkey::from_str("EzfnjRUKtc5vweE1GCLdHV4MkDQ3ebSpQXLobSKgQ9RB").unwrap()
+            )
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn get_tokens_symbol_by_mint_with_fallback() {
+        let solana_client = RpcClient::new("https://api.mainnet-beta.solana.com".into());
+
+        // token-list has ETH where as metadata hs WETH
+        assert_ok_eq!(
+            get_token_symbol_by_mint(
+                &solana_client,
+                &Pubkey::from_str("7vfCXTUXx5WJV5JADk17DUJ4ksgau7utNKj4b963voxs").unwrap()
+            )
+            .await,
+            "WETH".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint(
+                &solana_client,
+                &Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap()
+            )
+            .await,
+            "USDC".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint(
+                &solana_client,
+                &Pubkey::from_str("9n4nbM75f5Ui33ZbPYXn59EwSgE8CGsHtAeTH5YFeJ9E").unwrap()
+            )
+            .await,
+            "BTC".to_string()
+        );
+
+        assert_ok_eq!(
+            get_token_symbol_by_mint(
+                &solana_client,
+                &Pubkey::from_str("EzfnjRUKtc5vweE1GCLdHV4MkDQ3ebSpQXLobSKgQ9RB").unwrap()
+            )
+            .await,
+            "CSM".to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn get_tokens_metadata_batch() {
+        let solana_client = RpcClient::new("https://api.mainnet-beta.solana.com".into());
+        let mints = [
+            "7vfCXTUXx5WJV5JADk17DUJ4ksgau7utNKj4b963voxs",
+            "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            "EzfnjRUKtc5vweE1GCLdHV4MkDQ3ebSpQXLobSKgQ9RB",
+        ]
+        .map(|mint| Pubkey::from_str(mint).unwrap());
+
+        let tokens = get_token_metadata_batch(&solana_client, &mints).await.unwrap();
+
+        assert_eq!(tokens[&mints[0]].symbol(), "WETH");
+        assert!(matches!(&tokens[&mints[1]], ResolvedToken::Metadata(metadata) if metadata.symbol == "USDC"));
+        // This mint doesn't have metadata
+        assert_eq!(tokens[&mints[2]], ResolvedToken::TokenList {
+            symbol: "CSM".to_string()
+        });
+    }
+}
@@ -0,0 +1,303 @@
+//! Fee tokens persisted in versioned JSON snapshots, saved periodically and reloaded once the file is changed by
+//! another process
+//!
+//! # Usage
+//! ```ignore
+//! let provider = FeeTokenProvider::default();
+//! provider.load(&settings.fee_tokens_path).await?;
+//! let _autosave = provider.spawn_autosave(&settings.fee_tokens_path, Duration::from_secs(10))?;
+//! let _reload = provider.watch_file(&settings.fee_tokens_path)?;
+//! ```
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt, task::JoinHandle};
+
+use super::{FeeToken, FeeTokenProvider, FeeTokens};
+use crate::error::{FeeTokenProviderError, UtilsError, UtilsResult};
+
+/// Version of the snapshot format, snapshots of later versions aren't loaded
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Distinguishes the temporary files of the snapshots written at once
+static TEMPORARY_FILES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    revision: u64,
+    tokens: Vec<&'a FeeToken>,
+}
+
+#[derive(Deserialize)]
+struct VersionedSnapshot {
+    version: u32,
+    revision: u64,
+    tokens: Vec<FeeToken>,
+}
+
+/// Tokens read from a file, files written before the snapshots have no revision
+pub(super) struct Snapshot {
+    pub revision: Option<u64>,
+    pub tokens: FeeTokens,
+}
+
+pub(super) async fn read_snapshot(path: &Path) -> UtilsResult<Snapshot> {
+    let contents: serde_json::Value = serde_json::from_slice(&fs::read(path).await?)?;
+
+    let (revision, tokens) = if contents.is_array() {
+        (None, serde_json::from_value::<Vec<FeeToken>>(contents)?)
+    } else {
+        let snapshot: VersionedSnapshot = serde_json::from_value(contents)?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(FeeTokenProviderError::UnsupportedSnapshotVersion(snapshot.version.to_string()).into());
+        }
+        (Some(snapshot.revision), snapshot.tokens)
+    };
+
+    let mut fee_tokens = HashMap::new();
+    for token in tokens {
+        if fee_tokens.contains_key(token.mint()) {
+            return Err(UtilsError::FeeTokenProviderError(
+                FeeTokenProviderError::DuplicateTokenMint(token.mint().to_string()),
+            ));
+        }
+        fee_tokens.insert(*token.mint(), token);
+    }
+
+    Ok(Snapshot {
+        revision,
+        tokens: fee_tokens,
+    })
+}
+
+/// Writes a temporary file next to the path, syncs it and renames it to the path, so the file is either the previous
+/// snapshot or the new one after a crash
+pub(super) async fn write_snapshot(path: &Path, revision: u64, tokens: &FeeTokens) -> UtilsResult<()> {
+    let mut tokens = tokens.values().collect::<Vec<_>>();
+    tokens.sort_by_key(|token| token.mint());
+    let contents = serde_json::to_vec_pretty(&SnapshotRef {
+        version: SNAPSHOT_VERSION,
+        revision,
+        tokens,
+    })?;
+
+    let mut temporary_file = path.as_os_str().to_owned();
+    temporary_file.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temporary_file = PathBuf::from(temporary_file);
+
+    let mut file = fs::File::create(&temporary_file).await?;
+    file.write_all(&contents).await?;
+    file.sync_all().await?;
+    drop(file);
+    if let Err(error) = fs::rename(&temporary_file, path).await {
+        let _ = fs::remove_file(&temporary_file).await;
+        return Err(error.into());
+    }
+
+    // the rename itself is durable once the directory is synced
+    #[cfg(unix)]
+    {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::File::open(directory).await?.sync_all().await?;
+    }
+
+    Ok(())
+}
+
+impl FeeTokenProvider {
+    /// Saves the tokens to the file every `interval` if they have changed since they were loaded or saved. The task
+    /// has to be aborted to stop, call `save` on shutdown to persist the last changes
+    pub fn spawn_autosave(&self, path: impl Into<PathBuf>, interval: Duration) -> UtilsResult<JoinHandle<()>> {
+        if interval.is_zero() {
+            return Err(FeeTokenProviderError::InvalidInterval("autosave interval is zero".to_owned()).into());
+        }
+        let provider = self.clone();
+        let path = path.into();
+
+        Ok(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if !provider.is_dirty().await {
+                    continue;
+                }
+                if let Err(error) = provider.save(&path).await {
+                    log::warn!("Failed to save fee tokens to {}: {error}", path.display());
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(feature = "tokens-watch")]
+mod watch {
+    use std::path::PathBuf;
+
+    use tokio::task::JoinHandle;
+
+    use super::FeeTokenProvider;
+    use crate::file_watch::{self, FileChanges};
+
+    impl FeeTokenProvider {
+        /// Reloads the tokens once the file is replaced with a newer snapshot, e.g. by another replica or an
+        /// operator. Snapshots saved by this provider are skipped. The file is watched until the task is aborted
+        pub fn watch_file(&self, path: impl Into<PathBuf>) -> notify::Result<JoinHandle<()>> {
+            let path = path.into();
+            let file_name = path.file_name().map(ToOwned::to_owned);
+            let mut changes = FileChanges::watch(
+                [file_watch::directory(&path)],
+                move |changed| changed.file_name() == file_name.as_deref(),
+                "fee tokens file",
+            )?;
+
+            let provider = self.clone();
+            Ok(tokio::spawn(async move {
+                while changes.changed().await.is_some() {
+                    match provider.reload(&path).await {
+                        Ok(true) => log::info!("Reloaded fee tokens from {}", path.display()),
+                        Ok(false) => {},
+                        Err(error) => log::warn!("Failed to reload fee tokens, the previous ones are kept: {error}"),
+                    }
+                }
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    fn fee_tokens() -> FeeTokens {
+        (0..3)
+            .map(|i| {
+                let token = FeeToken::new(
+                    format!("token{i}"),
+                    format!("tkn{i}"),
+                    Pubkey::new_unique(),
+                    Pubkey::new_unique(),
+                    i as f64,
+                );
+                (*token.mint(), token)
+            })
+            .collect()
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fee-tokens-{name}-{}", Pubkey::new_unique()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("fee_tokens.json")
+    }
+
+    #[tokio::test]
+    async fn versioned_snapshots() {
+        let path = temp_file("snapshots");
+        let provider = FeeTokenProvider::new(fee_tokens());
+        provider
+            .update_exchange_rates(&HashMap::from([("token0".to_owned(), 5.0)]))
+            .await
+            .unwrap();
+        assert!(provider.is_dirty().await);
+        provider.save(&path).await.unwrap();
+        assert!(!provider.is_dirty().await);
+
+        let snapshot: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(snapshot["version"], SNAPSHOT_VERSION);
+        assert_eq!(snapshot["revision"], 1);
+
+        let loaded = FeeTokenProvider::default();
+        loaded.load(&path).await.unwrap();
+        assert_eq!(loaded.revision().await, 1);
+        assert_eq!(*loaded.read().await, *provider.read().await);
+        // the same revision isn't reloaded
+        assert!(!loaded.reload(&path).await.unwrap());
+
+        // files written before the snapshots are plain arrays
+        std::fs::write(&path, serde_json::to_vec(&snapshot["tokens"]).unwrap()).unwrap();
+        let legacy = FeeTokenProvider::default();
+        legacy.load(&path).await.unwrap();
+        assert_eq!(*legacy.read().await, *provider.read().await);
+
+        std::fs::write(&path, r#"{"version": 2, "revision": 1, "tokens": []}"#).unwrap();
+        assert!(legacy.load(&path).await.is_err());
+
+        // concurrent saves don't share a temporary file and the latest revision is saved last
+        provider
+            .update_exchange_rates(&HashMap::from([("token0".to_owned(), 6.0)]))
+            .await
+            .unwrap();
+        let (first, second) = tokio::join!(provider.save(&path), provider.save(&path));
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(read_snapshot(&path).await.unwrap().revision, Some(2));
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn autosave_changes() {
+        let path = temp_file("autosave");
+        let provider = FeeTokenProvider::new(fee_tokens());
+        assert!(provider.spawn_autosave(&path, Duration::ZERO).is_err());
+        let autosave = provider.spawn_autosave(&path, Duration::from_millis(10)).unwrap();
+
+        provider
+            .update_exchange_rates(&HashMap::from([("token1".to_owned(), 7.0)]))
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while provider.is_dirty().await {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        autosave.abort();
+
+        let snapshot = read_snapshot(&path).await.unwrap();
+        assert_eq!(snapshot.revision, Some(1));
+        assert_eq!(snapshot.tokens, *provider.read().await);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(feature = "tokens-watch")]
+    #[tokio::test]
+    async fn reload_changed_file() {
+        let path = temp_file("watch");
+        let provider = FeeTokenProvider::new(fee_tokens());
+        provider.save(&path).await.unwrap();
+        let mut changes = provider.subscribe();
+        let watch = provider.watch_file(&path).unwrap();
+
+        let tokens = fee_tokens();
+        write_snapshot(&path, 5, &tokens).await.unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), changes.changed()).await;
+        watch.abort();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        changed.unwrap().unwrap();
+        assert_eq!(**changes.borrow(), tokens);
+        assert_eq!(provider.revision().await, 5);
+    }
+}
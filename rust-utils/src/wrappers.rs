@@ -1,13 +1,26 @@
-use jsonrpsee::core::Cow;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_with::{DeserializeAs, Same, SerializeAs};
 use std::{
-    convert::Infallible,
+    borrow::Cow,
     fmt::{Display, Formatter},
     ops::{Deref, DerefMut},
     str::FromStr,
 };
 
+mod base64;
+mod hex;
+mod redacted;
+pub mod serde;
+
+pub use self::{
+    base64::{Base64, Base64Error},
+    hex::{Hex, HexError},
+    redacted::Redacted,
+};
+pub use crate::secret::Secret;
+/// Zeroes the wrapped value on drop without redacting it, e.g. key bytes decoded on the way to a keypair
+pub use zeroize::Zeroizing;
+
 #[serde_with::serde_as]
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, Hash, PartialOrd, Ord)]
 #[serde(transparent)]
@@ -38,10 +51,17 @@ where
     }
 }
 
+/// Base58 encoded value, with a 4-byte checksum appended when `CHECK` is set, see [`Base58Check`]
+///
+/// With `serde_as`, `Base58<[u8; N]>` asserts the length of any value convertible from `[u8; N]`, e.g. a `Pubkey`, so
+/// malformed values are rejected at deserialization.
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
-pub struct Base58<T = Same>(pub T);
+pub struct Base58<T = Same, const CHECK: bool = false>(pub T);
+
+/// Base58Check, i.e. base58 with the first 4 bytes of the double SHA-256 of the value appended
+pub type Base58Check<T = Same> = Base58<T, true>;
 
-impl<T> Base58<T> {
+impl<T, const CHECK: bool> Base58<T, CHECK> {
     pub fn into_inner(self) -> T {
         self.0
     }
@@ -50,13 +70,13 @@ impl<T> Base58<T> {
     }
 }
 
-impl<T> From<T> for Base58<T> {
+impl<T, const CHECK: bool> From<T> for Base58<T, CHECK> {
     fn from(value: T) -> Self {
         Self(value)
     }
 }
 
-impl<T> Deref for Base58<T> {
+impl<T, const CHECK: bool> Deref for Base58<T, CHECK> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -64,15 +84,31 @@ impl<T> Deref for Base58<T> {
     }
 }
 
-impl<T> DerefMut for Base58<T> {
+impl<T, const CHECK: bool> DerefMut for Base58<T, CHECK> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
-impl<T: AsRef<[u8]>> Display for Base58<T> {
+fn encode<const CHECK: bool>(bytes: &[u8]) -> String {
+    if CHECK {
+        bs58::encode(bytes).with_check().into_string()
+    } else {
+        bs58::encode(bytes).into_string()
+    }
+}
+
+fn decode<const CHECK: bool>(s: &str) -> Result<Vec<u8>, bs58::decode::Error> {
+    if CHECK {
+        bs58::decode(s).with_check(None).into_vec()
+    } else {
+        bs58::decode(s).into_vec()
+    }
+}
+
+impl<T: AsRef<[u8]>, const CHECK: bool> Display for Base58<T, CHECK> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        bs58::encode(self.0.as_ref()).into_string().fmt(f)
+        encode::<CHECK>(self.0.as_ref()).fmt(f)
     }
 }
 
@@ -84,15 +120,16 @@ pub enum Base58Error<T> {
     Decode(#[from] bs58::decode::Error),
 }
 
-impl<T, E> FromStr for Base58<T>
+impl<T, E, const CHECK: bool> FromStr for Base58<T, CHECK>
 where
     Base58<T>: for<'a> TryFrom<&'a [u8], Error = E>,
 {
     type Err = Base58Error<E>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = bs58::decode(s).into_vec()?;
-        (&*bytes).try_into().map_err(Base58Error::Error)
+        let bytes = decode::<CHECK>(s)?;
+        let value: Base58<T> = (&*bytes).try_into().map_err(Base58Error::Error)?;
+        Ok(Self(value.0))
     }
 }
 
@@ -113,30 +150,28 @@ impl<'a, const N: usize> TryFrom<&'a [u8]> for Base58<[u8; N]> {
     }
 }
 
-impl<'a> TryFrom<&'a [u8]> for Base58<Vec<u8>> {
-    type Error = Infallible;
-
-    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
-        Ok(Base58(value.into()))
+impl<'a> From<&'a [u8]> for Base58<Vec<u8>> {
+    fn from(value: &'a [u8]) -> Self {
+        Base58(value.into())
     }
 }
 
-impl<'a, 'de: 'a, T> Deserialize<'de> for Base58<T>
+impl<'de, T, const CHECK: bool> Deserialize<'de> for Base58<T, CHECK>
 where
-    Base58<T>: FromStr,
-    <Base58<T> as FromStr>::Err: Display,
+    Base58<T, CHECK>: FromStr,
+    <Base58<T, CHECK> as FromStr>::Err: Display,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         let bytes = Cow::<'de, str>::deserialize(deserializer)?;
-        let bytes = Base58::from_str(&*bytes).map_err(serde::de::Error::custom)?;
+        let bytes = Base58::from_str(&bytes).map_err(::serde::de::Error::custom)?;
         Ok(bytes)
     }
 }
 
-impl<T: AsRef<[u8]>> Serialize for Base58<T> {
+impl<T: AsRef<[u8]>, const CHECK: bool> Serialize for Base58<T, CHECK> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -145,38 +180,128 @@ impl<T: AsRef<[u8]>> Serialize for Base58<T> {
     }
 }
 
-impl<'de, T> DeserializeAs<'de, T> for Base58
+impl<'de, T, const CHECK: bool> DeserializeAs<'de, T> for Base58<Same, CHECK>
 where
-    Base58<T>: Deserialize<'de>,
+    Base58<T, CHECK>: Deserialize<'de>,
 {
     fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
     where
         D: Deserializer<'de>,
     {
-        Ok(Base58::deserialize(deserializer)?.0)
+        Ok(Base58::<T, CHECK>::deserialize(deserializer)?.0)
     }
 }
 
-impl<T: AsRef<[u8]>> SerializeAs<T> for Base58 {
+impl<T: AsRef<[u8]>, const CHECK: bool> SerializeAs<T> for Base58<Same, CHECK> {
     fn serialize_as<S>(source: &T, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&bs58::encode(source).into_string())
+        serializer.serialize_str(&encode::<CHECK>(source.as_ref()))
     }
 }
 
-#[cfg(feature = "db")]
+impl<'de, T, const N: usize, const CHECK: bool> DeserializeAs<'de, T> for Base58<[u8; N], CHECK>
+where
+    T: From<[u8; N]>,
+{
+    fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Base58::<[u8; N], CHECK>::deserialize(deserializer)?.0.into())
+    }
+}
+
+impl<T: AsRef<[u8]>, const N: usize, const CHECK: bool> SerializeAs<T> for Base58<[u8; N], CHECK> {
+    fn serialize_as<S>(source: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes = source.as_ref();
+        if bytes.len() != N {
+            return Err(::serde::ser::Error::custom(WrongSliceSize(bytes.len(), N)));
+        }
+        serializer.serialize_str(&encode::<CHECK>(bytes))
+    }
+}
+
+/// Re-encodes the wrapped value, e.g. `Hex::from(base58)`
+macro_rules! impl_conversions {
+    ($from:ident => $($to:ident),+) => {
+        $(
+            impl<T> From<$from<T>> for $to<T> {
+                fn from(value: $from<T>) -> Self {
+                    Self(value.0)
+                }
+            }
+        )+
+    };
+}
+
+impl_conversions!(Base58 => Hex, Base64);
+impl_conversions!(Hex => Base58, Base64);
+impl_conversions!(Base64 => Base58, Hex);
+
+#[cfg(any(feature = "db", feature = "db-sqlite"))]
 mod db {
-    use super::{AsString, Base58};
+    use super::{AsString, Base58, Base64, Hex};
     use sqlx::{
         database::{HasArguments, HasValueRef},
         encode::IsNull,
         error::BoxDynError,
-        Database, Decode, Encode, Type,
+        Database, Decode, Encode, FromRow, Row, Type,
     };
     use std::{fmt::Display, str::FromStr};
 
+    /// Wrappers are decoded from the first column, e.g. with `query_as::<_, Base58<Pubkey>>`
+    macro_rules! impl_from_row {
+        ($row:ty) => {
+            impl<'r, T> FromRow<'r, $row> for AsString<T>
+            where
+                T: Display + FromStr,
+                <T as FromStr>::Err: Display,
+                Self: Decode<'r, <$row as Row>::Database> + Type<<$row as Row>::Database>,
+            {
+                fn from_row(row: &'r $row) -> Result<Self, sqlx::Error> {
+                    row.try_get(0)
+                }
+            }
+
+            impl<'r, T, const CHECK: bool> FromRow<'r, $row> for Base58<T, CHECK>
+            where
+                Self: Decode<'r, <$row as Row>::Database> + Type<<$row as Row>::Database>,
+            {
+                fn from_row(row: &'r $row) -> Result<Self, sqlx::Error> {
+                    row.try_get(0)
+                }
+            }
+
+            impl<'r, T> FromRow<'r, $row> for Hex<T>
+            where
+                Self: Decode<'r, <$row as Row>::Database> + Type<<$row as Row>::Database>,
+            {
+                fn from_row(row: &'r $row) -> Result<Self, sqlx::Error> {
+                    row.try_get(0)
+                }
+            }
+
+            impl<'r, T> FromRow<'r, $row> for Base64<T>
+            where
+                Self: Decode<'r, <$row as Row>::Database> + Type<<$row as Row>::Database>,
+            {
+                fn from_row(row: &'r $row) -> Result<Self, sqlx::Error> {
+                    row.try_get(0)
+                }
+            }
+        };
+    }
+
+    #[cfg(feature = "db")]
+    impl_from_row!(sqlx::postgres::PgRow);
+    #[cfg(feature = "db-sqlite")]
+    impl_from_row!(sqlx::sqlite::SqliteRow);
+
     impl<T, DB> Type<DB> for AsString<T>
     where
         T: Display + FromStr,
@@ -219,7 +344,7 @@ mod db {
         }
     }
 
-    impl<T, DB> Type<DB> for Base58<T>
+    impl<T, DB, const CHECK: bool> Type<DB> for Base58<T, CHECK>
     where
         T: AsRef<[u8]>,
         DB: Database,
@@ -234,7 +359,7 @@ mod db {
         }
     }
 
-    impl<'q, T, DB> Encode<'q, DB> for Base58<T>
+    impl<'q, T, DB, const CHECK: bool> Encode<'q, DB> for Base58<T, CHECK>
     where
         T: AsRef<[u8]>,
         DB: Database,
@@ -245,10 +370,10 @@ mod db {
         }
     }
 
-    impl<'r, T, DB> Decode<'r, DB> for Base58<T>
+    impl<'r, T, DB, const CHECK: bool> Decode<'r, DB> for Base58<T, CHECK>
     where
-        Base58<T>: FromStr,
-        <Base58<T> as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+        Base58<T, CHECK>: FromStr,
+        <Base58<T, CHECK> as FromStr>::Err: std::error::Error + Send + Sync + 'static,
         DB: Database,
         String: Decode<'r, DB>,
     {
@@ -262,7 +387,7 @@ mod db {
 
 #[cfg(test)]
 mod tests {
-    use super::Base58;
+    use super::{Base58, Base58Check, Base64, Hex};
     use serde::{Deserialize, Serialize};
     use serde_with::serde_as;
 
@@ -297,4 +422,107 @@ mod tests {
         let data1 = serde_json::from_str(&json).unwrap();
         assert_eq!(data, data1);
     }
+
+    #[test]
+    fn conversions() {
+        let value = Base58(vec![0_u8, 255]);
+        let hex: Hex<Vec<u8>> = value.clone().into();
+        assert_eq!(hex.to_string(), "0x00ff");
+        let base64: Base64<Vec<u8>> = hex.into();
+        assert_eq!(base64.to_string(), "AP8=");
+        assert_eq!(Base58::from(base64), value);
+    }
+
+    #[test]
+    fn base58_check() {
+        let value = Base58Check::new(vec![0_u8, 1, 2, 3]);
+        let encoded = value.to_string();
+        assert_eq!(encoded, bs58::encode([0_u8, 1, 2, 3]).with_check().into_string());
+        assert_eq!(encoded.parse::<Base58Check<Vec<u8>>>().unwrap(), value);
+
+        // the checksum is taken as a part of the value without the check
+        assert_eq!(encoded.parse::<Base58<Vec<u8>>>().unwrap().len(), 8);
+        let mut corrupted = encoded.into_bytes();
+        corrupted[2] = if corrupted[2] == b'2' { b'3' } else { b'2' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert!(corrupted.parse::<Base58Check<Vec<u8>>>().is_err());
+    }
+
+    #[test]
+    fn base58_length_serde_as() {
+        #[serde_as]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Data {
+            #[serde_as(as = "Base58<[u8; 4]>")]
+            value: Vec<u8>,
+            #[serde_as(as = "Base58Check")]
+            checked: Vec<u8>,
+        }
+
+        let data = Data {
+            value: vec![1, 2, 3, 4],
+            checked: vec![5, 6],
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(serde_json::from_str::<Data>(&json).unwrap(), data);
+
+        let json = json.replace(&bs58::encode([1, 2, 3, 4]).into_string(), "2");
+        assert!(serde_json::from_str::<Data>(&json).is_err());
+
+        let data = Data {
+            value: vec![1, 2, 3],
+            checked: vec![],
+        };
+        assert!(serde_json::to_string(&data).is_err());
+    }
+
+    #[cfg(feature = "db-sqlite")]
+    #[tokio::test]
+    async fn sqlite_roundtrip() {
+        use super::{AsString, Base58Check};
+
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE wrappers (value TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let key = Base58Check::new([7_u8; 32]);
+        sqlx::query("INSERT INTO wrappers (value) VALUES (?), (?)")
+            .bind(key)
+            .bind(AsString(42_u64))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let row: Base58Check<[u8; 32]> = sqlx::query_as("SELECT value FROM wrappers ORDER BY rowid LIMIT 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(row, key);
+        // the checksum is verified on decoding
+        let row = sqlx::query_as::<_, Base58<[u8; 32]>>("SELECT value FROM wrappers ORDER BY rowid LIMIT 1")
+            .fetch_one(&pool)
+            .await;
+        assert!(row.is_err());
+
+        let row: AsString<u64> = sqlx::query_as("SELECT value FROM wrappers ORDER BY rowid LIMIT 1 OFFSET 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(*row, 42);
+
+        let hex: Hex<Vec<u8>> = sqlx::query_scalar("SELECT ?")
+            .bind(Hex(vec![1_u8, 2]))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(hex.to_string(), "0x0102");
+        let base64: Base64<Vec<u8>> = sqlx::query_as("SELECT ?")
+            .bind(Base64(vec![1_u8, 2]))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(base64.0, vec![1, 2]);
+    }
 }
@@ -0,0 +1,205 @@
+use ::base64::{engine::general_purpose::STANDARD, DecodeError, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{DeserializeAs, Same, SerializeAs};
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+    ops::{Deref, DerefMut},
+    str::FromStr,
+};
+
+/// Standard padded base64
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Base64<T = Same>(pub T);
+
+impl<T> Base64<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> From<T> for Base64<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Base64<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Base64<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+impl<T: AsRef<[u8]>> Display for Base64<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        encode(self.0.as_ref()).fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Base64Error<T> {
+    #[error("{0}")]
+    Error(T),
+    #[error("base64 decode error: {0}")]
+    Decode(#[from] DecodeError),
+}
+
+impl<T, E> FromStr for Base64<T>
+where
+    T: for<'a> TryFrom<&'a [u8], Error = E>,
+{
+    type Err = Base64Error<E>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = STANDARD.decode(s)?;
+        T::try_from(&bytes).map(Self).map_err(Base64Error::Error)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Base64<T>
+where
+    Base64<T>: FromStr,
+    <Base64<T> as FromStr>::Err: Display,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = Cow::<'de, str>::deserialize(deserializer)?;
+        Base64::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T: AsRef<[u8]>> Serialize for Base64<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de, T> DeserializeAs<'de, T> for Base64
+where
+    Base64<T>: Deserialize<'de>,
+{
+    fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Base64::deserialize(deserializer)?.0)
+    }
+}
+
+impl<T: AsRef<[u8]>> SerializeAs<T> for Base64 {
+    fn serialize_as<S>(source: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&encode(source.as_ref()))
+    }
+}
+
+#[cfg(any(feature = "db", feature = "db-sqlite"))]
+mod db {
+    use super::Base64;
+    use sqlx::{
+        database::{HasArguments, HasValueRef},
+        encode::IsNull,
+        error::BoxDynError,
+        Database, Decode, Encode, Type,
+    };
+    use std::str::FromStr;
+
+    impl<T, DB> Type<DB> for Base64<T>
+    where
+        T: AsRef<[u8]>,
+        DB: Database,
+        String: Type<DB>,
+    {
+        fn type_info() -> DB::TypeInfo {
+            <String as Type<DB>>::type_info()
+        }
+
+        fn compatible(ty: &DB::TypeInfo) -> bool {
+            <String as Type<DB>>::compatible(ty)
+        }
+    }
+
+    impl<'q, T, DB> Encode<'q, DB> for Base64<T>
+    where
+        T: AsRef<[u8]>,
+        DB: Database,
+        String: Encode<'q, DB>,
+    {
+        fn encode_by_ref(&self, buf: &mut <DB as HasArguments<'q>>::ArgumentBuffer) -> IsNull {
+            <String as Encode<DB>>::encode(self.to_string(), buf)
+        }
+    }
+
+    impl<'r, T, DB> Decode<'r, DB> for Base64<T>
+    where
+        Base64<T>: FromStr,
+        <Base64<T> as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+        DB: Database,
+        String: Decode<'r, DB>,
+    {
+        fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<Self, BoxDynError> {
+            let s = <String as Decode<DB>>::decode(value)?;
+            let bytes = Base64::from_str(&s).map_err(|e| Box::new(e) as BoxDynError)?;
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Base64;
+    use serde::{Deserialize, Serialize};
+    use serde_with::serde_as;
+    use std::str::FromStr;
+
+    #[test]
+    fn base64_padding() {
+        let value = Base64(b"hello".to_vec());
+        assert_eq!(value.to_string(), "aGVsbG8=");
+        assert_eq!(Base64::<Vec<u8>>::from_str("aGVsbG8=").unwrap(), value);
+        assert!(Base64::<Vec<u8>>::from_str("aGVsbG8").is_err());
+        assert!(Base64::<[u8; 4]>::from_str("aGVsbG8=").is_err());
+    }
+
+    #[test]
+    fn base64_serde_as() {
+        #[serde_as]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Data {
+            #[serde_as(as = "Base64")]
+            value: Vec<u8>,
+            wrapped: Base64<[u8; 2]>,
+        }
+
+        let data = Data {
+            value: vec![1, 2, 3, 4, 5],
+            wrapped: Base64([0xab, 0xcd]),
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(json, r#"{"value":"AQIDBAU=","wrapped":"q80="}"#);
+        let data1 = serde_json::from_str(&json).unwrap();
+        assert_eq!(data, data1);
+    }
+}
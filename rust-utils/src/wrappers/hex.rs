@@ -0,0 +1,207 @@
+use rustc_hex::{FromHex, FromHexError, ToHex};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{DeserializeAs, Same, SerializeAs};
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+    ops::{Deref, DerefMut},
+    str::FromStr,
+};
+
+/// Lowercase `0x` prefixed hex, parsed with or without the prefix and in either case
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Hex<T = Same>(pub T);
+
+impl<T> Hex<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> From<T> for Hex<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Hex<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Hex<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.to_hex::<String>())
+}
+
+impl<T: AsRef<[u8]>> Display for Hex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        encode(self.0.as_ref()).fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum HexError<T> {
+    #[error("{0}")]
+    Error(T),
+    #[error("hex decode error: {0}")]
+    Decode(#[from] FromHexError),
+}
+
+impl<T, E> FromStr for Hex<T>
+where
+    T: for<'a> TryFrom<&'a [u8], Error = E>,
+{
+    type Err = HexError<E>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+        let bytes: Vec<u8> = s.from_hex()?;
+        T::try_from(&bytes).map(Self).map_err(HexError::Error)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Hex<T>
+where
+    Hex<T>: FromStr,
+    <Hex<T> as FromStr>::Err: Display,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = Cow::<'de, str>::deserialize(deserializer)?;
+        Hex::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<T: AsRef<[u8]>> Serialize for Hex<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de, T> DeserializeAs<'de, T> for Hex
+where
+    Hex<T>: Deserialize<'de>,
+{
+    fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Hex::deserialize(deserializer)?.0)
+    }
+}
+
+impl<T: AsRef<[u8]>> SerializeAs<T> for Hex {
+    fn serialize_as<S>(source: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&encode(source.as_ref()))
+    }
+}
+
+#[cfg(any(feature = "db", feature = "db-sqlite"))]
+mod db {
+    use super::Hex;
+    use sqlx::{
+        database::{HasArguments, HasValueRef},
+        encode::IsNull,
+        error::BoxDynError,
+        Database, Decode, Encode, Type,
+    };
+    use std::str::FromStr;
+
+    impl<T, DB> Type<DB> for Hex<T>
+    where
+        T: AsRef<[u8]>,
+        DB: Database,
+        String: Type<DB>,
+    {
+        fn type_info() -> DB::TypeInfo {
+            <String as Type<DB>>::type_info()
+        }
+
+        fn compatible(ty: &DB::TypeInfo) -> bool {
+            <String as Type<DB>>::compatible(ty)
+        }
+    }
+
+    impl<'q, T, DB> Encode<'q, DB> for Hex<T>
+    where
+        T: AsRef<[u8]>,
+        DB: Database,
+        String: Encode<'q, DB>,
+    {
+        fn encode_by_ref(&self, buf: &mut <DB as HasArguments<'q>>::ArgumentBuffer) -> IsNull {
+            <String as Encode<DB>>::encode(self.to_string(), buf)
+        }
+    }
+
+    impl<'r, T, DB> Decode<'r, DB> for Hex<T>
+    where
+        Hex<T>: FromStr,
+        <Hex<T> as FromStr>::Err: std::error::Error + Send + Sync + 'static,
+        DB: Database,
+        String: Decode<'r, DB>,
+    {
+        fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<Self, BoxDynError> {
+            let s = <String as Decode<DB>>::decode(value)?;
+            let bytes = Hex::from_str(&s).map_err(|e| Box::new(e) as BoxDynError)?;
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hex;
+    use serde::{Deserialize, Serialize};
+    use serde_with::serde_as;
+    use std::str::FromStr;
+
+    #[test]
+    fn hex_prefix() {
+        let value = Hex([0xde_u8, 0xad, 0xbe, 0xef]);
+        assert_eq!(value.to_string(), "0xdeadbeef");
+        assert_eq!(Hex::<[u8; 4]>::from_str("DEADBEEF").unwrap(), value);
+        assert_eq!(Hex::<[u8; 4]>::from_str("0xdeadbeef").unwrap(), value);
+        assert!(Hex::<[u8; 3]>::from_str("0xdeadbeef").is_err());
+        assert!(Hex::<Vec<u8>>::from_str("0xdeadbee").is_err());
+    }
+
+    #[test]
+    fn hex_serde_as() {
+        #[serde_as]
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Data {
+            #[serde_as(as = "Hex")]
+            value: Vec<u8>,
+            wrapped: Hex<[u8; 2]>,
+        }
+
+        let data = Data {
+            value: vec![1, 2, 3, 4, 5],
+            wrapped: Hex([0xab, 0xcd]),
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(json, r#"{"value":"0x0102030405","wrapped":"0xabcd"}"#);
+        let data1 = serde_json::from_str(&json).unwrap();
+        assert_eq!(data, data1);
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt::{Debug, Display, Formatter},
+    ops::{Deref, DerefMut},
+};
+
+use crate::secret::REDACTED;
+
+const MASK: &str = "***";
+
+/// Value which is printed as `***` in `Debug` and `Display`, e.g. a keypair or an URL with a token in settings.
+/// It's serialized redacted as `Secret` is unless `SERIALIZE` is set and deserialized as is
+///
+/// Unlike `Secret` it doesn't require the value to be zeroizable, wrap it in `Zeroizing` for that.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Redacted<T, const SERIALIZE: bool = false>(pub T);
+
+impl<T, const SERIALIZE: bool> Redacted<T, SERIALIZE> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T, const SERIALIZE: bool> From<T> for Redacted<T, SERIALIZE> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T, const SERIALIZE: bool> Deref for Redacted<T, SERIALIZE> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T, const SERIALIZE: bool> DerefMut for Redacted<T, SERIALIZE> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T, const SERIALIZE: bool> Debug for Redacted<T, SERIALIZE> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+impl<T, const SERIALIZE: bool> Display for Redacted<T, SERIALIZE> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+impl<T: Serialize, const SERIALIZE: bool> Serialize for Redacted<T, SERIALIZE> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if SERIALIZE {
+            self.0.serialize(serializer)
+        } else {
+            serializer.serialize_str(REDACTED)
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const SERIALIZE: bool> Deserialize<'de> for Redacted<T, SERIALIZE> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Redacted;
+    use crate::wrappers::Base58;
+
+    #[test]
+    fn redacted() {
+        let key: Redacted<Base58<Vec<u8>>> = serde_json::from_str(r#""2VfUX""#).unwrap();
+        assert_eq!(key.0 .0, vec![1, 2, 3, 4]);
+        assert_eq!(format!("{key} {key:?}"), "*** ***");
+        assert_eq!(serde_json::to_string(&key).unwrap(), r#""[REDACTED]""#);
+
+        let key = Redacted::<Base58<Vec<u8>>, true>::new(Base58(vec![1, 2, 3, 4]));
+        assert_eq!(format!("{key:?}"), "***");
+        assert_eq!(serde_json::to_string(&key).unwrap(), r#""2VfUX""#);
+    }
+}
@@ -0,0 +1,64 @@
+//! `serde_as` adapters shared across crates
+//!
+//! ```ignore
+//! #[serde_as]
+//! #[derive(Serialize, Deserialize)]
+//! struct Settings {
+//!     #[serde_as(as = "PubkeyAsBase58")]
+//!     mint: Pubkey,
+//!     #[serde_as(as = "DurationMs")]
+//!     timeout: Duration,
+//! }
+//! ```
+
+use serde_with::{formats::Flexible, DurationMilliSeconds, DurationSeconds};
+
+use super::Base58;
+
+/// Base58 of a 32 bytes key, e.g. a Solana `Pubkey`, rejecting values of other lengths at deserialization
+pub type PubkeyAsBase58 = Base58<[u8; 32]>;
+
+/// Whole milliseconds, deserialized from numeric strings as well, e.g. from environment variables
+pub type DurationMs = DurationMilliSeconds<u64, Flexible>;
+
+/// Whole seconds, deserialized from numeric strings as well, e.g. from environment variables
+pub type DurationSec = DurationSeconds<u64, Flexible>;
+
+#[cfg(test)]
+mod tests {
+    use super::{DurationMs, DurationSec, PubkeyAsBase58};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use serde_with::serde_as;
+    use std::time::Duration;
+
+    #[serde_as]
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Data {
+        #[serde_as(as = "PubkeyAsBase58")]
+        key: [u8; 32],
+        #[serde_as(as = "DurationMs")]
+        timeout: Duration,
+        #[serde_as(as = "Option<DurationSec>")]
+        ttl: Option<Duration>,
+    }
+
+    #[test]
+    fn serde_adapters() {
+        let data = Data {
+            key: [1; 32],
+            timeout: Duration::from_millis(1500),
+            ttl: Some(Duration::from_secs(60)),
+        };
+        let key = bs58::encode([1; 32]).into_string();
+        let value = serde_json::to_value(&data).unwrap();
+        assert_eq!(value, json!({"key": key, "timeout": 1500, "ttl": 60}));
+        assert_eq!(serde_json::from_value::<Data>(value).unwrap(), data);
+
+        let value = json!({"key": key, "timeout": "1500", "ttl": "60"});
+        assert_eq!(serde_json::from_value::<Data>(value).unwrap(), data);
+
+        let value = json!({"key": bs58::encode([1; 31]).into_string(), "timeout": 1500, "ttl": null});
+        assert!(serde_json::from_value::<Data>(value).is_err());
+    }
+}
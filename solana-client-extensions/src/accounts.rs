@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt, TryStreamExt};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_request::MAX_MULTIPLE_ACCOUNTS,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+use crate::ERR_PREFIX;
+
+/// Number of `getMultipleAccounts` requests sent at the same time
+const MAX_CONCURRENT_CHUNKS: usize = 4;
+
+#[async_trait]
+pub trait GetMultipleAccountsChunked {
+    /// Same as `get_multiple_accounts`, but splits the keys into requests of at most `chunk_size` keys, clamped to
+    /// the RPC limit of 100, which are sent concurrently. Accounts are returned in the order of the keys
+    async fn get_multiple_accounts_chunked(
+        &self,
+        pubkeys: &[Pubkey],
+        chunk_size: usize,
+    ) -> Result<Vec<Option<Account>>, ClientError>;
+}
+
+#[async_trait]
+impl GetMultipleAccountsChunked for RpcClient {
+    async fn get_multiple_accounts_chunked(
+        &self,
+        pubkeys: &[Pubkey],
+        chunk_size: usize,
+    ) -> Result<Vec<Option<Account>>, ClientError> {
+        let chunk_size = chunk_size.clamp(1, MAX_MULTIPLE_ACCOUNTS);
+
+        // Owned chunks keep the future `Send`
+        let chunks: Vec<Vec<Option<Account>>> = stream::iter(pubkeys.chunks(chunk_size).map(<[Pubkey]>::to_vec))
+            .map(|chunk| async move {
+                let accounts = self.get_multiple_accounts(&chunk).await?;
+                if accounts.len() != chunk.len() {
+                    return Err(ClientError::from(ClientErrorKind::Custom(format!(
+                        "{ERR_PREFIX}: requested {} accounts, got {}",
+                        chunk.len(),
+                        accounts.len()
+                    ))));
+                }
+                Ok(accounts)
+            })
+            .buffered(MAX_CONCURRENT_CHUNKS)
+            .try_collect()
+            .await?;
+
+        Ok(chunks.into_iter().flatten().collect())
+    }
+}
+
+#[async_trait]
+impl GetMultipleAccountsChunked for Arc<RpcClient> {
+    async fn get_multiple_accounts_chunked(
+        &self,
+        pubkeys: &[Pubkey],
+        chunk_size: usize,
+    ) -> Result<Vec<Option<Account>>, ClientError> {
+        self.as_ref().get_multiple_accounts_chunked(pubkeys, chunk_size).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn get_multiple_accounts_in_chunks() {
+        // The mock returns two missing accounts for every request
+        let client = RpcClient::new_mock("succeeds".to_string());
+        let pubkeys: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+
+        let accounts = client.get_multiple_accounts_chunked(&pubkeys, 2).await.unwrap();
+        assert_eq!(accounts, vec![None; 6]);
+        assert!(client.get_multiple_accounts_chunked(&pubkeys[..5], 2).await.is_err());
+        assert!(client.get_multiple_accounts_chunked(&[], 2).await.unwrap().is_empty());
+    }
+}
@@ -1 +1,8 @@
+pub mod accounts;
 pub mod lookup_tables;
+pub mod priority_fees;
+pub mod pubsub;
+pub mod simulate;
+pub mod token_accounts;
+
+const ERR_PREFIX: &str = "SolanaClientsExtension";
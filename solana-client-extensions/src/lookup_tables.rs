@@ -5,10 +5,11 @@ use solana_address_lookup_table_program::state::AddressLookupTable;
 use solana_client::{
     client_error::{ClientError, ClientErrorKind},
     nonblocking::rpc_client::RpcClient,
+    rpc_request::MAX_MULTIPLE_ACCOUNTS,
 };
 use solana_sdk::{address_lookup_table_account::AddressLookupTableAccount, message::v0, pubkey::Pubkey};
 
-const ERR_PREFIX: &str = "SolanaClientsExtension";
+use crate::{accounts::GetMultipleAccountsChunked, ERR_PREFIX};
 
 #[async_trait]
 pub trait LoadFromLookupTable {
@@ -58,7 +59,7 @@ impl LoadFromLookupTable for RpcClient {
             .collect();
 
         let accounts = self
-            .get_multiple_accounts(&address_table_lookup_addresses)
+            .get_multiple_accounts_chunked(&address_table_lookup_addresses, MAX_MULTIPLE_ACCOUNTS)
             .await?
             .into_iter()
             .collect::<Option<Vec<_>>>()
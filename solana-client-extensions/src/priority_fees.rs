@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use solana_client::{
+    client_error::ClientError, nonblocking::rpc_client::RpcClient, rpc_response::RpcPrioritizationFee,
+};
+use solana_sdk::pubkey::Pubkey;
+
+/// Default number of the latest slots the fees are aggregated over, the RPC returns at most 150
+pub const DEFAULT_PRIORITY_FEE_SLOTS: usize = 20;
+
+/// Percentile of the recent fees suggested as the compute-unit price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PriorityLevel {
+    Low,
+    #[default]
+    Medium,
+    High,
+    VeryHigh,
+}
+
+impl PriorityLevel {
+    pub fn percentile(&self) -> u8 {
+        match self {
+            PriorityLevel::Low => 25,
+            PriorityLevel::Medium => 50,
+            PriorityLevel::High => 75,
+            PriorityLevel::VeryHigh => 95,
+        }
+    }
+}
+
+/// Prioritization fees in micro-lamports per compute unit over the latest slots
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PriorityFeeEstimate {
+    /// Fees of the slots sorted in ascending order
+    fees: Vec<u64>,
+}
+
+impl PriorityFeeEstimate {
+    /// Estimate from the fees of the latest `max_slots` slots
+    pub fn from_fees(fees: &[RpcPrioritizationFee], max_slots: usize) -> Self {
+        let mut fees = fees.to_vec();
+        fees.sort_unstable_by_key(|fee| std::cmp::Reverse(fee.slot));
+        fees.truncate(max_slots);
+
+        let mut fees: Vec<u64> = fees.into_iter().map(|fee| fee.prioritization_fee).collect();
+        fees.sort_unstable();
+        Self { fees }
+    }
+
+    pub fn slots(&self) -> usize {
+        self.fees.len()
+    }
+
+    /// Nearest-rank percentile of the fees, 0 without fees
+    pub fn percentile(&self, percentile: u8) -> u64 {
+        if self.fees.is_empty() {
+            return 0;
+        }
+        let rank = (usize::from(percentile.min(100)) * self.fees.len()).div_ceil(100);
+        self.fees[rank.saturating_sub(1)]
+    }
+
+    pub fn min(&self) -> u64 {
+        self.fees.first().copied().unwrap_or_default()
+    }
+
+    pub fn max(&self) -> u64 {
+        self.fees.last().copied().unwrap_or_default()
+    }
+
+    /// Suggested compute-unit price for the priority level
+    pub fn compute_unit_price(&self, level: PriorityLevel) -> u64 {
+        self.percentile(level.percentile())
+    }
+}
+
+#[async_trait]
+pub trait PriorityFeeExt {
+    /// Fees paid in the latest `max_slots` slots by transactions locking the writable accounts, at most 128 accounts
+    /// are accepted by the RPC
+    async fn estimate_priority_fees(
+        &self,
+        writable_accounts: &[Pubkey],
+        max_slots: usize,
+    ) -> Result<PriorityFeeEstimate, ClientError>;
+
+    /// Compute-unit price in micro-lamports for a transaction locking the writable accounts
+    async fn suggest_compute_unit_price(
+        &self,
+        writable_accounts: &[Pubkey],
+        level: PriorityLevel,
+    ) -> Result<u64, ClientError> {
+        let estimate = self
+            .estimate_priority_fees(writable_accounts, DEFAULT_PRIORITY_FEE_SLOTS)
+            .await?;
+        Ok(estimate.compute_unit_price(level))
+    }
+}
+
+#[async_trait]
+impl PriorityFeeExt for RpcClient {
+    async fn estimate_priority_fees(
+        &self,
+        writable_accounts: &[Pubkey],
+        max_slots: usize,
+    ) -> Result<PriorityFeeEstimate, ClientError> {
+        let fees = self.get_recent_prioritization_fees(writable_accounts).await?;
+        Ok(PriorityFeeEstimate::from_fees(&fees, max_slots))
+    }
+}
+
+#[async_trait]
+impl PriorityFeeExt for Arc<RpcClient> {
+    async fn estimate_priority_fees(
+        &self,
+        writable_accounts: &[Pubkey],
+        max_slots: usize,
+    ) -> Result<PriorityFeeEstimate, ClientError> {
+        self.as_ref().estimate_priority_fees(writable_accounts, max_slots).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priority_fee_percentiles() {
+        // Slot 0 is the oldest and is left out
+        let fees: Vec<RpcPrioritizationFee> = (0..=100)
+            .map(|slot| RpcPrioritizationFee {
+                slot,
+                prioritization_fee: if slot == 0 { 1_000_000 } else { slot * 10 },
+            })
+            .rev()
+            .collect();
+        let estimate = PriorityFeeEstimate::from_fees(&fees, 100);
+
+        assert_eq!(estimate.slots(), 100);
+        assert_eq!(estimate.min(), 10);
+        assert_eq!(estimate.max(), 1000);
+        assert_eq!(estimate.compute_unit_price(PriorityLevel::Low), 250);
+        assert_eq!(estimate.compute_unit_price(PriorityLevel::Medium), 500);
+        assert_eq!(estimate.compute_unit_price(PriorityLevel::VeryHigh), 950);
+        assert_eq!(estimate.percentile(0), 10);
+
+        let empty = PriorityFeeEstimate::from_fees(&[], DEFAULT_PRIORITY_FEE_SLOTS);
+        assert_eq!(empty.compute_unit_price(PriorityLevel::High), 0);
+    }
+}
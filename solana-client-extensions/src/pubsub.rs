@@ -0,0 +1,245 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use borsh::BorshDeserialize;
+use futures::{channel::mpsc, stream::BoxStream, SinkExt, Stream, StreamExt};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcAccountInfoConfig, RpcSignatureSubscribeConfig},
+    rpc_response::RpcSignatureResult,
+};
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature, transaction::TransactionError};
+use tokio::task::JoinHandle;
+
+/// Number of notifications buffered for slow consumers
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Slots are notified every ~400ms, a connection without them for this long is considered dead
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Notifications of a subscription, the background task keeping it alive is stopped when it's dropped
+pub struct Subscription<T> {
+    receiver: mpsc::Receiver<T>,
+    task: JoinHandle<()>,
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Websocket subscriptions which reconnect and resubscribe with an exponential backoff when the connection fails.
+/// Slots are subscribed on the same connection as a heartbeat, it's reconnected once it's idle for `idle_timeout`
+#[derive(Debug, Clone)]
+pub struct ReconnectingPubsubClient {
+    url: String,
+    backoff: ExponentialBackoff,
+    idle_timeout: Duration,
+}
+
+impl ReconnectingPubsubClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            backoff: ExponentialBackoff {
+                max_interval: Duration::from_secs(30),
+                max_elapsed_time: None,
+                ..Default::default()
+            },
+            idle_timeout: IDLE_TIMEOUT,
+        }
+    }
+
+    pub fn with_backoff(mut self, backoff: ExponentialBackoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Borsh-deserialized data of the account on every change, the config's encoding is replaced with Base64.
+    /// Changes made while reconnecting are missed
+    pub fn subscribe_account<T: BorshDeserialize + Send + 'static>(
+        &self,
+        pubkey: Pubkey,
+        config: RpcAccountInfoConfig,
+    ) -> Subscription<io::Result<T>> {
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..config
+        };
+        let (mut sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let mut backoff = self.backoff.clone();
+        let url = self.url.clone();
+        let idle_timeout = self.idle_timeout;
+
+        let task = tokio::spawn(async move {
+            loop {
+                let client = connect(&url, &mut backoff).await;
+                match client.account_subscribe(&pubkey, Some(config.clone())).await {
+                    Ok((notifications, _unsubscribe)) => {
+                        backoff.reset();
+                        let mut events = with_heartbeat(&client, notifications).await;
+                        while let Some(response) = next_notification(&mut events, idle_timeout).await {
+                            if sender.send(decode_account(response.value.decode())).await.is_err() {
+                                return;
+                            }
+                        }
+                    },
+                    Err(error) => log::warn!("Failed to subscribe to account {pubkey}: {error}"),
+                }
+                wait(&mut backoff).await;
+            }
+        });
+        Subscription { receiver, task }
+    }
+
+    /// Single notification with the error of the transaction once it's processed with the commitment of the config,
+    /// the stream ends after it
+    pub fn subscribe_signature(
+        &self,
+        signature: Signature,
+        config: RpcSignatureSubscribeConfig,
+    ) -> Subscription<Option<TransactionError>> {
+        let (mut sender, receiver) = mpsc::channel(1);
+        let mut backoff = self.backoff.clone();
+        let url = self.url.clone();
+        let idle_timeout = self.idle_timeout;
+
+        let task = tokio::spawn(async move {
+            loop {
+                let client = connect(&url, &mut backoff).await;
+                match client.signature_subscribe(&signature, Some(config.clone())).await {
+                    Ok((notifications, _unsubscribe)) => {
+                        backoff.reset();
+                        let mut events = with_heartbeat(&client, notifications).await;
+                        while let Some(response) = next_notification(&mut events, idle_timeout).await {
+                            if let RpcSignatureResult::ProcessedSignature(result) = response.value {
+                                let _ = sender.send(result.err).await;
+                                return;
+                            }
+                        }
+                    },
+                    Err(error) => log::warn!("Failed to subscribe to signature {signature}: {error}"),
+                }
+                wait(&mut backoff).await;
+            }
+        });
+        Subscription { receiver, task }
+    }
+}
+
+async fn connect(url: &str, backoff: &mut ExponentialBackoff) -> PubsubClient {
+    loop {
+        match PubsubClient::new(url).await {
+            Ok(client) => return client,
+            Err(error) => {
+                log::warn!("Failed to connect to the pubsub endpoint: {error}");
+                wait(backoff).await
+            },
+        }
+    }
+}
+
+enum Event<T> {
+    Notification(T),
+    Heartbeat,
+    Closed,
+}
+
+/// Notifications merged with slot notifications of the connection, the connection is only watched by the idle
+/// timeout if slots can't be subscribed
+async fn with_heartbeat<'a, T: Send + 'a>(
+    client: &'a PubsubClient,
+    notifications: BoxStream<'a, T>,
+) -> BoxStream<'a, Event<T>> {
+    let notifications = notifications
+        .map(Event::Notification)
+        .chain(futures::stream::once(async { Event::Closed }));
+    match client.slot_subscribe().await {
+        Ok((slots, _unsubscribe)) => futures::stream::select(notifications, slots.map(|_| Event::Heartbeat)).boxed(),
+        Err(error) => {
+            log::warn!("Failed to subscribe to slots: {error}");
+            notifications.boxed()
+        },
+    }
+}
+
+/// `None` once the subscription is closed or nothing is received in `idle_timeout`
+async fn next_notification<T>(events: &mut BoxStream<'_, Event<T>>, idle_timeout: Duration) -> Option<T> {
+    loop {
+        match tokio::time::timeout(idle_timeout, events.next()).await {
+            Ok(Some(Event::Notification(notification))) => return Some(notification),
+            Ok(Some(Event::Heartbeat)) => continue,
+            Ok(Some(Event::Closed) | None) => {
+                log::warn!("Subscription is closed, reconnecting");
+                return None;
+            },
+            Err(_) => {
+                log::warn!("Nothing is received in {idle_timeout:?}, reconnecting");
+                return None;
+            },
+        }
+    }
+}
+
+async fn wait(backoff: &mut ExponentialBackoff) {
+    let delay = backoff.next_backoff().unwrap_or(backoff.max_interval);
+    tokio::time::sleep(delay).await;
+}
+
+fn decode_account<T: BorshDeserialize>(account: Option<Account>) -> io::Result<T> {
+    let account = account.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unable to decode the account"))?;
+    T::try_from_slice(&account.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_account_data() {
+        let account = Account {
+            data: borsh::to_vec(&(42u64, true)).unwrap(),
+            ..Account::default()
+        };
+        assert_eq!(
+            decode_account::<(u64, bool)>(Some(account.clone())).unwrap(),
+            (42, true)
+        );
+        assert!(decode_account::<(u64, bool, u8)>(Some(account)).is_err());
+        assert!(decode_account::<u64>(None).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reconnect_idle_subscription() {
+        let idle_timeout = Duration::from_secs(30);
+
+        let mut events = futures::stream::iter([Event::Heartbeat, Event::Notification(1), Event::Closed]).boxed();
+        assert_eq!(next_notification(&mut events, idle_timeout).await, Some(1));
+        assert_eq!(next_notification(&mut events, idle_timeout).await, None);
+
+        let mut events = futures::stream::pending::<Event<u64>>().boxed();
+        let started = tokio::time::Instant::now();
+        assert_eq!(next_notification(&mut events, idle_timeout).await, None);
+        assert_eq!(started.elapsed(), idle_timeout);
+    }
+}
@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_client::{
+    client_error::ClientError, nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig,
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey,
+    pubkey::Pubkey,
+    transaction::{TransactionError, VersionedTransaction},
+};
+
+use crate::lookup_tables::LoadFromLookupTable;
+
+/// Custom error of the SPL token programs for transfers above the balance
+const SPL_TOKEN_INSUFFICIENT_FUNDS: u32 = 1;
+const SPL_TOKEN_PROGRAMS: [Pubkey; 2] = [
+    pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+    pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"),
+];
+
+/// Outcome of a simulated transaction, failed simulations are `Ok` with `error` set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationResult {
+    pub units_consumed: u64,
+    pub error: Option<SimulationError>,
+    /// Program which set the return data and the data
+    pub return_data: Option<(Pubkey, Vec<u8>)>,
+    pub logs: Vec<String>,
+}
+
+impl SimulationResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Failure of the transaction, common errors have their own variants
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SimulationError {
+    #[error("blockhash not found")]
+    BlockhashNotFound,
+    #[error("insufficient funds for fee")]
+    InsufficientFundsForFee,
+    #[error("insufficient funds in instruction {index}")]
+    InsufficientFunds { index: u8 },
+    #[error("instruction {index} exceeded the compute budget")]
+    ComputeBudgetExceeded { index: u8 },
+    #[error("account not found")]
+    AccountNotFound,
+    /// Failure of other instructions, `message` is the failure log of the program where the error originated
+    #[error("instruction {index} of {} failed: {error}", program_id.map(|id| id.to_string()).unwrap_or_default())]
+    Instruction {
+        index: u8,
+        program_id: Option<Pubkey>,
+        error: InstructionError,
+        message: Option<String>,
+    },
+    #[error(transparent)]
+    Transaction(TransactionError),
+}
+
+/// Compute units and the failure message found in the simulation logs
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ParsedLogs {
+    /// Units consumed by top-level instructions
+    pub units_consumed: u64,
+    /// Innermost program which failed, i.e. where the error originated
+    pub failed_program: Option<Pubkey>,
+    pub failure_message: Option<String>,
+}
+
+/// Parses `Program <id> invoke [<depth>]`, `Program <id> consumed <units> of <limit> compute units` and
+/// `Program <id> failed: <message>` lines
+pub fn parse_logs(logs: &[String]) -> ParsedLogs {
+    let mut parsed = ParsedLogs::default();
+    let mut depth = 0usize;
+
+    for log in logs {
+        let Some(rest) = log.strip_prefix("Program ") else {
+            continue;
+        };
+        let Some((program, rest)) = rest.split_once(' ') else {
+            continue;
+        };
+
+        if rest.starts_with("invoke [") {
+            depth += 1;
+        } else if rest == "success" {
+            depth = depth.saturating_sub(1);
+        } else if let Some(message) = rest.strip_prefix("failed: ") {
+            depth = depth.saturating_sub(1);
+            if parsed.failure_message.is_none() {
+                parsed.failed_program = program.parse().ok();
+                parsed.failure_message = Some(message.to_owned());
+            }
+        } else if let Some(units) = rest.strip_prefix("consumed ") {
+            let units = units.split_once(' ').and_then(|(units, _)| units.parse::<u64>().ok());
+            if let (1, Some(units)) = (depth, units) {
+                parsed.units_consumed += units;
+            }
+        }
+    }
+    parsed
+}
+
+#[async_trait]
+pub trait SimulateExt {
+    /// Simulates the transaction without the signature check and with the latest blockhash
+    async fn simulate_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<SimulationResult, ClientError>;
+
+    async fn simulate_versioned_transaction_with_config(
+        &self,
+        transaction: &VersionedTransaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> Result<SimulationResult, ClientError>;
+}
+
+/// Program of the instruction, resolving it from lookup tables if it isn't one of the static keys
+async fn program_id(
+    client: &RpcClient,
+    transaction: &VersionedTransaction,
+    index: u8,
+) -> Result<Option<Pubkey>, ClientError> {
+    let message = &transaction.message;
+    let Some(instruction) = message.instructions().get(usize::from(index)) else {
+        return Ok(None);
+    };
+    let key_index = usize::from(instruction.program_id_index);
+    if let Some(key) = message.static_account_keys().get(key_index) {
+        return Ok(Some(*key));
+    }
+
+    let Some(lookups) = message.address_table_lookups() else {
+        return Ok(None);
+    };
+    let loaded = client.load_address_lookup_table_addresses(lookups).await?;
+    Ok(loaded
+        .writable
+        .iter()
+        .chain(&loaded.readonly)
+        .nth(key_index - message.static_account_keys().len())
+        .copied())
+}
+
+async fn simulation_error(
+    client: &RpcClient,
+    transaction: &VersionedTransaction,
+    error: TransactionError,
+    logs: &ParsedLogs,
+) -> Result<SimulationError, ClientError> {
+    Ok(match error {
+        TransactionError::BlockhashNotFound => SimulationError::BlockhashNotFound,
+        TransactionError::InsufficientFundsForFee => SimulationError::InsufficientFundsForFee,
+        TransactionError::AccountNotFound => SimulationError::AccountNotFound,
+        TransactionError::InstructionError(index, InstructionError::ComputationalBudgetExceeded) => {
+            SimulationError::ComputeBudgetExceeded { index }
+        },
+        TransactionError::InstructionError(index, InstructionError::InsufficientFunds) => {
+            SimulationError::InsufficientFunds { index }
+        },
+        TransactionError::InstructionError(index, InstructionError::Custom(SPL_TOKEN_INSUFFICIENT_FUNDS))
+            if logs
+                .failed_program
+                .is_some_and(|program| SPL_TOKEN_PROGRAMS.contains(&program)) =>
+        {
+            SimulationError::InsufficientFunds { index }
+        },
+        TransactionError::InstructionError(index, InstructionError::ProgramFailedToComplete)
+            if logs
+                .failure_message
+                .as_deref()
+                .is_some_and(|message| message.contains("exceeded CUs meter")) =>
+        {
+            SimulationError::ComputeBudgetExceeded { index }
+        },
+        TransactionError::InstructionError(index, error) => SimulationError::Instruction {
+            index,
+            program_id: program_id(client, transaction, index).await?,
+            error,
+            message: logs.failure_message.clone(),
+        },
+        error => SimulationError::Transaction(error),
+    })
+}
+
+fn return_data(result: &RpcSimulateTransactionResult) -> Option<(Pubkey, Vec<u8>)> {
+    let return_data = result.return_data.as_ref()?;
+    let program_id = return_data.program_id.parse().ok()?;
+    let data = STANDARD.decode(&return_data.data.0).ok()?;
+    Some((program_id, data))
+}
+
+#[async_trait]
+impl SimulateExt for RpcClient {
+    async fn simulate_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<SimulationResult, ClientError> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(self.commitment()),
+            ..Default::default()
+        };
+        self.simulate_versioned_transaction_with_config(transaction, config)
+            .await
+    }
+
+    async fn simulate_versioned_transaction_with_config(
+        &self,
+        transaction: &VersionedTransaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> Result<SimulationResult, ClientError> {
+        let result = self.simulate_transaction_with_config(transaction, config).await?.value;
+
+        let logs = result.logs.clone().unwrap_or_default();
+        let parsed = parse_logs(&logs);
+        let error = match result.err.clone() {
+            Some(error) => Some(simulation_error(self, transaction, error, &parsed).await?),
+            None => None,
+        };
+
+        Ok(SimulationResult {
+            units_consumed: result.units_consumed.unwrap_or(parsed.units_consumed),
+            error,
+            return_data: return_data(&result),
+            logs,
+        })
+    }
+}
+
+#[async_trait]
+impl SimulateExt for Arc<RpcClient> {
+    async fn simulate_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> Result<SimulationResult, ClientError> {
+        self.as_ref().simulate_versioned_transaction(transaction).await
+    }
+
+    async fn simulate_versioned_transaction_with_config(
+        &self,
+        transaction: &VersionedTransaction,
+        config: RpcSimulateTransactionConfig,
+    ) -> Result<SimulationResult, ClientError> {
+        self.as_ref()
+            .simulate_versioned_transaction_with_config(transaction, config)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_simulation_logs() {
+        let logs: Vec<String> = [
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 invoke [1]",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+            "Program log: Error: insufficient funds",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA consumed 4645 of 180000 compute units",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA failed: custom program error: 0x1",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 consumed 20000 of 200000 compute units",
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 failed: custom program error: 0x1",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        assert_eq!(parse_logs(&logs), ParsedLogs {
+            units_consumed: 20000,
+            failed_program: Some(SPL_TOKEN_PROGRAMS[0]),
+            failure_message: Some("custom program error: 0x1".to_owned()),
+        });
+        assert_eq!(parse_logs(&[]), ParsedLogs::default());
+    }
+}
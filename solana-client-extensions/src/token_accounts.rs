@@ -0,0 +1,263 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+};
+use solana_sdk::{account::Account, clock::Epoch, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use spl_token_2022::{
+    extension::{transfer_fee, BaseState, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::{self, AccountState},
+};
+
+use crate::ERR_PREFIX;
+
+const ONE_IN_BASIS_POINTS: u128 = 10_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum UnpackTokenError {
+    #[error("{0} isn't owned by a token program")]
+    NotTokenProgram(Pubkey),
+    #[error("unable to unpack {0}: {1}")]
+    InvalidData(Pubkey, ProgramError),
+}
+
+impl From<UnpackTokenError> for ClientError {
+    fn from(value: UnpackTokenError) -> Self {
+        ClientError::from(ClientErrorKind::Custom(format!("{ERR_PREFIX}: {value}")))
+    }
+}
+
+/// Token account of the classic or Token-2022 program
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedTokenAccount {
+    pub address: Pubkey,
+    pub program_id: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub delegate: Option<Pubkey>,
+    pub delegated_amount: u64,
+    pub state: AccountState,
+    /// Rent-exempt reserve of wrapped SOL accounts
+    pub is_native: Option<u64>,
+    pub close_authority: Option<Pubkey>,
+    pub extensions: Vec<ExtensionType>,
+}
+
+/// Mint of the classic or Token-2022 program
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedMint {
+    pub address: Pubkey,
+    pub program_id: Pubkey,
+    pub mint_authority: Option<Pubkey>,
+    pub supply: u64,
+    pub decimals: u8,
+    pub freeze_authority: Option<Pubkey>,
+    pub transfer_fee: Option<TransferFeeConfig>,
+    pub extensions: Vec<ExtensionType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFee {
+    /// First epoch where the fee takes effect
+    pub epoch: Epoch,
+    pub maximum_fee: u64,
+    pub basis_points: u16,
+}
+
+impl TransferFee {
+    /// Fee withheld from the transfer of the amount, rounded up
+    pub fn calculate_fee(&self, amount: u64) -> u64 {
+        let fee = (u128::from(amount) * u128::from(self.basis_points)).div_ceil(ONE_IN_BASIS_POINTS);
+        u64::try_from(fee).unwrap_or(u64::MAX).min(self.maximum_fee)
+    }
+}
+
+impl From<&transfer_fee::TransferFee> for TransferFee {
+    fn from(value: &transfer_fee::TransferFee) -> Self {
+        Self {
+            epoch: value.epoch.into(),
+            maximum_fee: value.maximum_fee.into(),
+            basis_points: value.transfer_fee_basis_points.into(),
+        }
+    }
+}
+
+/// Transfer fee extension of Token-2022 mints, the newer fee replaces the older one from its epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferFeeConfig {
+    pub older: TransferFee,
+    pub newer: TransferFee,
+}
+
+impl TransferFeeConfig {
+    pub fn fee(&self, epoch: Epoch) -> &TransferFee {
+        if epoch >= self.newer.epoch {
+            &self.newer
+        } else {
+            &self.older
+        }
+    }
+
+    pub fn calculate_fee(&self, epoch: Epoch, amount: u64) -> u64 {
+        self.fee(epoch).calculate_fee(amount)
+    }
+}
+
+fn unpack<S: BaseState + Pack>(
+    address: &Pubkey,
+    account: &Account,
+) -> Result<(S, Option<TransferFeeConfig>, Vec<ExtensionType>), UnpackTokenError> {
+    spl_token_2022::check_spl_token_program_account(&account.owner)
+        .map_err(|_| UnpackTokenError::NotTokenProgram(*address))?;
+    let state = StateWithExtensions::<S>::unpack(&account.data)
+        .map_err(|error| UnpackTokenError::InvalidData(*address, error))?;
+
+    let transfer_fee = state
+        .get_extension::<transfer_fee::TransferFeeConfig>()
+        .ok()
+        .map(|config| TransferFeeConfig {
+            older: (&config.older_transfer_fee).into(),
+            newer: (&config.newer_transfer_fee).into(),
+        });
+    let extensions = state.get_extension_types().unwrap_or_default();
+    Ok((state.base, transfer_fee, extensions))
+}
+
+impl ParsedTokenAccount {
+    pub fn unpack(address: &Pubkey, account: &Account) -> Result<Self, UnpackTokenError> {
+        let (base, _, extensions) = unpack::<state::Account>(address, account)?;
+        Ok(Self {
+            address: *address,
+            program_id: account.owner,
+            mint: base.mint,
+            owner: base.owner,
+            amount: base.amount,
+            delegate: base.delegate.into(),
+            delegated_amount: base.delegated_amount,
+            state: base.state,
+            is_native: base.is_native.into(),
+            close_authority: base.close_authority.into(),
+            extensions,
+        })
+    }
+}
+
+impl ParsedMint {
+    pub fn unpack(address: &Pubkey, account: &Account) -> Result<Self, UnpackTokenError> {
+        let (base, transfer_fee, extensions) = unpack::<state::Mint>(address, account)?;
+        Ok(Self {
+            address: *address,
+            program_id: account.owner,
+            mint_authority: base.mint_authority.into(),
+            supply: base.supply,
+            decimals: base.decimals,
+            freeze_authority: base.freeze_authority.into(),
+            transfer_fee,
+            extensions,
+        })
+    }
+}
+
+#[async_trait]
+pub trait TokenAccountsExt {
+    /// `None` if the account doesn't exist, an error if it isn't a token account
+    async fn get_parsed_token_account(&self, address: &Pubkey) -> Result<Option<ParsedTokenAccount>, ClientError>;
+
+    /// `None` if the account doesn't exist, an error if it isn't a mint
+    async fn get_parsed_mint(&self, address: &Pubkey) -> Result<Option<ParsedMint>, ClientError>;
+}
+
+#[async_trait]
+impl TokenAccountsExt for RpcClient {
+    async fn get_parsed_token_account(&self, address: &Pubkey) -> Result<Option<ParsedTokenAccount>, ClientError> {
+        self.get_account_with_commitment(address, self.commitment())
+            .await?
+            .value
+            .map(|account| ParsedTokenAccount::unpack(address, &account))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    async fn get_parsed_mint(&self, address: &Pubkey) -> Result<Option<ParsedMint>, ClientError> {
+        self.get_account_with_commitment(address, self.commitment())
+            .await?
+            .value
+            .map(|account| ParsedMint::unpack(address, &account))
+            .transpose()
+            .map_err(Into::into)
+    }
+}
+
+#[async_trait]
+impl TokenAccountsExt for Arc<RpcClient> {
+    async fn get_parsed_token_account(&self, address: &Pubkey) -> Result<Option<ParsedTokenAccount>, ClientError> {
+        self.as_ref().get_parsed_token_account(address).await
+    }
+
+    async fn get_parsed_mint(&self, address: &Pubkey) -> Result<Option<ParsedMint>, ClientError> {
+        self.as_ref().get_parsed_mint(address).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::program_option::COption;
+
+    use super::*;
+
+    #[test]
+    fn unpack_classic_accounts() {
+        let mint = state::Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: 1_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let mut data = vec![0; state::Mint::LEN];
+        mint.pack_into_slice(&mut data);
+        let account = Account {
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        };
+
+        let address = Pubkey::new_unique();
+        let parsed = ParsedMint::unpack(&address, &account).unwrap();
+        assert_eq!(parsed.supply, 1_000_000);
+        assert_eq!(parsed.decimals, 6);
+        assert_eq!(parsed.mint_authority, mint.mint_authority.into());
+        assert_eq!(parsed.transfer_fee, None);
+        assert!(parsed.extensions.is_empty());
+
+        assert!(ParsedTokenAccount::unpack(&address, &account).is_err());
+        let foreign = Account {
+            owner: Pubkey::new_unique(),
+            ..account
+        };
+        assert!(ParsedMint::unpack(&address, &foreign).is_err());
+    }
+
+    #[test]
+    fn transfer_fees() {
+        let config = TransferFeeConfig {
+            older: TransferFee {
+                epoch: 0,
+                maximum_fee: 1_000,
+                basis_points: 100,
+            },
+            newer: TransferFee {
+                epoch: 10,
+                maximum_fee: 50,
+                basis_points: 50,
+            },
+        };
+        assert_eq!(config.calculate_fee(5, 1_001), 11);
+        assert_eq!(config.calculate_fee(5, 1_000_000), 1_000);
+        assert_eq!(config.calculate_fee(10, 1_000), 5);
+        assert_eq!(config.calculate_fee(11, 1_000_000), 50);
+    }
+}
@@ -0,0 +1,328 @@
+//! Integer amounts of tokens in the smallest units together with the decimals of the token
+
+use normdecimal::NormDecimal;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueFormat, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
+use std::{fmt, fmt::Formatter, str::FromStr};
+
+const NUMERIC_BASE_DIGITS: usize = 4;
+const NUMERIC_POSITIVE: u16 = 0x0000;
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid token amount")]
+pub struct TokenAmountParseError;
+
+/// Amount of the token in the smallest units, e.g. lamports or wei, so that `raw = 1500000, decimals = 6` is 1.5 USDC.
+/// Displayed, serialized and stored in Postgres `NUMERIC` columns as the decimal number with exactly `decimals`
+/// fractional digits, e.g. `"1.500000"`, so that the decimals survive the roundtrip
+#[derive(SerializeDisplay, DeserializeFromStr, Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct TokenAmount {
+    pub raw: u128,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(raw: u128, decimals: u8) -> Self {
+        Self { raw, decimals }
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Self::new(0, decimals)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw == 0
+    }
+
+    /// `None` if the amount has more fractional digits than `decimals`, is negative or doesn't fit
+    pub fn from_decimal(amount: NormDecimal, decimals: u8) -> Option<Self> {
+        if amount.is_sign_negative() && !amount.is_zero() {
+            return None;
+        }
+        let scale = amount.scale();
+        if scale > u32::from(decimals) {
+            return None;
+        }
+
+        let mantissa = u128::try_from(amount.mantissa()).ok()?;
+        let raw = mantissa.checked_mul(10u128.checked_pow(u32::from(decimals) - scale)?)?;
+        Some(Self::new(raw, decimals))
+    }
+
+    /// `None` if the amount doesn't fit `NormDecimal`, i.e. it has more than 28 decimals or 96 bits
+    pub fn to_decimal(&self) -> Option<NormDecimal> {
+        // Parsing rounds the digits which don't fit
+        let amount = self.to_string().parse().ok()?;
+        (Self::from_decimal(amount, self.decimals)? == *self).then_some(amount)
+    }
+
+    /// Same amount with other decimals, `None` if it loses precision or overflows
+    pub fn rescale(&self, decimals: u8) -> Option<Self> {
+        let raw = if decimals >= self.decimals {
+            self.raw
+                .checked_mul(10u128.checked_pow(u32::from(decimals - self.decimals))?)?
+        } else {
+            // every non-zero amount is less than the divisor which doesn't fit
+            let Some(divisor) = 10u128.checked_pow(u32::from(self.decimals - decimals)) else {
+                return (self.raw == 0).then(|| Self::zero(decimals));
+            };
+            if !self.raw.is_multiple_of(divisor) {
+                return None;
+            }
+            self.raw / divisor
+        };
+        Some(Self::new(raw, decimals))
+    }
+
+    /// `None` on overflow or if the decimals differ
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        Some(Self::new(self.raw.checked_add(other.raw)?, self.decimals))
+    }
+
+    /// `None` on underflow or if the decimals differ
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        if self.decimals != other.decimals {
+            return None;
+        }
+        Some(Self::new(self.raw.checked_sub(other.raw)?, self.decimals))
+    }
+
+    pub fn checked_mul(&self, factor: u128) -> Option<Self> {
+        Some(Self::new(self.raw.checked_mul(factor)?, self.decimals))
+    }
+
+    /// Rounds down
+    pub fn checked_div(&self, divisor: u128) -> Option<Self> {
+        Some(Self::new(self.raw.checked_div(divisor)?, self.decimals))
+    }
+
+    /// Binary form of Postgres `NUMERIC`: the number of digits, the weight of the first digit, the sign, the display
+    /// scale and the digits in base 10000
+    fn to_pg_numeric(self) -> Vec<u8> {
+        let string = self.raw.to_string();
+        let decimals = usize::from(self.decimals);
+        let (integer, fraction) = if string.len() > decimals {
+            string.split_at(string.len() - decimals)
+        } else {
+            ("", string.as_str())
+        };
+
+        let integer = format!(
+            "{}{integer}",
+            "0".repeat((NUMERIC_BASE_DIGITS - integer.len() % NUMERIC_BASE_DIGITS) % NUMERIC_BASE_DIGITS)
+        );
+        let fraction = format!("{}{fraction}", "0".repeat(decimals - fraction.len()));
+        let fraction = format!(
+            "{fraction}{}",
+            "0".repeat((NUMERIC_BASE_DIGITS - fraction.len() % NUMERIC_BASE_DIGITS) % NUMERIC_BASE_DIGITS)
+        );
+
+        let digits: Vec<i16> = format!("{integer}{fraction}")
+            .as_bytes()
+            .chunks(NUMERIC_BASE_DIGITS)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap().parse().unwrap())
+            .collect();
+        let mut weight = (integer.len() / NUMERIC_BASE_DIGITS) as i16 - 1;
+
+        let leading_zeros = digits.iter().take_while(|digit| **digit == 0).count();
+        weight -= leading_zeros as i16;
+        let digits = &digits[leading_zeros..];
+        let trailing_zeros = digits.iter().rev().take_while(|digit| **digit == 0).count();
+        let digits = &digits[..digits.len() - trailing_zeros];
+        if digits.is_empty() {
+            weight = 0;
+        }
+
+        let mut buf = Vec::with_capacity(8 + digits.len() * 2);
+        buf.extend_from_slice(&(digits.len() as i16).to_be_bytes());
+        buf.extend_from_slice(&weight.to_be_bytes());
+        buf.extend_from_slice(&NUMERIC_POSITIVE.to_be_bytes());
+        buf.extend_from_slice(&u16::from(self.decimals).to_be_bytes());
+        for digit in digits {
+            buf.extend_from_slice(&digit.to_be_bytes());
+        }
+        buf
+    }
+
+    fn from_pg_numeric(bytes: &[u8]) -> Option<Self> {
+        let read = |index: usize| bytes.get(index * 2..index * 2 + 2).map(|bytes| [bytes[0], bytes[1]]);
+        let ndigits = i16::from_be_bytes(read(0)?);
+        let weight = i16::from_be_bytes(read(1)?);
+        let sign = u16::from_be_bytes(read(2)?);
+        let decimals = u8::try_from(u16::from_be_bytes(read(3)?)).ok()?;
+        if sign != NUMERIC_POSITIVE {
+            return None;
+        }
+
+        let mut raw = 0u128;
+        for index in 0..usize::try_from(ndigits).ok()? {
+            let digit = u128::try_from(i16::from_be_bytes(read(4 + index)?)).ok()?;
+            let exponent = (i32::from(weight) - index as i32) * NUMERIC_BASE_DIGITS as i32 + i32::from(decimals);
+            let value = if exponent >= 0 {
+                digit.checked_mul(10u128.checked_pow(exponent as u32)?)?
+            } else {
+                let divisor = 10u128.checked_pow(exponent.unsigned_abs())?;
+                if !digit.is_multiple_of(divisor) {
+                    return None;
+                }
+                digit / divisor
+            };
+            raw = raw.checked_add(value)?;
+        }
+        Some(Self::new(raw, decimals))
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let decimals = usize::from(self.decimals);
+        if decimals == 0 {
+            return write!(f, "{}", self.raw);
+        }
+
+        let digits = format!("{:0>width$}", self.raw, width = decimals + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - decimals);
+        write!(f, "{integer}.{fraction}")
+    }
+}
+
+impl FromStr for TokenAmount {
+    type Err = TokenAmountParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (integer, fraction) = match s.split_once('.') {
+            Some((_, "")) => return Err(TokenAmountParseError),
+            Some(parts) => parts,
+            None => (s, ""),
+        };
+        if integer.is_empty()
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|char| char.is_ascii_digit())
+        {
+            return Err(TokenAmountParseError);
+        }
+
+        let decimals = u8::try_from(fraction.len()).map_err(|_| TokenAmountParseError)?;
+        let raw = format!("{integer}{fraction}")
+            .parse()
+            .map_err(|_| TokenAmountParseError)?;
+        Ok(Self::new(raw, decimals))
+    }
+}
+
+impl Type<Postgres> for TokenAmount {
+    fn type_info() -> PgTypeInfo {
+        <NormDecimal as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <NormDecimal as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for TokenAmount {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        buf.extend_from_slice(&self.to_pg_numeric());
+        IsNull::No
+    }
+}
+
+impl Decode<'_, Postgres> for TokenAmount {
+    fn decode(value: PgValueRef<'_>) -> Result<Self, BoxDynError> {
+        match value.format() {
+            PgValueFormat::Binary => {
+                Self::from_pg_numeric(value.as_bytes()?).ok_or_else(|| Box::new(TokenAmountParseError) as BoxDynError)
+            },
+            PgValueFormat::Text => Ok(value.as_str()?.parse()?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_and_parse() {
+        for (amount, string) in [
+            (TokenAmount::new(1_500_000, 6), "1.500000"),
+            (TokenAmount::new(5, 9), "0.000000005"),
+            (TokenAmount::new(42, 0), "42"),
+            (
+                TokenAmount::new(u128::MAX, 18),
+                "340282366920938463463.374607431768211455",
+            ),
+        ] {
+            assert_eq!(amount.to_string(), string);
+            assert_eq!(string.parse::<TokenAmount>().unwrap(), amount);
+            assert_eq!(serde_json::to_string(&amount).unwrap(), format!("\"{string}\""));
+        }
+
+        for invalid in ["", "-1.5", "1.", ".5", "1e5", "+1"] {
+            assert!(invalid.parse::<TokenAmount>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn decimal_conversions() {
+        let amount = TokenAmount::new(1_500_000, 6);
+        assert_eq!(amount.to_decimal().unwrap(), "1.5".parse().unwrap());
+        assert_eq!(TokenAmount::from_decimal("1.5".parse().unwrap(), 6).unwrap(), amount);
+        assert_eq!(TokenAmount::from_decimal("1.0000001".parse().unwrap(), 6), None);
+        assert_eq!(TokenAmount::from_decimal("-1".parse().unwrap(), 6), None);
+        assert_eq!(TokenAmount::new(u128::MAX, 18).to_decimal(), None);
+
+        assert_eq!(amount.rescale(9).unwrap(), TokenAmount::new(1_500_000_000, 9));
+        assert_eq!(amount.rescale(1).unwrap(), TokenAmount::new(15, 1));
+        assert_eq!(amount.rescale(0), None);
+
+        // 10^39 doesn't fit u128
+        assert_eq!(TokenAmount::new(u128::MAX, 39).rescale(0), None);
+        assert_eq!(TokenAmount::zero(39).rescale(0), Some(TokenAmount::zero(0)));
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        let amount = TokenAmount::new(1_500_000, 6);
+        assert_eq!(amount.checked_add(&amount).unwrap(), TokenAmount::new(3_000_000, 6));
+        assert_eq!(
+            amount.checked_sub(&TokenAmount::new(500_000, 6)).unwrap(),
+            TokenAmount::new(1_000_000, 6)
+        );
+        assert_eq!(amount.checked_sub(&TokenAmount::new(2_000_000, 6)), None);
+        assert_eq!(amount.checked_add(&TokenAmount::new(1, 9)), None);
+        assert_eq!(amount.checked_mul(3).unwrap(), TokenAmount::new(4_500_000, 6));
+        assert_eq!(amount.checked_div(4).unwrap(), TokenAmount::new(375_000, 6));
+        assert_eq!(amount.checked_div(0), None);
+    }
+
+    #[test]
+    fn pg_numeric_roundtrip() {
+        // 12.5 with the display scale 1 is the digits 12 and 5000 with the weight 0
+        assert_eq!(TokenAmount::new(125, 1).to_pg_numeric(), [
+            0, 2, 0, 0, 0, 0, 0, 1, 0, 12, 0x13, 0x88
+        ]);
+        assert_eq!(TokenAmount::zero(6).to_pg_numeric(), [0, 0, 0, 0, 0, 0, 0, 6]);
+
+        for amount in [
+            TokenAmount::new(1_500_000, 6),
+            TokenAmount::new(5, 9),
+            TokenAmount::new(10_000_000_000, 0),
+            TokenAmount::new(u128::MAX, 18),
+            TokenAmount::new(u128::MAX, 0),
+            TokenAmount::zero(18),
+        ] {
+            assert_eq!(TokenAmount::from_pg_numeric(&amount.to_pg_numeric()).unwrap(), amount);
+        }
+    }
+}
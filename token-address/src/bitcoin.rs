@@ -0,0 +1,105 @@
+//! Mainnet Bitcoin addresses: legacy P2PKH and P2SH ones in Base58Check and SegWit ones in Bech32/Bech32m
+
+use crate::db::TokenAddressParseError;
+use bech32::{FromBase32, Variant};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use sha2::{Digest, Sha256};
+use std::{fmt, fmt::Formatter, str::FromStr};
+
+const P2PKH_VERSION: u8 = 0x00;
+const P2SH_VERSION: u8 = 0x05;
+const SEGWIT_HRP: &str = "bc";
+
+/// Validated Bitcoin address, SegWit addresses are kept in lowercase
+#[derive(SerializeDisplay, DeserializeFromStr, Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct BitcoinAddress(String);
+
+impl BitcoinAddress {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn is_valid_base58(s: &str) -> bool {
+        let Ok(bytes) = bs58::decode(s).into_vec() else {
+            return false;
+        };
+        if bytes.len() != 25 || ![P2PKH_VERSION, P2SH_VERSION].contains(&bytes[0]) {
+            return false;
+        }
+
+        let (payload, checksum) = bytes.split_at(21);
+        Sha256::digest(Sha256::digest(payload))[..4] == *checksum
+    }
+
+    fn is_valid_segwit(s: &str) -> bool {
+        let Ok((hrp, data, variant)) = bech32::decode(s) else {
+            return false;
+        };
+        let Some((version, program)) = data.split_first() else {
+            return false;
+        };
+        let Ok(program) = Vec::<u8>::from_base32(program) else {
+            return false;
+        };
+
+        hrp == SEGWIT_HRP
+            && match version.to_u8() {
+                0 => variant == Variant::Bech32 && [20, 32].contains(&program.len()),
+                1..=16 => variant == Variant::Bech32m && (2..=40).contains(&program.len()),
+                _ => false,
+            }
+    }
+}
+
+impl fmt::Display for BitcoinAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for BitcoinAddress {
+    type Err = TokenAddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if Self::is_valid_segwit(s) {
+            Ok(Self(s.to_lowercase()))
+        } else if Self::is_valid_base58(s) {
+            Ok(Self(s.to_owned()))
+        } else {
+            Err(TokenAddressParseError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bitcoin_addresses() {
+        for address in [
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ] {
+            assert_eq!(address.parse::<BitcoinAddress>().unwrap().as_str(), address);
+        }
+
+        assert_eq!(
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4"
+                .parse::<BitcoinAddress>()
+                .unwrap()
+                .as_str(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        // bad checksum, testnet and Bech32m checksum on a v0 program
+        for address in [
+            "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb",
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+        ] {
+            assert!(address.parse::<BitcoinAddress>().is_err(), "{address}");
+        }
+    }
+}
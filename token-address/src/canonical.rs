@@ -0,0 +1,222 @@
+//! Chain-qualified form of token addresses in the style of CAIP-19, e.g. `solana:So11...`,
+//! `eip155:1:0xc02a...`, `eip155:137:native`, `bip122:000000000019d6689c085ae165831e93:native` and `tvm:-239:0:83df...`
+
+use crate::{db::TokenAddressParseError, ChainId, StoredTokenAddress, TokenAddress};
+use primitive_types::H160;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{
+    database::{HasArguments, HasValueRef},
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::PgRow,
+    Database, Decode, Encode, Error, FromRow, Row, Type,
+};
+use std::{fmt, fmt::Formatter, str::FromStr};
+
+const SOLANA_NAMESPACE: &str = "solana";
+const EVM_NAMESPACE: &str = "eip155";
+/// Namespace and the genesis block hash prefix of Bitcoin mainnet
+const BITCOIN_CHAIN: &str = "bip122:000000000019d6689c085ae165831e93";
+/// Namespace and the global id of TON mainnet
+const TON_CHAIN: &str = "tvm:-239";
+const NATIVE: &str = "native";
+
+/// `TokenAddress` which is displayed and stored in the canonical form, unlike the bare addresses of `TokenAddress`
+/// it distinguishes native coins of all chains
+#[derive(SerializeDisplay, DeserializeFromStr, Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CanonicalTokenAddress(pub TokenAddress);
+
+impl From<TokenAddress> for CanonicalTokenAddress {
+    fn from(value: TokenAddress) -> Self {
+        Self(value)
+    }
+}
+
+impl From<CanonicalTokenAddress> for TokenAddress {
+    fn from(value: CanonicalTokenAddress) -> Self {
+        value.0
+    }
+}
+
+impl From<StoredTokenAddress> for CanonicalTokenAddress {
+    fn from(value: StoredTokenAddress) -> Self {
+        Self(value.into())
+    }
+}
+
+impl fmt::Display for CanonicalTokenAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let chain = self.0.platform();
+        match chain {
+            ChainId::Solana => write!(f, "{SOLANA_NAMESPACE}:")?,
+            ChainId::Bitcoin => write!(f, "{BITCOIN_CHAIN}:")?,
+            ChainId::Ton => write!(f, "{TON_CHAIN}:")?,
+            _ => write!(f, "{EVM_NAMESPACE}:{}:", chain.evm_chain_id().unwrap_or_default())?,
+        }
+
+        match &self.0 {
+            TokenAddress::Spl(pubkey) => write!(f, "{pubkey}"),
+            TokenAddress::Erc20(address) | TokenAddress::Evm(_, address) => write!(f, "0x{address:x}"),
+            TokenAddress::Native(_) => f.write_str(NATIVE),
+            TokenAddress::Bitcoin(address) => write!(f, "{address}"),
+            TokenAddress::Ton(address) => write!(f, "{address}"),
+        }
+    }
+}
+
+impl FromStr for CanonicalTokenAddress {
+    type Err = TokenAddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(address) = s.strip_prefix(BITCOIN_CHAIN).and_then(|s| s.strip_prefix(':')) {
+            return Ok(Self(match address {
+                NATIVE => TokenAddress::Native(ChainId::Bitcoin),
+                address => TokenAddress::Bitcoin(address.parse()?),
+            }));
+        }
+        if let Some(address) = s.strip_prefix(TON_CHAIN).and_then(|s| s.strip_prefix(':')) {
+            return Ok(Self(match address {
+                NATIVE => TokenAddress::Native(ChainId::Ton),
+                address => TokenAddress::Ton(address.parse()?),
+            }));
+        }
+
+        let (namespace, rest) = s.split_once(':').ok_or(TokenAddressParseError)?;
+        let (chain, address) = match namespace {
+            SOLANA_NAMESPACE => (ChainId::Solana, rest),
+            EVM_NAMESPACE => {
+                let (chain_id, address) = rest.split_once(':').ok_or(TokenAddressParseError)?;
+                let chain_id = chain_id.parse().map_err(|_| TokenAddressParseError)?;
+                (
+                    ChainId::from_evm_chain_id(chain_id).ok_or(TokenAddressParseError)?,
+                    address,
+                )
+            },
+            _ => return Err(TokenAddressParseError),
+        };
+
+        let address = match (chain, address) {
+            (_, NATIVE) => TokenAddress::Native(chain),
+            (ChainId::Solana, address) => {
+                TokenAddress::Spl(Pubkey::from_str(address).map_err(|_| TokenAddressParseError)?)
+            },
+            (_, address) if address.starts_with("0x") => {
+                TokenAddress::evm(chain, H160::from_str(address).map_err(|_| TokenAddressParseError)?)
+                    .ok_or(TokenAddressParseError)?
+            },
+            _ => return Err(TokenAddressParseError),
+        };
+        Ok(Self(address))
+    }
+}
+
+impl TokenAddress {
+    /// Canonical form of the address, parsed by `CanonicalTokenAddress`
+    pub fn to_canonical_string(&self) -> String {
+        CanonicalTokenAddress(self.clone()).to_string()
+    }
+}
+
+impl StoredTokenAddress {
+    /// Canonical form of the address, parsed by `CanonicalTokenAddress`
+    pub fn to_canonical_string(&self) -> String {
+        CanonicalTokenAddress::from(self.clone()).to_string()
+    }
+}
+
+impl<DB> Type<DB> for CanonicalTokenAddress
+where
+    DB: Database,
+    String: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'q, DB> Encode<'q, DB> for CanonicalTokenAddress
+where
+    DB: Database,
+    String: Encode<'q, DB>,
+{
+    fn encode_by_ref(&self, buf: &mut <DB as HasArguments<'q>>::ArgumentBuffer) -> IsNull {
+        <String as Encode<DB>>::encode(self.to_string(), buf)
+    }
+}
+
+/// Falls back to the form of `StoredTokenAddress` so that columns can be migrated to the canonical form gradually
+impl<'r, DB> Decode<'r, DB> for CanonicalTokenAddress
+where
+    DB: Database,
+    String: Decode<'r, DB>,
+{
+    fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<Self, BoxDynError> {
+        let s = <String as Decode<DB>>::decode(value)?;
+        let token_address = CanonicalTokenAddress::from_str(&s)
+            .or_else(|_| StoredTokenAddress::from_str(&s).map(CanonicalTokenAddress::from))
+            .map_err(|e| Box::new(e) as BoxDynError)?;
+        Ok(token_address)
+    }
+}
+
+impl FromRow<'_, PgRow> for CanonicalTokenAddress {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        row.try_get(0)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for CanonicalTokenAddress {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, Error> {
+        row.try_get(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_roundtrip() {
+        let pubkey = Pubkey::new_unique();
+        let address = H160::random();
+        for (token, canonical) in [
+            (TokenAddress::Spl(pubkey), format!("solana:{pubkey}")),
+            (TokenAddress::Native(ChainId::Solana), "solana:native".to_owned()),
+            (TokenAddress::Erc20(address), format!("eip155:1:0x{address:x}")),
+            (
+                TokenAddress::evm(ChainId::Polygon, address).unwrap(),
+                format!("eip155:137:0x{address:x}"),
+            ),
+            (TokenAddress::Native(ChainId::Bsc), "eip155:56:native".to_owned()),
+            (
+                TokenAddress::Native(ChainId::Bitcoin),
+                "bip122:000000000019d6689c085ae165831e93:native".to_owned(),
+            ),
+            (
+                TokenAddress::Ton(format!("0:{}", "ab".repeat(32)).parse().unwrap()),
+                format!("tvm:-239:0:{}", "ab".repeat(32)),
+            ),
+        ] {
+            assert_eq!(token.to_canonical_string(), canonical);
+            assert_eq!(canonical.parse::<CanonicalTokenAddress>().unwrap().0, token);
+        }
+
+        assert_eq!(
+            StoredTokenAddress::evm(ChainId::Base, address)
+                .unwrap()
+                .to_canonical_string(),
+            format!("eip155:8453:0x{address:x}")
+        );
+        assert!("eip155:10:native".parse::<CanonicalTokenAddress>().is_err());
+        assert!("eip155:1:abc".parse::<CanonicalTokenAddress>().is_err());
+        assert!(format!("polygon:0x{address:x}")
+            .parse::<CanonicalTokenAddress>()
+            .is_err());
+    }
+}
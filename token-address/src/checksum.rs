@@ -0,0 +1,69 @@
+//! EIP-55 mixed-case checksums of Ethereum addresses
+
+use primitive_types::H160;
+use rustc_hex::ToHex;
+use sha3::{Digest, Keccak256};
+use std::str::FromStr;
+
+use crate::db::TokenAddressParseError;
+
+/// `0x` prefixed address with EIP-55 checksum
+pub fn to_checksum(address: &H160) -> String {
+    let hex: String = address.as_bytes().to_hex();
+    let hash = Keccak256::digest(hex.as_bytes());
+
+    let checksummed: String = hex
+        .chars()
+        .enumerate()
+        .map(|(index, char)| {
+            let nibble = hash[index / 2] >> (if index % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 {
+                char.to_ascii_uppercase()
+            } else {
+                char
+            }
+        })
+        .collect();
+    format!("0x{checksummed}")
+}
+
+/// Whether the address has a valid checksum or has none, i.e. it's all lowercase or uppercase
+pub fn is_valid_checksum(address: &str) -> bool {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    if hex == hex.to_lowercase() || hex == hex.to_uppercase() {
+        return true;
+    }
+
+    H160::from_str(hex).is_ok_and(|parsed| to_checksum(&parsed)[2..] == *hex)
+}
+
+/// Parse the address, rejecting mixed-case addresses with a bad checksum
+pub fn parse_strict(address: &str) -> Result<H160, TokenAddressParseError> {
+    if !is_valid_checksum(address) {
+        return Err(TokenAddressParseError);
+    }
+    H160::from_str(address).map_err(|_| TokenAddressParseError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eip55_checksums() {
+        // EIP-55 test vectors
+        for checksummed in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let address = parse_strict(checksummed).unwrap();
+            assert_eq!(to_checksum(&address), checksummed);
+            assert_eq!(parse_strict(&checksummed.to_lowercase()).unwrap(), address);
+        }
+
+        assert!(parse_strict("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(!is_valid_checksum("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"));
+    }
+}
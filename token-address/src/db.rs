@@ -1,5 +1,8 @@
 use crate::{
+    bitcoin::BitcoinAddress,
+    checksum,
     rpc::{EthereumAddress, SolanaAddress, TokenAddress},
+    ton::TonAddress,
     ChainId,
 };
 use primitive_types::H160;
@@ -13,10 +16,17 @@ use sqlx::{
 };
 use std::{fmt, fmt::Formatter, str::FromStr};
 
+/// Stored as the address for Solana and Ethereum and with the chain prefix for other chains, e.g.
+/// `polygon:0x0d50...` and `ton:0:83df...`. Addresses are displayed the same way, so `Display` and `FromStr` round
+/// trip, EVM addresses in lowercase, see `to_checksum_string` for EIP-55 checksums
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum StoredTokenAddress {
     Solana(Pubkey),
     Ethereum(H160),
+    /// Token on an EVM chain other than Ethereum, created by `StoredTokenAddress::evm`
+    Evm(ChainId, H160),
+    Bitcoin(BitcoinAddress),
+    Ton(TonAddress),
 }
 
 impl From<StoredTokenAddress> for TokenAddress {
@@ -24,24 +34,82 @@ impl From<StoredTokenAddress> for TokenAddress {
         match value {
             StoredTokenAddress::Solana(address) => address.into(),
             StoredTokenAddress::Ethereum(address) => address.into(),
+            StoredTokenAddress::Evm(ChainId::Ethereum, address) => address.into(),
+            StoredTokenAddress::Evm(chain, address) => TokenAddress::Evm(chain, address),
+            StoredTokenAddress::Bitcoin(address) => TokenAddress::Bitcoin(address),
+            StoredTokenAddress::Ton(address) => TokenAddress::Ton(address),
         }
     }
 }
 
 impl StoredTokenAddress {
+    /// Token of the EVM chain, `Ethereum` on Ethereum. `None` if the chain isn't an EVM chain
+    pub fn evm(chain: ChainId, address: H160) -> Option<Self> {
+        match chain {
+            ChainId::Ethereum => Some(Self::Ethereum(address)),
+            _ if chain.is_evm() => Some(Self::Evm(chain, address)),
+            _ => None,
+        }
+    }
+
     pub fn platform(&self) -> ChainId {
         match self {
             Self::Solana(_) => ChainId::Solana,
             Self::Ethereum(_) => ChainId::Ethereum,
+            Self::Evm(chain, _) => *chain,
+            Self::Bitcoin(_) => ChainId::Bitcoin,
+            Self::Ton(_) => ChainId::Ton,
+        }
+    }
+
+    /// Form of the address in the database, parsed by `FromStr`
+    pub fn to_prefixed_string(&self) -> String {
+        match self {
+            Self::Solana(pubkey) => pubkey.to_string(),
+            Self::Ethereum(address) => format!("0x{address:x}"),
+            Self::Evm(chain, address) => format!("{chain}:0x{address:x}"),
+            Self::Bitcoin(address) => format!("{}:{address}", ChainId::Bitcoin),
+            Self::Ton(address) => format!("{}:{address}", ChainId::Ton),
+        }
+    }
+
+    /// Address without the chain prefix, e.g. the contract address of token APIs
+    pub fn to_unprefixed_string(&self) -> String {
+        match self {
+            Self::Solana(pubkey) => pubkey.to_string(),
+            Self::Ethereum(address) | Self::Evm(_, address) => format!("0x{address:x}"),
+            Self::Bitcoin(address) => address.to_string(),
+            Self::Ton(address) => address.to_string(),
         }
     }
+
+    /// Display form with EIP-55 checksums of EVM addresses, e.g. `base:0x5aAe...`
+    pub fn to_checksum_string(&self) -> String {
+        match self {
+            Self::Ethereum(address) => checksum::to_checksum(address),
+            Self::Evm(chain, address) => format!("{chain}:{}", checksum::to_checksum(address)),
+            Self::Solana(_) | Self::Bitcoin(_) | Self::Ton(_) => self.to_string(),
+        }
+    }
+
+    /// Same as `FromStr`, but rejects EVM addresses with bad EIP-55 checksums
+    pub fn from_str_strict(s: &str) -> Result<Self, TokenAddressParseError> {
+        let address = s.split_once(':').map_or(s, |(_, address)| address);
+        if address.starts_with("0x") && !checksum::is_valid_checksum(address) {
+            return Err(TokenAddressParseError);
+        }
+        s.parse()
+    }
 }
 
 impl fmt::Display for StoredTokenAddress {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Self::Solana(pubkey) => write!(f, "{pubkey}",),
-            Self::Ethereum(ethereum) => write!(f, "0x{ethereum:x}",),
+            Self::Ethereum(ethereum) => write!(f, "0x{ethereum:x}"),
+            Self::Evm(chain, address) => write!(f, "{chain}:0x{address:x}"),
+            Self::Bitcoin(address) => write!(f, "{}:{address}", ChainId::Bitcoin),
+            Self::Ton(address) => write!(f, "{}:{address}", ChainId::Ton),
         }
     }
 }
@@ -79,10 +147,24 @@ impl fmt::Display for TokenAddressParseError {
     }
 }
 
+/// Parses addresses with and without the chain prefix, unprefixed ones are Solana or Ethereum addresses
 impl FromStr for StoredTokenAddress {
     type Err = TokenAddressParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((chain, address)) = s.split_once(':') {
+            let chain = ChainId::from_str(chain).map_err(|_| TokenAddressParseError)?;
+            return match chain {
+                ChainId::Solana => Ok(Self::Solana(
+                    Pubkey::from_str(address).map_err(|_| TokenAddressParseError)?,
+                )),
+                ChainId::Bitcoin => Ok(Self::Bitcoin(address.parse()?)),
+                ChainId::Ton => Ok(Self::Ton(address.parse()?)),
+                _ => Self::evm(chain, H160::from_str(address).map_err(|_| TokenAddressParseError)?)
+                    .ok_or(TokenAddressParseError),
+            };
+        }
+
         match (Pubkey::from_str(s), H160::from_str(s)) {
             (Ok(pubkey), _) => Ok(Self::Solana(pubkey)),
             (_, Ok(ethereum)) => Ok(Self::Ethereum(ethereum)),
@@ -123,7 +205,7 @@ where
     String: Encode<'q, DB>,
 {
     fn encode_by_ref(&self, buf: &mut <DB as HasArguments<'q>>::ArgumentBuffer) -> IsNull {
-        <String as Encode<DB>>::encode(self.to_string(), buf)
+        <String as Encode<DB>>::encode(self.to_prefixed_string(), buf)
     }
 }
 
@@ -176,3 +258,115 @@ impl FromRow<'_, PgRow> for TokenAddress {
         row.try_get(0)
     }
 }
+
+#[cfg(feature = "sqlite")]
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for StoredTokenAddress {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, Error> {
+        row.try_get(0)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromRow<'_, sqlx::sqlite::SqliteRow> for TokenAddress {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self, Error> {
+        row.try_get(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_prefixed_address() {
+        let address = H160::random();
+        let stored = StoredTokenAddress::evm(ChainId::Base, address).unwrap();
+        assert_eq!(
+            StoredTokenAddress::evm(ChainId::Ethereum, address).unwrap(),
+            StoredTokenAddress::Ethereum(address)
+        );
+        assert_eq!(StoredTokenAddress::evm(ChainId::Ton, address), None);
+        assert_eq!(stored.to_prefixed_string(), format!("base:0x{address:x}"));
+        assert_eq!(stored.to_string(), format!("base:0x{address:x}"));
+        assert_eq!(stored.to_unprefixed_string(), format!("0x{address:x}"));
+        assert_eq!(
+            stored.to_checksum_string(),
+            format!("base:{}", checksum::to_checksum(&address))
+        );
+        assert_eq!(
+            stored.to_prefixed_string().parse::<StoredTokenAddress>().unwrap(),
+            stored
+        );
+
+        let ethereum = StoredTokenAddress::Ethereum(address);
+        assert_eq!(ethereum.to_prefixed_string(), format!("0x{address:x}"));
+        assert_eq!(ethereum.to_string(), format!("0x{address:x}"));
+        assert_ne!(ethereum.to_string(), stored.to_string());
+        assert_eq!(
+            format!("ethereum:0x{address:x}").parse::<StoredTokenAddress>().unwrap(),
+            ethereum
+        );
+        assert!("tron:0x00".parse::<StoredTokenAddress>().is_err());
+
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let stored = StoredTokenAddress::from_str_strict(checksummed).unwrap();
+        assert_eq!(stored.to_checksum_string(), checksummed);
+        assert_eq!(stored.to_string(), checksummed.to_lowercase());
+        assert_eq!(stored, checksummed.to_lowercase().parse().unwrap());
+        assert!(StoredTokenAddress::from_str_strict("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(StoredTokenAddress::from_str_strict("base:0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+
+        for prefixed in [
+            "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "ton:0:83dfd552e63729b472fcbcc8c45ebcc6691702558b68ec7527e1ba403a0f31a8",
+        ] {
+            let stored = prefixed.parse::<StoredTokenAddress>().unwrap();
+            assert_eq!(stored.to_prefixed_string(), prefixed);
+            assert_eq!(stored.to_string(), prefixed);
+            assert_eq!(stored.to_string().parse::<StoredTokenAddress>().unwrap(), stored);
+            assert_eq!(stored.to_unprefixed_string(), prefixed.split_once(':').unwrap().1);
+            assert_eq!(
+                TokenAddress::from(stored)
+                    .as_stored_token_address()
+                    .unwrap()
+                    .to_prefixed_string(),
+                prefixed
+            );
+        }
+        assert!("bitcoin:0x00".parse::<StoredTokenAddress>().is_err());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_roundtrip() {
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE tokens (address TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let stored = [
+            StoredTokenAddress::Solana(Pubkey::new_unique()),
+            StoredTokenAddress::evm(ChainId::Base, H160::random()).unwrap(),
+        ];
+        for address in &stored {
+            sqlx::query("INSERT INTO tokens (address) VALUES (?)")
+                .bind(address)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let rows: Vec<StoredTokenAddress> = sqlx::query_as("SELECT address FROM tokens ORDER BY rowid")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, stored);
+
+        let rows: Vec<TokenAddress> = sqlx::query_as("SELECT address FROM tokens ORDER BY rowid")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, stored.map(TokenAddress::from));
+    }
+}
@@ -1,15 +1,123 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use primitive_types::H160;
 use serde::{Deserialize, Serialize};
 
+pub mod amount;
+pub mod bitcoin;
+pub mod canonical;
+pub mod checksum;
 pub mod db;
+#[cfg(feature = "prost")]
+pub mod proto;
 pub mod rpc;
+pub mod solana;
+pub mod ton;
 
+pub use amount::TokenAmount;
+pub use canonical::CanonicalTokenAddress;
 pub use db::StoredTokenAddress;
 pub use rpc::{EthereumAddress, SolanaAddress, TokenAddress};
+pub use solana::TokenProgram;
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Hash, strum::Display)]
+/// Borsh encodes the chains by their order, new chains must be added to the end
+#[derive(
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    strum::Display,
+    strum::EnumString,
+)]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
 pub enum ChainId {
     Solana,
     Ethereum,
+    Polygon,
+    Bsc,
+    Arbitrum,
+    Base,
+    Bitcoin,
+    Ton,
+}
+
+impl ChainId {
+    pub const EVM_CHAINS: [ChainId; 5] = [
+        ChainId::Ethereum,
+        ChainId::Polygon,
+        ChainId::Bsc,
+        ChainId::Arbitrum,
+        ChainId::Base,
+    ];
+
+    /// EIP-155 chain id of EVM chains
+    pub fn evm_chain_id(&self) -> Option<u64> {
+        match self {
+            ChainId::Solana | ChainId::Bitcoin | ChainId::Ton => None,
+            ChainId::Ethereum => Some(1),
+            ChainId::Polygon => Some(137),
+            ChainId::Bsc => Some(56),
+            ChainId::Arbitrum => Some(42161),
+            ChainId::Base => Some(8453),
+        }
+    }
+
+    pub fn from_evm_chain_id(chain_id: u64) -> Option<Self> {
+        Self::EVM_CHAINS
+            .into_iter()
+            .find(|chain| chain.evm_chain_id() == Some(chain_id))
+    }
+
+    pub fn is_evm(&self) -> bool {
+        self.evm_chain_id().is_some()
+    }
+
+    /// Id of the asset platform in Coingecko API
+    pub fn coingecko_platform(&self) -> &'static str {
+        match self {
+            ChainId::Solana => "solana",
+            ChainId::Ethereum => "ethereum",
+            ChainId::Polygon => "polygon-pos",
+            ChainId::Bsc => "binance-smart-chain",
+            ChainId::Arbitrum => "arbitrum-one",
+            ChainId::Base => "base",
+            ChainId::Bitcoin => "bitcoin",
+            ChainId::Ton => "the-open-network",
+        }
+    }
+
+    /// Wrapped token of the native coin of EVM chains, e.g. WETH
+    pub fn wrapped_native(&self) -> Option<H160> {
+        let address = match self {
+            ChainId::Solana | ChainId::Bitcoin | ChainId::Ton => return None,
+            ChainId::Ethereum => hex_literal::hex!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"),
+            ChainId::Polygon => hex_literal::hex!("0d500b1d8e8ef31e21c99d1db9a6444d3adf1270"),
+            ChainId::Bsc => hex_literal::hex!("bb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c"),
+            ChainId::Arbitrum => hex_literal::hex!("82af49447d8a07e3bd95bd0d56f35241523fbab1"),
+            ChainId::Base => hex_literal::hex!("4200000000000000000000000000000000000006"),
+        };
+        Some(address.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evm_chain_ids() {
+        assert_eq!(ChainId::from_evm_chain_id(137), Some(ChainId::Polygon));
+        assert_eq!(ChainId::from_evm_chain_id(101), None);
+        assert_eq!("bsc".parse::<ChainId>().unwrap(), ChainId::Bsc);
+        assert_eq!(ChainId::Arbitrum.to_string(), "arbitrum");
+        assert!(!ChainId::Solana.is_evm());
+        assert!(!ChainId::Ton.is_evm());
+        assert_eq!("bitcoin".parse::<ChainId>().unwrap(), ChainId::Bitcoin);
+    }
 }
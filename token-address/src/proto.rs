@@ -0,0 +1,102 @@
+//! Protobuf message of `TokenAddress` for gRPC APIs:
+//!
+//! ```proto
+//! message TokenAddress {
+//!   // `ChainId` in lowercase, e.g. "solana" or "polygon"
+//!   string chain = 1;
+//!   // 32 bytes of the mint on Solana, 20 bytes of the contract on EVM chains, UTF-8 of the address on Bitcoin
+//!   // and TON, empty for the native coin
+//!   bytes address = 2;
+//! }
+//! ```
+
+use crate::{db::TokenAddressParseError, ChainId, TokenAddress};
+use primitive_types::H160;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+#[derive(Clone, PartialEq, Eq, Hash, prost::Message)]
+pub struct TokenAddressProto {
+    #[prost(string, tag = "1")]
+    pub chain: String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub address: Vec<u8>,
+}
+
+impl From<&TokenAddress> for TokenAddressProto {
+    fn from(value: &TokenAddress) -> Self {
+        let address = match value {
+            TokenAddress::Spl(pubkey) => pubkey.to_bytes().to_vec(),
+            TokenAddress::Erc20(address) | TokenAddress::Evm(_, address) => address.as_bytes().to_vec(),
+            TokenAddress::Bitcoin(address) => address.to_string().into_bytes(),
+            TokenAddress::Ton(address) => address.to_string().into_bytes(),
+            TokenAddress::Native(_) => Vec::new(),
+        };
+        Self {
+            chain: value.platform().to_string(),
+            address,
+        }
+    }
+}
+
+impl From<TokenAddress> for TokenAddressProto {
+    fn from(value: TokenAddress) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl TryFrom<TokenAddressProto> for TokenAddress {
+    type Error = TokenAddressParseError;
+
+    fn try_from(value: TokenAddressProto) -> Result<Self, Self::Error> {
+        let chain = ChainId::from_str(&value.chain).map_err(|_| TokenAddressParseError)?;
+        if value.address.is_empty() {
+            return Ok(TokenAddress::Native(chain));
+        }
+
+        match chain {
+            ChainId::Solana => Pubkey::try_from(value.address.as_slice())
+                .map(TokenAddress::Spl)
+                .map_err(|_| TokenAddressParseError),
+            ChainId::Bitcoin | ChainId::Ton => {
+                let address = String::from_utf8(value.address).map_err(|_| TokenAddressParseError)?;
+                Ok(match chain {
+                    ChainId::Bitcoin => TokenAddress::Bitcoin(address.parse()?),
+                    _ => TokenAddress::Ton(address.parse()?),
+                })
+            },
+            _ if value.address.len() == H160::len_bytes() => {
+                TokenAddress::evm(chain, H160::from_slice(&value.address)).ok_or(TokenAddressParseError)
+            },
+            _ => Err(TokenAddressParseError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn proto_roundtrip() {
+        for address in [
+            TokenAddress::Spl(Pubkey::new_unique()),
+            TokenAddress::Erc20(H160::random()),
+            TokenAddress::evm(ChainId::Arbitrum, H160::random()).unwrap(),
+            TokenAddress::Native(ChainId::Solana),
+            TokenAddress::Native(ChainId::Bsc),
+            TokenAddress::Bitcoin("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy".parse().unwrap()),
+        ] {
+            let encoded = TokenAddressProto::from(&address).encode_to_vec();
+            let decoded = TokenAddressProto::decode(encoded.as_slice()).unwrap();
+            assert_eq!(TokenAddress::try_from(decoded).unwrap(), address);
+        }
+
+        let invalid = TokenAddressProto {
+            chain: "ethereum".to_owned(),
+            address: vec![0; 32],
+        };
+        assert!(TokenAddress::try_from(invalid).is_err());
+    }
+}
@@ -1,10 +1,11 @@
-use crate::{ChainId, StoredTokenAddress};
+use crate::{bitcoin::BitcoinAddress, db::TokenAddressParseError, ton::TonAddress, ChainId, StoredTokenAddress};
+use borsh::{BorshDeserialize, BorshSerialize};
 use hex_literal::hex;
 use primitive_types::H160;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use solana_sdk::{pubkey, pubkey::Pubkey};
-use std::{fmt, fmt::Formatter};
+use std::{fmt, fmt::Formatter, io, str::FromStr};
 
 const WRAPPED_SOL_STR: &str = "So11111111111111111111111111111111111111112";
 const WRAPPED_SOL: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
@@ -59,14 +60,55 @@ enum RawTokenAddress {
     Erc20(H160),
     #[serde(with = "strings::native")]
     Native,
+    Prefixed(#[serde_as(as = "DisplayFromStr")] PrefixedTokenAddress),
 }
 
+/// `<chain>:<address>` or `<chain>:native`, the form of addresses which are ambiguous without the chain
+struct PrefixedTokenAddress(TokenAddress);
+
+impl FromStr for PrefixedTokenAddress {
+    type Err = TokenAddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (chain, address) = s.split_once(':').ok_or(TokenAddressParseError)?;
+        let chain = ChainId::from_str(chain).map_err(|_| TokenAddressParseError)?;
+
+        let address = match (chain, address) {
+            (_, "native") => TokenAddress::Native(chain),
+            (ChainId::Solana, address) => TokenAddress::Spl(address.parse().map_err(|_| TokenAddressParseError)?),
+            (ChainId::Bitcoin, address) => TokenAddress::Bitcoin(address.parse()?),
+            (ChainId::Ton, address) => TokenAddress::Ton(address.parse()?),
+            (_, address) => TokenAddress::evm(chain, address.parse().map_err(|_| TokenAddressParseError)?)
+                .ok_or(TokenAddressParseError)?,
+        };
+        Ok(Self(address))
+    }
+}
+
+impl fmt::Display for PrefixedTokenAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            TokenAddress::Native(chain) => write!(f, "{chain}:native"),
+            address => write!(f, "{}:{address}", address.platform()),
+        }
+    }
+}
+
+/// Addresses are serialized without the chain for Solana and Ethereum, e.g. `"So11..."`, `"0xc02a..."` and
+/// `"native"` of SOL, and with it for other chains, e.g. `"polygon:0x0d50..."`, `"ton:0:83df..."` and
+/// `"ethereum:native"`
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash)]
 #[serde(from = "RawTokenAddress", into = "RawTokenAddress")]
 pub enum TokenAddress {
     Spl(Pubkey),
+    /// ERC-20 token on Ethereum
     Erc20(H160),
+    /// Token on an EVM chain other than Ethereum, created by `TokenAddress::evm`
+    Evm(ChainId, H160),
     Native(ChainId),
+    Bitcoin(BitcoinAddress),
+    /// Jetton master contract on TON
+    Ton(TonAddress),
 }
 
 impl From<Pubkey> for TokenAddress {
@@ -88,6 +130,7 @@ impl From<RawTokenAddress> for TokenAddress {
             RawTokenAddress::Spl(pubkey) => TokenAddress::Spl(pubkey),
             RawTokenAddress::Erc20(address) => TokenAddress::Erc20(address),
             RawTokenAddress::Native => TokenAddress::Native(ChainId::Solana),
+            RawTokenAddress::Prefixed(address) => address.0,
         }
     }
 }
@@ -97,18 +140,75 @@ impl From<TokenAddress> for RawTokenAddress {
         match value {
             TokenAddress::Spl(pubkey) => RawTokenAddress::Spl(pubkey),
             TokenAddress::Erc20(address) => RawTokenAddress::Erc20(address),
-            TokenAddress::Native(_) => RawTokenAddress::Native,
+            TokenAddress::Native(ChainId::Solana) => RawTokenAddress::Native,
+            address => RawTokenAddress::Prefixed(PrefixedTokenAddress(address)),
         }
     }
 }
 
+/// Borsh form of `TokenAddress`, new variants must be added to the end
+#[derive(BorshSerialize, BorshDeserialize)]
+enum BorshTokenAddress {
+    Spl([u8; 32]),
+    Erc20([u8; 20]),
+    Evm(ChainId, [u8; 20]),
+    Native(ChainId),
+    Bitcoin(String),
+    Ton(i8, [u8; 32]),
+}
+
+impl BorshSerialize for TokenAddress {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let address = match self {
+            TokenAddress::Spl(pubkey) => BorshTokenAddress::Spl(pubkey.to_bytes()),
+            TokenAddress::Erc20(address) => BorshTokenAddress::Erc20(address.0),
+            TokenAddress::Evm(chain, address) => BorshTokenAddress::Evm(*chain, address.0),
+            TokenAddress::Native(chain) => BorshTokenAddress::Native(*chain),
+            TokenAddress::Bitcoin(address) => BorshTokenAddress::Bitcoin(address.to_string()),
+            TokenAddress::Ton(address) => BorshTokenAddress::Ton(address.workchain, address.hash),
+        };
+        address.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for TokenAddress {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(match BorshTokenAddress::deserialize(buf)? {
+            BorshTokenAddress::Spl(pubkey) => TokenAddress::Spl(pubkey.into()),
+            BorshTokenAddress::Erc20(address) => TokenAddress::Erc20(address.into()),
+            BorshTokenAddress::Evm(chain, address) if chain.is_evm() && chain != ChainId::Ethereum => {
+                TokenAddress::Evm(chain, address.into())
+            },
+            BorshTokenAddress::Evm(chain, _) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{chain} isn't an EVM chain other than Ethereum"),
+                ))
+            },
+            BorshTokenAddress::Native(chain) => TokenAddress::Native(chain),
+            BorshTokenAddress::Bitcoin(address) => TokenAddress::Bitcoin(
+                address
+                    .parse()
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?,
+            ),
+            BorshTokenAddress::Ton(workchain, hash) => TokenAddress::Ton(TonAddress { workchain, hash }),
+        })
+    }
+}
+
 impl fmt::Display for TokenAddress {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             TokenAddress::Spl(pubkey) => write!(f, "{pubkey}"),
-            TokenAddress::Erc20(address) => write!(f, "0x{address:x}"),
+            TokenAddress::Erc20(address) | TokenAddress::Evm(_, address) => write!(f, "0x{address:x}"),
             TokenAddress::Native(ChainId::Solana) => f.write_str(WRAPPED_SOL_STR),
             TokenAddress::Native(ChainId::Ethereum) => f.write_str(WRAPPED_ETH_STR),
+            TokenAddress::Native(chain) => match chain.wrapped_native() {
+                Some(address) => write!(f, "0x{address:x}"),
+                None => write!(f, "{chain}:native"),
+            },
+            TokenAddress::Bitcoin(address) => write!(f, "{address}"),
+            TokenAddress::Ton(address) => write!(f, "{address}"),
         }
     }
 }
@@ -119,6 +219,15 @@ pub enum StoredTokenAddressExtra {
 }
 
 impl TokenAddress {
+    /// Token of the EVM chain, `Erc20` on Ethereum. `None` if the chain isn't an EVM chain
+    pub fn evm(chain: ChainId, address: H160) -> Option<Self> {
+        match chain {
+            ChainId::Ethereum => Some(TokenAddress::Erc20(address)),
+            _ if chain.is_evm() => Some(TokenAddress::Evm(chain, address)),
+            _ => None,
+        }
+    }
+
     pub fn spl(&self) -> Option<Pubkey> {
         match self {
             TokenAddress::Spl(pubkey) => Some(*pubkey),
@@ -133,6 +242,15 @@ impl TokenAddress {
         }
     }
 
+    /// Address of a token on any EVM chain
+    pub fn evm_address(&self) -> Option<(ChainId, H160)> {
+        match self {
+            TokenAddress::Erc20(address) => Some((ChainId::Ethereum, *address)),
+            TokenAddress::Evm(chain, address) => Some((*chain, *address)),
+            _ => None,
+        }
+    }
+
     pub fn as_solana_address(&self) -> Option<SolanaAddress> {
         match self {
             TokenAddress::Spl(address) => Some(SolanaAddress::Spl(*address)),
@@ -153,6 +271,9 @@ impl TokenAddress {
         match self {
             TokenAddress::Spl(pubkey) => Some(StoredTokenAddress::Solana(*pubkey)),
             TokenAddress::Erc20(address) => Some(StoredTokenAddress::Ethereum(*address)),
+            TokenAddress::Evm(chain, address) => Some(StoredTokenAddress::Evm(*chain, *address)),
+            TokenAddress::Bitcoin(address) => Some(StoredTokenAddress::Bitcoin(address.clone())),
+            TokenAddress::Ton(address) => Some(StoredTokenAddress::Ton(*address)),
             TokenAddress::Native(_) => None,
         }
     }
@@ -161,7 +282,9 @@ impl TokenAddress {
         match self {
             TokenAddress::Spl(_) => ChainId::Solana,
             TokenAddress::Erc20(_) => ChainId::Ethereum,
-            TokenAddress::Native(chain_id) => *chain_id,
+            TokenAddress::Evm(chain_id, _) | TokenAddress::Native(chain_id) => *chain_id,
+            TokenAddress::Bitcoin(_) => ChainId::Bitcoin,
+            TokenAddress::Ton(_) => ChainId::Ton,
         }
     }
 }
@@ -175,6 +298,15 @@ impl From<&TokenAddress> for StoredTokenAddressExtra {
             TokenAddress::Erc20(address) => {
                 StoredTokenAddressExtra::StoredTokenAddress(StoredTokenAddress::Ethereum(*address))
             },
+            TokenAddress::Evm(chain_id, address) => {
+                StoredTokenAddressExtra::StoredTokenAddress(StoredTokenAddress::Evm(*chain_id, *address))
+            },
+            TokenAddress::Bitcoin(address) => {
+                StoredTokenAddressExtra::StoredTokenAddress(StoredTokenAddress::Bitcoin(address.clone()))
+            },
+            TokenAddress::Ton(address) => {
+                StoredTokenAddressExtra::StoredTokenAddress(StoredTokenAddress::Ton(*address))
+            },
             TokenAddress::Native(chain_id) => StoredTokenAddressExtra::Native(*chain_id),
         }
     }
@@ -297,6 +429,7 @@ impl EthereumAddress {
 #[cfg(test)]
 mod test {
     use crate::{rpc::TokenAddress, ChainId};
+    use borsh::BorshDeserialize;
     use primitive_types::H160;
     use solana_sdk::pubkey::Pubkey;
 
@@ -330,5 +463,41 @@ mod test {
         let serialized = serde_json::to_string(&TokenAddress::Native(ChainId::Solana)).unwrap();
         let deserialized: TokenAddress = serde_json::from_str(&serialized).unwrap();
         assert_eq!(deserialized, TokenAddress::Native(ChainId::Solana));
+
+        let serialized = serde_json::to_string(&TokenAddress::Native(ChainId::Ethereum)).unwrap();
+        assert_eq!(serialized, r#""ethereum:native""#);
+        let deserialized: TokenAddress = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, TokenAddress::Native(ChainId::Ethereum));
+    }
+
+    #[test]
+    fn should_borsh_token_address() {
+        for address in [
+            TokenAddress::Spl(Pubkey::new_unique()),
+            TokenAddress::Erc20(H160::random()),
+            TokenAddress::evm(ChainId::Polygon, H160::random()).unwrap(),
+            TokenAddress::Native(ChainId::Solana),
+            TokenAddress::Native(ChainId::Base),
+        ] {
+            let serialized = borsh::to_vec(&address).unwrap();
+            assert_eq!(TokenAddress::try_from_slice(&serialized).unwrap(), address);
+        }
+
+        let serialized = borsh::to_vec(&TokenAddress::Evm(ChainId::Solana, H160::zero())).unwrap();
+        assert!(TokenAddress::try_from_slice(&serialized).is_err());
+    }
+
+    #[test]
+    fn should_serde_evm_chain_address() {
+        let address = H160::random();
+        let token = TokenAddress::evm(ChainId::Polygon, address).unwrap();
+        let serialized = serde_json::to_string(&token).unwrap();
+        assert_eq!(serialized, format!(r#""polygon:0x{address:x}""#));
+        let deserialized: TokenAddress = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, token);
+        assert_eq!(deserialized.evm_address(), Some((ChainId::Polygon, address)));
+
+        let deserialized: TokenAddress = serde_json::from_str(&format!(r#""ethereum:0x{address:x}""#)).unwrap();
+        assert_eq!(deserialized, TokenAddress::Erc20(address));
     }
 }
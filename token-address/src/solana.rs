@@ -0,0 +1,104 @@
+//! Token programs and associated token accounts of SPL tokens
+
+use crate::SolanaAddress;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Program owning the mint and the token accounts of an SPL token
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Hash, strum::Display, strum::EnumString)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum TokenProgram {
+    Token,
+    Token2022,
+}
+
+impl TokenProgram {
+    pub fn id(&self) -> Pubkey {
+        match self {
+            TokenProgram::Token => TOKEN_PROGRAM_ID,
+            TokenProgram::Token2022 => TOKEN_2022_PROGRAM_ID,
+        }
+    }
+
+    /// Token program with the id, `None` for other programs
+    pub fn from_id(id: &Pubkey) -> Option<Self> {
+        match *id {
+            TOKEN_PROGRAM_ID => Some(TokenProgram::Token),
+            TOKEN_2022_PROGRAM_ID => Some(TokenProgram::Token2022),
+            _ => None,
+        }
+    }
+}
+
+impl SolanaAddress {
+    /// Associated token account of the wallet for the mint owned by the token program
+    pub fn associated_token_address(&self, wallet: &Pubkey, program: TokenProgram) -> Pubkey {
+        Pubkey::find_program_address(
+            &[wallet.as_ref(), program.id().as_ref(), self.pubkey().as_ref()],
+            &ASSOCIATED_TOKEN_PROGRAM_ID,
+        )
+        .0
+    }
+
+    /// Token program owning the mint, `None` if the account is missing or isn't owned by a token program
+    #[cfg(feature = "rpc-client")]
+    pub async fn token_program(
+        &self,
+        client: &solana_client::nonblocking::rpc_client::RpcClient,
+    ) -> solana_client::client_error::Result<Option<TokenProgram>> {
+        let account = client
+            .get_account_with_commitment(&self.pubkey(), client.commitment())
+            .await?
+            .value;
+        Ok(account.and_then(|account| TokenProgram::from_id(&account.owner)))
+    }
+
+    /// Associated token account of the wallet, deriving it for the token program owning the mint
+    #[cfg(feature = "rpc-client")]
+    pub async fn associated_token_address_with_rpc(
+        &self,
+        client: &solana_client::nonblocking::rpc_client::RpcClient,
+        wallet: &Pubkey,
+    ) -> solana_client::client_error::Result<Option<Pubkey>> {
+        let program = self.token_program(client).await?;
+        Ok(program.map(|program| self.associated_token_address(wallet, program)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn associated_token_addresses() {
+        let usdc = SolanaAddress::Spl(pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"));
+        let wallet = pubkey!("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM");
+
+        assert_eq!(
+            usdc.associated_token_address(&wallet, TokenProgram::Token),
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &wallet,
+                &usdc.pubkey(),
+                &TokenProgram::Token.id()
+            )
+        );
+        assert_eq!(
+            usdc.associated_token_address(&wallet, TokenProgram::Token2022),
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &wallet,
+                &usdc.pubkey(),
+                &TokenProgram::Token2022.id()
+            )
+        );
+        assert_eq!(
+            TokenProgram::from_id(&TokenProgram::Token2022.id()),
+            Some(TokenProgram::Token2022)
+        );
+        assert_eq!(TokenProgram::from_id(&wallet), None);
+    }
+}
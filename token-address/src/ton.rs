@@ -0,0 +1,118 @@
+//! TON addresses in the raw form `<workchain>:<hex>` and the user-friendly Base64 form
+
+use crate::db::TokenAddressParseError;
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE},
+    Engine,
+};
+use rustc_hex::{FromHex, ToHex};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::{fmt, fmt::Formatter, str::FromStr};
+
+const BOUNCEABLE_TAG: u8 = 0x11;
+const NON_BOUNCEABLE_TAG: u8 = 0x51;
+const TESTNET_FLAG: u8 = 0x80;
+
+/// TON address, parsed from both forms and displayed in the raw form, e.g. `0:83df...`
+#[derive(SerializeDisplay, DeserializeFromStr, Debug, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct TonAddress {
+    pub workchain: i8,
+    pub hash: [u8; 32],
+}
+
+impl TonAddress {
+    /// Base64url form used by wallets, e.g. `EQCD39...`
+    pub fn to_user_friendly(&self, bounceable: bool, testnet: bool) -> String {
+        let mut bytes = Vec::with_capacity(36);
+        let tag = if bounceable { BOUNCEABLE_TAG } else { NON_BOUNCEABLE_TAG };
+        bytes.push(if testnet { tag | TESTNET_FLAG } else { tag });
+        bytes.push(self.workchain as u8);
+        bytes.extend_from_slice(&self.hash);
+        bytes.extend_from_slice(&crc16(&bytes).to_be_bytes());
+        URL_SAFE.encode(bytes)
+    }
+
+    fn from_raw(s: &str) -> Option<Self> {
+        let (workchain, hash) = s.split_once(':')?;
+        let hash: Vec<u8> = hash.from_hex().ok()?;
+        Some(Self {
+            workchain: workchain.parse().ok()?,
+            hash: hash.try_into().ok()?,
+        })
+    }
+
+    fn from_user_friendly(s: &str) -> Option<Self> {
+        if s.len() != 48 {
+            return None;
+        }
+        let bytes = URL_SAFE.decode(s).or_else(|_| STANDARD.decode(s)).ok()?;
+        let (payload, checksum) = bytes.split_at(34);
+        if crc16(payload).to_be_bytes() != checksum
+            || ![BOUNCEABLE_TAG, NON_BOUNCEABLE_TAG].contains(&(payload[0] & !TESTNET_FLAG))
+        {
+            return None;
+        }
+
+        Some(Self {
+            workchain: payload[1] as i8,
+            hash: payload[2..].try_into().ok()?,
+        })
+    }
+}
+
+/// CRC-16/XMODEM of user-friendly addresses
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ (u16::from(*byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+impl fmt::Display for TonAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.workchain, self.hash.to_hex::<String>())
+    }
+}
+
+impl FromStr for TonAddress {
+    type Err = TokenAddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_raw(s)
+            .or_else(|| Self::from_user_friendly(s))
+            .ok_or(TokenAddressParseError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ton_addresses() {
+        let raw = "0:83dfd552e63729b472fcbcc8c45ebcc6691702558b68ec7527e1ba403a0f31a8";
+        let address = raw.parse::<TonAddress>().unwrap();
+        assert_eq!(address.to_string(), raw);
+
+        let friendly = address.to_user_friendly(true, false);
+        assert_eq!(friendly, "EQCD39VS5jcptHL8vMjEXrzGaRcCVYto7HUn4bpAOg8xqB2N");
+        assert_eq!(friendly.parse::<TonAddress>().unwrap(), address);
+        assert_eq!(
+            address.to_user_friendly(false, false).parse::<TonAddress>().unwrap(),
+            address
+        );
+
+        let masterchain = format!("-1:{}", "ab".repeat(32));
+        assert_eq!(masterchain.parse::<TonAddress>().unwrap().workchain, -1);
+
+        assert!("EQCD39VS5jcptHL8vMjEXrzGaRcCVYto7HUn4bpAOg8xqB2M"
+            .parse::<TonAddress>()
+            .is_err());
+        assert!("0:83df".parse::<TonAddress>().is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, Weak},
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{global, KeyValue};
+use rust_utils::wrappers::serde::DurationMs;
+use serde::Deserialize;
+use serde_with::serde_as;
+
+/// Breakers exported by the state gauge by checker, dropped ones are removed on the next observation
+type Registry = Mutex<Vec<(String, Weak<CircuitBreaker>)>>;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CircuitBreakerSettings {
+    /// Number of consecutive failures which opens the breaker
+    #[serde(default = "CircuitBreakerSettings::default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before a probe call is allowed
+    #[serde(
+        rename = "open_duration_ms",
+        default = "CircuitBreakerSettings::default_open_duration"
+    )]
+    #[serde_as(as = "DurationMs")]
+    pub open_duration: Duration,
+}
+
+impl CircuitBreakerSettings {
+    fn default_failure_threshold() -> u32 {
+        5
+    }
+
+    fn default_open_duration() -> Duration {
+        Duration::from_secs(30)
+    }
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: Self::default_failure_threshold(),
+            open_duration: Self::default_open_duration(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    /// A single probe call is allowed to check if the checker is back
+    HalfOpen,
+}
+
+impl BreakerState {
+    fn as_i64(&self) -> i64 {
+        match self {
+            BreakerState::Closed => 0,
+            BreakerState::HalfOpen => 1,
+            BreakerState::Open => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker(Mutex<Inner>);
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self(Mutex::new(Inner {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            probe_in_flight: false,
+        }))
+    }
+}
+
+impl CircuitBreaker {
+    pub fn state(&self) -> BreakerState {
+        self.lock().state
+    }
+
+    /// Returns `None` if the call must be skipped
+    pub(crate) fn try_acquire<'a>(&'a self, settings: &'a CircuitBreakerSettings) -> Option<Permit<'a>> {
+        let mut inner = self.lock();
+
+        let probe = match inner.state {
+            BreakerState::Closed => false,
+            BreakerState::Open => {
+                let expired = inner
+                    .opened_at
+                    .map(|opened_at| opened_at.elapsed() >= settings.open_duration)
+                    .unwrap_or(true);
+                if !expired {
+                    return None;
+                }
+
+                inner.state = BreakerState::HalfOpen;
+                inner.probe_in_flight = true;
+                true
+            },
+            BreakerState::HalfOpen if inner.probe_in_flight => return None,
+            BreakerState::HalfOpen => {
+                inner.probe_in_flight = true;
+                true
+            },
+        };
+
+        Some(Permit {
+            breaker: self,
+            settings,
+            probe,
+            recorded: false,
+        })
+    }
+
+    fn record(&self, settings: &CircuitBreakerSettings, success: bool) {
+        let mut inner = self.lock();
+        inner.probe_in_flight = false;
+
+        if success {
+            inner.state = BreakerState::Closed;
+            inner.consecutive_failures = 0;
+            inner.opened_at = None;
+            return;
+        }
+
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+
+        if inner.state == BreakerState::HalfOpen || inner.consecutive_failures >= settings.failure_threshold {
+            inner.state = BreakerState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Allowed call, a probe dropped without its result (e.g. the check is cancelled) counts as failed so the breaker
+/// isn't left half-open
+#[must_use]
+pub(crate) struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    settings: &'a CircuitBreakerSettings,
+    probe: bool,
+    recorded: bool,
+}
+
+impl Permit<'_> {
+    pub(crate) fn record(mut self, success: bool) {
+        self.recorded = true;
+        self.breaker.record(self.settings, success);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.recorded {
+            self.breaker.record(self.settings, false);
+        }
+    }
+}
+
+/// Export state of the breaker as `tokens_filter.circuit_breaker.state` gauge (0 - closed, 1 - half-open, 2 - open).
+/// The gauge callback is registered once for all breakers and doesn't keep them alive
+pub(crate) fn register_gauge(checker: String, breaker: &Arc<CircuitBreaker>) {
+    let registry = REGISTRY.get_or_init(|| {
+        let meter = global::meter("tokens-filter");
+        let gauge = meter
+            .i64_observable_gauge("tokens_filter.circuit_breaker.state")
+            .with_description("State of the checker circuit breaker: 0 - closed, 1 - half-open, 2 - open")
+            .init();
+
+        let callback = meter.register_callback(move |cx| {
+            for (checker, state) in observe() {
+                gauge.observe(cx, state, &[KeyValue::new("checker", checker)]);
+            }
+        });
+        if let Err(error) = callback {
+            tracing::warn!(%error, "unable to register circuit breaker gauge");
+        }
+
+        Mutex::default()
+    });
+
+    let mut breakers = registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    breakers.retain(|(_, registered)| registered.strong_count() > 0);
+    if !breakers
+        .iter()
+        .any(|(_, registered)| registered.as_ptr() == Arc::as_ptr(breaker))
+    {
+        breakers.push((checker, Arc::downgrade(breaker)));
+    }
+}
+
+/// The worst state of live breakers by checker, so filters with the same checkers share one series
+fn observe() -> HashMap<String, i64> {
+    let mut states = HashMap::new();
+    let Some(registry) = REGISTRY.get() else {
+        return states;
+    };
+
+    let mut breakers = registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    breakers.retain(|(checker, breaker)| {
+        let Some(breaker) = breaker.upgrade() else {
+            return false;
+        };
+
+        let state = states.entry(checker.clone()).or_insert(i64::MIN);
+        *state = (*state).max(breaker.state().as_i64());
+        true
+    });
+
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_and_recover() {
+        let settings = CircuitBreakerSettings {
+            failure_threshold: 2,
+            open_duration: Duration::from_millis(20),
+        };
+        let breaker = CircuitBreaker::default();
+
+        breaker.try_acquire(&settings).unwrap().record(false);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.try_acquire(&settings).unwrap().record(false);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(breaker.try_acquire(&settings).is_none());
+
+        std::thread::sleep(Duration::from_millis(30));
+
+        // only one probe is allowed
+        let probe = breaker.try_acquire(&settings).unwrap();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.try_acquire(&settings).is_none());
+
+        // failed probe opens the breaker again
+        probe.record(false);
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+
+        breaker.try_acquire(&settings).unwrap().record(true);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.try_acquire(&settings).is_some());
+    }
+
+    #[test]
+    fn dropped_probe_fails() {
+        let settings = CircuitBreakerSettings {
+            failure_threshold: 1,
+            open_duration: Duration::from_millis(20),
+        };
+        let breaker = CircuitBreaker::default();
+
+        // calls of the closed breaker aren't recorded once dropped
+        drop(breaker.try_acquire(&settings).unwrap());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.try_acquire(&settings).unwrap().record(false);
+        std::thread::sleep(Duration::from_millis(30));
+
+        drop(breaker.try_acquire(&settings).unwrap());
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire(&settings).is_some());
+    }
+
+    #[test]
+    fn gauge_registry() {
+        let settings = CircuitBreakerSettings {
+            failure_threshold: 1,
+            ..Default::default()
+        };
+        let closed = Arc::new(CircuitBreaker::default());
+        let open = Arc::new(CircuitBreaker::default());
+        open.record(&settings, false);
+
+        register_gauge("gauge_registry".to_owned(), &closed);
+        register_gauge("gauge_registry".to_owned(), &closed);
+        register_gauge("gauge_registry".to_owned(), &open);
+        assert_eq!(observe()["gauge_registry"], BreakerState::Open.as_i64());
+
+        drop(open);
+        assert_eq!(observe()["gauge_registry"], BreakerState::Closed.as_i64());
+
+        drop(closed);
+        assert!(!observe().contains_key("gauge_registry"));
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let settings = CircuitBreakerSettings {
+            failure_threshold: 2,
+            ..Default::default()
+        };
+        let breaker = CircuitBreaker::default();
+
+        breaker.record(&settings, false);
+        breaker.record(&settings, true);
+        breaker.record(&settings, false);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+}
@@ -1,27 +1,29 @@
 use async_trait::async_trait;
 use coingecko_client::CoingeckoClient;
-use solana_sdk::pubkey::Pubkey;
-use token_address::StoredTokenAddress;
+use token_address::TokenAddress;
 
 use crate::CheckToken;
 
 #[async_trait]
 impl CheckToken for CoingeckoClient {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     #[tracing::instrument(skip(self), err)]
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
-        Ok(self
-            .get_metadata_by_address(&StoredTokenAddress::Solana(*token))
-            .await?
-            .is_some())
+        let Some(address) = token.as_stored_token_address() else {
+            tracing::debug!("Native token is not supported");
+            return Ok(false);
+        };
+
+        Ok(self.get_metadata_by_address(&address).await?.is_some())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use http_client::settings::HttpClientSettings;
-    use solana_sdk::pubkey;
+    use primitive_types::H160;
+    use solana_sdk::{pubkey, pubkey::Pubkey};
 
     use super::*;
 
@@ -31,13 +33,29 @@ mod tests {
         let client = CoingeckoClient::new(HttpClientSettings::default()).unwrap();
 
         let good = client
-            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")) // USDC
+            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").into()) // USDC
+            .await
+            .unwrap();
+
+        assert!(good);
+
+        let good = client
+            .check_token(
+                &"a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+                    .parse::<H160>()
+                    .unwrap()
+                    .into(),
+            ) // USDC
             .await
             .unwrap();
 
         assert!(good);
 
-        let bad = client.check_token(&Pubkey::new_unique()).await.unwrap();
+        let bad = client.check_token(&Pubkey::new_unique().into()).await.unwrap();
+
+        assert!(!bad);
+
+        let bad = client.check_token(&H160::random().into()).await.unwrap();
 
         assert!(!bad);
     }
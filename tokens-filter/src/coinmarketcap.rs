@@ -1,29 +1,33 @@
 use async_trait::async_trait;
-use coinmarketcap_client::CoinmarketcapClient;
-use solana_sdk::pubkey::Pubkey;
+use coinmarketcap_client::{types::CoinmarketcapError, CoinmarketcapClient};
+use token_address::TokenAddress;
 
 use crate::CheckToken;
 
 #[async_trait]
 impl CheckToken for CoinmarketcapClient {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     #[tracing::instrument(skip(self), err)]
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
-        let response = self.cryptocurrency_info(token.to_string()).await?;
-
-        let Some(data) = response.get("data").and_then(|x| x.as_object()) else {
-            tracing::debug!("No data in response");
+        if let TokenAddress::Native(_) = token {
+            tracing::debug!("Native token is not supported");
             return Ok(false);
-        };
+        }
 
-        let Some((_, meta)) = data.iter().next() else {
-            tracing::debug!("No metadata in response");
-            return Ok(false);
+        let data = match self.cryptocurrency_info(token.to_string()).await {
+            Ok(data) => data,
+            Err(error) => match error.downcast::<CoinmarketcapError>() {
+                Ok(error) => {
+                    tracing::debug!(%error, "token is not found");
+                    return Ok(false);
+                },
+                Err(error) => return Err(error),
+            },
         };
 
-        let Some(symbol) = meta.get("symbol") else {
-            tracing::debug!("No symbol in response");
+        let Some(symbol) = data.into_values().next().map(|info| info.symbol) else {
+            tracing::debug!("No metadata in response");
             return Ok(false);
         };
 
@@ -35,7 +39,7 @@ impl CheckToken for CoinmarketcapClient {
 #[cfg(test)]
 mod tests {
     use http_client::settings::HttpClientSettings;
-    use solana_sdk::pubkey;
+    use solana_sdk::{pubkey, pubkey::Pubkey};
 
     use super::*;
 
@@ -45,16 +49,17 @@ mod tests {
         let client = CoinmarketcapClient::new(HttpClientSettings {
             api_key: Some("...".into()),
             ..Default::default()
-        });
+        })
+        .unwrap();
 
         let good = client
-            .check_token(&pubkey!("7gjNiPun3AzEazTZoFEjZgcBMeuaXdpjHq2raZTmTrfs")) // CRV DAO
+            .check_token(&pubkey!("7gjNiPun3AzEazTZoFEjZgcBMeuaXdpjHq2raZTmTrfs").into()) // CRV DAO
             .await
             .unwrap();
 
         assert!(good);
 
-        let bad = client.check_token(&Pubkey::new_unique()).await.unwrap();
+        let bad = client.check_token(&Pubkey::new_unique().into()).await.unwrap();
 
         assert!(!bad);
     }
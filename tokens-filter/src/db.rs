@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use token_address::TokenAddress;
+
+const CREATE_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS token_verdicts (
+    token TEXT PRIMARY KEY,
+    verdict BOOLEAN NOT NULL,
+    checker TEXT,
+    checked_at TIMESTAMPTZ NOT NULL
+)"#;
+
+#[derive(Debug, Clone, FromRow, PartialEq, Eq)]
+pub struct Verdict {
+    pub token: String,
+    pub verdict: bool,
+    /// Name of the checker which accepted the token, `None` if the token was rejected
+    pub checker: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Tokens are checked again once their verdict is older than this
+pub const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Rejected tokens may get listed later, so their verdicts expire sooner
+pub const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Postgres storage of `TokensFilter` verdicts shared between service replicas
+#[derive(Clone)]
+pub struct VerdictStore {
+    pool: PgPool,
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+impl VerdictStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            ttl: DEFAULT_TTL,
+            negative_ttl: DEFAULT_NEGATIVE_TTL,
+        }
+    }
+
+    /// Expire accepted verdicts after `ttl` and rejected ones after `negative_ttl`
+    pub fn with_ttl(mut self, ttl: Duration, negative_ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    /// Create `token_verdicts` table if it doesn't exist
+    pub async fn init(&self) -> sqlx::Result<()> {
+        sqlx::query(CREATE_TABLE).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Stored verdict of the token, `None` if it has expired
+    pub async fn get(&self, token: &TokenAddress) -> sqlx::Result<Option<Verdict>> {
+        let verdict: Option<Verdict> =
+            sqlx::query_as("SELECT token, verdict, checker, checked_at FROM token_verdicts WHERE token = $1")
+                .bind(token.to_string())
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(verdict.filter(|verdict| !self.is_expired(verdict, Utc::now())))
+    }
+
+    fn is_expired(&self, verdict: &Verdict, now: DateTime<Utc>) -> bool {
+        let ttl = if verdict.verdict { self.ttl } else { self.negative_ttl };
+        chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| verdict.checked_at.checked_add_signed(ttl))
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub async fn save(&self, token: &TokenAddress, verdict: bool, checker: Option<String>) -> sqlx::Result<()> {
+        sqlx::query(
+            "INSERT INTO token_verdicts (token, verdict, checker, checked_at) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (token) DO UPDATE SET verdict = $2, checker = $3, checked_at = $4",
+        )
+        .bind(token.to_string())
+        .bind(verdict)
+        .bind(checker)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+
+    use rust_utils::db::{
+        testing::{TestDb, TEST_DATABASE_URL},
+        DbSettings,
+    };
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs postgres"]
+    async fn save_and_get() {
+        let settings = std::env::var(TEST_DATABASE_URL)
+            .map(DbSettings::from_url)
+            .unwrap_or_default();
+        let db = TestDb::with_settings(&settings, None).await.unwrap();
+        let store = VerdictStore::new(db.repo().deref().clone());
+        store.init().await.unwrap();
+
+        let token = Pubkey::new_unique().into();
+        assert_eq!(store.get(&token).await.unwrap(), None);
+
+        store.save(&token, true, Some("Jupiter".into())).await.unwrap();
+        let verdict = store.get(&token).await.unwrap().unwrap();
+        assert!(verdict.verdict);
+        assert_eq!(verdict.checker.as_deref(), Some("Jupiter"));
+
+        store.save(&token, false, None).await.unwrap();
+        let verdict = store.get(&token).await.unwrap().unwrap();
+        assert!(!verdict.verdict);
+        assert_eq!(verdict.checker, None);
+
+        // the negative verdict is rechecked sooner
+        let store = store.with_ttl(Duration::from_secs(3600), Duration::ZERO);
+        assert_eq!(store.get(&token).await.unwrap(), None);
+        store.save(&token, true, Some("Jupiter".into())).await.unwrap();
+        assert!(store.get(&token).await.unwrap().unwrap().verdict);
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use primitive_types::U256;
+use serde::Deserialize;
+use serde_json::json;
+use token_address::TokenAddress;
+
+use crate::CheckToken;
+
+/// ERC-20 `totalSupply()` selector
+const TOTAL_SUPPLY_SELECTOR: &str = "0x18160ddd";
+
+#[derive(Clone)]
+pub struct EthereumChecker {
+    client: reqwest::Client,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    result: Option<String>,
+    error: Option<RpcError>,
+}
+
+impl EthereumChecker {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    async fn total_supply(&self, token: &str) -> anyhow::Result<Option<U256>> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_call",
+            "params": [{ "to": token, "data": TOTAL_SUPPLY_SELECTOR }, "latest"],
+        });
+
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<RpcResponse>()
+            .await?;
+
+        if let Some(RpcError { code, message }) = response.error {
+            bail!("Ethereum RPC error {code}: {message}");
+        }
+
+        let result = response.result.unwrap_or_default();
+        let result = result.trim_start_matches("0x");
+
+        // Call to an account without code returns empty data
+        if result.is_empty() {
+            return Ok(None);
+        }
+
+        U256::from_str_radix(result, 16)
+            .map(Some)
+            .with_context(|| format!("Unable to parse totalSupply({result}) to U256"))
+    }
+}
+
+#[async_trait]
+impl CheckToken for EthereumChecker {
+    type Token = TokenAddress;
+
+    #[tracing::instrument(skip(self), err)]
+    async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
+        let Some(address) = token.erc20() else {
+            return Ok(false);
+        };
+
+        let supply = self.total_supply(&format!("0x{address:x}")).await?;
+
+        Ok(supply.map(|supply| !supply.is_zero()).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::H160;
+
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs to mock Ethereum RPC"]
+    async fn check() {
+        let client = EthereumChecker::new("https://cloudflare-eth.com".into());
+
+        let good = client
+            .check_token(
+                &"a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+                    .parse::<H160>()
+                    .unwrap()
+                    .into(),
+            ) // USDC
+            .await
+            .unwrap();
+        assert!(good);
+
+        let bad = client.check_token(&H160::random().into()).await.unwrap();
+        assert!(!bad);
+    }
+}
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use rust_utils::tokens::get_token_symbol_by_mint_from_json;
-use solana_sdk::pubkey::Pubkey;
+use token_address::TokenAddress;
 
 use crate::CheckToken;
 
@@ -9,11 +9,16 @@ pub struct JsonChecker;
 
 #[async_trait]
 impl CheckToken for JsonChecker {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     #[tracing::instrument(skip(self), err)]
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
-        match get_token_symbol_by_mint_from_json(&token.to_string()).await {
+        let Some(mint) = token.spl() else {
+            tracing::debug!("Only SPL tokens are supported");
+            return Ok(false);
+        };
+
+        match get_token_symbol_by_mint_from_json(&mint.to_string()).await {
             Ok(_) => Ok(true),
             Err(e) => {
                 if e.to_string().contains("token not found") {
@@ -28,7 +33,7 @@ impl CheckToken for JsonChecker {
 
 #[cfg(test)]
 mod tests {
-    use solana_sdk::pubkey;
+    use solana_sdk::{pubkey, pubkey::Pubkey};
 
     use super::*;
 
@@ -38,11 +43,11 @@ mod tests {
         let client = JsonChecker;
 
         let good = client
-            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")) // USDC
+            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").into()) // USDC
             .await
             .unwrap();
         assert!(good);
-        let not_found = client.check_token(&Pubkey::new_unique()).await.unwrap();
+        let not_found = client.check_token(&Pubkey::new_unique().into()).await.unwrap();
         assert!(!not_found);
     }
 }
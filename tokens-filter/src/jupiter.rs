@@ -1,53 +1,106 @@
 use std::{
     collections::HashMap,
     sync::{Arc, OnceLock},
+    time::{Duration, Instant},
 };
 
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
-use cached::{Cached, TimedCache};
-use serde::Deserialize;
-use solana_sdk::pubkey::Pubkey;
-use tokio::sync::Mutex;
+use serde::{de::DeserializeOwned, Deserialize};
+use token_address::TokenAddress;
+use tokio::task::JoinHandle;
 
 use crate::CheckToken;
 
+/// Deprecated by Jupiter, used as a fallback for the token lists
 pub static DEFAULT_URL: &str = "https://cache.jup.ag/indexed-route-maps-v3";
+pub static TOKEN_LIST_URL: &str = "https://token.jup.ag";
+#[deprecated(note = "the cache is refreshed by a background task, SOL routes aren't used to check it anymore")]
 pub static SOL: OnceLock<String> = OnceLock::new();
 
-pub struct RoutesCache(TimedCache<String, usize>);
+/// Which Jupiter token list is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListMode {
+    /// All tradable tokens including unverified ones
+    All,
+    /// Only tokens verified by Jupiter
+    #[default]
+    Strict,
+}
+
+impl ListMode {
+    pub fn url(&self) -> String {
+        match self {
+            ListMode::All => format!("{TOKEN_LIST_URL}/all"),
+            ListMode::Strict => format!("{TOKEN_LIST_URL}/strict"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenTag {
+    Community,
+    Strict,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenInfo {
+    address: String,
+    #[serde(default)]
+    tags: Vec<TokenTag>,
+}
+
+/// Snapshot of the known tokens, replaced as a whole on refresh
+pub struct RoutesCache {
+    tokens: HashMap<String, Vec<TokenTag>>,
+    updated_at: Instant,
+}
 
 impl RoutesCache {
-    fn update_from_json(&mut self, input: RawResponse) {
-        input
-            .mint_keys
-            .into_iter()
-            .zip(input.indexed_route_map.into_iter())
-            .for_each(|(mint_key, (_, routes))| {
-                let routes_count = routes.len();
-                self.0.cache_set(mint_key, routes_count);
-            });
+    pub fn contains(&self, mint: &str) -> bool {
+        self.tokens.contains_key(mint)
     }
 
-    /// Check if json cache is updated via SOL routes. Because SOL token always should be in json.
-    fn is_updated(&self) -> bool {
-        let lifespan = self.0.cache_lifespan().unwrap_or_default();
+    /// Tags of the token, always empty for the route map
+    pub fn tags(&self, mint: &str) -> &[TokenTag] {
+        self.tokens.get(mint).map(Vec::as_slice).unwrap_or_default()
+    }
 
-        self.0
-            .get_store()
-            .get(SOL.get_or_init(|| spl_token::native_mint::id().to_string()))
-            .map(|(instant, _)| instant.elapsed().as_secs() < lifespan)
-            .unwrap_or_default()
+    pub fn age(&self) -> Duration {
+        self.updated_at.elapsed()
     }
 }
 
-impl From<(RawResponse, u64)> for RoutesCache {
-    fn from(input: (RawResponse, u64)) -> Self {
-        let value = input.0;
-        let ttl = input.1;
+impl From<RawResponse> for RoutesCache {
+    fn from(input: RawResponse) -> Self {
+        // keys of the route map are indexes in `mint_keys`
+        let tokens = input
+            .indexed_route_map
+            .into_iter()
+            .filter(|(_, routes)| !routes.is_empty())
+            .filter_map(|(index, _)| {
+                let mint_key = input.mint_keys.get(usize::try_from(index).ok()?)?;
+                Some((mint_key.clone(), vec![]))
+            })
+            .collect();
+
+        Self {
+            tokens,
+            updated_at: Instant::now(),
+        }
+    }
+}
 
-        let mut cache = Self(TimedCache::with_lifespan_and_capacity(ttl, value.mint_keys.len()));
-        cache.update_from_json(value);
-        cache
+impl From<Vec<TokenInfo>> for RoutesCache {
+    fn from(input: Vec<TokenInfo>) -> Self {
+        Self {
+            tokens: input.into_iter().map(|token| (token.address, token.tags)).collect(),
+            updated_at: Instant::now(),
+        }
     }
 }
 
@@ -58,63 +111,116 @@ pub struct RawResponse {
     indexed_route_map: HashMap<i32, Vec<i32>>,
 }
 
+#[derive(Debug, Clone)]
+enum Source {
+    RouteMap(String),
+    TokenList(ListMode),
+}
+
+impl Source {
+    async fn fetch(&self) -> anyhow::Result<RoutesCache> {
+        match self {
+            Source::RouteMap(url) => Ok(get_json::<RawResponse>(url).await?.into()),
+            Source::TokenList(mode) => match get_json::<Vec<TokenInfo>>(&mode.url()).await {
+                Ok(list) => Ok(list.into()),
+                Err(error) => {
+                    tracing::warn!(?mode, %error, "unable to get jupiter token list, falling back to route map");
+                    Ok(get_json::<RawResponse>(DEFAULT_URL).await?.into())
+                },
+            },
+        }
+    }
+}
+
+async fn get_json<T: DeserializeOwned>(url: &str) -> anyhow::Result<T> {
+    Ok(reqwest::get(url).await?.error_for_status()?.json().await?)
+}
+
+/// Checks that the token is known to Jupiter.
+/// The token list is downloaded by a background task every `ttl` seconds, so checks never wait for the download.
 pub struct JupiterChecker {
-    url: String,
-    cache: Mutex<RoutesCache>,
+    cache: Arc<ArcSwap<RoutesCache>>,
+    refresh: JoinHandle<()>,
 }
 
 impl JupiterChecker {
-    async fn get_from_cache_or_update(&self, key: String) -> anyhow::Result<usize> {
-        let mut guard = self.cache.lock().await;
+    /// Use the route map at `url`, the token is accepted if it has any routes
+    pub async fn new(url: String, ttl: u64) -> anyhow::Result<Self> {
+        Self::from_source(Source::RouteMap(url), ttl).await
+    }
 
-        if let Some(routes_count) = guard.0.cache_get(&key) {
-            return Ok(*routes_count);
-        }
+    /// Use the token list, the route map at `DEFAULT_URL` is used if the list is unavailable
+    pub async fn new_token_list(mode: ListMode, ttl: u64) -> anyhow::Result<Self> {
+        Self::from_source(Source::TokenList(mode), ttl).await
+    }
 
-        if !guard.is_updated() {
-            tracing::debug!(key, "cache expired");
-            let new_json = Self::get_json(&self.url).await?;
-            guard.update_from_json(new_json);
-        }
+    async fn from_source(source: Source, ttl: u64) -> anyhow::Result<Self> {
+        anyhow::ensure!(ttl > 0, "Jupiter tokens ttl must be positive");
+        let cache = Arc::new(ArcSwap::from_pointee(source.fetch().await?));
+        let refresh = Self::spawn_refresh(source, Duration::from_secs(ttl), cache.clone());
 
-        Ok(*guard.0.cache_get_or_set_with(key, || 0))
+        Ok(Self { cache, refresh })
     }
 
-    async fn get_json(url: &str) -> anyhow::Result<RawResponse> {
-        Ok(reqwest::get(url).await?.json().await?)
-    }
+    /// The current tokens are kept if the download fails
+    fn spawn_refresh(source: Source, period: Duration, cache: Arc<ArcSwap<RoutesCache>>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval.tick().await;
 
-    pub async fn new(url: String, ttl: u64) -> anyhow::Result<Self> {
-        Ok(Self {
-            cache: Mutex::new((Self::get_json(&url).await?, ttl).into()),
-            url,
+            loop {
+                interval.tick().await;
+                match source.fetch().await {
+                    Ok(tokens) => {
+                        cache.store(Arc::new(tokens));
+                        tracing::debug!(?source, "jupiter tokens are refreshed");
+                    },
+                    Err(error) => tracing::warn!(?source, %error, "unable to refresh jupiter tokens"),
+                }
+            }
         })
     }
+
+    pub fn cache(&self) -> Arc<RoutesCache> {
+        self.cache.load_full()
+    }
+}
+
+impl Drop for JupiterChecker {
+    fn drop(&mut self) {
+        self.refresh.abort();
+    }
 }
 
 #[async_trait]
 impl CheckToken for JupiterChecker {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     #[tracing::instrument(skip(self), err)]
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
-        Ok(self.get_from_cache_or_update(token.to_string()).await? > 0)
+        let Some(mint) = token.spl() else {
+            tracing::debug!("Only SPL tokens are supported");
+            return Ok(false);
+        };
+
+        Ok(self.cache.load().contains(&mint.to_string()))
     }
 }
 
 #[async_trait]
 impl CheckToken for Arc<JupiterChecker> {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     #[tracing::instrument(skip(self), err)]
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
-        Ok(self.as_ref().get_from_cache_or_update(token.to_string()).await? > 0)
+        self.as_ref().check_token(token).await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use solana_sdk::pubkey;
+    use solana_sdk::{pubkey, pubkey::Pubkey};
 
     use super::*;
 
@@ -124,11 +230,49 @@ mod tests {
         let client = Arc::new(JupiterChecker::new(DEFAULT_URL.to_owned(), 2).await.unwrap());
 
         let good = client
-            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")) // USDC
+            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").into()) // USDC
             .await
             .unwrap();
         assert!(good);
-        let bad = client.check_token(&Pubkey::new_unique()).await.unwrap();
+        let bad = client.check_token(&Pubkey::new_unique().into()).await.unwrap();
         assert!(!bad);
     }
+
+    #[tokio::test]
+    async fn reject_zero_ttl() {
+        assert!(JupiterChecker::new_token_list(ListMode::Strict, 0).await.is_err());
+    }
+
+    #[test]
+    fn routes_cache_from_json() {
+        let json: RawResponse = serde_json::from_str(
+            r#"{"mintKeys": ["A", "B", "C"], "indexedRouteMap": {"0": [1, 2], "1": [0], "2": []}}"#,
+        )
+        .unwrap();
+        let cache = RoutesCache::from(json);
+
+        assert!(cache.contains("A"));
+        assert!(cache.contains("B"));
+        assert!(!cache.contains("C"));
+        assert!(!cache.contains("D"));
+    }
+
+    #[test]
+    fn routes_cache_from_token_list() {
+        let json: Vec<TokenInfo> = serde_json::from_str(
+            r#"[
+                {"address": "A", "chainId": 101, "symbol": "A", "tags": ["community", "strict"]},
+                {"address": "B", "chainId": 101, "symbol": "B", "tags": ["old-registry"]},
+                {"address": "C", "chainId": 101, "symbol": "C"}
+            ]"#,
+        )
+        .unwrap();
+        let cache = RoutesCache::from(json);
+
+        assert_eq!(cache.tags("A"), [TokenTag::Community, TokenTag::Strict]);
+        assert_eq!(cache.tags("B"), [TokenTag::Other]);
+        assert!(cache.contains("C"));
+        assert!(cache.tags("C").is_empty());
+        assert!(!cache.contains("D"));
+    }
 }
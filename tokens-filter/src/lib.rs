@@ -1,23 +1,38 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Instant};
 
-use anyhow::Ok;
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
+use circuit_breaker::{BreakerState, CircuitBreaker, CircuitBreakerSettings};
 use coingecko_client::CoingeckoClient;
 use coinmarketcap_client::CoinmarketcapClient;
 use derive_more::From;
 use http_client::settings::HttpClientSettings;
-use permissions_list::PermissionsList;
+use permissions_list::{PermissionsList, PermissionsListProvider};
+use report::{CheckReport, CheckerResult, VerdictSource};
+use settings::TokensFilterSettings;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
+use strategy::{Strategy, DEFAULT_WEIGHT};
+use token_address::TokenAddress;
 
-use crate::{json::JsonChecker, jupiter::JupiterChecker};
+use crate::{
+    ethereum::EthereumChecker,
+    json::JsonChecker,
+    jupiter::{JupiterChecker, ListMode},
+};
 
+pub mod circuit_breaker;
 pub mod coingecko;
 pub mod coinmarketcap;
+#[cfg(feature = "db")]
+pub mod db;
+pub mod ethereum;
 pub mod json;
 pub mod jupiter;
 pub mod permissions_list;
+pub mod report;
+pub mod settings;
 pub mod solana;
+pub mod strategy;
 
 #[derive(From)]
 pub enum Checker {
@@ -31,6 +46,8 @@ pub enum Checker {
     Jupiter(JupiterChecker),
     #[from]
     Solana(Arc<RpcClient>),
+    #[from]
+    Ethereum(EthereumChecker),
 }
 
 impl std::fmt::Display for Checker {
@@ -41,6 +58,7 @@ impl std::fmt::Display for Checker {
             Checker::Coingecko(_) => "Coingecko",
             Checker::Jupiter(_) => "Jupiter",
             Checker::Solana(_) => "Solana",
+            Checker::Ethereum(_) => "Ethereum",
         };
 
         f.write_str(msg)
@@ -49,7 +67,7 @@ impl std::fmt::Display for Checker {
 
 #[async_trait]
 impl CheckToken for Checker {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
         match self {
@@ -58,6 +76,7 @@ impl CheckToken for Checker {
             Checker::Coingecko(x) => x.check_token(token),
             Checker::Jupiter(x) => x.check_token(token),
             Checker::Solana(x) => x.check_token(token),
+            Checker::Ethereum(x) => x.check_token(token),
         }
         .await
     }
@@ -72,10 +91,33 @@ pub trait CheckToken {
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool>;
 }
 
+/// What kind of asset the token is, so NFTs can be handled by a separate policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Fungible,
+    /// Single token without decimals
+    Nft,
+    /// Several tokens without decimals, e.g. an edition
+    SemiFungible,
+    /// The token has no supply or is not supported by the classifier
+    Unknown,
+}
+
+#[async_trait]
+pub trait ClassifyToken {
+    type Token;
+
+    async fn classify_token(&self, token: &Self::Token) -> anyhow::Result<TokenKind>;
+}
+
 #[derive(Default)]
 pub struct TokensFilter {
-    permissions_list: PermissionsList,
-    checkers: Vec<Checker>,
+    permissions_list: Arc<ArcSwap<PermissionsList>>,
+    checkers: Vec<(Checker, Arc<CircuitBreaker>)>,
+    settings: TokensFilterSettings,
+    weights: HashMap<String, f64>,
+    #[cfg(feature = "db")]
+    verdicts: Option<db::VerdictStore>,
 }
 
 impl TokensFilter {
@@ -90,84 +132,278 @@ impl TokensFilter {
         Self::default()
             .with_coingecko(coingecko_settings)?
             .with_json()
-            .with_coinmarketcap(coinmarketcap_settings)
+            .with_coinmarketcap(coinmarketcap_settings)?
             .with_jupiter(jupiter_url, ttl)
             .await
     }
 
     pub fn with_json(mut self) -> Self {
         let checker = JsonChecker;
-        self.checkers.push(checker.into());
+        self.push_checker(checker.into());
         self
     }
 
-    pub fn with_coinmarketcap(mut self, coinmarketcap_settings: HttpClientSettings) -> Self {
-        let checker = CoinmarketcapClient::new(coinmarketcap_settings);
-        self.checkers.push(checker.into());
-        self
+    pub fn with_coinmarketcap(mut self, coinmarketcap_settings: HttpClientSettings) -> anyhow::Result<Self> {
+        let checker = CoinmarketcapClient::new(coinmarketcap_settings)?;
+        self.push_checker(checker.into());
+        Ok(self)
     }
 
     pub async fn with_jupiter(mut self, jupiter_url: String, ttl: u64) -> anyhow::Result<Self> {
         let checker = JupiterChecker::new(jupiter_url, ttl).await?;
-        self.checkers.push(checker.into());
+        self.push_checker(checker.into());
+        Ok(self)
+    }
+
+    pub async fn with_jupiter_token_list(mut self, mode: ListMode, ttl: u64) -> anyhow::Result<Self> {
+        let checker = JupiterChecker::new_token_list(mode, ttl).await?;
+        self.push_checker(checker.into());
         Ok(self)
     }
 
     pub fn with_coingecko(mut self, coingecko_settings: HttpClientSettings) -> anyhow::Result<Self> {
         let checker = CoingeckoClient::new(coingecko_settings)?;
-        self.checkers.push(checker.into());
+        self.push_checker(checker.into());
         Ok(self)
     }
 
     pub fn with_solana_rpc(mut self, client: Arc<RpcClient>) -> Self {
-        self.checkers.push(client.into());
+        self.push_checker(client.into());
         self
     }
 
     pub fn with_solana(mut self, url: String) -> Self {
         let client = Arc::new(RpcClient::new(url));
-        self.checkers.push(client.into());
+        self.push_checker(client.into());
+        self
+    }
+
+    pub fn with_ethereum(mut self, url: String) -> Self {
+        let checker = EthereumChecker::new(url);
+        self.push_checker(checker.into());
         self
     }
 
     pub fn with_permissions_list(mut self, permissions_list: PermissionsList) -> Self {
-        self.permissions_list = permissions_list;
+        self.permissions_list = Arc::new(ArcSwap::from_pointee(permissions_list));
         self
     }
-}
 
-#[async_trait]
-impl CheckToken for TokensFilter {
-    type Token = Pubkey;
+    /// Use the permissions list of the provider, refreshes of the provider are visible to the filter
+    pub fn with_permissions_list_provider(mut self, provider: &PermissionsListProvider) -> Self {
+        self.permissions_list = provider.shared();
+        self
+    }
+
+    pub fn with_settings(mut self, settings: TokensFilterSettings) -> Self {
+        self.settings = settings;
+        self.register_gauges();
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.settings.strategy = strategy;
+        self
+    }
+
+    /// Skip the checker after `failure_threshold` consecutive errors or timeouts until `open_duration` passes
+    pub fn with_circuit_breaker(mut self, settings: CircuitBreakerSettings) -> Self {
+        self.settings.circuit_breaker = Some(settings);
+        self.register_gauges();
+        self
+    }
+
+    /// Set weight of the checker by its name (e.g. "Coingecko") for `Strategy::WeightedScore`, default is 1.0
+    pub fn with_checker_weight(mut self, checker: impl Into<String>, weight: f64) -> Self {
+        self.weights.insert(checker.into(), weight);
+        self
+    }
+
+    fn push_checker(&mut self, checker: Checker) {
+        self.checkers.push((checker, Arc::new(CircuitBreaker::default())));
+        self.register_gauges();
+    }
+
+    /// Export the breaker states once a circuit breaker is configured
+    fn register_gauges(&self) {
+        if self.settings.circuit_breaker.is_none() {
+            return;
+        }
+
+        for (checker, breaker) in &self.checkers {
+            circuit_breaker::register_gauge(checker.to_string(), breaker);
+        }
+    }
+
+    /// Circuit breaker state of every checker in order they are called
+    pub fn circuit_breakers(&self) -> impl Iterator<Item = (String, BreakerState)> + '_ {
+        self.checkers
+            .iter()
+            .map(|(checker, breaker)| (checker.to_string(), breaker.state()))
+    }
+
+    fn weight(&self, checker: &str) -> f64 {
+        self.weights.get(checker).copied().unwrap_or(DEFAULT_WEIGHT)
+    }
 
+    /// Store verdicts in Postgres and consult them before calling the checkers, verdicts expire after
+    /// `db::DEFAULT_TTL` or `db::DEFAULT_NEGATIVE_TTL`
+    #[cfg(feature = "db")]
+    pub async fn with_db_cache(self, pool: sqlx::PgPool) -> anyhow::Result<Self> {
+        self.with_verdict_store(db::VerdictStore::new(pool)).await
+    }
+
+    /// Same as `with_db_cache` with the TTLs of the store
+    #[cfg(feature = "db")]
+    pub async fn with_verdict_store(mut self, store: db::VerdictStore) -> anyhow::Result<Self> {
+        store.init().await?;
+        self.verdicts = Some(store);
+        Ok(self)
+    }
+
+    /// Check the token and explain which rule or checker made the verdict
     #[tracing::instrument(skip(self))]
-    async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
-        if self.permissions_list.is_blacklisted(token) {
+    pub async fn check_token_detailed(&self, token: &TokenAddress) -> anyhow::Result<CheckReport> {
+        let started = Instant::now();
+        let permissions_list = self.permissions_list.load();
+
+        if permissions_list.is_blacklisted(token) {
             tracing::debug!(?token, "token is blacklisted");
-            return Ok(false);
+            return Ok(CheckReport::new(false, VerdictSource::Blacklisted, started));
         }
 
-        if self.permissions_list.is_whitelisted(token) {
+        if permissions_list.is_whitelisted(token) {
             tracing::debug!(?token, "token is whitelisted");
-            return Ok(true);
+            return Ok(CheckReport::new(true, VerdictSource::Whitelisted, started));
+        }
+
+        if let TokenAddress::Native(_) = token {
+            tracing::debug!(?token, "token is native");
+            return Ok(CheckReport::new(true, VerdictSource::Native, started));
+        }
+
+        #[cfg(feature = "db")]
+        if let Some(store) = &self.verdicts {
+            match store.get(token).await {
+                Ok(Some(verdict)) => {
+                    tracing::debug!(?token, checker = ?verdict.checker, "token verdict is found in db");
+                    return Ok(CheckReport {
+                        matched_checker: verdict.checker,
+                        ..CheckReport::new(verdict.verdict, VerdictSource::Db, started)
+                    });
+                },
+                Ok(None) => {},
+                Err(error) => tracing::warn!(?token, %error, "unable to get token verdict from db"),
+            }
+        }
+
+        let mut report = CheckReport::new(false, VerdictSource::Checkers, started);
+        let (mut passed_count, mut score) = (0, 0.0);
+
+        for (checker, breaker) in &self.checkers {
+            let checker_started = Instant::now();
+            let name = checker.to_string();
+
+            let timeout = self.settings.next_timeout(started.elapsed());
+            if timeout.is_some_and(|timeout| timeout.is_zero()) {
+                tracing::debug!(?token, "deadline exceeded");
+                report.deadline_exceeded = true;
+                break;
+            }
+
+            let permit = match self
+                .settings
+                .circuit_breaker
+                .as_ref()
+                .map(|settings| breaker.try_acquire(settings))
+            {
+                Some(None) => {
+                    tracing::debug!(?token, %checker, "circuit breaker is open, checker is skipped");
+                    report.per_checker_results.push(CheckerResult {
+                        checker: name,
+                        passed: false,
+                        timed_out: false,
+                        circuit_open: true,
+                        elapsed: checker_started.elapsed(),
+                    });
+                    continue;
+                },
+                Some(Some(permit)) => Some(permit),
+                None => None,
+            };
+
+            let result = match timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, checker.check_token(token)).await {
+                    Ok(result) => result.map(|passed| (passed, false)),
+                    Err(_) => {
+                        tracing::warn!(?token, %checker, ?timeout, "checker timed out");
+                        Ok((false, true))
+                    },
+                },
+                None => checker.check_token(token).await.map(|passed| (passed, false)),
+            };
+
+            if let Some(permit) = permit {
+                permit.record(matches!(result, Ok((_, false))));
+            }
+
+            let (passed, timed_out) = result?;
+
+            if passed {
+                passed_count += 1;
+                score += self.weight(&name);
+            }
+
+            report.per_checker_results.push(CheckerResult {
+                checker: name.clone(),
+                passed,
+                timed_out,
+                circuit_open: false,
+                elapsed: checker_started.elapsed(),
+            });
+
+            if passed && self.settings.strategy.is_reached(passed_count, score) {
+                report.verdict = true;
+                report.matched_checker = Some(name);
+                break;
+            }
+        }
+
+        match &report.matched_checker {
+            Some(checker) => tracing::debug!(?token, %checker, "token is checked"),
+            None => tracing::debug!(?token, "token is not checked"),
         }
 
-        for checker in &self.checkers {
-            if checker.check_token(token).await? {
-                tracing::debug!(?token, %checker, "token is checked");
-                return Ok(true);
+        // An incomplete negative verdict is not stored, the token should be checked again
+        #[cfg(feature = "db")]
+        if let Some(store) = self
+            .verdicts
+            .as_ref()
+            .filter(|_| report.verdict || report.is_complete())
+        {
+            if let Err(error) = store.save(token, report.verdict, report.matched_checker.clone()).await {
+                tracing::warn!(?token, %error, "unable to save token verdict to db");
             }
         }
 
-        tracing::debug!(?token, "token is not checked");
-        Ok(false)
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl CheckToken for TokensFilter {
+    type Token = TokenAddress;
+
+    async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
+        Ok(self.check_token_detailed(token).await?.verdict)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use rust_utils::telemetry::{make_resource, Telemetry, TracingSettings};
-    use solana_sdk::pubkey;
+    use solana_sdk::{pubkey, pubkey::Pubkey};
 
     use super::*;
 
@@ -228,30 +464,59 @@ mod tests {
         .unwrap();
 
         for token in NOT_SCAM.iter() {
-            let r = filter.check_token(token).await.unwrap();
+            let r = filter.check_token(&(*token).into()).await.unwrap();
             assert!(r, "token: {}", token);
-            tokio::time::sleep(std::time::Duration::from_secs(10)).await; // Coingecko API limit
         }
 
         for token in SCAM.iter() {
-            let r = filter.check_token(token).await.unwrap();
+            let r = filter.check_token(&(*token).into()).await.unwrap();
             assert!(!r, "token: {}", token);
-            tokio::time::sleep(std::time::Duration::from_secs(10)).await; // Coingecko API limit
         }
 
         // before added permission list
-        assert!(!filter.check_token(&WHITELISTED_TOKEN).await.unwrap());
-        assert!(filter.check_token(&BLACKLISTED_TOKEN).await.unwrap());
+        assert!(!filter.check_token(&WHITELISTED_TOKEN.into()).await.unwrap());
+        assert!(filter.check_token(&BLACKLISTED_TOKEN.into()).await.unwrap());
 
         let pl = PermissionsList::new(
-            [(WHITELISTED_TOKEN, true), (BLACKLISTED_TOKEN, false)]
+            [(WHITELISTED_TOKEN.into(), true), (BLACKLISTED_TOKEN.into(), false)]
                 .into_iter()
                 .collect(),
         );
 
         let filter = filter.with_permissions_list(pl);
 
-        assert!(filter.check_token(&WHITELISTED_TOKEN).await.unwrap());
-        assert!(!filter.check_token(&BLACKLISTED_TOKEN).await.unwrap());
+        assert!(filter.check_token(&WHITELISTED_TOKEN.into()).await.unwrap());
+        assert!(!filter.check_token(&BLACKLISTED_TOKEN.into()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn check_detailed_without_checkers() {
+        let pl = PermissionsList::new(
+            [(WHITELISTED_TOKEN.into(), true), (BLACKLISTED_TOKEN.into(), false)]
+                .into_iter()
+                .collect(),
+        );
+        let filter = TokensFilter::default().with_permissions_list(pl);
+
+        let report = filter.check_token_detailed(&WHITELISTED_TOKEN.into()).await.unwrap();
+        assert!(report.verdict);
+        assert_eq!(report.source, VerdictSource::Whitelisted);
+
+        let report = filter.check_token_detailed(&BLACKLISTED_TOKEN.into()).await.unwrap();
+        assert!(!report.verdict);
+        assert_eq!(report.source, VerdictSource::Blacklisted);
+
+        let report = filter
+            .check_token_detailed(&TokenAddress::Native(token_address::ChainId::Solana))
+            .await
+            .unwrap();
+        assert!(report.verdict);
+        assert_eq!(report.source, VerdictSource::Native);
+
+        let report = filter.check_token_detailed(&Pubkey::new_unique().into()).await.unwrap();
+        assert!(!report.verdict);
+        assert_eq!(report.source, VerdictSource::Checkers);
+        assert_eq!(report.matched_checker, None);
+        assert!(report.per_checker_results.is_empty());
     }
 }
@@ -1,39 +1,175 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
-use solana_sdk::pubkey::Pubkey;
+use anyhow::{bail, Context};
+use arc_swap::ArcSwap;
+use serde::Deserialize;
+use token_address::{StoredTokenAddress, TokenAddress};
+use tokio::task::JoinHandle;
 
 const NOT_DENIED: bool = true;
 
 #[derive(Default)]
 pub struct PermissionsList {
-    tokens: HashMap<Pubkey, bool>,
+    tokens: HashMap<TokenAddress, bool>,
 }
 
 impl PermissionsList {
-    pub fn new(tokens: HashMap<Pubkey, bool>) -> Self {
+    pub fn new(tokens: HashMap<TokenAddress, bool>) -> Self {
         Self { tokens }
     }
 
-    pub fn is_whitelisted(&self, token: &Pubkey) -> bool {
+    pub fn is_whitelisted(&self, token: &TokenAddress) -> bool {
         self.tokens.get(token).copied().unwrap_or_default()
     }
 
-    pub fn is_blacklisted(&self, token: &Pubkey) -> bool {
+    pub fn is_blacklisted(&self, token: &TokenAddress) -> bool {
         !self.tokens.get(token).copied().unwrap_or(NOT_DENIED)
     }
+
+    /// Parse JSON list of entries: `[{"token": "<address>", "allowed": true}, ...]`
+    pub fn from_json(input: &str) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        struct Entry {
+            token: TokenAddress,
+            allowed: bool,
+        }
+
+        let entries: Vec<Entry> = serde_json::from_str(input).context("Unable to parse permissions list json")?;
+
+        Ok(Self::new(
+            entries.into_iter().map(|entry| (entry.token, entry.allowed)).collect(),
+        ))
+    }
+
+    /// Parse CSV with `token,allowed` lines, an optional header is skipped
+    pub fn from_csv(input: &str) -> anyhow::Result<Self> {
+        let mut tokens = HashMap::new();
+
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((token, allowed)) = line.split_once(',') else {
+                bail!("Invalid permissions list line {}: {line}", index + 1);
+            };
+
+            let (token, allowed) = (token.trim(), allowed.trim());
+            if index == 0 && token.eq_ignore_ascii_case("token") {
+                continue;
+            }
+
+            let token = StoredTokenAddress::from_str(token)
+                .with_context(|| format!("Invalid token address on line {}: {token}", index + 1))?;
+            let allowed = allowed
+                .parse::<bool>()
+                .with_context(|| format!("Invalid permission on line {}: {allowed}", index + 1))?;
+
+            tokens.insert(token.into(), allowed);
+        }
+
+        Ok(Self::new(tokens))
+    }
+}
+
+/// Where `PermissionsListProvider` reloads the list from.
+/// The format is chosen by extension: `.csv` is parsed as CSV, anything else as JSON
+#[derive(Debug, Clone)]
+pub enum PermissionsSource {
+    Url(String),
+    File(PathBuf),
+}
+
+impl PermissionsSource {
+    fn is_csv(&self) -> bool {
+        match self {
+            PermissionsSource::Url(url) => url.split('?').next().unwrap_or_default().ends_with(".csv"),
+            PermissionsSource::File(path) => path.extension().map(|ext| ext == "csv").unwrap_or_default(),
+        }
+    }
+
+    async fn fetch(&self, client: &reqwest::Client) -> anyhow::Result<PermissionsList> {
+        let content = match self {
+            PermissionsSource::Url(url) => client.get(url).send().await?.error_for_status()?.text().await?,
+            PermissionsSource::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Unable to read {}", path.display()))?,
+        };
+
+        if self.is_csv() {
+            PermissionsList::from_csv(&content)
+        } else {
+            PermissionsList::from_json(&content)
+        }
+    }
+}
+
+/// Permissions list which can be reloaded from its source without redeploying.
+/// The list is swapped atomically so readers are never blocked.
+pub struct PermissionsListProvider {
+    source: PermissionsSource,
+    client: reqwest::Client,
+    list: Arc<ArcSwap<PermissionsList>>,
+}
+
+impl PermissionsListProvider {
+    pub async fn new(source: PermissionsSource) -> anyhow::Result<Self> {
+        let client = reqwest::Client::new();
+        let list = source.fetch(&client).await?;
+
+        Ok(Self {
+            source,
+            client,
+            list: Arc::new(ArcSwap::from_pointee(list)),
+        })
+    }
+
+    /// Reload the list from the source. The current list is kept on error.
+    pub async fn refresh(&self) -> anyhow::Result<()> {
+        let list = self.source.fetch(&self.client).await?;
+        self.list.store(Arc::new(list));
+        Ok(())
+    }
+
+    pub fn load(&self) -> Arc<PermissionsList> {
+        self.list.load_full()
+    }
+
+    pub(crate) fn shared(&self) -> Arc<ArcSwap<PermissionsList>> {
+        self.list.clone()
+    }
+
+    /// Spawn a task which refreshes the list every `interval`
+    pub fn spawn_refresh(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                if let Err(error) = self.refresh().await {
+                    tracing::warn!(source = ?self.source, %error, "unable to refresh permissions list");
+                }
+            }
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use primitive_types::H160;
+    use solana_sdk::pubkey::Pubkey;
+
     use super::*;
 
     #[test]
     fn permissions_list() {
-        let allowed = Pubkey::new_unique();
-        let denied = Pubkey::new_unique();
-        let unknown = Pubkey::new_unique();
+        let allowed: TokenAddress = Pubkey::new_unique().into();
+        let denied: TokenAddress = H160::random().into();
+        let unknown: TokenAddress = Pubkey::new_unique().into();
 
-        let tokens = [(allowed, true), (denied, false)].into_iter().collect();
+        let tokens = [(allowed.clone(), true), (denied.clone(), false)].into_iter().collect();
 
         let list = PermissionsList::new(tokens);
 
@@ -42,4 +178,51 @@ mod tests {
         assert!(!list.is_whitelisted(&unknown));
         assert!(!list.is_blacklisted(&unknown));
     }
+
+    #[test]
+    fn permissions_list_from_json() {
+        let allowed = Pubkey::new_unique();
+        let denied = H160::random();
+
+        let json =
+            format!(r#"[{{"token": "{allowed}", "allowed": true}}, {{"token": "0x{denied:x}", "allowed": false}}]"#);
+        let list = PermissionsList::from_json(&json).unwrap();
+
+        assert!(list.is_whitelisted(&allowed.into()));
+        assert!(list.is_blacklisted(&denied.into()));
+    }
+
+    #[test]
+    fn permissions_list_from_csv() {
+        let allowed = Pubkey::new_unique();
+        let denied = Pubkey::new_unique();
+
+        let csv = format!("token,allowed\n{allowed},true\n\n{denied}, false\n");
+        let list = PermissionsList::from_csv(&csv).unwrap();
+
+        assert!(list.is_whitelisted(&allowed.into()));
+        assert!(list.is_blacklisted(&denied.into()));
+
+        assert!(PermissionsList::from_csv("not-a-token,true").is_err());
+    }
+
+    #[tokio::test]
+    async fn provider_refresh() {
+        let token = Pubkey::new_unique();
+        let path = std::env::temp_dir().join(format!("permissions-{token}.csv"));
+
+        std::fs::write(&path, format!("{token},true")).unwrap();
+        let provider = PermissionsListProvider::new(PermissionsSource::File(path.clone()))
+            .await
+            .unwrap();
+        assert!(provider.load().is_whitelisted(&token.into()));
+
+        std::fs::write(&path, format!("{token},false")).unwrap();
+        provider.refresh().await.unwrap();
+        assert!(provider.load().is_blacklisted(&token.into()));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(provider.refresh().await.is_err());
+        assert!(provider.load().is_blacklisted(&token.into()));
+    }
 }
@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+/// What made the final decision about the token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerdictSource {
+    Blacklisted,
+    Whitelisted,
+    Native,
+    Db,
+    Checkers,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckerResult {
+    pub checker: String,
+    pub passed: bool,
+    pub timed_out: bool,
+    /// The checker was skipped because its circuit breaker is open
+    pub circuit_open: bool,
+    pub elapsed: Duration,
+}
+
+/// Explanation of a `TokensFilter` verdict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckReport {
+    pub verdict: bool,
+    pub source: VerdictSource,
+    /// Name of the checker which accepted the token
+    pub matched_checker: Option<String>,
+    /// Results of the checkers in order they were called
+    pub per_checker_results: Vec<CheckerResult>,
+    /// The checker chain was interrupted by `TokensFilterSettings::deadline`
+    pub deadline_exceeded: bool,
+    pub elapsed: Duration,
+}
+
+impl CheckReport {
+    pub(crate) fn new(verdict: bool, source: VerdictSource, started: Instant) -> Self {
+        Self {
+            verdict,
+            source,
+            matched_checker: None,
+            per_checker_results: vec![],
+            deadline_exceeded: false,
+            elapsed: started.elapsed(),
+        }
+    }
+
+    /// All checkers gave their answers in time
+    pub fn is_complete(&self) -> bool {
+        !self.deadline_exceeded
+            && self
+                .per_checker_results
+                .iter()
+                .all(|result| !result.timed_out && !result.circuit_open)
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use rust_utils::wrappers::serde::DurationMs;
+use serde::Deserialize;
+use serde_with::serde_as;
+
+use crate::{circuit_breaker::CircuitBreakerSettings, strategy::Strategy};
+
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct TokensFilterSettings {
+    /// Maximum time of a single checker call, the next checker is called on timeout
+    #[serde(rename = "checker_timeout_ms", default)]
+    #[serde_as(as = "Option<DurationMs>")]
+    pub checker_timeout: Option<Duration>,
+    /// Maximum time of the whole checker chain, the token is not accepted on timeout
+    #[serde(rename = "deadline_ms", default)]
+    #[serde_as(as = "Option<DurationMs>")]
+    pub deadline: Option<Duration>,
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Skip checkers which keep failing, breakers are disabled if not set
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerSettings>,
+}
+
+impl TokensFilterSettings {
+    /// Timeout of the next checker call according to the per-checker timeout and the time left until deadline
+    pub(crate) fn next_timeout(&self, elapsed: Duration) -> Option<Duration> {
+        let left = self.deadline.map(|deadline| deadline.saturating_sub(elapsed));
+
+        match (self.checker_timeout, left) {
+            (Some(timeout), Some(left)) => Some(timeout.min(left)),
+            (timeout, left) => timeout.or(left),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let settings: TokensFilterSettings =
+            serde_json::from_str(r#"{"checker_timeout_ms": 500, "deadline_ms": 2000, "strategy": {"quorum": 2}, "circuit_breaker": {"failure_threshold": 3}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings, TokensFilterSettings {
+            checker_timeout: Some(Duration::from_millis(500)),
+            deadline: Some(Duration::from_secs(2)),
+            strategy: Strategy::Quorum(2),
+            circuit_breaker: Some(CircuitBreakerSettings {
+                failure_threshold: 3,
+                open_duration: Duration::from_secs(30),
+            }),
+        });
+
+        let settings: TokensFilterSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings, TokensFilterSettings::default());
+    }
+
+    #[test]
+    fn next_timeout() {
+        let settings = TokensFilterSettings {
+            checker_timeout: Some(Duration::from_millis(500)),
+            deadline: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+
+        assert_eq!(settings.next_timeout(Duration::ZERO), Some(Duration::from_millis(500)));
+        assert_eq!(
+            settings.next_timeout(Duration::from_millis(1800)),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(settings.next_timeout(Duration::from_secs(3)), Some(Duration::ZERO));
+        assert_eq!(TokensFilterSettings::default().next_timeout(Duration::ZERO), None);
+    }
+}
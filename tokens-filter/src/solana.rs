@@ -4,19 +4,37 @@ use anyhow::Context;
 use async_trait::async_trait;
 use normdecimal::NormDecimal;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::pubkey::Pubkey;
+use token_address::TokenAddress;
 
-use crate::CheckToken;
+use crate::{CheckToken, ClassifyToken, TokenKind};
 
 pub const NFT_AMOUNT: NormDecimal = NormDecimal::ONE;
 pub const NFT_DECIMALS: u8 = 0;
 
+impl TokenKind {
+    pub fn from_supply(amount: NormDecimal, decimals: u8) -> Self {
+        if amount <= NormDecimal::ZERO {
+            TokenKind::Unknown
+        } else if decimals != NFT_DECIMALS {
+            TokenKind::Fungible
+        } else if amount == NFT_AMOUNT {
+            TokenKind::Nft
+        } else {
+            TokenKind::SemiFungible
+        }
+    }
+}
+
 #[async_trait]
 impl CheckToken for RpcClient {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
-        self.get_token_supply(token).await.map(|supply| {
+        let Some(mint) = token.spl() else {
+            return Ok(false);
+        };
+
+        self.get_token_supply(&mint).await.map(|supply| {
             let amount = supply.ui_amount_string.parse::<NormDecimal>().with_context(|| {
                 format!(
                     "Unable to parse ui_amount_string({}) to Decimal",
@@ -31,16 +49,46 @@ impl CheckToken for RpcClient {
 
 #[async_trait]
 impl CheckToken for Arc<RpcClient> {
-    type Token = Pubkey;
+    type Token = TokenAddress;
 
     async fn check_token(&self, token: &Self::Token) -> anyhow::Result<bool> {
         self.as_ref().check_token(token).await
     }
 }
 
+#[async_trait]
+impl ClassifyToken for RpcClient {
+    type Token = TokenAddress;
+
+    async fn classify_token(&self, token: &Self::Token) -> anyhow::Result<TokenKind> {
+        let Some(mint) = token.spl() else {
+            return Ok(TokenKind::Unknown);
+        };
+
+        let supply = self.get_token_supply(&mint).await?;
+        let amount = supply.ui_amount_string.parse::<NormDecimal>().with_context(|| {
+            format!(
+                "Unable to parse ui_amount_string({}) to Decimal",
+                supply.ui_amount_string
+            )
+        })?;
+
+        Ok(TokenKind::from_supply(amount, supply.decimals))
+    }
+}
+
+#[async_trait]
+impl ClassifyToken for Arc<RpcClient> {
+    type Token = TokenAddress;
+
+    async fn classify_token(&self, token: &Self::Token) -> anyhow::Result<TokenKind> {
+        self.as_ref().classify_token(token).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use solana_sdk::pubkey;
+    use solana_sdk::{pubkey, pubkey::Pubkey};
 
     use super::*;
 
@@ -50,18 +98,29 @@ mod tests {
         let solana_client = Arc::new(RpcClient::new("https://api.mainnet-beta.solana.com".into()));
 
         let good = solana_client
-            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")) // USDC
+            .check_token(&pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").into()) // USDC
             .await
             .unwrap();
         assert!(good);
 
         let nft = solana_client
-            .check_token(&pubkey!("J7W8hKLg9a8KUsZu5wmMo6pufocW9qhRUR7Fx7isRoYU"))
+            .check_token(&pubkey!("J7W8hKLg9a8KUsZu5wmMo6pufocW9qhRUR7Fx7isRoYU").into())
             .await
             .unwrap();
         assert!(nft);
 
-        let error = solana_client.check_token(&Pubkey::new_unique()).await.is_err();
+        let error = solana_client.check_token(&Pubkey::new_unique().into()).await.is_err();
         assert!(error);
     }
+
+    #[test]
+    fn token_kind_from_supply() {
+        let amount = |s: &str| s.parse::<NormDecimal>().unwrap();
+
+        assert_eq!(TokenKind::from_supply(amount("1"), 0), TokenKind::Nft);
+        assert_eq!(TokenKind::from_supply(amount("100"), 0), TokenKind::SemiFungible);
+        assert_eq!(TokenKind::from_supply(amount("1000.5"), 6), TokenKind::Fungible);
+        assert_eq!(TokenKind::from_supply(amount("1"), 9), TokenKind::Fungible);
+        assert_eq!(TokenKind::from_supply(amount("0"), 0), TokenKind::Unknown);
+    }
 }
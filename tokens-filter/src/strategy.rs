@@ -0,0 +1,56 @@
+use serde::Deserialize;
+
+pub const DEFAULT_WEIGHT: f64 = 1.0;
+
+/// How results of the checkers are combined into the verdict
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Accept the token as soon as one checker accepts it
+    #[default]
+    FirstMatch,
+    /// Accept the token when at least `n` checkers accept it
+    Quorum(usize),
+    /// Accept the token when the sum of weights of accepting checkers reaches the threshold
+    WeightedScore { threshold: f64 },
+}
+
+impl Strategy {
+    pub(crate) fn is_reached(&self, passed: usize, score: f64) -> bool {
+        match self {
+            Strategy::FirstMatch => passed > 0,
+            Strategy::Quorum(n) => passed >= *n,
+            Strategy::WeightedScore { threshold } => score >= *threshold,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_reached() {
+        assert!(!Strategy::FirstMatch.is_reached(0, 0.0));
+        assert!(Strategy::FirstMatch.is_reached(1, 1.0));
+
+        assert!(!Strategy::Quorum(2).is_reached(1, 1.0));
+        assert!(Strategy::Quorum(2).is_reached(2, 2.0));
+
+        let weighted = Strategy::WeightedScore { threshold: 1.5 };
+        assert!(!weighted.is_reached(2, 1.0));
+        assert!(weighted.is_reached(1, 1.5));
+    }
+
+    #[test]
+    fn deserialize() {
+        let strategy: Strategy = serde_json::from_str(r#""first_match""#).unwrap();
+        assert_eq!(strategy, Strategy::FirstMatch);
+
+        let strategy: Strategy = serde_json::from_str(r#"{"quorum": 2}"#).unwrap();
+        assert_eq!(strategy, Strategy::Quorum(2));
+
+        let strategy: Strategy = serde_json::from_str(r#"{"weighted_score": {"threshold": 1.5}}"#).unwrap();
+        assert_eq!(strategy, Strategy::WeightedScore { threshold: 1.5 });
+    }
+}